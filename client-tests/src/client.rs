@@ -0,0 +1,209 @@
+// src/client.rs
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, StatusCode};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use x402_reqwest::{MaxTokenAmountFromAmount, ReqwestWithPayments, ReqwestWithPaymentsBuild};
+use x402_rs::network::{Network, USDCDeployment};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:8080";
+const DEFAULT_MAX_PAYMENT: f64 = 0.1;
+const DEFAULT_NETWORK: Network = Network::BaseSepolia;
+
+/// Connection and wallet settings for a `LibrarianClient`.
+pub struct LibrarianConfig {
+    pub base_url: String,
+    pub evm_private_key: String,
+    /// Upper bound in USDC the client will authorize for a single request.
+    pub max_payment: f64,
+    /// Network the client pays USDC on; it has to be one the server's `402`
+    /// challenge accepts.
+    pub network: Network,
+}
+
+impl LibrarianConfig {
+    /// Reads `LIBRARIAN_URL`, `EVM_PRIVATE_KEY`, `LIBRARIAN_MAX_PAYMENT` and
+    /// `LIBRARIAN_NETWORK` (an x402 network name such as `base`, defaulting to
+    /// `base-sepolia`).
+    pub fn from_env() -> Result<Self> {
+        let max_payment = match env::var("LIBRARIAN_MAX_PAYMENT") {
+            Ok(v) => v.parse().context("Invalid LIBRARIAN_MAX_PAYMENT")?,
+            Err(_) => DEFAULT_MAX_PAYMENT,
+        };
+        let network = match env::var("LIBRARIAN_NETWORK") {
+            Ok(v) => serde_json::from_value(Value::String(v.clone()))
+                .with_context(|| format!("Invalid LIBRARIAN_NETWORK: {}", v))?,
+            Err(_) => DEFAULT_NETWORK,
+        };
+        Ok(LibrarianConfig {
+            base_url: env::var("LIBRARIAN_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+            evm_private_key: env::var("EVM_PRIVATE_KEY").context("EVM_PRIVATE_KEY is not set")?,
+            max_payment,
+            network,
+        })
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct DiscoverRequest {
+    pub query: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_type: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DiscoverResponse {
+    #[serde(default)]
+    pub service_acknowledgement: String,
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub recommendations: Vec<Recommendation>,
+    #[serde(default)]
+    pub instructions: Value,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Recommendation {
+    pub name: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub protocol_version: String,
+    #[serde(default)]
+    pub transport: String,
+    #[serde(default)]
+    pub auth: Value,
+    #[serde(default)]
+    pub capabilities: Value,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub score: u32,
+    #[serde(default)]
+    pub rationale: String,
+    #[serde(default)]
+    pub overview: String,
+    #[serde(default)]
+    pub verification_status: String,
+    #[serde(default)]
+    pub last_checked: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResponse {
+    pub query: String,
+    pub results: Vec<SearchHit>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchHit {
+    pub score: f64,
+    pub name: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub desc: String,
+}
+
+/// Typed wrapper around the Librarian HTTP API that pays x402 challenges on
+/// the configured network with the configured EVM wallet.
+pub struct LibrarianClient {
+    http: ClientWithMiddleware,
+    base_url: String,
+    network: Network,
+}
+
+impl LibrarianClient {
+    pub fn new(config: LibrarianConfig) -> Result<Self> {
+        let signer: PrivateKeySigner = config
+            .evm_private_key
+            .parse()
+            .context("Invalid EVM private key")?;
+        let sender = x402_reqwest::chains::evm::EvmSenderWallet::new(signer);
+
+        let http = Client::new()
+            .with_payments(sender)
+            .prefer(USDCDeployment::by_network(config.network))
+            .max(USDCDeployment::by_network(config.network).amount(config.max_payment)?)
+            .build();
+
+        Ok(LibrarianClient {
+            http,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            network: config.network,
+        })
+    }
+
+    pub fn from_env() -> Result<Self> {
+        Self::new(LibrarianConfig::from_env()?)
+    }
+
+    pub async fn discover(&self, query: &str, filters: Option<Value>) -> Result<DiscoverResponse> {
+        self.discover_request(&DiscoverRequest {
+            query: query.to_string(),
+            filters,
+            ..DiscoverRequest::default()
+        })
+        .await
+    }
+
+    pub async fn discover_request(&self, request: &DiscoverRequest) -> Result<DiscoverResponse> {
+        self.post("/discover", request).await
+    }
+
+    pub async fn search(&self, query: &str) -> Result<SearchResponse> {
+        self.post("/search", &serde_json::json!({ "query": query }))
+            .await
+    }
+
+    /// `GET /payment/info`: the networks, tokens, pay-to addresses and route
+    /// prices the server accepts.
+    pub async fn payment_info(&self) -> Result<Value> {
+        let response = self
+            .http
+            .get(format!("{}/payment/info", self.base_url))
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            bail!("Librarian returned {}: {}", status, text);
+        }
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse Librarian response: {}", text))
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let body = serde_json::to_string(body).context("Failed to serialize JSON body")?;
+        let response = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status == StatusCode::PAYMENT_REQUIRED {
+            bail!(
+                "402 Payment Required: check wallet balance (0.001+ USDC on {}), that the server accepts that network, key validity, or the tx on the block explorer",
+                self.network
+            );
+        }
+        let text = response.text().await?;
+        if !status.is_success() {
+            bail!("Librarian returned {}: {}", status, text);
+        }
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse Librarian response: {}", text))
+    }
+}
@@ -0,0 +1,2 @@
+// src/lib.rs
+pub mod client;
@@ -0,0 +1,230 @@
+// src/backend/account.rs
+//! Per-caller usage over a sliding window, so clients can budget before hitting
+//! `402`/`429`. API-key callers are identified by key id; x402 callers by the
+//! address x402 settled, and read their usage back by presenting the
+//! `X-PAYMENT` header of a payment settled here. There is no prepaid-credit
+//! mode yet, so `remaining_balance` is always `null`.
+use super::apikey::{ApiKeys, presented_key};
+use super::payer::{SETTLED, settled_payer};
+use super::pricing::Quote;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_ACCOUNT_WINDOW_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Caller {
+    ApiKey(String),
+    Payer(String),
+}
+
+impl Caller {
+    fn kind(&self) -> &'static str {
+        match self {
+            Caller::ApiKey(_) => "api_key",
+            Caller::Payer(_) => "x402",
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            Caller::ApiKey(id) | Caller::Payer(id) => id,
+        }
+    }
+}
+
+fn api_key_caller(headers: &HeaderMap, keys: &ApiKeys) -> Option<Caller> {
+    presented_key(headers)
+        .and_then(|k| keys.identify(&k).map(str::to_string))
+        .map(Caller::ApiKey)
+}
+
+/// Authenticates the caller of a request: a valid API key wins, then the payer
+/// of a settled `X-PAYMENT` (see `payer::SETTLED`). A payer merely named in
+/// `X-PAYMENT` is not enough.
+pub fn identify(headers: &HeaderMap, keys: &ApiKeys) -> Option<Caller> {
+    api_key_caller(headers, keys).or_else(|| SETTLED.verified_payer(headers).map(Caller::Payer))
+}
+
+/// Successful requests and their USDC charge, per caller, within `window`.
+pub struct UsageLedger {
+    window: Duration,
+    entries: Mutex<HashMap<Caller, VecDeque<(Instant, f64)>>>,
+}
+
+impl UsageLedger {
+    /// Reads `LIBRARIAN_ACCOUNT_WINDOW_SECS`, defaulting to one hour.
+    pub fn from_env() -> Self {
+        let secs = env::var("LIBRARIAN_ACCOUNT_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_ACCOUNT_WINDOW_SECS);
+        UsageLedger {
+            window: Duration::from_secs(secs),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, caller: Caller, amount: f64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let history = entries.entry(caller).or_default();
+        history.push_back((Instant::now(), amount));
+        Self::prune(history, self.window);
+    }
+
+    /// `(request count, total spend)` for `caller` within the window.
+    pub fn usage(&self, caller: &Caller) -> (usize, f64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(history) = entries.get_mut(caller) else {
+            return (0, 0.0);
+        };
+        Self::prune(history, self.window);
+        (
+            history.len(),
+            history.iter().map(|(_, amount)| amount).sum(),
+        )
+    }
+
+    fn prune(history: &mut VecDeque<(Instant, f64)>, window: Duration) {
+        while history.front().is_some_and(|(at, _)| at.elapsed() > window) {
+            history.pop_front();
+        }
+    }
+}
+
+/// State for `usage_layer` on one paid route.
+#[derive(Clone)]
+pub struct UsageTracking {
+    pub ledger: Arc<UsageLedger>,
+    pub keys: Arc<ApiKeys>,
+    pub price: f64,
+}
+
+/// Records each successful request against its caller: the API key, or the
+/// payer x402 settled. API-key callers bypass x402 and are tallied at zero
+/// spend.
+pub async fn usage_layer(
+    State(tracking): State<UsageTracking>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let api_key = api_key_caller(request.headers(), &tracking.keys);
+    // a per-result `/discover` quote overrides the route price
+    let price = request
+        .extensions()
+        .get::<Quote>()
+        .map_or(tracking.price, |quote| quote.price);
+    let response = next.run(request).await;
+    let caller = api_key.or_else(|| settled_payer(response.headers()).map(Caller::Payer));
+    if let Some(caller) = caller
+        && response.status().is_success()
+    {
+        let amount = match caller {
+            Caller::ApiKey(_) => 0.0,
+            Caller::Payer(_) => price,
+        };
+        tracking.ledger.record(caller, amount);
+    }
+    response
+}
+
+#[derive(Clone)]
+pub struct AccountState {
+    pub ledger: Arc<UsageLedger>,
+    pub keys: Arc<ApiKeys>,
+}
+
+/// `GET /account`: the caller's recent request count and spend.
+pub async fn account_handler(State(state): State<AccountState>, headers: HeaderMap) -> Response {
+    let Some(caller) = identify(&headers, &state.keys) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            AxumJson(json!({
+                "error": "Present an API key, or the X-PAYMENT header of a payment settled here"
+            })),
+        )
+            .into_response();
+    };
+
+    let (requests, spend) = state.ledger.usage(&caller);
+    AxumJson(json!({
+        "caller": caller.id(),
+        "auth": caller.kind(),
+        "window_secs": state.ledger.window.as_secs(),
+        "recent_requests": requests,
+        "recent_spend_usdc": spend,
+        "remaining_balance": null,
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::payer::{PAYMENT_HEADER, payment_digest};
+    use axum::{Router, body::Body, routing::get};
+    use tower::ServiceExt as _;
+
+    fn app(ledger: Arc<UsageLedger>) -> Router {
+        Router::new()
+            .route("/account", get(account_handler))
+            .with_state(AccountState {
+                ledger,
+                keys: Arc::new(ApiKeys::default()),
+            })
+    }
+
+    fn ledger() -> Arc<UsageLedger> {
+        Arc::new(UsageLedger {
+            window: Duration::from_secs(60),
+            entries: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn account(uri: &str, payment: &str) -> Request {
+        Request::get(uri)
+            .header(PAYMENT_HEADER, payment)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn usage_needs_a_settled_payment() {
+        let owner = "0xaccount-owner";
+        let ledger = ledger();
+        ledger.record(Caller::Payer(owner.to_string()), 0.01);
+
+        let by_query = Request::get(format!("/account?payer={}", owner))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(Arc::clone(&ledger)).oneshot(by_query).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let unsettled = account("/account", "unsettled-payment");
+        let response = app(Arc::clone(&ledger)).oneshot(unsettled).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let settled = account("/account", "settled-payment");
+        SETTLED.record(
+            payment_digest(settled.headers()).unwrap(),
+            owner.to_string(),
+        );
+        let response = app(ledger).oneshot(settled).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["caller"], owner);
+        assert_eq!(body["recent_requests"], 1);
+    }
+}
@@ -0,0 +1,66 @@
+// src/backend/admin.rs
+use axum::{
+    extract::Request,
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::env;
+
+/// Guards `/admin/*` routes with the bearer token in `LIBRARIAN_ADMIN_TOKEN`.
+/// When no token is configured the admin surface is disabled entirely.
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    let Some(expected) = env::var("LIBRARIAN_ADMIN_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())
+    else {
+        return (StatusCode::NOT_FOUND, "Admin API disabled").into_response();
+    };
+
+    let presented = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    if !constant_time_eq(presented.as_bytes(), expected.as_bytes()) {
+        return (StatusCode::UNAUTHORIZED, "Invalid admin token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Header carrying the admin token on non-admin routes, where `Authorization`
+/// may already hold an API key.
+pub const ADMIN_TOKEN_HEADER: &str = "x-librarian-admin-token";
+
+/// True when `X-Librarian-Admin-Token` matches `LIBRARIAN_ADMIN_TOKEN`. Always
+/// false when no token is configured.
+pub fn is_admin(headers: &HeaderMap) -> bool {
+    let Some(expected) = env::var("LIBRARIAN_ADMIN_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())
+    else {
+        return false;
+    };
+    headers
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|presented| constant_time_eq(presented.as_bytes(), expected.as_bytes()))
+}
+
+/// Raw model output may echo catalog data or prompt internals, so `?raw=true`
+/// needs both `LIBRARIAN_ALLOW_RAW_OUTPUT=true` on the server and an admin token.
+pub fn raw_output_allowed(headers: &HeaderMap) -> bool {
+    env::var("LIBRARIAN_ALLOW_RAW_OUTPUT").is_ok_and(|v| v == "1" || v == "true")
+        && is_admin(headers)
+}
+
+/// Compares secrets without returning early at the first differing byte;
+/// shared with `apikey`.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
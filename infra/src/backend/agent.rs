@@ -0,0 +1,66 @@
+// src/backend/agent.rs
+//! Which OpenAI API the Librarian agent talks to, from
+//! `LIBRARIAN_COMPLETION_API`:
+//!
+//! - `responses` (default): the Responses API. No `seed` parameter, so a
+//!   seeded run only pins temperature to 0.
+//! - `chat`: Chat Completions, for providers and models that only speak it.
+//!   `seed` is forwarded, so seeded runs are reproducible where the provider
+//!   honours it.
+//!
+//! Neither path relies on provider-side structured output: the prompt asks for
+//! JSON and `check_discover_output` validates it against the published schema,
+//! so `/discover` behaves the same on both.
+use anyhow::{Result, bail};
+use rig::agent::Agent;
+use rig::completion::{Prompt as _, PromptError};
+use rig::providers::openai::completion::CompletionModel;
+use rig::providers::openai::responses_api::ResponsesCompletionModel;
+use std::env;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompletionApi {
+    #[default]
+    Responses,
+    Chat,
+}
+
+impl CompletionApi {
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_COMPLETION_API").as_deref() {
+            Err(_) | Ok("responses") => Ok(CompletionApi::Responses),
+            Ok("chat") => Ok(CompletionApi::Chat),
+            Ok(other) => bail!(
+                "Unknown LIBRARIAN_COMPLETION_API {:?} (expected responses or chat)",
+                other
+            ),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CompletionApi::Responses => "responses",
+            CompletionApi::Chat => "chat",
+        }
+    }
+
+    /// Whether the API takes a sampling `seed`.
+    pub fn supports_seed(self) -> bool {
+        self == CompletionApi::Chat
+    }
+}
+
+/// An agent on either API; callers only ever prompt it.
+pub enum LibrarianAgent {
+    Responses(Agent<ResponsesCompletionModel>),
+    Chat(Agent<CompletionModel>),
+}
+
+impl LibrarianAgent {
+    pub async fn prompt(&self, prompt: &str) -> Result<String, PromptError> {
+        match self {
+            LibrarianAgent::Responses(agent) => agent.prompt(prompt).await,
+            LibrarianAgent::Chat(agent) => agent.prompt(prompt).await,
+        }
+    }
+}
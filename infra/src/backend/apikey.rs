@@ -0,0 +1,109 @@
+// src/backend/apikey.rs
+//! Optional API-key access for deployments that bill callers themselves.
+//!
+//! The two auth modes compose per request: a valid key (`Authorization: Bearer`
+//! or `X-API-Key`) is checked first and routes the request to an unpaid copy of
+//! the handler, skipping x402 entirely. A request without a key falls through
+//! to the normal `402` payment flow. A key that is presented but unknown is
+//! rejected with `401` rather than silently charged.
+use super::admin::constant_time_eq;
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::env;
+use std::sync::Arc;
+use tower::ServiceExt as _;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Identity of a caller authenticated by API key, attached as a request extension.
+#[derive(Debug, Clone)]
+pub struct ApiKeyCaller(pub String);
+
+/// Keys from `LIBRARIAN_API_KEYS`: comma-separated `id:key` pairs, or bare keys
+/// which are identified by position (`key-1`, `key-2`, ...).
+#[derive(Debug, Default)]
+pub struct ApiKeys(Vec<(String, String)>);
+
+impl ApiKeys {
+    pub fn from_env() -> Self {
+        let keys = env::var("LIBRARIAN_API_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .enumerate()
+            .map(|(i, item)| match item.split_once(':') {
+                Some((id, key)) => (id.trim().to_string(), key.trim().to_string()),
+                None => (format!("key-{}", i + 1), item.to_string()),
+            })
+            .collect();
+        ApiKeys(keys)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the caller id for a presented key.
+    pub fn identify(&self, presented: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, key)| constant_time_eq(key.as_bytes(), presented.as_bytes()))
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+pub fn presented_key(headers: &HeaderMap) -> Option<String> {
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|k| k.trim().to_string())
+}
+
+/// State for `api_key_layer`: the configured keys and the unpaid route to use
+/// for authenticated callers.
+#[derive(Clone)]
+pub struct ApiKeyBypass {
+    pub keys: Arc<ApiKeys>,
+    pub unpaid: Router,
+}
+
+pub async fn api_key_layer(
+    State(bypass): State<ApiKeyBypass>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !bypass.keys.is_enabled() {
+        return next.run(request).await;
+    }
+    let Some(presented) = presented_key(request.headers()) else {
+        return next.run(request).await;
+    };
+    let Some(caller) = bypass.keys.identify(&presented).map(str::to_string) else {
+        return (StatusCode::UNAUTHORIZED, "Invalid API key").into_response();
+    };
+
+    tracing::debug!(caller = %caller, "API key accepted, bypassing x402");
+    request.extensions_mut().insert(ApiKeyCaller(caller));
+    match bypass.unpaid.oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
@@ -0,0 +1,147 @@
+// src/backend/breaker.rs
+//! Circuit breaker around the completion agent. After `threshold` consecutive
+//! agent failures the breaker opens and `/discover` answers from the catalog
+//! alone; once the cooldown passes a single probe request is let through, and
+//! its outcome either closes the breaker or re-opens it for another cooldown.
+use super::metrics::METRICS;
+use serde::Serialize;
+use std::env;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+const DEFAULT_THRESHOLD: u32 = 5;
+const DEFAULT_COOLDOWN_SECS: u64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub threshold: u32,
+    pub cooldown_secs: u64,
+}
+
+struct Inner {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// Set while the half-open probe is in flight.
+    probe_started: Option<Instant>,
+}
+
+pub struct CircuitBreaker {
+    /// `0` disables the breaker.
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    /// `LIBRARIAN_BREAKER_THRESHOLD` (default 5, `0` disables) and
+    /// `LIBRARIAN_BREAKER_COOLDOWN_SECS` (default 30).
+    pub fn from_env() -> Self {
+        let threshold = env::var("LIBRARIAN_BREAKER_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_THRESHOLD);
+        let cooldown_secs = env::var("LIBRARIAN_BREAKER_COOLDOWN_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_COOLDOWN_SECS);
+        Self::new(threshold, Duration::from_secs(cooldown_secs))
+    }
+
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            inner: Mutex::new(Inner {
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_started: None,
+            }),
+        }
+    }
+
+    fn state_of(&self, inner: &Inner) -> BreakerState {
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(opened) if opened.elapsed() < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether this request may call the agent. In the half-open state only one
+    /// probe is admitted; a probe that never reports back (e.g. the client hung
+    /// up) is replaced after another cooldown.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let allowed = match self.state_of(&inner) {
+            BreakerState::Closed => true,
+            BreakerState::Open => false,
+            BreakerState::HalfOpen => match inner.probe_started {
+                Some(started) if started.elapsed() < self.cooldown => false,
+                _ => {
+                    inner.probe_started = Some(Instant::now());
+                    tracing::info!("Agent circuit half-open: probing the completion API");
+                    true
+                }
+            },
+        };
+        if !allowed {
+            METRICS
+                .agent_breaker_short_circuits_total
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.opened_at.is_some() {
+            tracing::info!("Agent circuit closed: completion API recovered");
+        }
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_started = None;
+        METRICS.agent_breaker_open.store(0, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let reopen = inner.probe_started.take().is_some();
+        if reopen || (inner.opened_at.is_none() && inner.consecutive_failures >= self.threshold) {
+            inner.opened_at = Some(Instant::now());
+            METRICS.agent_breaker_open.store(1, Ordering::Relaxed);
+            METRICS
+                .agent_breaker_trips_total
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                consecutive_failures = inner.consecutive_failures,
+                cooldown_secs = self.cooldown.as_secs(),
+                "Agent circuit open: serving catalog-only results"
+            );
+        }
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let inner = self.inner.lock().unwrap();
+        BreakerStatus {
+            state: self.state_of(&inner),
+            consecutive_failures: inner.consecutive_failures,
+            threshold: self.threshold,
+            cooldown_secs: self.cooldown.as_secs(),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// src/backend/cache.rs
+//! HTTP caching for the read-only endpoints. The `ETag` is derived from the
+//! catalog hash, so it changes exactly when a reload changes the catalog.
+use super::metrics::METRICS;
+use super::{LibrarianHandle, McpEntry};
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{
+        HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::atomic::Ordering;
+
+const DEFAULT_MAX_AGE_SECS: u64 = 60;
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// SHA-256 over the served and disabled entries, as they are serialized.
+pub fn catalog_hash(catalog: &[McpEntry], disabled: &[McpEntry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in catalog.iter().chain(disabled) {
+        hasher.update(serde_json::to_vec(entry).unwrap_or_default());
+        hasher.update(b"\n");
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[derive(Clone)]
+pub struct CachePolicy {
+    pub handle: LibrarianHandle,
+    /// Paid responses are cacheable by the caller only, never by shared caches.
+    pub private: bool,
+}
+
+impl CachePolicy {
+    fn cache_control(&self) -> String {
+        let max_age = env::var("LIBRARIAN_CACHE_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_AGE_SECS);
+        let scope = if self.private { "private" } else { "public" };
+        format!("{}, max-age={}, must-revalidate", scope, max_age)
+    }
+}
+
+/// Sets `Cache-Control` and `ETag`, answering `304` when `If-None-Match`
+/// already names the current representation. The tag covers the catalog hash,
+/// the URI and, for `POST`, the request body, so it survives a restart.
+pub async fn cache_layer(
+    State(policy): State<CachePolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_REQUEST_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(policy.handle.current().catalog_hash.as_bytes());
+    hasher.update(parts.uri.to_string().as_bytes());
+    hasher.update(&body);
+    let etag = format!("\"{}\"", &hex::encode(hasher.finalize())[..32]);
+    let cache_control = policy.cache_control();
+
+    let matches = parts
+        .headers
+        .get(IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == etag
+            })
+        });
+    if matches {
+        METRICS
+            .query_cache_hits_total
+            .fetch_add(1, Ordering::Relaxed);
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(ETAG, etag), (CACHE_CONTROL, cache_control)],
+        )
+            .into_response();
+    }
+
+    let mut response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_success() {
+        METRICS
+            .query_cache_misses_total
+            .fetch_add(1, Ordering::Relaxed);
+        let headers = response.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&etag) {
+            headers.insert(ETAG, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&cache_control) {
+            headers.insert(CACHE_CONTROL, value);
+        }
+    }
+    response
+}
+
+/// `/discover` answers are generated per request and must never be reused.
+pub async fn no_store_layer(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
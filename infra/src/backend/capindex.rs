@@ -0,0 +1,94 @@
+// src/backend/capindex.rs
+//! Inverted index from capability to catalog entries, so a capability filter
+//! narrows the candidate universe before similarity ranking instead of
+//! discarding most of what the vector search returned.
+use super::McpEntry;
+use rig::OneOrMany;
+use rig::embeddings::Embedding;
+use std::collections::BTreeMap;
+
+/// Lowercased capability -> positions in `Librarian::embeddings` (which is in
+/// catalog order).
+#[derive(Debug, Default)]
+pub struct CapabilityIndex {
+    postings: BTreeMap<String, Vec<usize>>,
+}
+
+impl CapabilityIndex {
+    pub fn build<'a>(entries: impl IntoIterator<Item = &'a McpEntry>) -> Self {
+        let mut postings: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (id, entry) in entries.into_iter().enumerate() {
+            for capability in &entry.capabilities {
+                let ids = postings.entry(capability.to_lowercase()).or_default();
+                if ids.last() != Some(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        CapabilityIndex { postings }
+    }
+
+    /// Distinct capabilities indexed.
+    pub fn len(&self) -> usize {
+        self.postings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.postings.is_empty()
+    }
+
+    /// Entries with a capability containing `needle` (already lowercase), the
+    /// same substring rule `CandidateFilter` applies. Sorted and deduplicated.
+    pub fn lookup(&self, needle: &str) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .postings
+            .iter()
+            .filter(|(capability, _)| capability.contains(needle))
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Top `k` of the entries at `ids` by similarity to `query`, best first. Like the
+/// in-memory store, an entry scores as its best-matching embedding.
+pub fn rank(
+    query: &[f64],
+    embeddings: &[(McpEntry, OneOrMany<Embedding>)],
+    ids: &[usize],
+    k: usize,
+) -> Vec<(f64, McpEntry)> {
+    let mut scored: Vec<(f64, usize)> = ids
+        .iter()
+        .filter_map(|&id| {
+            let (_, vectors) = embeddings.get(id)?;
+            let score = vectors
+                .iter()
+                .map(|e| cosine_similarity(query, &e.vec))
+                .fold(f64::NEG_INFINITY, f64::max);
+            Some((score, id))
+        })
+        .collect();
+    // ties by endpoint, as in `search::rank_order`
+    scored.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| embeddings[a.1].0.endpoint.cmp(&embeddings[b.1].0.endpoint))
+    });
+    scored
+        .into_iter()
+        .take(k)
+        .map(|(score, id)| (score, embeddings[id].0.clone()))
+        .collect()
+}
@@ -0,0 +1,498 @@
+// src/backend/catalog.rs
+//! Deterministic, free browsing of the catalog, alongside the paid fuzzy search.
+use super::LibrarianHandle;
+use super::McpEntry;
+use super::feedback::FeedbackStore;
+use super::lint;
+use super::metrics;
+use super::mirrors;
+use super::normalize;
+use super::redact;
+use super::reload::Reloader;
+use super::request::{ApiJson, RequestError};
+use super::store::{self, Store};
+use crate::utils::{self, ADMIN_SOURCE, CATALOG_PATH};
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+
+const DEFAULT_STALE_AFTER_SECS: i64 = 24 * 60 * 60;
+const DEFAULT_FUZZY_MAX_DISTANCE: usize = 3;
+/// Longest name `lookup` tries a fuzzy match for; a longer one only matches exactly.
+const MAX_FUZZY_NAME_CHARS: usize = 128;
+
+#[derive(Deserialize, Default)]
+pub struct CatalogParams {
+    #[serde(default)]
+    pub include_disabled: bool,
+    /// Keep only entries loaded from this file path or URL.
+    pub source: Option<String>,
+}
+
+/// `GET /catalog`: the loaded entries; `?include_disabled=true` appends the
+/// disabled ones (serialized with `"enabled": false`) for auditing and
+/// `?source=` narrows to one originating file.
+pub async fn catalog_handler(
+    State(handle): State<LibrarianHandle>,
+    Query(params): Query<CatalogParams>,
+) -> impl IntoResponse {
+    let librarian = handle.current();
+    let mut entries: Vec<&McpEntry> = librarian.catalog.iter().collect();
+    if params.include_disabled {
+        entries.extend(librarian.disabled.iter());
+    }
+    if let Some(source) = &params.source {
+        entries.retain(|e| e.source.as_deref() == Some(source.as_str()));
+    }
+    AxumJson(json!({
+        "count": entries.len(),
+        "disabled": librarian.disabled.len(),
+        "entries": redact::public_values(entries),
+    }))
+}
+
+/// Page size of `GET /mcps` without `?limit=`.
+const DEFAULT_MCPS_LIMIT: usize = 50;
+/// Larger `?limit=` values are clamped to this rather than refused.
+const MAX_MCPS_LIMIT: usize = 200;
+
+#[derive(Deserialize, Default)]
+pub struct McpsParams {
+    /// Keep entries with a capability containing this (case-insensitive).
+    pub capability: Option<String>,
+    /// Signed so a negative value gets a field error, not a bare rejection.
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// `GET /mcps`: the entries `/discover` ranks from, paged in catalog order as
+/// `{total, offset, limit, items}`. `total` counts every match, not the page.
+pub async fn mcps_handler(
+    State(handle): State<LibrarianHandle>,
+    Query(params): Query<McpsParams>,
+) -> Response {
+    let non_negative = |field: &str, value: Option<i64>| match value {
+        Some(v) if v < 0 => Err(RequestError {
+            status: StatusCode::BAD_REQUEST,
+            field: Some(field.to_string()),
+            message: format!("{} must not be negative", field),
+        }),
+        Some(v) => Ok(Some(v as usize)),
+        None => Ok(None),
+    };
+    let (limit, offset) = match (
+        non_negative("limit", params.limit),
+        non_negative("offset", params.offset),
+    ) {
+        (Ok(limit), Ok(offset)) => (
+            limit.unwrap_or(DEFAULT_MCPS_LIMIT).min(MAX_MCPS_LIMIT),
+            offset.unwrap_or(0),
+        ),
+        (Err(e), _) | (_, Err(e)) => return e.into_response(),
+    };
+
+    let librarian = handle.current();
+    let capability = params
+        .capability
+        .as_deref()
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty());
+    let matching: Vec<&McpEntry> = librarian
+        .catalog
+        .iter()
+        .filter(|e| {
+            capability.as_ref().is_none_or(|c| {
+                e.capabilities
+                    .iter()
+                    .any(|cap| cap.to_lowercase().contains(c))
+            })
+        })
+        .collect();
+    let total = matching.len();
+    let page: Vec<&McpEntry> = matching.into_iter().skip(offset).take(limit).collect();
+    AxumJson(json!({
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+        "items": redact::public_values(page),
+    }))
+    .into_response()
+}
+
+/// `GET /catalog/by-tag/{tag}`: every catalog entry carrying `tag` (case-insensitive).
+pub async fn by_tag_handler(
+    State(handle): State<LibrarianHandle>,
+    Path(tag): Path<String>,
+) -> impl IntoResponse {
+    let librarian = handle.current();
+    let tag = normalize::active()
+        .tags(std::slice::from_ref(&tag))
+        .pop()
+        .unwrap_or_default();
+    let entries: Vec<&McpEntry> = librarian
+        .catalog
+        .iter()
+        .filter(|e| e.tags.contains(&tag))
+        .collect();
+    AxumJson(json!({
+        "tag": tag,
+        "count": entries.len(),
+        "entries": redact::public_values(entries),
+    }))
+}
+
+/// `GET /catalog/stats`: aggregate composition of the loaded catalog. An entry
+/// is stale when its last verification is older than `LIBRARIAN_STALE_AFTER_SECS`
+/// (default one day); entries never verified are counted separately. `feedback`
+/// holds client-reported outcomes per endpoint, as reported through `/feedback`.
+pub async fn stats_handler(
+    State(handle): State<LibrarianHandle>,
+    Extension(feedback): Extension<Arc<FeedbackStore>>,
+) -> impl IntoResponse {
+    let librarian = handle.current();
+    let catalog = &librarian.catalog;
+    let stale_after = env::var("LIBRARIAN_STALE_AFTER_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_STALE_AFTER_SECS);
+    let now = Utc::now();
+
+    let mut by_tag: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut by_capability: BTreeMap<String, usize> = BTreeMap::new();
+    let (mut requires_auth, mut stale, mut never_checked, mut capability_total) = (0, 0, 0, 0);
+    for entry in catalog {
+        for tag in &entry.tags {
+            *by_tag.entry(tag).or_default() += 1;
+        }
+        for capability in &entry.capabilities {
+            *by_capability.entry(capability.to_lowercase()).or_default() += 1;
+        }
+        capability_total += entry.capabilities.len();
+        if entry.auth.required {
+            requires_auth += 1;
+        }
+        match librarian.verification.get(&entry.endpoint) {
+            Some(result) if (now - result.checked_at).num_seconds() > stale_after => stale += 1,
+            Some(_) => {}
+            None => never_checked += 1,
+        }
+    }
+    let avg_capabilities = if catalog.is_empty() {
+        0.0
+    } else {
+        capability_total as f64 / catalog.len() as f64
+    };
+
+    AxumJson(json!({
+        "total_entries": catalog.len(),
+        "requires_auth": requires_auth,
+        "stale": stale,
+        "never_checked": never_checked,
+        "stale_after_secs": stale_after,
+        "avg_capabilities_per_entry": avg_capabilities,
+        "by_tag": by_tag,
+        "by_capability": by_capability,
+        "feedback": feedback.totals(),
+        "recommendations": metrics::RECOMMENDATION_HITS.snapshot(),
+    }))
+}
+
+/// Edit distance between two strings, by chars.
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Finds an entry by name: exact, then case-insensitive, then the closest name
+/// within `max_distance` edits (case-insensitive). Returns the match type too.
+/// Only names whose length is within `max_distance` of the wanted one are
+/// compared, and names over `MAX_FUZZY_NAME_CHARS` get no fuzzy match.
+pub fn lookup<'a>(
+    catalog: &'a [McpEntry],
+    name: &str,
+    max_distance: usize,
+) -> Option<(&'static str, &'a McpEntry)> {
+    if let Some(entry) = catalog.iter().find(|e| e.name == name) {
+        return Some(("exact", entry));
+    }
+    if let Some(entry) = catalog.iter().find(|e| e.name.eq_ignore_ascii_case(name)) {
+        return Some(("ci", entry));
+    }
+    let wanted = name.to_lowercase();
+    let wanted_len = wanted.chars().count();
+    if wanted_len > MAX_FUZZY_NAME_CHARS {
+        return None;
+    }
+    catalog
+        .iter()
+        .map(|e| (e.name.to_lowercase(), e))
+        // the length difference is a lower bound on the distance
+        .filter(|(candidate, _)| candidate.chars().count().abs_diff(wanted_len) <= max_distance)
+        .map(|(candidate, e)| (levenshtein(&candidate, &wanted), e))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, entry)| ("fuzzy", entry))
+}
+
+/// `GET /mcp/{name}`: direct lookup of one catalog entry, forgiving small
+/// spelling differences up to `LIBRARIAN_FUZZY_MAX_DISTANCE` edits (default 3).
+pub async fn mcp_handler(
+    State(handle): State<LibrarianHandle>,
+    Path(name): Path<String>,
+) -> Response {
+    let librarian = handle.current();
+    let max_distance = env::var("LIBRARIAN_FUZZY_MAX_DISTANCE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_FUZZY_MAX_DISTANCE);
+
+    match lookup(&librarian.catalog, &name, max_distance) {
+        Some((match_type, entry)) => AxumJson(json!({
+            "match_type": match_type,
+            "entry": redact::public_value(entry),
+        }))
+        .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            AxumJson(json!({ "error": format!("No catalog entry named {:?}", name) })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct AddEntryParams {
+    /// Also write the updated catalog back to the catalog file.
+    #[serde(default)]
+    pub persist: bool,
+}
+
+fn validate_new_entry(catalog: &[McpEntry], entry: &McpEntry) -> Result<(), RequestError> {
+    let invalid = |status: StatusCode, field: &str, message: String| RequestError {
+        status,
+        field: Some(field.to_string()),
+        message,
+    };
+    if entry.name.trim().is_empty() {
+        return Err(invalid(
+            StatusCode::BAD_REQUEST,
+            "name",
+            "name must not be empty".to_string(),
+        ));
+    }
+    for deployment in mirrors::deployments(entry) {
+        match url::Url::parse(&deployment.endpoint) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+            _ => {
+                return Err(invalid(
+                    StatusCode::BAD_REQUEST,
+                    "endpoint",
+                    format!("endpoint {:?} is not an http(s) URL", deployment.endpoint),
+                ));
+            }
+        }
+    }
+    if catalog.iter().any(|e| e.name == entry.name) {
+        return Err(invalid(
+            StatusCode::CONFLICT,
+            "name",
+            format!("A catalog entry named {:?} already exists", entry.name),
+        ));
+    }
+    for deployment in mirrors::deployments(entry) {
+        if catalog
+            .iter()
+            .any(|e| mirrors::serves(e, &deployment.endpoint))
+        {
+            return Err(invalid(
+                StatusCode::CONFLICT,
+                "endpoint",
+                format!("A catalog entry already serves {:?}", deployment.endpoint),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `POST /admin/catalog`: validates and embeds one entry and swaps in an index
+/// that includes it; `?persist=true` also writes the catalog file, otherwise
+/// the entry lasts until the next reload. Runs under `Reloader::exclusive`, so
+/// a concurrent reload or add can't drop it. `persist` is refused (`409`) when
+/// the catalog comes from `MCPS_URL`, which the next reload would read instead.
+/// Answers `201` with the entry's name and any lint warnings.
+pub async fn add_entry_handler(
+    State(handle): State<LibrarianHandle>,
+    Query(params): Query<AddEntryParams>,
+    Extension(audit): Extension<Arc<dyn Store>>,
+    Extension(reloader): Extension<Arc<Reloader>>,
+    ApiJson(mut entry): ApiJson<McpEntry>,
+) -> Response {
+    if let Err(e) = mirrors::settle(&mut entry) {
+        return RequestError {
+            status: StatusCode::BAD_REQUEST,
+            field: Some("endpoint".to_string()),
+            message: e.to_string(),
+        }
+        .into_response();
+    }
+    let _writes = reloader.exclusive().await;
+    let current = handle.current();
+    if params.persist
+        && let Some(remote) = &current.remote
+    {
+        return RequestError {
+            status: StatusCode::CONFLICT,
+            field: Some("persist".to_string()),
+            message: format!(
+                "The catalog is loaded from {}; add the entry there instead of persisting it",
+                remote.url()
+            ),
+        }
+        .into_response();
+    }
+    let known: Vec<McpEntry> = current
+        .catalog
+        .iter()
+        .chain(&current.disabled)
+        .cloned()
+        .collect();
+    if let Err(e) = validate_new_entry(&known, &entry) {
+        return e.into_response();
+    }
+    entry.source.get_or_insert_with(|| {
+        if params.persist {
+            CATALOG_PATH
+        } else {
+            ADMIN_SOURCE
+        }
+        .to_string()
+    });
+    normalize::active().apply(&mut entry);
+    let warnings = lint::lint_catalog(std::slice::from_ref(&entry), lint::min_desc_len());
+    let name = entry.name.clone();
+    let endpoint = entry.endpoint.clone();
+
+    let librarian = match utils::add_catalog_entry(&current, entry).await {
+        Ok(librarian) => librarian,
+        Err(e) => {
+            tracing::error!("Adding catalog entry {:?} failed: {:#}", name, e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                AxumJson(json!({ "error": format!("Failed to embed entry: {}", e) })),
+            )
+                .into_response();
+        }
+    };
+    if params.persist
+        && let Err(e) = utils::persist_catalog(&librarian, CATALOG_PATH)
+    {
+        tracing::error!("Persisting catalog after adding {:?} failed: {:#}", name, e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({ "error": format!("Entry not added: {}", e) })),
+        )
+            .into_response();
+    }
+    let count = librarian.catalog.len();
+    handle.swap(librarian);
+    tracing::info!(
+        "Added catalog entry {:?} via admin API: {} entries",
+        name,
+        count
+    );
+    store::audit(
+        audit.as_ref(),
+        "catalog_add",
+        json!({ "name": name, "endpoint": endpoint, "persisted": params.persist }),
+    );
+
+    (
+        StatusCode::CREATED,
+        AxumJson(json!({
+            "name": name,
+            "catalog_entries": count,
+            "persisted": params.persist,
+            "warnings": warnings,
+        })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Vec<McpEntry> {
+        ["example/weather", "example/geocode", "example/Issues"]
+            .iter()
+            .map(|name| {
+                serde_json::from_value(json!({
+                    "name": name,
+                    "endpoint": "https://example.com/mcp",
+                    "version": "1.0.0",
+                    "capabilities": [],
+                    "desc": "",
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn found(name: &str) -> Option<(&'static str, String)> {
+        let catalog = catalog();
+        lookup(&catalog, name, DEFAULT_FUZZY_MAX_DISTANCE)
+            .map(|(kind, entry)| (kind, entry.name.clone()))
+    }
+
+    #[test]
+    fn lookup_prefers_exact_then_case_insensitive_then_closest() {
+        assert_eq!(
+            found("example/weather"),
+            Some(("exact", "example/weather".to_string()))
+        );
+        assert_eq!(
+            found("EXAMPLE/ISSUES"),
+            Some(("ci", "example/Issues".to_string()))
+        );
+        assert_eq!(
+            found("example/wether"),
+            Some(("fuzzy", "example/weather".to_string()))
+        );
+        assert_eq!(
+            found("Example/GeoCodes"),
+            Some(("fuzzy", "example/geocode".to_string()))
+        );
+        assert_eq!(found("example/translate"), None);
+    }
+
+    #[test]
+    fn overlong_names_get_no_fuzzy_match() {
+        let long = format!("example/weather{}", "x".repeat(MAX_FUZZY_NAME_CHARS));
+        let catalog = catalog();
+        assert!(lookup(&catalog, &long, usize::MAX).is_none());
+        assert!(lookup(&catalog, "example/weatherx", usize::MAX).is_some());
+    }
+}
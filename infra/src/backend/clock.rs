@@ -0,0 +1,163 @@
+// src/backend/clock.rs
+//! The current time in the discover prompt, so the rubric's freshness
+//! component is judged against "now" rather than the model's training cutoff.
+//! `LIBRARIAN_PROMPT_TIME` is `rfc3339` (the default: the same second-precision
+//! UTC form as `last_checked` in responses), `date` (the day only) or `off`;
+//! anything else fails startup.
+//! When on, each verified candidate also carries its `last_checked` and a
+//! precomputed `checked_hours_ago`, so recency doesn't rest on the model's
+//! date arithmetic.
+use super::settings::select_once;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use serde::Deserialize;
+use std::env;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptTime {
+    Off,
+    #[default]
+    Rfc3339,
+    Date,
+}
+
+impl PromptTime {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "off" => Some(PromptTime::Off),
+            "rfc3339" => Some(PromptTime::Rfc3339),
+            "date" => Some(PromptTime::Date),
+            _ => None,
+        }
+    }
+
+    /// `LIBRARIAN_PROMPT_TIME`, `rfc3339` when unset.
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_PROMPT_TIME") {
+            Err(_) => Ok(PromptTime::default()),
+            Ok(raw) => PromptTime::parse(raw.trim()).ok_or_else(|| {
+                anyhow!(
+                    "Unknown LIBRARIAN_PROMPT_TIME {:?} (expected rfc3339, date or off)",
+                    raw
+                )
+            }),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PromptTime::Off => "off",
+            PromptTime::Rfc3339 => "rfc3339",
+            PromptTime::Date => "date",
+        }
+    }
+
+    /// `now` in this format; `None` when off.
+    pub fn format(self, now: DateTime<Utc>) -> Option<String> {
+        match self {
+            PromptTime::Off => None,
+            PromptTime::Rfc3339 => Some(timestamp(now)),
+            PromptTime::Date => Some(now.format("%Y-%m-%d").to_string()),
+        }
+    }
+
+    /// Whether `text` is a well-formed value of this format.
+    pub fn is_well_formed(self, text: &str) -> bool {
+        match self {
+            PromptTime::Off => false,
+            PromptTime::Rfc3339 => {
+                DateTime::parse_from_rfc3339(text).is_ok() && text.ends_with('Z')
+            }
+            PromptTime::Date => NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok(),
+        }
+    }
+
+    /// The prompt line stating the current time; `None` when off.
+    pub fn directive(self, now: DateTime<Utc>) -> Option<String> {
+        let now = self.format(now)?;
+        Some(format!(
+            "\nCurrent time (UTC): {}. Judge freshness against it, using each candidate's \
+             last_checked and checked_hours_ago; a candidate without them has never been verified.",
+            now
+        ))
+    }
+}
+
+/// The form `last_checked` takes everywhere: RFC 3339, whole seconds, `Z`.
+pub fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Whole hours from `checked` to `now`, never negative.
+pub fn hours_since(checked: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    (now - checked).num_hours().max(0)
+}
+
+static ACTIVE: OnceLock<PromptTime> = OnceLock::new();
+
+/// Fixes the prompt time format; see `settings::select_once`.
+pub fn select(format: PromptTime) -> Result<()> {
+    select_once(&ACTIVE, format, "Prompt time format")
+}
+
+/// The selected format, or `rfc3339` when nothing was selected.
+pub fn active() -> PromptTime {
+    ACTIVE.get().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(raw: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(raw)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn formats_in_utc_at_whole_seconds() {
+        let now = at("2026-03-01T09:30:15.987654+02:00");
+        assert_eq!(
+            PromptTime::Rfc3339.format(now).as_deref(),
+            Some("2026-03-01T07:30:15Z")
+        );
+        assert_eq!(
+            PromptTime::Date
+                .format(at("2026-12-31T23:59:59Z"))
+                .as_deref(),
+            Some("2026-12-31")
+        );
+        assert_eq!(PromptTime::Off.format(now), None);
+    }
+
+    #[test]
+    fn directive_states_the_formatted_time() {
+        let now = at("2026-03-01T09:30:15Z");
+        for format in [PromptTime::Rfc3339, PromptTime::Date] {
+            let value = format.format(now).unwrap();
+            assert!(format.is_well_formed(&value));
+            assert!(format.directive(now).unwrap().contains(&value));
+        }
+        assert_eq!(PromptTime::Off.directive(now), None);
+        assert!(!PromptTime::Rfc3339.is_well_formed("2026-03-01T09:30:15+02:00"));
+        assert!(!PromptTime::Date.is_well_formed("2026-02-30"));
+    }
+
+    #[test]
+    fn hours_since_rounds_down_and_never_goes_negative() {
+        let now = at("2026-03-01T09:30:15Z");
+        assert_eq!(hours_since(at("2026-02-28T08:00:00Z"), now), 25);
+        assert_eq!(hours_since(at("2026-03-01T08:40:00Z"), now), 0);
+        // a check stamped after `now` (clock skew) counts as just now
+        assert_eq!(hours_since(at("2026-03-02T00:00:00Z"), now), 0);
+    }
+
+    #[test]
+    fn parse_accepts_only_known_formats() {
+        assert_eq!(PromptTime::parse("date"), Some(PromptTime::Date));
+        assert_eq!(PromptTime::parse("iso"), None);
+    }
+}
@@ -0,0 +1,55 @@
+// src/backend/cors.rs
+use super::filters::FILTER_STATS_HEADER;
+use super::idempotency::{IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAY_HEADER};
+use super::payer::{PAYMENT_HEADER, PAYMENT_RESPONSE_HEADER};
+use super::settlement::PAYMENT_STATE_HEADER;
+use super::{COMPLETION_MODEL_HEADER, FALLBACK_MODEL_HEADER, RETRIEVAL_MODEL_HEADER};
+use anyhow::{Context as _, Result};
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use std::env;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Builds the CORS layer from `LIBRARIAN_CORS_ORIGINS` (comma-separated origins,
+/// or `*` for permissive dev mode). Unset means no cross-origin access at all.
+pub fn cors_layer_from_env() -> Result<CorsLayer> {
+    let origins = env::var("LIBRARIAN_CORS_ORIGINS").unwrap_or_default();
+    let origins = origins.trim();
+    if origins.is_empty() {
+        return Ok(CorsLayer::new());
+    }
+
+    let allow_origin = if origins == "*" {
+        AllowOrigin::any()
+    } else {
+        let list = origins
+            .split(',')
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .map(|o| {
+                HeaderValue::from_str(o)
+                    .with_context(|| format!("Invalid origin {:?} in LIBRARIAN_CORS_ORIGINS", o))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(list)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static(PAYMENT_HEADER),
+            HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+        ])
+        // browser clients must be able to read the x402 receipt and our diagnostics
+        .expose_headers([
+            HeaderName::from_static(PAYMENT_RESPONSE_HEADER),
+            HeaderName::from_static(FILTER_STATS_HEADER),
+            HeaderName::from_static(RETRIEVAL_MODEL_HEADER),
+            HeaderName::from_static(COMPLETION_MODEL_HEADER),
+            HeaderName::from_static(FALLBACK_MODEL_HEADER),
+            HeaderName::from_static(IDEMPOTENT_REPLAY_HEADER),
+            HeaderName::from_static(PAYMENT_STATE_HEADER),
+        ]))
+}
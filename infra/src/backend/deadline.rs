@@ -0,0 +1,348 @@
+// src/backend/deadline.rs
+//! Per-phase time limits for `/discover`, so a slow answer says where the time
+//! went. Retrieval (query embedding and ranking) and each model call have their
+//! own limit, and the handler has an overall deadline; a phase never runs past
+//! what remains of the deadline. Around all of it, `request_deadline_layer`
+//! bounds the whole request end to end, payment verification included, so a
+//! client has an upper bound on how long a call can take. Each limit fails
+//! with its own code and a `504`:
+//!
+//! - `RETRIEVAL_TIMEOUT`: `LIBRARIAN_RETRIEVAL_TIMEOUT_SECS` (default 10)
+//! - `MODEL_TIMEOUT`: `LIBRARIAN_MODEL_TIMEOUT_SECS` (default 60), per call
+//! - `DEADLINE_EXCEEDED`: `LIBRARIAN_DISCOVER_DEADLINE_SECS` (default 90)
+//! - `REQUEST_TIMEOUT`: `LIBRARIAN_REQUEST_DEADLINE_SECS` (default 120; `0`
+//!   disables it)
+//!
+//! Once a payment has verified and the handler has been reached, the
+//! end-to-end limit no longer cancels the request: the handler's phases already
+//! stop at what remains of it, and cutting settlement short could charge the
+//! client for an answer it never receives. Such a request finishes late
+//! instead: with the paid answer if the handler made it in time, otherwise with
+//! the handler's own `504`, which x402 doesn't settle. A `REQUEST_TIMEOUT` from
+//! this layer therefore means nothing was charged.
+use super::settlement::Served;
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::json;
+use std::env;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RETRIEVAL_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_MODEL_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_DISCOVER_DEADLINE_SECS: u64 = 90;
+const DEFAULT_REQUEST_DEADLINE_SECS: u64 = 120;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseTimeouts {
+    pub retrieval: Duration,
+    pub model: Duration,
+    pub total: Duration,
+    /// End to end, payment included; `None` when disabled.
+    pub request: Option<Duration>,
+}
+
+impl PhaseTimeouts {
+    pub fn from_env() -> Self {
+        let secs = |key: &str, default: u64| {
+            Duration::from_secs(
+                env::var(key)
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .filter(|v| *v > 0)
+                    .unwrap_or(default),
+            )
+        };
+        PhaseTimeouts {
+            retrieval: secs(
+                "LIBRARIAN_RETRIEVAL_TIMEOUT_SECS",
+                DEFAULT_RETRIEVAL_TIMEOUT_SECS,
+            ),
+            model: secs("LIBRARIAN_MODEL_TIMEOUT_SECS", DEFAULT_MODEL_TIMEOUT_SECS),
+            total: secs(
+                "LIBRARIAN_DISCOVER_DEADLINE_SECS",
+                DEFAULT_DISCOVER_DEADLINE_SECS,
+            ),
+            request: match env::var("LIBRARIAN_REQUEST_DEADLINE_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Some(Duration::from_secs(DEFAULT_REQUEST_DEADLINE_SECS)),
+            },
+        }
+    }
+}
+
+static TIMEOUTS: LazyLock<PhaseTimeouts> = LazyLock::new(PhaseTimeouts::from_env);
+
+pub fn timeouts() -> PhaseTimeouts {
+    *TIMEOUTS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutError {
+    Retrieval(Duration),
+    Model(Duration),
+    Deadline(Duration),
+    Request(Duration),
+}
+
+impl TimeoutError {
+    pub fn code(self) -> &'static str {
+        match self {
+            TimeoutError::Retrieval(_) => "RETRIEVAL_TIMEOUT",
+            TimeoutError::Model(_) => "MODEL_TIMEOUT",
+            TimeoutError::Deadline(_) => "DEADLINE_EXCEEDED",
+            TimeoutError::Request(_) => "REQUEST_TIMEOUT",
+        }
+    }
+}
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeoutError::Retrieval(limit) => {
+                write!(f, "retrieval did not finish within {:?}", limit)
+            }
+            TimeoutError::Model(limit) => write!(f, "the model did not answer within {:?}", limit),
+            TimeoutError::Deadline(limit) => {
+                write!(f, "the request did not finish within {:?}", limit)
+            }
+            TimeoutError::Request(limit) => write!(
+                f,
+                "the request, payment included, did not finish within {:?}",
+                limit
+            ),
+        }
+    }
+}
+
+impl IntoResponse for TimeoutError {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::GATEWAY_TIMEOUT,
+            AxumJson(json!({
+                "error": {
+                    "code": self.code(),
+                    "message": self.to_string(),
+                }
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// When `request_deadline_layer` let the request in, for the handler's phases
+/// to stop at what remains of the end-to-end limit.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestStarted(pub Instant);
+
+/// Enforces `PhaseTimeouts::request` around everything inside it, up to the
+/// point a payment is verified.
+pub async fn request_deadline_layer(request: Request, next: Next) -> Response {
+    match timeouts().request {
+        Some(limit) => run_within(limit, request, next).await,
+        None => next.run(request).await,
+    }
+}
+
+async fn run_within(limit: Duration, mut request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    // shared with `payment_state_layer`, so the flag `served_layer` sets is seen here
+    let served = Served::default();
+    request.extensions_mut().insert(RequestStarted(started));
+    request.extensions_mut().insert(served.clone());
+    let mut inner = std::pin::pin!(next.run(request));
+    match tokio::time::timeout(limit, &mut inner).await {
+        Ok(response) => response,
+        Err(_) if served.get() => {
+            tracing::warn!(
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "Paid request passed its end-to-end limit of {:?}; letting it settle",
+                limit
+            );
+            inner.await
+        }
+        Err(_) => {
+            let error = TimeoutError::Request(limit);
+            tracing::warn!(
+                code = error.code(),
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "{}",
+                error
+            );
+            error.into_response()
+        }
+    }
+}
+
+/// One request's clock.
+pub struct Deadline {
+    started: Instant,
+    timeouts: PhaseTimeouts,
+    request_started: Option<Instant>,
+}
+
+impl Deadline {
+    pub fn start(timeouts: PhaseTimeouts) -> Self {
+        Deadline {
+            started: Instant::now(),
+            timeouts,
+            request_started: None,
+        }
+    }
+
+    /// Also stops phases at the end-to-end limit, counted from `started`.
+    pub fn within(mut self, started: Option<RequestStarted>) -> Self {
+        self.request_started = started.map(|RequestStarted(at)| at);
+        self
+    }
+
+    pub async fn retrieval<F: Future>(&self, phase: F) -> Result<F::Output, TimeoutError> {
+        self.run(phase, self.timeouts.retrieval, TimeoutError::Retrieval)
+            .await
+    }
+
+    pub async fn model<F: Future>(&self, phase: F) -> Result<F::Output, TimeoutError> {
+        self.run(phase, self.timeouts.model, TimeoutError::Model)
+            .await
+    }
+
+    /// Runs `phase` under its own limit, the remaining deadline or the
+    /// remaining end-to-end limit, whichever is shortest, and reports whichever
+    /// ran out.
+    async fn run<F: Future>(
+        &self,
+        phase: F,
+        limit: Duration,
+        phase_error: fn(Duration) -> TimeoutError,
+    ) -> Result<F::Output, TimeoutError> {
+        let mut budget = (limit, phase_error(limit));
+        let remaining = self.timeouts.total.saturating_sub(self.started.elapsed());
+        if remaining < budget.0 {
+            budget = (remaining, TimeoutError::Deadline(self.timeouts.total));
+        }
+        if let (Some(request), Some(started)) = (self.timeouts.request, self.request_started) {
+            let remaining = request.saturating_sub(started.elapsed());
+            if remaining < budget.0 {
+                budget = (remaining, TimeoutError::Request(request));
+            }
+        }
+        let (budget, error) = budget;
+        tokio::time::timeout(budget, phase).await.map_err(|_| {
+            tracing::warn!(
+                code = error.code(),
+                elapsed_ms = self.started.elapsed().as_millis() as u64,
+                "{}",
+                error
+            );
+            error
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::settlement::served_layer;
+
+    fn timeouts(retrieval_ms: u64, model_ms: u64, total_ms: u64) -> PhaseTimeouts {
+        PhaseTimeouts {
+            retrieval: Duration::from_millis(retrieval_ms),
+            model: Duration::from_millis(model_ms),
+            total: Duration::from_millis(total_ms),
+            request: None,
+        }
+    }
+
+    /// Stands in for an embedding call or a model completion taking `ms`.
+    async fn slow(ms: u64) -> &'static str {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        "done"
+    }
+
+    #[tokio::test]
+    async fn slow_retrieval_is_a_retrieval_timeout() {
+        let deadline = Deadline::start(timeouts(20, 5_000, 10_000));
+        let error = deadline.retrieval(slow(5_000)).await.unwrap_err();
+        assert_eq!(error.code(), "RETRIEVAL_TIMEOUT");
+        assert_eq!(deadline.model(slow(1)).await, Ok("done"));
+    }
+
+    #[tokio::test]
+    async fn slow_model_is_a_model_timeout() {
+        let deadline = Deadline::start(timeouts(5_000, 20, 10_000));
+        assert_eq!(deadline.retrieval(slow(1)).await, Ok("done"));
+        let error = deadline.model(slow(5_000)).await.unwrap_err();
+        assert_eq!(error, TimeoutError::Model(Duration::from_millis(20)));
+    }
+
+    #[tokio::test]
+    async fn phases_stop_at_the_overall_deadline() {
+        let deadline = Deadline::start(timeouts(5_000, 5_000, 20));
+        let error = deadline.model(slow(5_000)).await.unwrap_err();
+        assert_eq!(error.code(), "DEADLINE_EXCEEDED");
+    }
+
+    #[tokio::test]
+    async fn phases_stop_at_the_end_to_end_limit() {
+        let timeouts = PhaseTimeouts {
+            request: Some(Duration::from_millis(50)),
+            ..timeouts(5_000, 5_000, 10_000)
+        };
+        let started = RequestStarted(Instant::now() - Duration::from_millis(40));
+        let deadline = Deadline::start(timeouts).within(Some(started));
+        let error = deadline.retrieval(slow(5_000)).await.unwrap_err();
+        assert_eq!(error.code(), "REQUEST_TIMEOUT");
+    }
+
+    /// `/paid` behind the end-to-end layer with a `limit_ms` limit; the handler
+    /// takes `ms`, and `served` puts `served_layer` in front of it.
+    async fn status_within(limit_ms: u64, ms: u64, served: bool) -> StatusCode {
+        use axum::{Router, body::Body, routing::get};
+        use tower::ServiceExt as _;
+
+        let mut route = get(move || slow(ms));
+        if served {
+            route = route.layer(axum::middleware::from_fn(served_layer));
+        }
+        let app = Router::new()
+            .route("/paid", route)
+            .layer(axum::middleware::from_fn(move |request, next| {
+                run_within(Duration::from_millis(limit_ms), request, next)
+            }));
+        let request = Request::get("/paid").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn end_to_end_limit_cuts_off_unpaid_requests() {
+        assert_eq!(
+            status_within(20, 5_000, false).await,
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(status_within(5_000, 1, false).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn end_to_end_limit_lets_a_served_request_settle() {
+        assert_eq!(status_within(20, 80, true).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn timeouts_answer_504_with_their_code() {
+        let response = TimeoutError::Model(Duration::from_secs(60)).into_response();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "MODEL_TIMEOUT");
+    }
+}
@@ -0,0 +1,156 @@
+// src/backend/diff.rs
+//! Entry-level comparison of two catalogs, matched by endpoint, for reviewing
+//! a catalog change before it is deployed.
+use super::McpEntry;
+use super::urls::endpoint_key;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntryChange {
+    pub endpoint: String,
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogDiff {
+    pub added: Vec<McpEntry>,
+    pub removed: Vec<McpEntry>,
+    pub modified: Vec<EntryChange>,
+}
+
+/// Compares `old` and `new` field by field. Entries are keyed by endpoint, so a
+/// renamed entry shows up as a `name` change rather than a removal plus addition.
+pub fn diff_catalogs(old: &[McpEntry], new: &[McpEntry]) -> CatalogDiff {
+    let old_by_key: BTreeMap<String, &McpEntry> = old
+        .iter()
+        .map(|e| (endpoint_key(&e.endpoint).to_string(), e))
+        .collect();
+    let new_by_key: BTreeMap<String, &McpEntry> = new
+        .iter()
+        .map(|e| (endpoint_key(&e.endpoint).to_string(), e))
+        .collect();
+
+    let added = new_by_key
+        .iter()
+        .filter(|(key, _)| !old_by_key.contains_key(*key))
+        .map(|(_, entry)| (*entry).clone())
+        .collect();
+    let removed = old_by_key
+        .iter()
+        .filter(|(key, _)| !new_by_key.contains_key(*key))
+        .map(|(_, entry)| (*entry).clone())
+        .collect();
+    let modified = old_by_key
+        .iter()
+        .filter_map(|(key, old_entry)| {
+            let new_entry = new_by_key.get(key)?;
+            let changes = field_changes(old_entry, new_entry);
+            (!changes.is_empty()).then(|| EntryChange {
+                endpoint: new_entry.endpoint.clone(),
+                name: new_entry.name.clone(),
+                changes,
+            })
+        })
+        .collect();
+
+    CatalogDiff {
+        added,
+        removed,
+        modified,
+    }
+}
+
+fn field_changes(old: &McpEntry, new: &McpEntry) -> Vec<FieldChange> {
+    let as_map = |entry: &McpEntry| match serde_json::to_value(entry) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let (mut old, mut new) = (as_map(old), as_map(new));
+    // the two sides are different files by construction
+    old.remove("source");
+    new.remove("source");
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let before = old.get(field).cloned().unwrap_or(Value::Null);
+            let after = new.get(field).cloned().unwrap_or(Value::Null);
+            (before != after).then(|| FieldChange {
+                field: field.clone(),
+                old: before,
+                new: after,
+            })
+        })
+        .collect()
+}
+
+impl CatalogDiff {
+    /// Human-readable summary: `+` added, `-` removed, `~` modified, with list
+    /// fields such as `capabilities` shown as the items added and removed.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for entry in &self.added {
+            let _ = writeln!(out, "+ {} ({})", entry.name, entry.endpoint);
+        }
+        for entry in &self.removed {
+            let _ = writeln!(out, "- {} ({})", entry.name, entry.endpoint);
+        }
+        for entry in &self.modified {
+            let _ = writeln!(out, "~ {} ({})", entry.name, entry.endpoint);
+            for change in &entry.changes {
+                let _ = writeln!(
+                    out,
+                    "    {}: {}",
+                    change.field,
+                    describe(&change.old, &change.new)
+                );
+            }
+        }
+        let _ = writeln!(
+            out,
+            "{} added, {} removed, {} modified",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        );
+        out
+    }
+}
+
+fn describe(old: &Value, new: &Value) -> String {
+    if let (Value::Array(old), Value::Array(new)) = (old, new) {
+        let mut parts: Vec<String> = new
+            .iter()
+            .filter(|v| !old.contains(v))
+            .map(|v| format!("+{}", plain(v)))
+            .collect();
+        parts.extend(
+            old.iter()
+                .filter(|v| !new.contains(v))
+                .map(|v| format!("-{}", plain(v))),
+        );
+        if parts.is_empty() {
+            return "reordered".to_string();
+        }
+        return parts.join(" ");
+    }
+    format!("{} -> {}", plain(old), plain(new))
+}
+
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
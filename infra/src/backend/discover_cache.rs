@@ -0,0 +1,214 @@
+// src/backend/discover_cache.rs
+//! Model answers to `/discover` kept for `LIBRARIAN_CACHE_TTL_SECS` (default
+//! 60; `0` disables), so identical requests arriving close together cost one
+//! model call. Keyed by every request field and query parameter that shapes
+//! the answer, with the query's case and spacing normalized; output format is
+//! applied afterwards and isn't part of the key.
+//!
+//! Entries remember the catalog hash they were answered from and are ignored
+//! once a reload changes it. Only the model path is cached: the empty and
+//! circuit-open answers are already free to produce.
+//!
+//! A hit is charged like a miss: the lookup runs in the handler, inside the
+//! x402 layer, and the caller gets the same paid artifact a miss would return,
+//! so caching saves the model call, not the client's payment. Each hit gets
+//! its own `query_id`, replay entry and hit counts; the response carries
+//! `x-librarian-cache: hit`.
+use super::{DiscoverParams, DiscoverRequest};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const CACHE_HEADER: &str = "x-librarian-cache";
+const DEFAULT_CACHE_TTL_SECS: u64 = 60;
+/// Entries kept at once; the oldest go first.
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+struct Cached {
+    stored_at: Instant,
+    catalog_hash: String,
+    response: Value,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Cached>,
+    /// Keys in insertion order, stamped with when they were stored. A key
+    /// stored again leaves its older stamp behind, which is skipped.
+    order: VecDeque<(Instant, String)>,
+}
+
+impl Entries {
+    /// Forgets the oldest entry; false when there is none.
+    fn pop_oldest(&mut self) -> bool {
+        let Some((stored_at, key)) = self.order.pop_front() else {
+            return false;
+        };
+        if self
+            .by_key
+            .get(&key)
+            .is_some_and(|cached| cached.stored_at == stored_at)
+        {
+            self.by_key.remove(&key);
+        }
+        true
+    }
+}
+
+pub struct DiscoverCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+impl DiscoverCache {
+    pub fn new(ttl: Duration) -> Self {
+        DiscoverCache {
+            ttl,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Reads `LIBRARIAN_CACHE_TTL_SECS`; `0` turns the cache off.
+    pub fn from_env() -> Self {
+        let ttl = env::var("LIBRARIAN_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_CACHE_TTL_SECS);
+        DiscoverCache::new(Duration::from_secs(ttl))
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// The answer stored under `key`, if it is fresh and from `catalog_hash`.
+    pub fn get(&self, key: &str, catalog_hash: &str) -> Option<Value> {
+        if !self.enabled() {
+            return None;
+        }
+        let entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let cached = entries.by_key.get(key)?;
+        (cached.stored_at.elapsed() <= self.ttl && cached.catalog_hash == catalog_hash)
+            .then(|| cached.response.clone())
+    }
+
+    /// Stores `response`, first dropping expired entries from the front of the
+    /// insertion order and then, at capacity, the oldest. Entries from an
+    /// earlier catalog are never served and expire with the rest.
+    pub fn insert(&self, key: String, catalog_hash: &str, response: &Value) {
+        if !self.enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        while entries
+            .order
+            .front()
+            .is_some_and(|(stored_at, _)| stored_at.elapsed() > self.ttl)
+        {
+            entries.pop_oldest();
+        }
+        while entries.by_key.len() >= MAX_CACHE_ENTRIES && entries.pop_oldest() {}
+
+        let stored_at = Instant::now();
+        entries.order.push_back((stored_at, key.clone()));
+        entries.by_key.insert(
+            key,
+            Cached {
+                stored_at,
+                catalog_hash: catalog_hash.to_string(),
+                response: response.clone(),
+            },
+        );
+    }
+}
+
+/// SHA-256 over the request as the answer depends on it. `filters` is hashed
+/// as parsed, so key order and spacing in the body don't matter.
+pub fn key(req: &DiscoverRequest, params: &DiscoverParams) -> String {
+    let query = req
+        .query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let shape = json!({
+        "query": query,
+        "filters": req.filters,
+        "client_type": req.client_type,
+        "allow_auth": req.allow_auth,
+        "preferred_region": req.preferred_region,
+        "lang": req.lang,
+        "restrict_to": req.restrict_to,
+        "min_score": req.min_score,
+        "prefer_verified": req.prefer_verified,
+        "exclude": req.exclude,
+        "max_results": req.max_results,
+        "debug": params.debug,
+        "explain": params.explain,
+        "perspectives": params.perspectives,
+    });
+    hex::encode(Sha256::digest(shape.to_string().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_served_only_for_their_catalog() {
+        let cache = DiscoverCache::new(Duration::from_secs(60));
+        cache.insert("k".to_string(), "catalog-a", &json!({ "n": 1 }));
+        assert_eq!(cache.get("k", "catalog-a"), Some(json!({ "n": 1 })));
+        assert_eq!(cache.get("k", "catalog-b"), None);
+        assert_eq!(cache.get("other", "catalog-a"), None);
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_on_the_next_insert() {
+        let cache = DiscoverCache::new(Duration::from_millis(20));
+        cache.insert("old".to_string(), "c", &json!(1));
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.get("old", "c"), None);
+        cache.insert("new".to_string(), "c", &json!(2));
+        let entries = cache.entries.lock().unwrap();
+        assert!(!entries.by_key.contains_key("old"));
+        assert_eq!(entries.order.len(), 1);
+    }
+
+    #[test]
+    fn capacity_evicts_the_oldest() {
+        let cache = DiscoverCache::new(Duration::from_secs(60));
+        for i in 0..=MAX_CACHE_ENTRIES {
+            cache.insert(i.to_string(), "c", &json!(i));
+        }
+        assert_eq!(cache.get("0", "c"), None);
+        assert_eq!(cache.get("1", "c"), Some(json!(1)));
+        assert_eq!(
+            cache.entries.lock().unwrap().by_key.len(),
+            MAX_CACHE_ENTRIES
+        );
+    }
+
+    #[test]
+    fn a_restored_key_outlives_its_first_stamp() {
+        let cache = DiscoverCache::new(Duration::from_millis(100));
+        cache.insert("a".to_string(), "old-catalog", &json!(1));
+        std::thread::sleep(Duration::from_millis(60));
+        cache.insert("a".to_string(), "new-catalog", &json!(2));
+        std::thread::sleep(Duration::from_millis(60));
+        // the first stamp for "a" has expired; the entry it left behind has not
+        cache.insert("b".to_string(), "new-catalog", &json!(3));
+        assert_eq!(cache.get("a", "new-catalog"), Some(json!(2)));
+    }
+
+    #[test]
+    fn zero_ttl_stores_nothing() {
+        let cache = DiscoverCache::new(Duration::ZERO);
+        cache.insert("k".to_string(), "c", &json!(1));
+        assert_eq!(cache.get("k", "c"), None);
+        assert!(cache.entries.lock().unwrap().order.is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+// src/backend/embed.rs
+//! `POST /embed`: the query embedding in the Librarian's own vector space, for
+//! clients re-ranking locally without their own OpenAI key. It is a paid route
+//! and, so it can't serve as a cheap embedding proxy, each caller is limited to
+//! `LIBRARIAN_EMBED_RATE_LIMIT` requests per minute; see `ratelimit`.
+use super::LibrarianHandle;
+use super::request::RequestError;
+use super::sanitize::max_query_chars;
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use rig::embeddings::EmbeddingModel as _;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct EmbedRequest {
+    pub text: String,
+}
+
+pub async fn embed_handler(
+    State(handle): State<LibrarianHandle>,
+    Json(req): Json<EmbedRequest>,
+) -> Response {
+    let chars = req.text.chars().count();
+    let max = max_query_chars();
+    if req.text.trim().is_empty() || chars > max {
+        return RequestError {
+            status: StatusCode::BAD_REQUEST,
+            field: Some("text".to_string()),
+            message: format!("text must be 1 to {} characters, got {}", max, chars),
+        }
+        .into_response();
+    }
+
+    let librarian = handle.current();
+    match librarian.embedding_model.embed_text(&req.text).await {
+        Ok(embedding) => (
+            StatusCode::OK,
+            librarian.model_headers(),
+            AxumJson(json!({
+                "model": librarian.retrieval_model,
+                "dimensions": embedding.vec.len(),
+                "embedding": embedding.vec,
+            })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({ "error": format!("Embedding failed: {}", e) })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,255 @@
+// src/backend/embed_cache.rs
+//! Entry vectors kept on disk between runs, so a restart or reload only embeds
+//! the entries that changed. On by default; `LIBRARIAN_EMBED_CACHE=0` turns it
+//! off and `LIBRARIAN_EMBED_CACHE_PATH` moves the file (default
+//! `embeddings.cache.json`).
+//!
+//! Each entry's vectors are keyed by a SHA-256 over the embedding model, its
+//! dimension, the embedding profile and the fields `Embed for McpEntry` reads,
+//! so an edited entry or a different model simply misses. A file written for
+//! another model is dropped whole; an unreadable one is ignored with a warning
+//! and the catalog is embedded from scratch. Vectors whose length isn't the
+//! model's dimension are treated as missing rather than indexed. The file is
+//! read and written on the blocking pool.
+use super::McpEntry;
+use super::embed_profile;
+use super::providers;
+use anyhow::{Context as _, Result, anyhow};
+use rig::OneOrMany;
+use rig::embeddings::Embedding;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CACHE_PATH: &str = "embeddings.cache.json";
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    /// `model_key()` of the vectors below.
+    model: String,
+    entries: HashMap<String, Vec<Embedding>>,
+}
+
+fn enabled() -> bool {
+    !env::var("LIBRARIAN_EMBED_CACHE").is_ok_and(|v| v == "0" || v == "false")
+}
+
+fn path() -> PathBuf {
+    PathBuf::from(
+        env::var("LIBRARIAN_EMBED_CACHE_PATH").unwrap_or_else(|_| DEFAULT_CACHE_PATH.to_string()),
+    )
+}
+
+/// `provider:model/dims;profile`: what must match for a cached vector to be
+/// comparable with a fresh one.
+fn model_key(ndims: usize) -> String {
+    format!(
+        "{}/{};{}",
+        providers::active().embedding.identity(),
+        ndims,
+        embed_profile::cache_key()
+    )
+}
+
+fn entry_key(model: &str, entry: &McpEntry) -> String {
+    let fields = serde_json::json!([
+        model,
+        entry.name,
+        entry.capabilities,
+        entry.desc,
+        entry.internal_notes,
+        entry.endpoint,
+    ]);
+    hex::encode(Sha256::digest(fields.to_string().as_bytes()))
+}
+
+pub struct EmbedCache {
+    path: PathBuf,
+    model: String,
+    ndims: usize,
+    entries: HashMap<String, Vec<Embedding>>,
+}
+
+impl EmbedCache {
+    /// The cache for the active model, without reading it; `None` when disabled.
+    pub fn open(ndims: usize) -> Option<Self> {
+        enabled().then(|| EmbedCache {
+            path: path(),
+            model: model_key(ndims),
+            ndims,
+            entries: HashMap::new(),
+        })
+    }
+
+    /// `open`, then whatever the file holds for the active model.
+    pub async fn load(ndims: usize) -> Option<Self> {
+        let mut cache = Self::open(ndims)?;
+        let reading = cache.path.clone();
+        let contents = tokio::task::spawn_blocking(move || read(&reading))
+            .await
+            .unwrap_or_else(|e| Err(anyhow!("Embedding cache read panicked: {}", e)));
+        let (path, model) = (&cache.path, &cache.model);
+        cache.entries = match contents {
+            Ok(Some(file)) if file.model == *model => file.entries,
+            Ok(Some(file)) => {
+                tracing::info!(
+                    "Embedding cache {:?} was built for {}, not {}; re-embedding the catalog",
+                    path,
+                    file.model,
+                    model
+                );
+                HashMap::new()
+            }
+            Ok(None) => HashMap::new(),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable embedding cache: {:#}", e);
+                HashMap::new()
+            }
+        };
+        Some(cache)
+    }
+
+    /// Entries read from the file.
+    pub fn stored(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Splits `mcps` into the entries with cached vectors and those to embed.
+    /// Cached vectors of the wrong length (a truncated or hand-edited file)
+    /// would skew every similarity they take part in, so their entry is
+    /// re-embedded.
+    pub fn split(
+        &self,
+        mcps: Vec<McpEntry>,
+    ) -> (Vec<(McpEntry, OneOrMany<Embedding>)>, Vec<McpEntry>) {
+        let mut cached = Vec::new();
+        let mut missing = Vec::new();
+        let mut malformed = 0;
+        for entry in mcps {
+            let vectors = self
+                .entries
+                .get(&entry_key(&self.model, &entry))
+                .filter(|vectors| {
+                    let whole = vectors.iter().all(|e| e.vec.len() == self.ndims);
+                    if !whole {
+                        malformed += 1;
+                    }
+                    whole
+                });
+            match vectors.and_then(|vectors| OneOrMany::many(vectors.clone()).ok()) {
+                Some(vectors) => cached.push((entry, vectors)),
+                None => missing.push(entry),
+            }
+        }
+        if malformed > 0 {
+            tracing::warn!(
+                malformed,
+                expected = self.ndims,
+                "Embedding cache {:?} holds vectors of the wrong dimension; re-embedding those entries",
+                self.path
+            );
+        }
+        (cached, missing)
+    }
+
+    /// Replaces the file with exactly `embeddings`, so entries gone from the
+    /// catalog are pruned. A failed write is logged; the next start re-embeds.
+    pub async fn save(&self, embeddings: &[(McpEntry, OneOrMany<Embedding>)]) {
+        let file = CacheFile {
+            model: self.model.clone(),
+            entries: embeddings
+                .iter()
+                .map(|(entry, vectors)| {
+                    (
+                        entry_key(&self.model, entry),
+                        vectors.iter().cloned().collect(),
+                    )
+                })
+                .collect(),
+        };
+        let path = self.path.clone();
+        let written = tokio::task::spawn_blocking(move || write(&path, &file))
+            .await
+            .unwrap_or_else(|e| Err(anyhow!("Embedding cache write panicked: {}", e)));
+        if let Err(e) = written {
+            tracing::warn!("Failed to write embedding cache: {:#}", e);
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Option<CacheFile>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let file =
+        serde_json::from_slice(&bytes).with_context(|| format!("Failed to parse {:?}", path))?;
+    Ok(Some(file))
+}
+
+/// Written to a temporary file and renamed, so a crash never leaves half a cache.
+fn write(path: &Path, file: &CacheFile) -> Result<()> {
+    let json = serde_json::to_vec(file).context("Failed to serialize embedding cache")?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, json).with_context(|| format!("Failed to write {:?}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(name: &str) -> McpEntry {
+        serde_json::from_value(json!({
+            "name": name,
+            "endpoint": format!("https://{}.example/mcp", name),
+            "version": "1.0.0",
+            "capabilities": [],
+            "desc": "",
+        }))
+        .unwrap()
+    }
+
+    fn vector(len: usize) -> Embedding {
+        Embedding {
+            document: String::new(),
+            vec: vec![0.5; len],
+        }
+    }
+
+    #[test]
+    fn vectors_of_the_wrong_dimension_are_re_embedded() {
+        let model = "test/3;default".to_string();
+        let stored =
+            |name: &str, vectors: Vec<Embedding>| (entry_key(&model, &entry(name)), vectors);
+        let cache = EmbedCache {
+            path: PathBuf::from("unused.json"),
+            ndims: 3,
+            entries: HashMap::from([
+                stored("whole", vec![vector(3), vector(3)]),
+                stored("truncated", vec![vector(3), vector(2)]),
+                stored("empty", Vec::new()),
+            ]),
+            model,
+        };
+
+        let (cached, missing) = cache.split(vec![
+            entry("whole"),
+            entry("truncated"),
+            entry("empty"),
+            entry("new"),
+        ]);
+        let cached: Vec<&str> = cached
+            .iter()
+            .map(|(entry, _)| entry.name.as_str())
+            .collect();
+        let missing: Vec<&str> = missing.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(cached, ["whole"]);
+        assert_eq!(missing, ["truncated", "empty", "new"]);
+    }
+}
@@ -0,0 +1,79 @@
+// src/backend/embed_health.rs
+//! Reachability of the embedding API. Every `/discover` and `/search` embeds
+//! the query before retrieval, so an embedding outage fails requests even when
+//! the completion model is healthy; `/health` reports it separately.
+//!
+//! One background task probes once the index is built and then every minute;
+//! `/health` only reads the last result, so health checks never embed.
+use super::LibrarianHandle;
+use super::probe::{LastProbe, ProbeStatus};
+use super::tasks::TASKS;
+use rig::embeddings::EmbeddingModel as _;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// A probe costs a (tiny) billed embedding, so it runs once a minute.
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+const PROBE_TEXT: &str = "health";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingStatus {
+    pub model: String,
+    #[serde(flatten)]
+    pub probe: ProbeStatus,
+}
+
+#[derive(Default)]
+pub struct EmbeddingHealth {
+    last: LastProbe<EmbeddingStatus>,
+}
+
+impl EmbeddingHealth {
+    pub fn new() -> Self {
+        EmbeddingHealth::default()
+    }
+
+    /// Embeds a one-word text with the live model.
+    pub async fn probe(&self, handle: &LibrarianHandle) -> EmbeddingStatus {
+        let librarian = handle.current();
+        let result = tokio::time::timeout(
+            PROBE_TIMEOUT,
+            librarian.embedding_model.embed_text(PROBE_TEXT),
+        )
+        .await;
+        let error = match result {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("timed out after {:?}", PROBE_TIMEOUT)),
+        };
+        let status = EmbeddingStatus {
+            model: librarian.retrieval_model.clone(),
+            probe: ProbeStatus::now(error),
+        };
+        self.last.record(status.clone());
+        status
+    }
+
+    /// The last probe result; `None` until the first probe finishes.
+    pub fn status(&self) -> Option<EmbeddingStatus> {
+        self.last.get()
+    }
+
+    /// Probes now and every `PROBE_INTERVAL` until shutdown.
+    pub fn spawn_monitor(self: &Arc<Self>, handle: LibrarianHandle) {
+        let health = Arc::clone(self);
+        TASKS.spawn("embedding_health", |cancel| async move {
+            let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = cancel.cancelled() => return,
+                }
+                health.probe(&handle).await;
+            }
+        });
+    }
+}
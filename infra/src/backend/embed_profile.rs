@@ -0,0 +1,267 @@
+// src/backend/embed_profile.rs
+//! Which `McpEntry` fields feed the embedding. Selected once at startup from
+//! `LIBRARIAN_EMBED_PROFILE` and fixed for the life of the process, since an
+//! index mixing profiles would compare unlike vectors.
+//!
+//! - `default`: `name`, each capability, `desc`.
+//! - `endpoint`: each capability, `desc`, and the endpoint's host and path
+//!   segments as words. For catalogs whose names are opaque IDs.
+//!
+//! Each field is embedded separately and an entry scores as its best-matching
+//! field. `LIBRARIAN_EMBED_WEIGHTS` (e.g. `name=1,capabilities=2,desc=1`)
+//! switches to one embedding per entry over a combined text in which each
+//! field is repeated by its weight, upweighting high-signal fields. Compare the
+//! two schemes with `bench` over the same cases.
+use super::McpEntry;
+use super::settings::select_once;
+use anyhow::{Context as _, Result, bail};
+use std::env;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmbedProfile {
+    #[default]
+    Default,
+    Endpoint,
+}
+
+static ACTIVE: OnceLock<EmbedProfile> = OnceLock::new();
+
+impl EmbedProfile {
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_EMBED_PROFILE").as_deref() {
+            Err(_) | Ok("default") => Ok(EmbedProfile::Default),
+            Ok("endpoint") => Ok(EmbedProfile::Endpoint),
+            Ok(other) => bail!(
+                "Unknown LIBRARIAN_EMBED_PROFILE {:?} (expected default or endpoint)",
+                other
+            ),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EmbedProfile::Default => "default",
+            EmbedProfile::Endpoint => "endpoint",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(EmbedProfile::Default),
+            "endpoint" => Some(EmbedProfile::Endpoint),
+            _ => None,
+        }
+    }
+}
+
+/// Fixes the process-wide profile; see `settings::select_once`.
+pub fn select(profile: EmbedProfile) -> Result<()> {
+    select_once(&ACTIVE, profile, "Embedding profile")
+}
+
+pub fn active() -> EmbedProfile {
+    ACTIVE.get().copied().unwrap_or_default()
+}
+
+/// Largest repeat count; beyond this the combined text only grows.
+const MAX_WEIGHT: u8 = 5;
+
+/// Per-field repeat counts for the weighted scheme. Under the `endpoint`
+/// profile, `name` weighs the endpoint terms instead. Internal notes share the
+/// `desc` weight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbedWeights {
+    pub name: u8,
+    pub capabilities: u8,
+    pub desc: u8,
+}
+
+static WEIGHTS: OnceLock<Option<EmbedWeights>> = OnceLock::new();
+
+impl EmbedWeights {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let mut weights = EmbedWeights {
+            name: 1,
+            capabilities: 1,
+            desc: 1,
+        };
+        for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (field, weight) = pair.split_once('=').with_context(|| {
+                format!(
+                    "Expected field=weight in LIBRARIAN_EMBED_WEIGHTS, got {:?}",
+                    pair
+                )
+            })?;
+            let weight: u8 = weight
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid weight in {:?}", pair))?;
+            if weight > MAX_WEIGHT {
+                bail!("Weight in {:?} exceeds the maximum of {}", pair, MAX_WEIGHT);
+            }
+            match field.trim() {
+                "name" => weights.name = weight,
+                "capabilities" => weights.capabilities = weight,
+                "desc" => weights.desc = weight,
+                other => bail!(
+                    "Unknown field {:?} in LIBRARIAN_EMBED_WEIGHTS (expected name, capabilities or desc)",
+                    other
+                ),
+            }
+        }
+        if weights.name + weights.capabilities + weights.desc == 0 {
+            bail!("LIBRARIAN_EMBED_WEIGHTS gives every field weight 0");
+        }
+        Ok(weights)
+    }
+
+    /// `None` (the per-field scheme) when `LIBRARIAN_EMBED_WEIGHTS` is unset.
+    pub fn from_env() -> Result<Option<Self>> {
+        match env::var("LIBRARIAN_EMBED_WEIGHTS") {
+            Ok(raw) if !raw.trim().is_empty() => EmbedWeights::parse(&raw).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Canonical form, recorded with snapshots and cached embeddings.
+    pub fn key(&self) -> String {
+        format!(
+            "name={},capabilities={},desc={}",
+            self.name, self.capabilities, self.desc
+        )
+    }
+
+    /// The single text embedded for `entry` under these weights.
+    pub fn combined_text(&self, entry: &McpEntry, profile: EmbedProfile) -> String {
+        let name = match profile {
+            EmbedProfile::Default => entry.name.clone(),
+            EmbedProfile::Endpoint => endpoint_terms(&entry.endpoint),
+        };
+        let capabilities = entry.capabilities.join(", ");
+        let desc = match &entry.internal_notes {
+            Some(notes) => format!("{} {}", entry.desc, notes),
+            None => entry.desc.clone(),
+        };
+
+        let mut parts: Vec<&str> = Vec::new();
+        for (text, weight) in [
+            (&name, self.name),
+            (&capabilities, self.capabilities),
+            (&desc, self.desc),
+        ] {
+            if !text.is_empty() {
+                parts.extend(std::iter::repeat_n(text.as_str(), weight as usize));
+            }
+        }
+        parts.join("\n")
+    }
+}
+
+/// Fixes the process-wide weighting alongside the profile.
+pub fn select_weights(weights: Option<EmbedWeights>) -> Result<()> {
+    select_once(&WEIGHTS, weights, "Embedding weights")
+}
+
+pub fn active_weights() -> Option<EmbedWeights> {
+    WEIGHTS.get().copied().flatten()
+}
+
+/// The texts embedded for `entry` under the active profile and weights, one
+/// vector each.
+pub fn texts(entry: &McpEntry) -> Vec<String> {
+    texts_for(entry, active(), active_weights())
+}
+
+fn texts_for(
+    entry: &McpEntry,
+    profile: EmbedProfile,
+    weights: Option<EmbedWeights>,
+) -> Vec<String> {
+    if let Some(weights) = weights {
+        return vec![weights.combined_text(entry, profile)];
+    }
+    let mut texts = Vec::with_capacity(entry.capabilities.len() + 3);
+    if profile == EmbedProfile::Default {
+        texts.push(entry.name.clone());
+    }
+    texts.extend(entry.capabilities.iter().cloned());
+    texts.push(entry.desc.clone());
+    if let Some(notes) = &entry.internal_notes {
+        texts.push(notes.clone());
+    }
+    if profile == EmbedProfile::Endpoint {
+        texts.push(endpoint_terms(&entry.endpoint));
+    }
+    texts
+}
+
+/// Profile plus weighting: what must match for two vectors to be comparable.
+pub fn cache_key() -> String {
+    match active_weights() {
+        Some(weights) => format!("{};{}", active().name(), weights.key()),
+        None => active().name().to_string(),
+    }
+}
+
+/// `https://mcp.example.com/servers/search-mcp` -> `mcp example com servers search mcp`
+pub fn endpoint_terms(endpoint: &str) -> String {
+    let without_scheme = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, rest)| rest);
+    without_scheme
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn opaque() -> McpEntry {
+        serde_json::from_value(json!({
+            "name": "srv-7f3a91",
+            "endpoint": "https://mcp.example.com/servers/weather-forecast",
+            "version": "1.0.0",
+            "capabilities": ["get_forecast"],
+            "desc": "Forecasts by city",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn default_profile_embeds_the_name() {
+        assert_eq!(
+            texts_for(&opaque(), EmbedProfile::Default, None),
+            ["srv-7f3a91", "get_forecast", "Forecasts by city"]
+        );
+    }
+
+    #[test]
+    fn endpoint_profile_embeds_endpoint_terms_instead_of_the_name() {
+        let texts = texts_for(&opaque(), EmbedProfile::Endpoint, None);
+        assert_eq!(
+            texts,
+            [
+                "get_forecast",
+                "Forecasts by city",
+                "mcp example com servers weather forecast"
+            ]
+        );
+        assert!(!texts.iter().any(|text| text.contains("srv-7f3a91")));
+    }
+
+    #[test]
+    fn weights_combine_the_selected_fields() {
+        let weights = EmbedWeights::parse("name=0,capabilities=2,desc=1").unwrap();
+        assert_eq!(
+            texts_for(&opaque(), EmbedProfile::Default, Some(weights)),
+            ["get_forecast\nget_forecast\nForecasts by city"]
+        );
+        assert!(EmbedWeights::parse("name=9").is_err());
+        assert!(EmbedWeights::parse("name=0,capabilities=0,desc=0").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// src/backend/envelope.rs
+//! An opt-in `{data, meta}` envelope for `/discover`, gathering what is
+//! otherwise spread over headers and the body into one shape. A client asks
+//! for it with `Accept: application/vnd.librarian.envelope+json`;
+//! `LIBRARIAN_RESPONSE_ENVELOPE=true` turns it on for everyone. Without either
+//! the bare body is unchanged. `meta` holds `request_id` (the `query_id` when
+//! one was issued), `latency_ms`, `model` (the completion model that answered,
+//! `null` when none was called), `catalog_hash`, `cache` (`hit` for an
+//! idempotent replay or a response-cache hit), `degraded` (always a bool) and
+//! `degraded_reason` (the `x-librarian-degraded` reason, or `null`).
+//! Successful and degraded JSON answers are wrapped; errors, payment
+//! challenges, the `ndjson`/`script` formats and bodies over
+//! `MAX_ENVELOPED_BYTES` are not.
+use super::LibrarianHandle;
+use super::discover_cache::CACHE_HEADER;
+use super::feedback::QUERY_ID_HEADER;
+use super::health::DEGRADED_HEADER;
+use super::idempotency::IDEMPOTENT_REPLAY_HEADER;
+use super::{COMPLETION_MODEL_HEADER, FALLBACK_MODEL_HEADER};
+use axum::{
+    body::{Body, HttpBody as _, to_bytes},
+    extract::{Request, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+use std::env;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+pub const ENVELOPE_MEDIA_TYPE: &str = "application/vnd.librarian.envelope+json";
+/// Largest body wrapped; a bigger or unsized one goes out bare and untouched.
+const MAX_ENVELOPED_BYTES: u64 = 1024 * 1024;
+
+/// `LIBRARIAN_RESPONSE_ENVELOPE`, read once.
+static ALWAYS: LazyLock<bool> = LazyLock::new(|| {
+    env::var("LIBRARIAN_RESPONSE_ENVELOPE").is_ok_and(|v| v == "1" || v == "true")
+});
+
+fn wants_envelope(headers: &HeaderMap) -> bool {
+    *ALWAYS
+        || headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|media| {
+                media
+                    .split(';')
+                    .next()
+                    .is_some_and(|m| m.trim() == ENVELOPE_MEDIA_TYPE)
+            })
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// The `query_id` of `data`, or the one in the response headers.
+pub fn query_id(headers: &HeaderMap, data: &Value) -> Option<String> {
+    data.get("query_id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| header(headers, QUERY_ID_HEADER))
+}
+
+/// `data` with its `meta`, or `None` for a response that stays bare.
+pub fn wrap(
+    status: StatusCode,
+    headers: &HeaderMap,
+    data: Value,
+    request_id: String,
+    latency_ms: u64,
+    catalog_hash: &str,
+) -> Option<Value> {
+    let degraded = header(headers, DEGRADED_HEADER);
+    if !status.is_success() && degraded.is_none() {
+        return None;
+    }
+    let model =
+        header(headers, FALLBACK_MODEL_HEADER).or_else(|| header(headers, COMPLETION_MODEL_HEADER));
+    let cache = if headers.contains_key(IDEMPOTENT_REPLAY_HEADER)
+        || header(headers, CACHE_HEADER).as_deref() == Some("hit")
+    {
+        "hit"
+    } else {
+        "miss"
+    };
+    Some(json!({
+        "data": data,
+        "meta": {
+            "request_id": request_id,
+            "latency_ms": latency_ms,
+            "model": model,
+            "catalog_hash": catalog_hash,
+            "cache": cache,
+            "degraded": degraded.is_some(),
+            "degraded_reason": degraded,
+        },
+    }))
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(';')
+                .next()
+                .is_some_and(|mime| mime.trim() == "application/json")
+        })
+}
+
+pub async fn envelope_layer(
+    State(handle): State<LibrarianHandle>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !wants_envelope(request.headers()) {
+        return next.run(request).await;
+    }
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|len| len <= MAX_ENVELOPED_BYTES);
+    if !is_json(response.headers()) || !fits {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_ENVELOPED_BYTES as usize).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to read /discover response to wrap: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Ok(data) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let request_id =
+        query_id(&parts.headers, &data).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let catalog_hash = handle.current().catalog_hash.clone();
+    let Some(enveloped) = wrap(
+        parts.status,
+        &parts.headers,
+        data,
+        request_id,
+        latency_ms,
+        &catalog_hash,
+    ) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(enveloped.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{AgentParams, assemble_librarian};
+    use axum::http::{HeaderValue, Request as HttpRequest};
+    use axum::{Router, middleware, routing::get};
+    use tower::ServiceExt as _;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn wraps_success_with_the_model_that_answered() {
+        let answered = headers(&[(COMPLETION_MODEL_HEADER, "gpt-4o-mini")]);
+        let data = json!({ "recommendations": [], "query_id": "q-1" });
+        let wrapped = wrap(
+            StatusCode::OK,
+            &answered,
+            data.clone(),
+            "q-1".to_string(),
+            840,
+            "3f2a9c",
+        )
+        .unwrap();
+        assert_eq!(wrapped["data"], data);
+        assert_eq!(
+            wrapped["meta"],
+            json!({
+                "request_id": "q-1",
+                "latency_ms": 840,
+                "model": "gpt-4o-mini",
+                "catalog_hash": "3f2a9c",
+                "cache": "miss",
+                "degraded": false,
+                "degraded_reason": null,
+            })
+        );
+
+        let fell_back = headers(&[
+            (COMPLETION_MODEL_HEADER, "gpt-4o-mini"),
+            (FALLBACK_MODEL_HEADER, "gpt-4.1-nano"),
+        ]);
+        let wrapped = wrap(
+            StatusCode::OK,
+            &fell_back,
+            json!({}),
+            "q-2".to_string(),
+            0,
+            "",
+        )
+        .unwrap();
+        assert_eq!(wrapped["meta"]["model"], "gpt-4.1-nano");
+
+        let replayed = headers(&[(IDEMPOTENT_REPLAY_HEADER, "true")]);
+        let wrapped = wrap(
+            StatusCode::OK,
+            &replayed,
+            json!({}),
+            "q-3".to_string(),
+            0,
+            "",
+        )
+        .unwrap();
+        assert_eq!(wrapped["meta"]["cache"], "hit");
+        assert_eq!(wrapped["meta"]["model"], Value::Null);
+
+        let cached = headers(&[(CACHE_HEADER, "hit")]);
+        let wrapped = wrap(StatusCode::OK, &cached, json!({}), "q-4".to_string(), 0, "").unwrap();
+        assert_eq!(wrapped["meta"]["cache"], "hit");
+    }
+
+    #[test]
+    fn degraded_errors_are_wrapped_and_plain_errors_are_not() {
+        let degraded = headers(&[(DEGRADED_HEADER, "empty-catalog")]);
+        let wrapped = wrap(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &degraded,
+            json!({}),
+            "id".to_string(),
+            2,
+            "",
+        )
+        .unwrap();
+        assert_eq!(wrapped["meta"]["degraded"], true);
+        assert_eq!(wrapped["meta"]["degraded_reason"], "empty-catalog");
+        assert!(
+            wrap(
+                StatusCode::BAD_REQUEST,
+                &HeaderMap::new(),
+                json!({}),
+                "id".to_string(),
+                2,
+                ""
+            )
+            .is_none()
+        );
+        assert!(
+            wrap(
+                StatusCode::PAYMENT_REQUIRED,
+                &HeaderMap::new(),
+                json!({}),
+                "id".to_string(),
+                2,
+                ""
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn request_id_prefers_the_body_then_the_header() {
+        let with_header = headers(&[(QUERY_ID_HEADER, "from-header")]);
+        assert_eq!(
+            query_id(&with_header, &json!({ "query_id": "from-body" })).as_deref(),
+            Some("from-body")
+        );
+        assert_eq!(
+            query_id(&with_header, &json!({})).as_deref(),
+            Some("from-header")
+        );
+        assert_eq!(query_id(&HeaderMap::new(), &json!({})), None);
+    }
+
+    #[test]
+    fn envelope_is_asked_for_by_media_type() {
+        let asked = headers(&[(
+            "accept",
+            "application/json, application/vnd.librarian.envelope+json;q=0.9",
+        )]);
+        assert!(wants_envelope(&asked));
+        assert!(!wants_envelope(&headers(&[("accept", "application/json")])));
+    }
+
+    /// `body` as a JSON answer behind the envelope layer, over an empty catalog.
+    fn app(body: String) -> Router {
+        let handle = LibrarianHandle::new(
+            assemble_librarian(AgentParams::default(), Vec::new(), Vec::new()).unwrap(),
+        );
+        let answer = move || {
+            let body = body.clone();
+            async move {
+                Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len())
+                    .header(COMPLETION_MODEL_HEADER, "gpt-4o-mini")
+                    .body(Body::from(body))
+                    .unwrap()
+            }
+        };
+        Router::new()
+            .route("/discover", get(answer))
+            .layer(middleware::from_fn_with_state(handle, envelope_layer))
+    }
+
+    /// The answer's `Content-Length`, the length actually sent, and the body.
+    async fn fetch(app: Router) -> (String, usize, Value) {
+        let request = HttpRequest::get("/discover")
+            .header(ACCEPT, ENVELOPE_MEDIA_TYPE)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.unwrap();
+        let length = parts.headers[CONTENT_LENGTH].to_str().unwrap().to_string();
+        (length, body.len(), serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn layer_wraps_a_json_answer_and_replaces_its_length() {
+        let (length, sent, body) =
+            fetch(app(r#"{"recommendations":[],"query_id":"q-1"}"#.to_string())).await;
+        assert_eq!(length, sent.to_string());
+        assert_eq!(body["data"]["query_id"], "q-1");
+        assert_eq!(body["meta"]["request_id"], "q-1");
+        assert_eq!(body["meta"]["model"], "gpt-4o-mini");
+    }
+
+    #[tokio::test]
+    async fn oversized_answers_pass_through_untouched() {
+        let big = format!("[{}0]", "0,".repeat(MAX_ENVELOPED_BYTES as usize));
+        let (length, sent, body) = fetch(app(big.clone())).await;
+        assert_eq!((length, sent), (big.len().to_string(), big.len()));
+        assert!(body.is_array());
+    }
+}
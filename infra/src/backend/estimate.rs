@@ -0,0 +1,144 @@
+// src/backend/estimate.rs
+//! `POST /discover/estimate`: a free dry run of `/discover` that performs
+//! retrieval and prompt assembly, counts tokens, and quotes the route price
+//! without calling the model. Retrieval still embeds the query, so the route
+//! is limited by `LIBRARIAN_ESTIMATE_RATE_LIMIT` (see `ratelimit`) and refused
+//! during maintenance.
+//!
+//! The same count backs `DiscoverRequest::max_cost`: a `/discover` call is
+//! estimated at the preamble plus the assembled prompt, as `o200k_base`
+//! counts them, plus the full completion cap (`LIBRARIAN_MAX_TOKENS`), since
+//! the model may use all of it. A corrective reprompt on invalid output is
+//! not included.
+use super::lang;
+use super::request::{ApiJson, RequestError};
+use super::{
+    DiscoverRequest, LibrarianHandle, discover_candidates, fit_discover_prompt,
+    pricing::DiscoverPricing, restricted_candidates, sanitize,
+};
+use crate::utils::LIBRARIAN_PREAMBLE;
+use axum::{
+    Extension,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::json;
+use std::env;
+use std::sync::LazyLock;
+use tiktoken_rs::CoreBPE;
+
+/// `o200k_base` is the encoding used by the gpt-4o model family.
+static TOKENIZER: LazyLock<CoreBPE> =
+    LazyLock::new(|| tiktoken_rs::o200k_base().expect("o200k_base tokenizer loads"));
+
+pub fn count_tokens(text: &str) -> usize {
+    TOKENIZER.encode_with_special_tokens(text).len()
+}
+
+/// Leaves room for the completion inside gpt-4o's 128k context window.
+const DEFAULT_MAX_PROMPT_TOKENS: usize = 100_000;
+
+/// Input budget (preamble plus prompt) from `LIBRARIAN_MAX_PROMPT_TOKENS`.
+pub fn max_prompt_tokens() -> usize {
+    env::var("LIBRARIAN_MAX_PROMPT_TOKENS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_PROMPT_TOKENS)
+}
+
+/// Worst-case tokens for one model call with `prompt`; see the module docs.
+pub fn estimated_cost(prompt: &str, max_output: u64) -> u64 {
+    (count_tokens(LIBRARIAN_PREAMBLE) + count_tokens(prompt)) as u64 + max_output
+}
+
+fn max_cost_error(message: String) -> RequestError {
+    RequestError {
+        status: StatusCode::BAD_REQUEST,
+        field: Some("max_cost".to_string()),
+        message,
+    }
+}
+
+/// Rejects a zero budget; unset means no budget.
+pub fn check_max_cost(max_cost: Option<u64>) -> Result<(), RequestError> {
+    if max_cost == Some(0) {
+        return Err(max_cost_error(
+            "max_cost must be a positive number of tokens".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Refuses a call whose `estimated_cost` is over the caller's `max_cost`.
+pub fn check_budget(max_cost: Option<u64>, cost: u64) -> Result<(), RequestError> {
+    match max_cost {
+        Some(max) if cost > max => Err(max_cost_error(format!(
+            "estimated cost of {} tokens exceeds max_cost {}",
+            cost, max
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub async fn estimate_handler(
+    State(handle): State<LibrarianHandle>,
+    Extension(pricing): Extension<DiscoverPricing>,
+    ApiJson(req): ApiJson<DiscoverRequest>,
+) -> Response {
+    let librarian = handle.current();
+    if let Err(e) = sanitize::check_query_length(&req.query) {
+        return e.into_response();
+    }
+    if let Err(e) = lang::resolve_lang(req.lang.as_deref()) {
+        return e.into_response();
+    }
+    if let Err(e) = check_max_cost(req.max_cost) {
+        return e.into_response();
+    }
+    if let Some(names) = &req.restrict_to
+        && let Err(e) = restricted_candidates(&librarian, names)
+    {
+        return e.into_response();
+    }
+    let sanitized = sanitize::sanitize_query(&req.query);
+    let (mut candidates, stats, _) = match discover_candidates(&librarian, &req).await {
+        Ok(retrieved) => retrieved,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({ "error": format!("Retrieval error: {}", e) })),
+            )
+                .into_response();
+        }
+    };
+    let (prompt, dropped) =
+        fit_discover_prompt(&librarian, &req, &sanitized.text, &mut candidates, false);
+    let preamble_tokens = count_tokens(LIBRARIAN_PREAMBLE);
+    let prompt_tokens = count_tokens(&prompt);
+    let estimated = estimated_cost(&prompt, librarian.params.max_tokens);
+    let quote = pricing.quote(req.max_results);
+
+    AxumJson(json!({
+        "query": req.query,
+        "candidates": candidates.len(),
+        "candidates_dropped_for_budget": dropped,
+        "filter_stats": stats,
+        "completion_model": librarian.completion_model,
+        "tokens": {
+            "preamble": preamble_tokens,
+            "prompt": prompt_tokens,
+            "input_total": preamble_tokens + prompt_tokens,
+            "max_output": librarian.params.max_tokens,
+            "estimated_cost": estimated,
+        },
+        "max_cost": req.max_cost,
+        "within_budget": req.max_cost.is_none_or(|max| estimated <= max),
+        "price": {
+            "route": "/discover",
+            "max_results": quote.results,
+            "amount_usdc": quote.price,
+        },
+    }))
+    .into_response()
+}
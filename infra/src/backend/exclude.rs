@@ -0,0 +1,245 @@
+// src/backend/exclude.rs
+//! `exclude` on `/discover`: servers a client already knows, by catalog name
+//! or endpoint, kept out of both the candidates the model sees and the final
+//! recommendations (pins included), so successive calls walk down the ranking
+//! instead of repeating themselves. An endpoint matches any deployment of an
+//! entry (see `mirrors`), so excluding one mirror excludes the whole entry.
+//! A request may exclude at most `LIBRARIAN_MAX_EXCLUDE` (default 200) servers.
+use super::request::RequestError;
+use super::urls::same_endpoint;
+use super::{McpEntry, mirrors};
+use axum::http::StatusCode;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::env;
+
+const DEFAULT_MAX_EXCLUDE: usize = 200;
+
+/// `LIBRARIAN_MAX_EXCLUDE`: how many servers one request may exclude.
+pub fn max_exclude() -> usize {
+    env::var("LIBRARIAN_MAX_EXCLUDE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_EXCLUDE)
+}
+
+#[derive(Debug, Default)]
+pub struct Exclusions {
+    names: HashSet<String>,
+    endpoints: Vec<String>,
+}
+
+impl Exclusions {
+    /// Sorts `items` into endpoints (anything with a scheme) and catalog
+    /// names. Refuses a list over `max_exclude` or with a blank item.
+    pub fn parse(items: Option<&[String]>) -> Result<Self, RequestError> {
+        let invalid = |message: String| RequestError {
+            status: StatusCode::BAD_REQUEST,
+            field: Some("exclude".to_string()),
+            message,
+        };
+        let items = items.unwrap_or_default();
+        let max = max_exclude();
+        if items.len() > max {
+            return Err(invalid(format!(
+                "exclude lists {} servers; at most {} are allowed",
+                items.len(),
+                max
+            )));
+        }
+
+        let mut exclusions = Exclusions::default();
+        for item in items {
+            let item = item.trim();
+            if item.is_empty() {
+                return Err(invalid(
+                    "exclude must not contain empty names or endpoints".to_string(),
+                ));
+            }
+            if item.contains("://") {
+                exclusions.endpoints.push(item.to_string());
+            } else {
+                exclusions.names.insert(item.to_string());
+            }
+        }
+        Ok(exclusions)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.endpoints.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len() + self.endpoints.len()
+    }
+
+    pub fn excludes(&self, entry: &McpEntry) -> bool {
+        self.names.contains(&entry.name)
+            || self
+                .endpoints
+                .iter()
+                .any(|endpoint| mirrors::serves(entry, endpoint))
+    }
+
+    /// Drops excluded candidates; returns how many.
+    pub fn retain_candidates(&self, candidates: &mut Vec<(f64, McpEntry)>) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let before = candidates.len();
+        candidates.retain(|(_, entry)| !self.excludes(entry));
+        before - candidates.len()
+    }
+
+    /// Drops excluded recommendations and their instructions, by name, by
+    /// endpoint, or by the catalog entry the endpoint belongs to. A response
+    /// left with none becomes the empty response. Returns how many were dropped.
+    pub fn retain_recommendations(
+        &self,
+        response: &mut Value,
+        query: &str,
+        catalog: &[McpEntry],
+    ) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let Some(recommendations) = response
+            .get_mut("recommendations")
+            .and_then(Value::as_array_mut)
+        else {
+            return 0;
+        };
+
+        let mut dropped_names = Vec::new();
+        recommendations.retain(|rec| {
+            let name = rec.get("name").and_then(Value::as_str).unwrap_or_default();
+            let endpoint = rec
+                .get("endpoint")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let excluded = self.names.contains(name)
+                || mirrors::find(catalog, endpoint).is_some_and(|entry| self.excludes(entry))
+                || self.endpoints.iter().any(|e| same_endpoint(e, endpoint));
+            if excluded {
+                dropped_names.push(name.to_string());
+            }
+            !excluded
+        });
+        if recommendations.is_empty() && !dropped_names.is_empty() {
+            tracing::debug!("Every recommendation was excluded by the caller");
+            *response = super::response::empty_response(query);
+            return dropped_names.len();
+        }
+
+        if let Some(instructions) = response
+            .get_mut("instructions")
+            .and_then(Value::as_object_mut)
+        {
+            for name in &dropped_names {
+                instructions.remove(name);
+            }
+        }
+        dropped_names.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(value: Value) -> McpEntry {
+        let mut entry: McpEntry = serde_json::from_value(value).unwrap();
+        mirrors::settle(&mut entry).unwrap();
+        entry
+    }
+
+    fn catalog() -> Vec<McpEntry> {
+        let plain = |name: &str, endpoint: &str| {
+            entry(
+                json!({ "name": name, "endpoint": endpoint, "version": "1.0.0", "capabilities": [], "desc": "" }),
+            )
+        };
+        vec![
+            plain("example/weather", "https://weather.example.com/mcp"),
+            plain("example/issues", "https://issues.example.com/mcp"),
+            entry(json!({
+                "name": "example/geocode",
+                "endpoints": ["https://us.geocode.example.com/mcp", "https://eu.geocode.example.com/mcp"],
+                "version": "1.0.0",
+                "capabilities": [],
+                "desc": "",
+            })),
+        ]
+    }
+
+    fn exclusions(items: &[&str]) -> Exclusions {
+        let items: Vec<String> = items.iter().map(|s| s.to_string()).collect();
+        Exclusions::parse(Some(&items)).unwrap()
+    }
+
+    fn names(candidates: &[(f64, McpEntry)]) -> Vec<&str> {
+        candidates.iter().map(|(_, e)| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_sorts_names_from_endpoints_and_refuses_blanks() {
+        let parsed = exclusions(&[" example/issues ", "https://weather.example.com/mcp"]);
+        assert_eq!(parsed.len(), 2);
+        assert!(Exclusions::parse(None).unwrap().is_empty());
+        let blank = Exclusions::parse(Some(&["  ".to_string()])).unwrap_err();
+        assert_eq!(blank.field.as_deref(), Some("exclude"));
+        let too_many = vec!["x".to_string(); DEFAULT_MAX_EXCLUDE + 1];
+        assert_eq!(
+            Exclusions::parse(Some(&too_many)).unwrap_err().status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn candidates_are_excluded_by_name_or_any_deployment() {
+        let mut candidates: Vec<(f64, McpEntry)> =
+            catalog().into_iter().map(|e| (1.0, e)).collect();
+        let dropped = exclusions(&["example/issues", "https://eu.geocode.example.com/mcp/"])
+            .retain_candidates(&mut candidates);
+        assert_eq!(dropped, 2);
+        assert_eq!(names(&candidates), ["example/weather"]);
+    }
+
+    #[test]
+    fn recommendations_and_their_instructions_are_dropped() {
+        let mut response = json!({
+            "query": "weather",
+            "recommendations": [
+                { "name": "example/weather", "endpoint": "https://weather.example.com/mcp" },
+                { "name": "example/issues", "endpoint": "https://issues.example.com/mcp" },
+                { "name": "renamed by the model", "endpoint": "https://us.geocode.example.com/mcp" },
+            ],
+            "instructions": { "example/weather": {}, "example/issues": {} },
+        });
+        let excluded = exclusions(&["example/issues", "https://eu.geocode.example.com/mcp"]);
+        assert_eq!(
+            excluded.retain_recommendations(&mut response, "weather", &catalog()),
+            2
+        );
+        assert_eq!(response["recommendations"].as_array().unwrap().len(), 1);
+        assert_eq!(response["recommendations"][0]["name"], "example/weather");
+        assert_eq!(response["instructions"], json!({ "example/weather": {} }));
+    }
+
+    #[test]
+    fn excluding_every_recommendation_leaves_the_empty_response() {
+        let mut response = json!({
+            "recommendations": [{ "name": "example/weather", "endpoint": "https://weather.example.com/mcp/" }],
+        });
+        let excluded = exclusions(&["https://weather.example.com/mcp"]);
+        assert_eq!(
+            excluded.retain_recommendations(&mut response, "weather", &catalog()),
+            1
+        );
+        assert_eq!(
+            response,
+            crate::backend::response::empty_response("weather")
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// src/backend/explain.rs
+//! `POST /discover/explain` (admin): why a given endpoint was or wasn't
+//! recommended for a query. The body is a `/discover` request plus `target`,
+//! the expected endpoint; the report follows that endpoint through the same
+//! pipeline: catalog membership, similarity to the retrieval query (and its
+//! rank over the whole catalog), each filter it fails, availability, the
+//! request's `exclude`, whether it survived as a candidate and the prompt budget, and finally whether the
+//! model recommended it and it survived `min_score` and pins. `verdict` names
+//! the first stage that lost it, or `recommended`. Reaching the model stage
+//! costs one model call, which is why this is admin-only.
+use super::capindex::cosine_similarity;
+use super::request::ApiJson;
+use super::urls::same_endpoint;
+use super::{
+    DiscoverRequest, Librarian, LibrarianHandle, McpEntry, apply_pins, candidate_filter,
+    check_discover_output, discover_candidates, exclude, fit_discover_prompt, mirrors,
+    restricted_candidates, retrieval_fetch_k, retrieval_query, sanitize, validate, verify,
+};
+use anyhow::{Result, anyhow};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use rig::embeddings::EmbeddingModel as _;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+#[derive(Deserialize)]
+pub struct ExplainRequest {
+    /// The endpoint the caller expected to see recommended.
+    pub target: String,
+    #[serde(flatten)]
+    pub discover: DiscoverRequest,
+}
+
+fn finish(mut report: Value, verdict: &str) -> Result<Value> {
+    report["verdict"] = json!(verdict);
+    Ok(report)
+}
+
+/// Position and body of `entry` among the response's recommendations, under
+/// any of its deployments.
+fn recommendation(response: &Value, entry: &McpEntry) -> Option<(usize, Value)> {
+    let recs = response.get("recommendations").and_then(Value::as_array)?;
+    let at = recs.iter().position(|rec| {
+        rec.get("endpoint")
+            .and_then(Value::as_str)
+            .is_some_and(|e| mirrors::serves(entry, e))
+    })?;
+    Some((at, recs[at].clone()))
+}
+
+fn candidate_rank(candidates: &[(f64, McpEntry)], endpoint: &str) -> Option<usize> {
+    candidates
+        .iter()
+        .position(|(_, e)| same_endpoint(&e.endpoint, endpoint))
+}
+
+async fn explain(librarian: &Librarian, req: &DiscoverRequest, target: &str) -> Result<Value> {
+    let mut report = json!({ "query": req.query, "target": target });
+    let Some(entry) = mirrors::find(&librarian.catalog, target) else {
+        if librarian
+            .disabled
+            .iter()
+            .any(|e| mirrors::serves(e, target))
+        {
+            return finish(report, "disabled");
+        }
+        return finish(report, "not_in_catalog");
+    };
+    report["name"] = json!(entry.name);
+
+    // scored against the whole catalog, not just what was fetched
+    let filter = candidate_filter(librarian, req);
+    let (retrieval_text, _) = retrieval_query(librarian, req);
+    let embedded = librarian
+        .embedding_model
+        .embed_text(&retrieval_text)
+        .await?;
+    let scores: Vec<(f64, &McpEntry)> = librarian
+        .embeddings
+        .iter()
+        .map(|(e, vectors)| {
+            let score = vectors
+                .iter()
+                .map(|v| cosine_similarity(&embedded.vec, &v.vec))
+                .fold(f64::NEG_INFINITY, f64::max);
+            (score, e)
+        })
+        .collect();
+    let similarity = scores
+        .iter()
+        .find(|(_, e)| same_endpoint(&e.endpoint, &entry.endpoint))
+        .map(|(score, _)| *score);
+    let similarity_rank =
+        similarity.map(|s| scores.iter().filter(|(other, _)| *other > s).count() + 1);
+    report["retrieval"] = json!({
+        "query": retrieval_text,
+        "similarity": similarity,
+        "similarity_rank": similarity_rank,
+        "catalog_size": scores.len(),
+        "fetch_k": retrieval_fetch_k(librarian, &filter),
+        "restricted": req.restrict_to.is_some(),
+    });
+
+    let failures = filter.failures(entry);
+    report["filters"] = json!(
+        failures
+            .iter()
+            .map(|stage| json!({ "stage": stage.name(), "soft": filter.is_soft(*stage) }))
+            .collect::<Vec<_>>()
+    );
+    let availability = mirrors::availability(entry, &librarian.verification);
+    let threshold = verify::min_availability(req.filters.as_ref());
+    report["availability"] = json!({ "recent": availability, "threshold": threshold });
+
+    let (mut candidates, _, _) = discover_candidates(librarian, req).await?;
+    report["candidate"] = match candidate_rank(&candidates, &entry.endpoint) {
+        Some(at) => json!({ "rank": at + 1, "score": candidates[at].0 }),
+        None => Value::Null,
+    };
+    if let Some(stage) = failures.iter().find(|stage| !filter.is_soft(**stage)) {
+        report["excluded_by"] = json!(stage.name());
+        return finish(report, "filtered");
+    }
+    if let (Some(availability), Some(threshold)) = (availability, threshold)
+        && availability < threshold
+    {
+        return finish(report, "below_min_availability");
+    }
+    let exclusions =
+        exclude::Exclusions::parse(req.exclude.as_deref()).map_err(|e| anyhow!(e.message))?;
+    if exclusions.excludes(entry) {
+        return finish(report, "excluded");
+    }
+    if report["candidate"].is_null() {
+        return finish(report, "not_retrieved");
+    }
+
+    let sanitized = sanitize::sanitize_query(&req.query);
+    let (prompt, _) = fit_discover_prompt(librarian, req, &sanitized.text, &mut candidates, false);
+    if candidate_rank(&candidates, &entry.endpoint).is_none() {
+        return finish(report, "dropped_for_prompt_budget");
+    }
+
+    let output = match librarian.prompt_with_fallback(&prompt).await {
+        Ok((output, _)) => output,
+        Err(e) => {
+            report["model"] = json!({ "error": e.to_string() });
+            return finish(report, "model_error");
+        }
+    };
+    let mut parsed = match check_discover_output(
+        librarian,
+        &req.query,
+        &output,
+        &candidates,
+        filter.region.as_deref(),
+        false,
+    ) {
+        Ok(parsed) => parsed,
+        Err(problem) => {
+            report["model"] = json!({ "error": problem });
+            return finish(report, "model_error");
+        }
+    };
+    let chosen = recommendation(&parsed, entry);
+    report["model"] = json!({
+        "recommended": chosen.is_some(),
+        "score": chosen.as_ref().and_then(|(_, rec)| rec.get("score").cloned()),
+        "rationale": chosen.as_ref().and_then(|(_, rec)| rec.get("rationale").cloned()),
+    });
+
+    let min_score = req.min_score.unwrap_or_else(validate::min_score_from_env);
+    report["min_score"] = json!(min_score);
+    validate::retain_min_score(&mut parsed, &req.query, min_score);
+    if req.prefer_verified {
+        verify::prefer_verified(&mut parsed, verify::prefer_verified_margin());
+    }
+    apply_pins(librarian, &req.query, &filter, &mut parsed);
+    match (chosen, recommendation(&parsed, entry)) {
+        (_, Some((at, rec))) => {
+            report["rank"] = json!(at + 1);
+            let pinned = rec.get("pinned").and_then(Value::as_bool).unwrap_or(false);
+            finish(report, if pinned { "pinned" } else { "recommended" })
+        }
+        (Some(_), None) => finish(report, "below_min_score"),
+        (None, None) => finish(report, "omitted_by_model"),
+    }
+}
+
+/// `POST /discover/explain`: the report described above, `400` for an
+/// invalid request.
+pub async fn explain_handler(
+    State(handle): State<LibrarianHandle>,
+    ApiJson(req): ApiJson<ExplainRequest>,
+) -> Response {
+    let librarian = handle.current();
+    let ExplainRequest { target, discover } = req;
+    if let Err(e) = sanitize::check_query_length(&discover.query) {
+        return e.into_response();
+    }
+    if let Some(names) = &discover.restrict_to
+        && let Err(e) = restricted_candidates(&librarian, names)
+    {
+        return e.into_response();
+    }
+    if let Err(e) = exclude::Exclusions::parse(discover.exclude.as_deref()) {
+        return e.into_response();
+    }
+    match explain(&librarian, &discover, &target).await {
+        Ok(report) => AxumJson(report).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({ "error": format!("Explain error: {:#}", e) })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,105 @@
+// src/backend/extract.rs
+//! Tolerant recovery of the JSON object from agent output that ignored the
+//! strict-JSON instruction: markdown fences, a leading sentence, trailing notes.
+use serde_json::Value;
+use std::env;
+
+/// On unless `LIBRARIAN_TOLERANT_JSON=false`.
+pub fn tolerant_json_enabled() -> bool {
+    !env::var("LIBRARIAN_TOLERANT_JSON").is_ok_and(|v| v == "0" || v == "false")
+}
+
+/// Parses `output` strictly, falling back to the first balanced `{...}` object
+/// in it when tolerant extraction is enabled. Cleanup is logged.
+pub fn parse_agent_json(output: &str) -> Result<Value, String> {
+    let strict = serde_json::from_str::<Value>(output.trim());
+    let err = match strict {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    if !tolerant_json_enabled() {
+        return Err(format!("not valid JSON: {}", err));
+    }
+
+    let Some(object) = first_object(output) else {
+        return Err(format!("not valid JSON: {}", err));
+    };
+    let value =
+        serde_json::from_str::<Value>(object).map_err(|e| format!("not valid JSON: {}", e))?;
+    tracing::warn!(
+        stripped_bytes = output.len() - object.len(),
+        "Extracted JSON from agent output wrapped in prose or fences"
+    );
+    Ok(value)
+}
+
+/// The first balanced JSON object in `text`, with braces inside string
+/// literals ignored. Fences need no special case: they sit outside the object.
+pub fn first_object(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + offset + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn clean_output_parses() {
+        let parsed =
+            parse_agent_json("  {\"query\": \"weather\", \"recommendations\": []}\n").unwrap();
+        assert_eq!(parsed, json!({ "query": "weather", "recommendations": [] }));
+    }
+
+    #[test]
+    fn fenced_output_parses() {
+        let output = "```json\n{\"query\": \"weather\", \"recommendations\": []}\n```";
+        assert_eq!(parse_agent_json(output).unwrap()["query"], "weather");
+    }
+
+    #[test]
+    fn leading_and_trailing_prose_is_stripped() {
+        let output = "Here are my picks:\n{\"query\": \"weather\", \"note\": \"use {city} } here\"}\nHope this helps {:";
+        let parsed = parse_agent_json(output).unwrap();
+        assert_eq!(
+            parsed,
+            json!({ "query": "weather", "note": "use {city} } here" })
+        );
+    }
+
+    #[test]
+    fn output_without_an_object_is_an_error() {
+        assert!(parse_agent_json("I could not find a match.").is_err());
+        assert!(parse_agent_json("{\"query\": \"weather\"").is_err());
+        assert_eq!(
+            first_object("{\"a\": \"\\\"}\"}"),
+            Some("{\"a\": \"\\\"}\"}")
+        );
+    }
+}
@@ -0,0 +1,331 @@
+// src/backend/facilitator.rs
+//! Reachability of the x402 facilitator. A down facilitator otherwise only
+//! shows up as a confusing failure at payment time, so it is probed at startup
+//! and reported by `/health`.
+//!
+//! While it is down, paid routes follow `PAYMENT_FAILURE_MODE`:
+//!
+//! - `closed` (the default): `503` with code `FACILITATOR_UNAVAILABLE` and a
+//!   `Retry-After`, so nothing is served unpaid.
+//! - `open`: the request is served without the x402 layer, logged as an error,
+//!   counted and marked with `x-librarian-payment-state: PAYMENT_BYPASSED`.
+//!
+//! An unknown `PAYMENT_FAILURE_MODE` fails startup.
+//!
+//! The facilitator is probed by one background task every 30s; requests and
+//! `/health` only read the last result. API-key callers never reach the
+//! facilitator and are unaffected. A payment the x402 layer refuses asks for
+//! an early probe, at most one every 5s however many are refused, so an outage
+//! that starts between probes is picked up quickly.
+use super::metrics::METRICS;
+use super::payer::PAYMENT_HEADER;
+use super::probe::{LastProbe, ProbeStatus};
+use super::settlement::PAYMENT_STATE_HEADER;
+use super::tasks::TASKS;
+use super::urls;
+use anyhow::{Result, bail};
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::env;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower::ServiceExt as _;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Background probe period, and the `Retry-After` of a refused request.
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// Refused payments ask for a probe at most this often.
+const MIN_REPROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FacilitatorStatus {
+    pub url: String,
+    #[serde(flatten)]
+    pub probe: ProbeStatus,
+}
+
+pub struct FacilitatorHealth {
+    url: String,
+    client: reqwest::Client,
+    last: LastProbe<FacilitatorStatus>,
+    /// When the last background or early probe was started.
+    probed_at: Mutex<Option<Instant>>,
+}
+
+impl FacilitatorHealth {
+    pub fn new(url: impl Into<String>) -> Self {
+        FacilitatorHealth {
+            url: url.into(),
+            client: reqwest::Client::new(),
+            last: LastProbe::default(),
+            probed_at: Mutex::new(None),
+        }
+    }
+
+    /// GETs the facilitator's `/supported` endpoint; any 2xx counts as reachable.
+    pub async fn probe(&self) -> FacilitatorStatus {
+        let endpoint = urls::join_str(&self.url, "supported");
+        let result = self
+            .client
+            .get(&endpoint)
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        let status = FacilitatorStatus {
+            url: self.url.clone(),
+            probe: ProbeStatus::now(result.err().map(|e| e.to_string())),
+        };
+        self.last.record(status.clone());
+        status
+    }
+
+    /// The last probe result; `None` until the first probe finishes.
+    pub fn status(&self) -> Option<FacilitatorStatus> {
+        self.last.get()
+    }
+
+    /// Claims the next probe unless one started within `gap`.
+    fn claim_probe(&self, gap: Duration) -> bool {
+        let mut probed_at = self.probed_at.lock().unwrap_or_else(|p| p.into_inner());
+        if probed_at.is_some_and(|at| at.elapsed() < gap) {
+            return false;
+        }
+        *probed_at = Some(Instant::now());
+        true
+    }
+
+    /// Probes every `PROBE_INTERVAL` until shutdown.
+    pub fn spawn_monitor(self: &Arc<Self>) {
+        let health = Arc::clone(self);
+        TASKS.spawn("facilitator_health", |cancel| async move {
+            let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // the first tick fires immediately and startup has just probed
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = cancel.cancelled() => return,
+                }
+                if health.claim_probe(MIN_REPROBE_INTERVAL) {
+                    health.probe().await;
+                }
+            }
+        });
+    }
+
+    /// Probes in the background now, unless a probe started within
+    /// `MIN_REPROBE_INTERVAL`.
+    pub fn reprobe(self: &Arc<Self>) {
+        if !self.claim_probe(MIN_REPROBE_INTERVAL) {
+            return;
+        }
+        let health = Arc::clone(self);
+        TASKS.spawn("facilitator_reprobe", |_| async move {
+            health.probe().await;
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentFailureMode {
+    Open,
+    Closed,
+}
+
+impl PaymentFailureMode {
+    /// Reads `PAYMENT_FAILURE_MODE`: `open` or `closed` (the default).
+    pub fn from_env() -> Result<Self> {
+        match env::var("PAYMENT_FAILURE_MODE")
+            .ok()
+            .as_deref()
+            .map(str::trim)
+        {
+            Some("open") => Ok(PaymentFailureMode::Open),
+            Some("closed") | Some("") | None => Ok(PaymentFailureMode::Closed),
+            Some(other) => bail!(
+                "Unknown PAYMENT_FAILURE_MODE {:?} (expected open or closed)",
+                other
+            ),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PaymentFailureMode::Open => "open",
+            PaymentFailureMode::Closed => "closed",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PaymentFailurePolicy {
+    pub health: Arc<FacilitatorHealth>,
+    pub mode: PaymentFailureMode,
+    /// The route without its x402 layer, served when failing open.
+    pub unpaid: Router,
+}
+
+/// Route middleware placed between the API-key bypass and the x402 layer on
+/// paid routes; see the module docs.
+pub async fn payment_failure_layer(
+    State(policy): State<PaymentFailurePolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // not probed yet counts as reachable: x402 reports its own failures
+    let Some(status) = policy
+        .health
+        .status()
+        .filter(|status| !status.probe.reachable)
+    else {
+        let presented = request.headers().contains_key(PAYMENT_HEADER);
+        let response = next.run(request).await;
+        if presented && response.status() == StatusCode::PAYMENT_REQUIRED {
+            policy.health.reprobe();
+        }
+        return response;
+    };
+
+    let path = request.uri().path().to_string();
+    let error = status.probe.error.as_deref().unwrap_or_default();
+    match policy.mode {
+        PaymentFailureMode::Closed => {
+            METRICS
+                .payment_unavailable_total
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                facilitator = %status.url,
+                error,
+                "Facilitator unreachable, refusing {} (PAYMENT_FAILURE_MODE=closed)",
+                path
+            );
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, PROBE_INTERVAL.as_secs().to_string())],
+                AxumJson(json!({
+                    "error": {
+                        "code": "FACILITATOR_UNAVAILABLE",
+                        "message": "the payment facilitator is unreachable, so payments cannot be verified; retry later",
+                    }
+                })),
+            )
+                .into_response()
+        }
+        PaymentFailureMode::Open => {
+            METRICS
+                .payment_bypassed_total
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::error!(
+                facilitator = %status.url,
+                error,
+                "PAYMENT BYPASSED: facilitator unreachable, serving {} unpaid (PAYMENT_FAILURE_MODE=open)",
+                path
+            );
+            let mut response = match policy.unpaid.oneshot(request).await {
+                Ok(response) => response,
+                Err(never) => match never {},
+            };
+            response.headers_mut().insert(
+                PAYMENT_STATE_HEADER,
+                HeaderValue::from_static("PAYMENT_BYPASSED"),
+            );
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get};
+
+    fn down() -> Arc<FacilitatorHealth> {
+        let health = FacilitatorHealth::new("http://127.0.0.1:9");
+        health.last.record(FacilitatorStatus {
+            url: health.url.clone(),
+            probe: ProbeStatus::now(Some("connection refused".to_string())),
+        });
+        Arc::new(health)
+    }
+
+    fn app(health: Arc<FacilitatorHealth>, mode: PaymentFailureMode) -> Router {
+        let policy = PaymentFailurePolicy {
+            health,
+            mode,
+            unpaid: Router::new().route("/paid", get(|| async { "unpaid" })),
+        };
+        Router::new()
+            .route("/paid", get(|| async { StatusCode::PAYMENT_REQUIRED }))
+            .layer(axum::middleware::from_fn_with_state(
+                policy,
+                payment_failure_layer,
+            ))
+    }
+
+    fn paid() -> Request {
+        Request::get("/paid").body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn early_probes_are_rate_limited() {
+        let health = FacilitatorHealth::new("http://127.0.0.1:9");
+        assert!(health.claim_probe(MIN_REPROBE_INTERVAL));
+        assert!(!health.claim_probe(MIN_REPROBE_INTERVAL));
+        assert!(health.claim_probe(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn closed_mode_refuses_from_the_cached_status() {
+        let health = down();
+        let response = app(Arc::clone(&health), PaymentFailureMode::Closed)
+            .oneshot(paid())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // requests never probe themselves
+        assert!(health.probed_at.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn open_mode_serves_the_unpaid_route() {
+        let response = app(down(), PaymentFailureMode::Open)
+            .oneshot(paid())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[PAYMENT_STATE_HEADER], "PAYMENT_BYPASSED");
+    }
+
+    #[tokio::test]
+    async fn unprobed_facilitator_is_left_to_x402() {
+        let health = Arc::new(FacilitatorHealth::new("http://127.0.0.1:9"));
+        let response = app(health, PaymentFailureMode::Closed)
+            .oneshot(paid())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+    }
+
+    #[test]
+    fn unknown_failure_mode_is_an_error() {
+        // SAFETY: no other test reads PAYMENT_FAILURE_MODE
+        unsafe { env::set_var("PAYMENT_FAILURE_MODE", "opne") };
+        assert!(PaymentFailureMode::from_env().is_err());
+        unsafe { env::set_var("PAYMENT_FAILURE_MODE", "open") };
+        assert_eq!(
+            PaymentFailureMode::from_env().unwrap(),
+            PaymentFailureMode::Open
+        );
+        unsafe { env::remove_var("PAYMENT_FAILURE_MODE") };
+    }
+}
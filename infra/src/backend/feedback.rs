@@ -0,0 +1,222 @@
+// src/backend/feedback.rs
+//! Outcome signals from clients: did a recommendation actually work? Every
+//! successful `/discover` carries a `query_id`, and `POST /feedback` reports on
+//! one of that query's recommended endpoints. Reports are kept by the
+//! configured `store::Store` (by default appended as JSON lines to
+//! `LIBRARIAN_FEEDBACK_PATH`) and aggregated per endpoint for `/catalog/stats`.
+//! Ranking does not read them yet.
+use super::request::{ApiJson, RequestError};
+use super::store::Store;
+use anyhow::Result;
+use axum::{
+    Extension,
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+pub const QUERY_ID_HEADER: &str = "x-librarian-query-id";
+/// Issued query ids remembered for validation; the oldest are forgotten first.
+const MAX_TRACKED_QUERIES: usize = 10_000;
+const MAX_NOTES_CHARS: usize = 2000;
+
+#[derive(Debug, Deserialize)]
+pub struct FeedbackRequest {
+    pub query_id: String,
+    pub endpoint: String,
+    pub useful: bool,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// One stored report; a line of the feedback file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FeedbackRecord {
+    pub query_id: String,
+    pub endpoint: String,
+    pub useful: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct EndpointFeedback {
+    pub useful: u64,
+    pub not_useful: u64,
+}
+
+impl EndpointFeedback {
+    fn add(&mut self, useful: bool) {
+        if useful {
+            self.useful += 1;
+        } else {
+            self.not_useful += 1;
+        }
+    }
+}
+
+/// What a query recommended, and which of those already have feedback.
+struct IssuedQuery {
+    endpoints: Vec<String>,
+    rated: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    queries: HashMap<String, IssuedQuery>,
+    order: VecDeque<String>,
+    totals: BTreeMap<String, EndpointFeedback>,
+}
+
+pub struct FeedbackStore {
+    store: Arc<dyn Store>,
+    inner: Mutex<Inner>,
+}
+
+impl FeedbackStore {
+    /// Replays the reports already in `store` into the aggregates.
+    pub fn open(store: Arc<dyn Store>) -> Result<Self> {
+        let mut inner = Inner::default();
+        for record in store.feedback()? {
+            inner
+                .totals
+                .entry(record.endpoint)
+                .or_default()
+                .add(record.useful);
+        }
+        Ok(FeedbackStore {
+            store,
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Registers query `id` and the endpoints it recommended.
+    pub fn issue(&self, id: &str, response: &Value) {
+        let endpoints = response
+            .get("recommendations")
+            .and_then(Value::as_array)
+            .map(|recs| {
+                recs.iter()
+                    .filter_map(|rec| rec.get("endpoint").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let id = id.to_string();
+
+        let mut inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        if inner.order.len() >= MAX_TRACKED_QUERIES
+            && let Some(oldest) = inner.order.pop_front()
+        {
+            inner.queries.remove(&oldest);
+        }
+        inner.order.push_back(id.clone());
+        inner.queries.insert(
+            id.clone(),
+            IssuedQuery {
+                endpoints,
+                rated: HashSet::new(),
+            },
+        );
+    }
+
+    /// Checks `req` against the issued query and appends it to the store. Each
+    /// recommended endpoint takes one report per query.
+    fn record(&self, req: FeedbackRequest) -> Result<Result<(), RequestError>> {
+        let invalid = |status, field: &str, message: String| {
+            Ok(Err(RequestError {
+                status,
+                field: Some(field.to_string()),
+                message,
+            }))
+        };
+        if req
+            .notes
+            .as_ref()
+            .is_some_and(|n| n.chars().count() > MAX_NOTES_CHARS)
+        {
+            return invalid(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "notes",
+                format!("notes must be at most {} characters", MAX_NOTES_CHARS),
+            );
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(issued) = inner.queries.get_mut(&req.query_id) else {
+            return invalid(
+                StatusCode::NOT_FOUND,
+                "query_id",
+                format!("Unknown or expired query_id {:?}", req.query_id),
+            );
+        };
+        if !issued.endpoints.contains(&req.endpoint) {
+            return invalid(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "endpoint",
+                format!("{:?} was not recommended for this query", req.endpoint),
+            );
+        }
+        if issued.rated.contains(&req.endpoint) {
+            return invalid(
+                StatusCode::CONFLICT,
+                "endpoint",
+                format!(
+                    "Feedback for {:?} on this query was already recorded",
+                    req.endpoint
+                ),
+            );
+        }
+
+        let record = FeedbackRecord {
+            query_id: req.query_id,
+            endpoint: req.endpoint,
+            useful: req.useful,
+            notes: req.notes,
+            recorded_at: Utc::now(),
+        };
+        self.store.record_feedback(&record)?;
+
+        issued.rated.insert(record.endpoint.clone());
+        inner
+            .totals
+            .entry(record.endpoint)
+            .or_default()
+            .add(record.useful);
+        Ok(Ok(()))
+    }
+
+    /// Aggregate reports per endpoint, including those from earlier runs.
+    pub fn totals(&self) -> BTreeMap<String, EndpointFeedback> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .totals
+            .clone()
+    }
+}
+
+/// `POST /feedback`: `204` once stored; `404` for an unknown `query_id`, `422`
+/// for an endpoint the query did not recommend, `409` for a repeat report.
+pub async fn feedback_handler(
+    Extension(store): Extension<Arc<FeedbackStore>>,
+    ApiJson(req): ApiJson<FeedbackRequest>,
+) -> Response {
+    match store.record(req) {
+        Ok(Ok(())) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => e.into_response(),
+        Err(e) => {
+            tracing::error!("Failed to store feedback: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({ "error": "Failed to store feedback" })),
+            )
+                .into_response()
+        }
+    }
+}
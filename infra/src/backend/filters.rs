@@ -0,0 +1,812 @@
+// src/backend/filters.rs
+//! Request filters over catalog entries. The capability, transport, tag and
+//! region filters are hard by default: a non-matching entry is dropped. With
+//! `filters.mode = "soft"` (or per filter, `filters.modes = {"capability":
+//! "soft"}`; default from `LIBRARIAN_FILTER_MODE`), a miss instead costs
+//! `LIBRARIAN_SOFT_FILTER_PENALTY` (default 0.05) similarity per soft filter
+//! it fails, so non-matching servers sink rather than vanish. The endpoint
+//! policy and auth rule are always hard.
+//!
+//! `filters.require_all_capabilities` and `filters.require_any_capabilities`
+//! (lists, normalized like catalog capabilities) match whole capability names
+//! rather than substrings: an entry must have every one of the first and at
+//! least one of the second. They are always hard, and a request nothing
+//! satisfies gets the empty response without a model call.
+//!
+//! `filters.cost` caps the declared `price_usd`: `"<0.001"` excludes servers
+//! charging 0.001 or more, `"<=0.001"` or a bare number allows exactly that
+//! price. Entries without a declared price count as free and always pass. A
+//! value that doesn't parse is ignored, like any other malformed filter.
+//!
+//! A softened entry only reaches the recommendations (at most three) when
+//! fewer matching entries outrank it. Its lowered similarity also lowers the
+//! catalog-only fallback's `score`, so `min_score` can still drop it there;
+//! the model assigns its own scores, which `min_score` then applies to.
+use super::McpEntry;
+use super::mirrors;
+use super::normalize;
+use super::search::rank_order;
+use serde::Serialize;
+use serde_json::Value;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+pub const FILTER_STATS_HEADER: &str = "x-librarian-filter-stats";
+
+/// Structured predicate over catalog entries, shared by `/discover` and `/search`.
+#[derive(Debug, Clone, Default)]
+pub struct CandidateFilter {
+    pub capability: Option<String>,
+    /// Every one of these capabilities must be present, by exact name.
+    pub require_all_capabilities: Vec<String>,
+    /// At least one of these capabilities must be present, by exact name.
+    pub require_any_capabilities: Vec<String>,
+    pub transport: Option<String>,
+    pub allow_auth: bool,
+    /// Every one of these tags must be present.
+    pub tags: Vec<String>,
+    /// None of these tags may be present.
+    pub exclude_tags: Vec<String>,
+    /// Preferred region, normalized. Same-region entries get a ranking boost.
+    pub region: Option<String>,
+    /// With a preferred region, exclude entries from other regions.
+    pub region_strict: bool,
+    /// Highest acceptable `price_usd`, from `filters.cost`.
+    pub max_price: Option<PriceCeiling>,
+    pub policy: Arc<EndpointPolicy>,
+    /// Stages that penalize instead of excluding; never `Policy` or `Auth`.
+    pub soft: Vec<FilterStage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceCeiling {
+    pub limit: f64,
+    /// `<=` rather than `<`.
+    pub inclusive: bool,
+}
+
+impl PriceCeiling {
+    /// `"<0.001"`, `"<=0.001"`, `"0.001"` or a JSON number; `None` otherwise.
+    pub fn parse(value: &Value) -> Option<Self> {
+        let (limit, inclusive) = match value {
+            Value::Number(n) => (n.as_f64()?, true),
+            Value::String(s) => {
+                let s = s.trim();
+                match s.strip_prefix("<=").or_else(|| s.strip_prefix('<')) {
+                    Some(rest) => (rest.trim().parse::<f64>().ok()?, s.starts_with("<=")),
+                    None => (s.parse::<f64>().ok()?, true),
+                }
+            }
+            _ => return None,
+        };
+        (limit.is_finite() && limit >= 0.0).then_some(PriceCeiling { limit, inclusive })
+    }
+
+    pub fn permits(&self, entry: &McpEntry) -> bool {
+        match entry.price_usd {
+            None => true,
+            Some(price) if self.inclusive => price <= self.limit,
+            Some(price) => price < self.limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Hard,
+    Soft,
+}
+
+impl FilterMode {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "hard" => Some(FilterMode::Hard),
+            "soft" => Some(FilterMode::Soft),
+            _ => None,
+        }
+    }
+
+    /// `LIBRARIAN_FILTER_MODE`: `hard` (default) or `soft`.
+    pub fn from_env() -> Self {
+        env::var("LIBRARIAN_FILTER_MODE")
+            .ok()
+            .and_then(|v| FilterMode::parse(&v))
+            .unwrap_or(FilterMode::Hard)
+    }
+}
+
+pub const DEFAULT_SOFT_FILTER_PENALTY: f64 = 0.05;
+
+/// Similarity subtracted per failed soft filter, from `LIBRARIAN_SOFT_FILTER_PENALTY`.
+pub fn soft_filter_penalty() -> f64 {
+    env::var("LIBRARIAN_SOFT_FILTER_PENALTY")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|p| p.is_finite() && *p >= 0.0)
+        .unwrap_or(DEFAULT_SOFT_FILTER_PENALTY)
+}
+
+/// Operator-controlled hard filter from `MCP_ALLOWLIST` / `MCP_DENYLIST`.
+/// Patterns are comma-separated and match an entry's endpoint or name; `*` is a
+/// wildcard. The denylist always wins over the allowlist, and an empty
+/// allowlist permits everything not denied.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointPolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    /// Reject loopback and private-range endpoints; see `is_private_endpoint`.
+    pub deny_private: bool,
+}
+
+/// `LIBRARIAN_DENY_PRIVATE_ENDPOINTS`, defaulting to on unless
+/// `LIBRARIAN_ENV` is `development` (or `dev`).
+pub fn deny_private_from_env() -> bool {
+    match env::var("LIBRARIAN_DENY_PRIVATE_ENDPOINTS").as_deref() {
+        Ok("1") | Ok("true") => true,
+        Ok("0") | Ok("false") => false,
+        _ => !matches!(
+            env::var("LIBRARIAN_ENV").as_deref(),
+            Ok("development") | Ok("dev")
+        ),
+    }
+}
+
+/// True when the endpoint's host is `localhost` (or a `.localhost` name) or an
+/// IP literal in a loopback, private (RFC 1918 / unique local), link-local or
+/// unspecified range. Hostnames are not resolved: a lookup per candidate would
+/// be slow, and its answer could change by the time a client connects.
+pub fn is_private_endpoint(endpoint: &str) -> bool {
+    let Ok(url) = url::Url::parse(endpoint) else {
+        return false;
+    };
+    match url.host() {
+        Some(url::Host::Domain(host)) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => is_private_ip(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_private_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_private_ip(IpAddr::V4(v4));
+            }
+            let segment = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || (segment & 0xfe00) == 0xfc00 // unique local
+                || (segment & 0xffc0) == 0xfe80 // link-local
+        }
+    }
+}
+
+impl EndpointPolicy {
+    pub fn from_env() -> Self {
+        let list = |key: &str| {
+            env::var(key)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        EndpointPolicy {
+            allow: list("MCP_ALLOWLIST"),
+            deny: list("MCP_DENYLIST"),
+            deny_private: deny_private_from_env(),
+        }
+    }
+
+    pub fn permits(&self, entry: &McpEntry) -> bool {
+        if self.deny_private
+            && mirrors::deployments(entry)
+                .iter()
+                .any(|d| is_private_endpoint(&d.endpoint))
+        {
+            return false;
+        }
+        let hit = |pattern: &String| {
+            glob_match(pattern, &entry.name)
+                || glob_match(
+                    pattern.trim_end_matches('/'),
+                    entry.endpoint.trim_end_matches('/'),
+                )
+        };
+        if self.deny.iter().any(hit) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(hit)
+    }
+
+    /// Logs each catalog entry excluded for pointing at a private address.
+    pub fn log_private_exclusions(&self, catalog: &[McpEntry]) {
+        if !self.deny_private {
+            return;
+        }
+        let private = |e: &&McpEntry| {
+            mirrors::deployments(e)
+                .iter()
+                .any(|d| is_private_endpoint(&d.endpoint))
+        };
+        for entry in catalog.iter().filter(private) {
+            tracing::warn!(
+                name = %entry.name,
+                endpoint = %entry.endpoint,
+                "Excluding catalog entry with a loopback/private endpoint"
+            );
+        }
+    }
+
+    /// Number of catalog entries the active lists would never let through.
+    pub fn blocked_count(&self, catalog: &[McpEntry]) -> usize {
+        catalog.iter().filter(|e| !self.permits(e)).count()
+    }
+}
+
+const DEFAULT_FILTERED_FETCH_MULTIPLIER: usize = 4;
+
+/// How many candidates to retrieve when filters are active:
+/// `LIBRARIAN_FILTERED_FETCH_MULTIPLIER` (default 4) times `top_k`.
+pub fn filtered_fetch_k(top_k: usize) -> usize {
+    let multiplier = env::var("LIBRARIAN_FILTERED_FETCH_MULTIPLIER")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_FILTERED_FETCH_MULTIPLIER)
+        .max(1);
+    top_k.saturating_mul(multiplier)
+}
+
+/// Tags match case-insensitively: trimmed, lowercased, empties and duplicates dropped.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+/// Region codes compare lowercase with `_` and spaces read as `-`, so `EU_West`
+/// and `eu-west` are the same region.
+pub fn normalize_region(region: &str) -> String {
+    region.trim().to_lowercase().replace(['_', ' '], "-")
+}
+
+/// Regions match when equal or when one is a `-`-delimited prefix of the other:
+/// `eu` matches `eu-west-1`, but `eu-west` does not match `eu-central-1`.
+/// Entries without a region are global: strict filtering keeps them, but they
+/// never receive the same-region boost.
+pub fn region_matches(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long == short
+        || long
+            .strip_prefix(short)
+            .is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// Case-sensitive glob match supporting `*` only.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let mut rest = text;
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else if let Some(pos) = rest.find(part) {
+            rest = &rest[pos + part.len()..];
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+/// The filter stage that rejected a candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStage {
+    Policy,
+    Auth,
+    Transport,
+    Tag,
+    Region,
+    Capability,
+    RequiredCapabilities,
+    Cost,
+}
+
+impl FilterStage {
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterStage::Policy => "policy",
+            FilterStage::Auth => "auth",
+            FilterStage::Transport => "transport",
+            FilterStage::Tag => "tags",
+            FilterStage::Region => "region",
+            FilterStage::Capability => "capability",
+            FilterStage::RequiredCapabilities => "required_capabilities",
+            FilterStage::Cost => "cost",
+        }
+    }
+
+    /// Stages a request may soften, by their `filters.modes` key.
+    const SOFTENABLE: [(&'static str, FilterStage); 5] = [
+        ("capability", FilterStage::Capability),
+        ("transport", FilterStage::Transport),
+        ("tags", FilterStage::Tag),
+        ("region", FilterStage::Region),
+        ("cost", FilterStage::Cost),
+    ];
+}
+
+/// Per-request counts of candidates dropped at each filter stage.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FilterStats {
+    pub retrieved: usize,
+    pub policy: usize,
+    pub auth: usize,
+    pub transport: usize,
+    pub tag: usize,
+    pub region: usize,
+    pub capability: usize,
+    pub cost: usize,
+    pub availability: usize,
+    /// Dropped by the request's `exclude`.
+    pub excluded: usize,
+    /// Kept despite failing a soft filter, with a score penalty.
+    pub softened: usize,
+    pub kept: usize,
+    /// Raw similarity of everything retrieved, before filters and boosts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<SimilarityStats>,
+}
+
+/// Spread of the index's similarity scores for one query: a low `max` or a
+/// flat `min`..`max` says the query landed in a sparse part of the catalog.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SimilarityStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl SimilarityStats {
+    /// `None` when nothing was retrieved.
+    pub fn of(candidates: &[(f64, McpEntry)]) -> Option<Self> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let scores = candidates.iter().map(|(score, _)| *score);
+        Some(SimilarityStats {
+            count: candidates.len(),
+            min: scores.clone().fold(f64::INFINITY, f64::min),
+            max: scores.clone().fold(f64::NEG_INFINITY, f64::max),
+            mean: scores.sum::<f64>() / candidates.len() as f64,
+        })
+    }
+
+    /// Compact `key=value` form used for the `X-Librarian-Similarity` header.
+    pub fn header_value(&self) -> String {
+        format!(
+            "count={},min={:.4},max={:.4},mean={:.4}",
+            self.count, self.min, self.max, self.mean
+        )
+    }
+}
+
+impl FilterStats {
+    fn record(&mut self, stage: FilterStage) {
+        match stage {
+            FilterStage::Policy => self.policy += 1,
+            FilterStage::Auth => self.auth += 1,
+            FilterStage::Transport => self.transport += 1,
+            FilterStage::Tag => self.tag += 1,
+            FilterStage::Region => self.region += 1,
+            FilterStage::Capability | FilterStage::RequiredCapabilities => self.capability += 1,
+            FilterStage::Cost => self.cost += 1,
+        }
+    }
+
+    /// Compact `key=value` form used for the `X-Librarian-Filter-Stats` header.
+    pub fn header_value(&self) -> String {
+        format!(
+            "retrieved={},policy={},auth={},transport={},tag={},region={},capability={},cost={},availability={},excluded={},softened={},kept={}",
+            self.retrieved,
+            self.policy,
+            self.auth,
+            self.transport,
+            self.tag,
+            self.region,
+            self.capability,
+            self.cost,
+            self.availability,
+            self.excluded,
+            self.softened,
+            self.kept
+        )
+    }
+}
+
+impl CandidateFilter {
+    /// Builds a filter from the request's free-form `filters` object. Unknown keys
+    /// and malformed values are ignored so a bad filter never turns into an error.
+    pub fn from_request(
+        filters: Option<&Value>,
+        allow_auth: bool,
+        policy: Arc<EndpointPolicy>,
+    ) -> Self {
+        let field = |key: &str| {
+            filters
+                .and_then(|f| f.get(key))
+                .and_then(Value::as_str)
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+        };
+
+        // a single tag may be given as a string instead of a list
+        let tag_list = |key: &str| match filters.and_then(|f| f.get(key)) {
+            Some(Value::String(tag)) => normalize::active().tags(std::slice::from_ref(tag)),
+            Some(Value::Array(tags)) => normalize::active().tags(
+                &tags
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect::<Vec<_>>(),
+            ),
+            _ => Vec::new(),
+        };
+        let capability_list = |key: &str| match filters.and_then(|f| f.get(key)) {
+            Some(Value::String(capability)) => {
+                normalize::active().capabilities(std::slice::from_ref(capability))
+            }
+            Some(Value::Array(capabilities)) => normalize::active().capabilities(
+                &capabilities
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(|c| c.trim().to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            _ => Vec::new(),
+        };
+
+        let default_mode = filters
+            .and_then(|f| f.get("mode"))
+            .and_then(Value::as_str)
+            .and_then(FilterMode::parse)
+            .unwrap_or_else(FilterMode::from_env);
+        let modes = filters
+            .and_then(|f| f.get("modes"))
+            .and_then(Value::as_object);
+        let soft = FilterStage::SOFTENABLE
+            .iter()
+            .filter(|(key, _)| {
+                let mode = modes
+                    .and_then(|m| m.get(*key))
+                    .and_then(Value::as_str)
+                    .and_then(FilterMode::parse)
+                    .unwrap_or(default_mode);
+                mode == FilterMode::Soft
+            })
+            .map(|(_, stage)| *stage)
+            .collect();
+
+        CandidateFilter {
+            capability: field("capability").map(|c| normalize::active().term(&c)),
+            require_all_capabilities: capability_list("require_all_capabilities"),
+            require_any_capabilities: capability_list("require_any_capabilities"),
+            transport: field("transport"),
+            allow_auth,
+            tags: tag_list("tags"),
+            exclude_tags: tag_list("exclude_tags"),
+            region: field("preferred_region").map(|r| normalize_region(&r)),
+            region_strict: filters
+                .and_then(|f| f.get("region_strict"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            max_price: filters
+                .and_then(|f| f.get("cost"))
+                .and_then(PriceCeiling::parse),
+            policy,
+            soft,
+        }
+    }
+
+    pub fn is_soft(&self, stage: FilterStage) -> bool {
+        self.soft.contains(&stage)
+    }
+
+    /// True when the request narrows candidates beyond the always-on policy and
+    /// auth stages, i.e. when a small retrieval is likely to be filtered empty.
+    /// Soft filters never narrow, so they don't count.
+    pub fn is_active(&self) -> bool {
+        let hard = |stage| !self.is_soft(stage);
+        (self.capability.is_some() && hard(FilterStage::Capability))
+            || (self.transport.is_some() && hard(FilterStage::Transport))
+            || ((!self.tags.is_empty() || !self.exclude_tags.is_empty()) && hard(FilterStage::Tag))
+            || (self.region_strict && self.region.is_some() && hard(FilterStage::Region))
+            || !self.require_all_capabilities.is_empty()
+            || !self.require_any_capabilities.is_empty()
+            || (self.max_price.is_some() && hard(FilterStage::Cost))
+    }
+
+    /// Every stage `entry` fails, in pipeline order, ignoring soft modes.
+    pub fn failures(&self, entry: &McpEntry) -> Vec<FilterStage> {
+        let mut failed = Vec::new();
+        if !self.policy.permits(entry) {
+            failed.push(FilterStage::Policy);
+        }
+        if entry.auth.required && !self.allow_auth {
+            failed.push(FilterStage::Auth);
+        }
+        if let Some(transport) = &self.transport {
+            if !entry.transport.eq_ignore_ascii_case(transport) {
+                failed.push(FilterStage::Transport);
+            }
+        }
+        if !self.tags.iter().all(|t| entry.tags.contains(t))
+            || self.exclude_tags.iter().any(|t| entry.tags.contains(t))
+        {
+            failed.push(FilterStage::Tag);
+        }
+        if self.region_strict
+            && let Some(preferred) = &self.region
+            && !mirrors::reaches_region(entry, preferred)
+        {
+            failed.push(FilterStage::Region);
+        }
+        if let Some(capability) = &self.capability {
+            if !entry
+                .capabilities
+                .iter()
+                .any(|c| c.to_lowercase().contains(capability))
+            {
+                failed.push(FilterStage::Capability);
+            }
+        }
+        let has = |required: &String| {
+            entry
+                .capabilities
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(required))
+        };
+        if !self.require_all_capabilities.iter().all(has)
+            || (!self.require_any_capabilities.is_empty()
+                && !self.require_any_capabilities.iter().any(has))
+        {
+            failed.push(FilterStage::RequiredCapabilities);
+        }
+        if let Some(ceiling) = &self.max_price
+            && !ceiling.permits(entry)
+        {
+            failed.push(FilterStage::Cost);
+        }
+        failed
+    }
+
+    /// Returns the first hard stage that rejects `entry`, or `None` if it passes.
+    pub fn rejection(&self, entry: &McpEntry) -> Option<FilterStage> {
+        self.failures(entry)
+            .into_iter()
+            .find(|stage| !self.is_soft(*stage))
+    }
+
+    pub fn matches(&self, entry: &McpEntry) -> bool {
+        self.rejection(entry).is_none()
+    }
+
+    /// Drops entries failing a hard stage and penalizes those failing soft
+    /// ones, re-sorting best first when any score changed.
+    pub fn apply(&self, candidates: Vec<(f64, McpEntry)>) -> (Vec<(f64, McpEntry)>, FilterStats) {
+        let mut stats = FilterStats {
+            retrieved: candidates.len(),
+            ..FilterStats::default()
+        };
+        let penalty = if self.soft.is_empty() {
+            0.0
+        } else {
+            soft_filter_penalty()
+        };
+        let mut kept: Vec<(f64, McpEntry)> = Vec::with_capacity(candidates.len());
+        for (score, entry) in candidates {
+            let failures = self.failures(&entry);
+            if let Some(stage) = failures.iter().find(|stage| !self.is_soft(**stage)) {
+                stats.record(*stage);
+                continue;
+            }
+            if failures.is_empty() {
+                kept.push((score, entry));
+                continue;
+            }
+            stats.softened += 1;
+            tracing::debug!(name = %entry.name, missed = ?failures, "Soft filter penalty");
+            kept.push((score - penalty * failures.len() as f64, entry));
+        }
+        if stats.softened > 0 {
+            kept.sort_by(rank_order);
+        }
+        stats.kept = kept.len();
+        (kept, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(name: &str, capabilities: &[&str]) -> McpEntry {
+        serde_json::from_value(json!({
+            "name": name,
+            "endpoint": format!("https://{}.example.com/mcp", name),
+            "version": "1.0.0",
+            "capabilities": capabilities,
+            "desc": "",
+        }))
+        .unwrap()
+    }
+
+    fn priced(name: &str, capabilities: &[&str], price_usd: f64) -> McpEntry {
+        McpEntry {
+            price_usd: Some(price_usd),
+            ..entry(name, capabilities)
+        }
+    }
+
+    fn catalog() -> Vec<McpEntry> {
+        vec![
+            entry("weather", &["get_forecast", "get_alerts"]),
+            entry("docs-search", &["search_docs"]),
+            priced("issues", &["create_issue", "list_issues"], 0.002),
+            priced("geocode", &["geocode", "reverse_geocode"], 0.0005),
+        ]
+    }
+
+    fn filter(filters: Value) -> CandidateFilter {
+        CandidateFilter::from_request(Some(&filters), false, Arc::new(EndpointPolicy::default()))
+    }
+
+    /// Names of the catalog entries `filters` keeps, through `apply`.
+    fn kept(filters: Value) -> Vec<String> {
+        let candidates = catalog().into_iter().map(|entry| (1.0, entry)).collect();
+        let (kept, _) = filter(filters).apply(candidates);
+        let mut names: Vec<String> = kept.into_iter().map(|(_, entry)| entry.name).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn require_all_keeps_entries_with_every_capability_in_any_case() {
+        assert_eq!(
+            kept(json!({ "require_all_capabilities": ["get_forecast", "Get_Alerts"] })),
+            ["weather"]
+        );
+    }
+
+    #[test]
+    fn require_all_split_across_entries_keeps_none() {
+        assert!(
+            kept(json!({ "require_all_capabilities": ["get_forecast", "geocode"] })).is_empty()
+        );
+    }
+
+    #[test]
+    fn require_any_keeps_entries_with_one_of_them() {
+        assert_eq!(
+            kept(json!({ "require_any_capabilities": ["geocode", "search_docs"] })),
+            ["docs-search", "geocode"]
+        );
+        let filter = filter(json!({ "require_any_capabilities": ["geocode"] }));
+        assert_eq!(
+            filter.rejection(&catalog()[0]),
+            Some(FilterStage::RequiredCapabilities)
+        );
+    }
+
+    #[test]
+    fn widened_fetch_survives_filters_that_empty_a_narrow_one() {
+        let ranked: Vec<(f64, McpEntry)> = (0..8)
+            .map(|rank| {
+                let capabilities: &[&str] = if rank == 5 {
+                    &["geocode"]
+                } else {
+                    &["get_forecast"]
+                };
+                (
+                    1.0 - rank as f64 / 10.0,
+                    entry(&format!("server-{}", rank), capabilities),
+                )
+            })
+            .collect();
+        let filter = filter(json!({ "capability": "geocode" }));
+        assert!(filter.is_active());
+        let top_k = 3;
+
+        let narrow: Vec<_> = ranked.iter().take(top_k).cloned().collect();
+        assert!(filter.apply(narrow).0.is_empty());
+
+        let widened: Vec<_> = ranked
+            .iter()
+            .take(filtered_fetch_k(top_k))
+            .cloned()
+            .collect();
+        let (kept, _) = filter.apply(widened);
+        let names: Vec<&str> = kept.iter().map(|(_, entry)| entry.name.as_str()).collect();
+        assert_eq!(names, ["server-5"]);
+    }
+
+    #[test]
+    fn policy_and_auth_alone_do_not_widen() {
+        assert!(!filter(json!({})).is_active());
+        assert!(!filter(json!({ "region": "eu" })).is_active());
+        assert_eq!(filtered_fetch_k(3), 3 * DEFAULT_FILTERED_FETCH_MULTIPLIER);
+    }
+
+    #[test]
+    fn price_ceilings_parse_strict_and_inclusive_limits() {
+        let ceiling = |limit, inclusive| Some(PriceCeiling { limit, inclusive });
+        assert_eq!(PriceCeiling::parse(&json!("<0.001")), ceiling(0.001, false));
+        assert_eq!(
+            PriceCeiling::parse(&json!(" <= 0.001")),
+            ceiling(0.001, true)
+        );
+        assert_eq!(PriceCeiling::parse(&json!("0.001")), ceiling(0.001, true));
+        assert_eq!(PriceCeiling::parse(&json!(0.5)), ceiling(0.5, true));
+        for malformed in [json!("cheap"), json!("<-1"), json!(true), json!(null)] {
+            assert_eq!(PriceCeiling::parse(&malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn cost_keeps_unpriced_and_cheaper_entries() {
+        assert_eq!(
+            kept(json!({ "cost": "<0.001", "latency": "low" })),
+            ["docs-search", "geocode", "weather"]
+        );
+        assert_eq!(kept(json!({ "cost": "<=0.002" })).len(), 4);
+        assert_eq!(
+            filter(json!({ "cost": "<0.0005" })).rejection(&catalog()[3]),
+            Some(FilterStage::Cost)
+        );
+    }
+
+    #[test]
+    fn cost_can_exclude_every_candidate() {
+        let issues = vec![(1.0, catalog().remove(2))];
+        let (kept, stats) = filter(json!({ "cost": "<0.001" })).apply(issues);
+        assert!(kept.is_empty());
+        assert_eq!((stats.retrieved, stats.cost), (1, 1));
+    }
+
+    #[test]
+    fn empty_or_malformed_filters_keep_everything() {
+        for filters in [
+            json!({}),
+            json!({ "cost": "cheap" }),
+            json!([1, 2]),
+            json!("fast"),
+        ] {
+            assert!(!filter(filters.clone()).is_active(), "{}", filters);
+            assert_eq!(kept(filters).len(), 4);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// src/backend/freshness.rs
+//! `MAX_CATALOG_AGE` (seconds): a safety valve for deployments where stale
+//! recommendations are worse than none. A catalog was last refreshed when it
+//! was loaded, when a refresh last found its source unchanged (a `304`, or the
+//! same body or file hash), or when one of its entries last verified
+//! successfully, whichever is newer. Past the limit `/discover` answers `503` and `/health`
+//! reports degraded until a reload brings a fresher catalog in. Unset or `0`
+//! disables the check.
+use super::health::DEGRADED_HEADER;
+use super::{Librarian, LibrarianHandle};
+use anyhow::{Context as _, Result};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::env;
+use std::sync::RwLock;
+
+pub fn max_catalog_age_from_env() -> Result<Option<Duration>> {
+    let Ok(raw) = env::var("MAX_CATALOG_AGE") else {
+        return Ok(None);
+    };
+    let secs: i64 = raw
+        .trim()
+        .parse()
+        .ok()
+        .filter(|secs| *secs >= 0)
+        .with_context(|| format!("MAX_CATALOG_AGE must be a number of seconds, got {:?}", raw))?;
+    Ok((secs > 0).then(|| Duration::seconds(secs)))
+}
+
+/// When a refresh last confirmed the catalog source unchanged. Shared across
+/// reloads, like the verification cache.
+#[derive(Debug, Default)]
+pub struct SourceCheck(RwLock<Option<DateTime<Utc>>>);
+
+impl SourceCheck {
+    pub fn mark(&self) {
+        *self.0.write().unwrap_or_else(|p| p.into_inner()) = Some(Utc::now());
+    }
+
+    pub fn last(&self) -> Option<DateTime<Utc>> {
+        *self.0.read().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// The newest of the load time, the last unchanged-source check and the
+/// latest successful verification of a catalog entry.
+pub fn refreshed_at(librarian: &Librarian) -> DateTime<Utc> {
+    let checked = librarian.source_check.last().unwrap_or(librarian.loaded_at);
+    librarian
+        .catalog
+        .iter()
+        .filter_map(|entry| librarian.verification.get(&entry.endpoint))
+        .filter(|result| result.ok)
+        .map(|result| result.checked_at)
+        .fold(librarian.loaded_at.max(checked), DateTime::max)
+}
+
+#[derive(Debug, Serialize)]
+pub struct FreshnessStatus {
+    pub refreshed_at: String,
+    pub age_secs: i64,
+    /// `None` when `MAX_CATALOG_AGE` is unset.
+    pub max_age_secs: Option<i64>,
+    pub stale: bool,
+}
+
+/// Shared by `/health` and the `/discover` guard.
+#[derive(Clone)]
+pub struct CatalogFreshness {
+    pub handle: LibrarianHandle,
+    pub max_age: Option<Duration>,
+}
+
+impl CatalogFreshness {
+    pub fn status(&self) -> FreshnessStatus {
+        let refreshed_at = refreshed_at(&self.handle.current());
+        let age = Utc::now() - refreshed_at;
+        FreshnessStatus {
+            refreshed_at: refreshed_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            age_secs: age.num_seconds(),
+            max_age_secs: self.max_age.map(|max| max.num_seconds()),
+            stale: self.max_age.is_some_and(|max| age > max),
+        }
+    }
+}
+
+/// Placed outside the x402 layer on `/discover`, like `health::empty_catalog_guard`,
+/// so a stale catalog is refused before anyone is charged.
+pub async fn stale_catalog_guard(
+    State(freshness): State<CatalogFreshness>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if freshness.max_age.is_none() {
+        return next.run(request).await;
+    }
+    let status = freshness.status();
+    if !status.stale {
+        return next.run(request).await;
+    }
+
+    tracing::warn!(
+        age_secs = status.age_secs,
+        max_age_secs = ?status.max_age_secs,
+        "Refusing /discover: catalog is older than MAX_CATALOG_AGE"
+    );
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(DEGRADED_HEADER, "stale-catalog")],
+        AxumJson(json!({
+            "error": {
+                "code": "stale_catalog",
+                "message": format!(
+                    "The catalog was last refreshed {}s ago, over the {}s limit; reload it to resume recommendations.",
+                    status.age_secs,
+                    status.max_age_secs.unwrap_or_default()
+                ),
+                "refreshed_at": status.refreshed_at,
+            }
+        })),
+    )
+        .into_response()
+}
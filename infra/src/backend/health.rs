@@ -0,0 +1,86 @@
+// src/backend/health.rs
+use super::LibrarianHandle;
+use super::breaker::{BreakerState, CircuitBreaker};
+use super::embed_health::EmbeddingHealth;
+use super::facilitator::FacilitatorHealth;
+use super::freshness::CatalogFreshness;
+use super::maintenance::Maintenance;
+use super::response::empty_response;
+use axum::{
+    Extension,
+    body::to_bytes,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+pub const DEGRADED_HEADER: &str = "x-librarian-degraded";
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// `GET /health`: liveness plus catalog freshness, facilitator, embedding and
+/// agent readiness, and whether maintenance mode is on.
+pub async fn health_handler(
+    State(handle): State<LibrarianHandle>,
+    Extension(facilitator): Extension<Arc<FacilitatorHealth>>,
+    Extension(maintenance): Extension<Maintenance>,
+    Extension(embedding): Extension<Arc<EmbeddingHealth>>,
+    Extension(breaker): Extension<Arc<CircuitBreaker>>,
+    Extension(freshness): Extension<CatalogFreshness>,
+) -> impl IntoResponse {
+    let librarian = handle.current();
+    let entries = librarian.catalog.len();
+    let facilitator = facilitator.status();
+    let embedding = embedding.status();
+    let agent = breaker.status();
+    let catalog_freshness = freshness.status();
+    let status = if entries == 0
+        || catalog_freshness.stale
+        || facilitator.as_ref().is_none_or(|f| !f.probe.reachable)
+        || embedding.as_ref().is_none_or(|e| !e.probe.reachable)
+        || agent.state != BreakerState::Closed
+    {
+        "degraded"
+    } else {
+        "ok"
+    };
+    AxumJson(json!({
+        "status": status,
+        "catalog_entries": entries,
+        "catalog_freshness": catalog_freshness,
+        "facilitator": facilitator,
+        "embedding": embedding,
+        "agent_circuit": agent,
+        "maintenance": maintenance.status(),
+    }))
+}
+
+/// Placed outside the x402 layer on `/discover`: with an empty catalog there is
+/// nothing to recommend, so answer with the canonical empty response and `503`
+/// instead of charging for it.
+pub async fn empty_catalog_guard(
+    State(handle): State<LibrarianHandle>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !handle.current().catalog.is_empty() {
+        return next.run(request).await;
+    }
+
+    let body = to_bytes(request.into_body(), MAX_REQUEST_BYTES)
+        .await
+        .unwrap_or_default();
+    let query = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|v| v.get("query").and_then(Value::as_str).map(str::to_string))
+        .unwrap_or_default();
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(DEGRADED_HEADER, "empty-catalog")],
+        AxumJson(empty_response(&query)),
+    )
+        .into_response()
+}
@@ -0,0 +1,364 @@
+// src/backend/idempotency.rs
+use super::metrics::METRICS;
+use super::payer::{PAYMENT_RESPONSE_HEADER, payer_from_headers, payment_digest, settled_payer};
+use super::request::{self, RequestError};
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header::CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as AsyncMutex;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAY_HEADER: &str = "idempotent-replayed";
+const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 600;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+const MAX_CACHED_BODY_BYTES: usize = 1024 * 1024;
+
+struct StoredResponse {
+    stored_at: Instant,
+    /// `payer::payment_digest` of the request that paid for this response.
+    payment_digest: String,
+    /// SHA-256 of that request's body.
+    body_digest: String,
+    status: StatusCode,
+    content_type: Option<HeaderValue>,
+    payment_response: Option<HeaderValue>,
+    body: Bytes,
+}
+
+type CacheKey = (String, String);
+
+/// Successful responses keyed by `(payer, Idempotency-Key)` so a client retrying
+/// after a network blip gets the original body back without paying again.
+///
+/// A response is only stored once x402 has settled the claimed payer's
+/// payment, and only replayed to a request carrying the same `X-PAYMENT` and
+/// the same body, so neither a forged header nor a different query can read it.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<CacheKey, StoredResponse>>,
+    /// Held while a key's request runs, so a retry racing the original waits
+    /// for its answer instead of paying a second time.
+    in_flight: Mutex<HashMap<CacheKey, Arc<AsyncMutex<()>>>>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads `IDEMPOTENCY_TTL_SECS`, defaulting to ten minutes.
+    pub fn from_env() -> Self {
+        let ttl = env::var("IDEMPOTENCY_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_IDEMPOTENCY_TTL_SECS);
+        IdempotencyCache::new(Duration::from_secs(ttl))
+    }
+
+    fn lock_for(&self, key: &CacheKey) -> Arc<AsyncMutex<()>> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|p| p.into_inner());
+        // a request dropped mid-flight never reaches `release`
+        in_flight.retain(|_, l| Arc::strong_count(l) > 1);
+        Arc::clone(in_flight.entry(key.clone()).or_default())
+    }
+
+    /// Drops the key's lock once nobody else holds or waits on it.
+    fn release(&self, key: &CacheKey, lock: Arc<AsyncMutex<()>>) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|p| p.into_inner());
+        drop(lock);
+        if in_flight
+            .get(key)
+            .is_some_and(|l| Arc::strong_count(l) == 1)
+        {
+            in_flight.remove(key);
+        }
+    }
+
+    /// The stored response for a matching retry; a `409` or `422` when the
+    /// key was used with another payment or body.
+    fn get(
+        &self,
+        key: &CacheKey,
+        payment_digest: &str,
+        body_digest: &str,
+    ) -> Result<Option<Response>, RequestError> {
+        let entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(stored) = entries
+            .get(key)
+            .filter(|s| s.stored_at.elapsed() <= self.ttl)
+        else {
+            return Ok(None);
+        };
+        if stored.body_digest != body_digest {
+            return Err(RequestError {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                field: None,
+                message: "Idempotency-Key was already used with a different request body"
+                    .to_string(),
+            });
+        }
+        if stored.payment_digest != payment_digest {
+            return Err(RequestError {
+                status: StatusCode::CONFLICT,
+                field: None,
+                message: "Idempotency-Key was already used with a different X-PAYMENT; \
+                          resend the original payment header to replay the response"
+                    .to_string(),
+            });
+        }
+
+        let mut response = (stored.status, stored.body.clone()).into_response();
+        let headers = response.headers_mut();
+        if let Some(content_type) = &stored.content_type {
+            headers.insert(CONTENT_TYPE, content_type.clone());
+        }
+        if let Some(payment_response) = &stored.payment_response {
+            headers.insert(PAYMENT_RESPONSE_HEADER, payment_response.clone());
+        }
+        headers.insert(IDEMPOTENT_REPLAY_HEADER, HeaderValue::from_static("true"));
+        Ok(Some(response))
+    }
+
+    fn insert(&self, key: CacheKey, stored: StoredResponse) {
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        entries.retain(|_, v| v.stored_at.elapsed() <= self.ttl);
+        entries.insert(key, stored);
+    }
+}
+
+/// Route middleware placed outside the x402 layer. Requests without a key, or
+/// whose payer can't be identified from `X-PAYMENT`, pass straight through.
+pub async fn idempotency_layer(
+    State(cache): State<Arc<IdempotencyCache>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|k| !k.is_empty() && k.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+        .map(str::to_string)
+    else {
+        return next.run(request).await;
+    };
+    let (Some(payer), Some(payment_digest)) = (
+        payer_from_headers(request.headers()),
+        payment_digest(request.headers()),
+    ) else {
+        return next.run(request).await;
+    };
+    let (parts, body) = match request::buffer(request).await {
+        Ok(buffered) => buffered,
+        Err(e) => return e.into_response(),
+    };
+    let body_digest = hex::encode(Sha256::digest(&body));
+    let request = Request::from_parts(parts, Body::from(body));
+
+    let cache_key = (payer, key);
+    let lock = cache.lock_for(&cache_key);
+    let guard = lock.lock().await;
+    let response = run(
+        &cache,
+        &cache_key,
+        payment_digest,
+        body_digest,
+        request,
+        next,
+    )
+    .await;
+    drop(guard);
+    cache.release(&cache_key, lock);
+    response
+}
+
+async fn run(
+    cache: &IdempotencyCache,
+    cache_key: &CacheKey,
+    payment_digest: String,
+    body_digest: String,
+    request: Request,
+    next: Next,
+) -> Response {
+    match cache.get(cache_key, &payment_digest, &body_digest) {
+        Ok(Some(replay)) => {
+            METRICS
+                .idempotency_cache_hits_total
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::info!(payer = %cache_key.0, "Replaying idempotent discover response");
+            return replay;
+        }
+        Ok(None) => {}
+        Err(e) => return e.into_response(),
+    }
+    METRICS
+        .idempotency_cache_misses_total
+        .fetch_add(1, Ordering::Relaxed);
+
+    let response = next.run(request).await;
+    // API-key and fail-open answers carry no settlement, so the claimed payer
+    // was never proven and nothing is stored for it
+    if !response.status().is_success()
+        || settled_payer(response.headers()).as_deref() != Some(cache_key.0.as_str())
+    {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_CACHED_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::warn!("Failed to buffer response for idempotency cache: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read response body",
+            )
+                .into_response();
+        }
+    };
+    cache.insert(
+        cache_key.clone(),
+        StoredResponse {
+            stored_at: Instant::now(),
+            payment_digest,
+            body_digest,
+            status: parts.status,
+            content_type: parts.headers.get(CONTENT_TYPE).cloned(),
+            payment_response: parts.headers.get(PAYMENT_RESPONSE_HEADER).cloned(),
+            body: body.clone(),
+        },
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::payer::PAYMENT_HEADER;
+    use axum::{Router, middleware, routing::post};
+    use base64::Engine as _;
+    use base64::engine::general_purpose::STANDARD;
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+    use tower::ServiceExt as _;
+
+    const PAYER: &str = "0xabc";
+
+    fn payment(signature: &str) -> String {
+        let payload =
+            json!({"payload": {"authorization": {"from": PAYER}, "signature": signature}});
+        STANDARD.encode(payload.to_string())
+    }
+
+    /// Echoes the body; with `settle`, reports `PAYER` settled the way x402 does.
+    fn app(calls: Arc<AtomicUsize>, settle: bool) -> Router {
+        let handler = move |body: Bytes| async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let mut response = body.into_response();
+            if settle {
+                let receipt = STANDARD.encode(json!({"success": true, "payer": PAYER}).to_string());
+                response.headers_mut().insert(
+                    PAYMENT_RESPONSE_HEADER,
+                    HeaderValue::from_str(&receipt).unwrap(),
+                );
+            }
+            response
+        };
+        let cache = Arc::new(IdempotencyCache::new(Duration::from_secs(60)));
+        Router::new()
+            .route("/discover", post(handler))
+            .layer(middleware::from_fn_with_state(cache, idempotency_layer))
+    }
+
+    fn request(signature: &str, body: &str) -> Request {
+        Request::post("/discover")
+            .header(IDEMPOTENCY_KEY_HEADER, "key-1")
+            .header(PAYMENT_HEADER, payment(signature))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn replayed(response: &Response) -> bool {
+        response.headers().contains_key(IDEMPOTENT_REPLAY_HEADER)
+    }
+
+    #[tokio::test]
+    async fn replays_a_settled_response() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Arc::clone(&calls), true);
+        let first = app.clone().oneshot(request("sig", "q1")).await.unwrap();
+        let second = app.oneshot(request("sig", "q1")).await.unwrap();
+
+        assert!(!replayed(&first));
+        assert!(replayed(&second));
+        assert!(second.headers().contains_key(PAYMENT_RESPONSE_HEADER));
+        assert_eq!(
+            to_bytes(second.into_body(), usize::MAX).await.unwrap(),
+            "q1"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn forged_payment_for_the_same_payer_is_refused() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Arc::clone(&calls), true);
+        app.clone().oneshot(request("sig", "q1")).await.unwrap();
+        let forged = app.oneshot(request("forged", "q1")).await.unwrap();
+
+        assert_eq!(forged.status(), StatusCode::CONFLICT);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn same_key_with_another_body_is_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Arc::clone(&calls), true);
+        app.clone().oneshot(request("sig", "q1")).await.unwrap();
+        let other = app.oneshot(request("sig", "q2")).await.unwrap();
+
+        assert_eq!(other.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn unsettled_responses_are_not_stored() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Arc::clone(&calls), false);
+        app.clone().oneshot(request("sig", "q1")).await.unwrap();
+        let second = app.oneshot(request("sig", "q1")).await.unwrap();
+
+        assert!(!replayed(&second));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_retries_run_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app(Arc::clone(&calls), true);
+        let (a, b) = tokio::join!(
+            app.clone().oneshot(request("sig", "q1")),
+            app.oneshot(request("sig", "q1")),
+        );
+
+        assert_ne!(replayed(&a.unwrap()), replayed(&b.unwrap()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,55 @@
+// src/backend/lang.rs
+//! Localized free-text fields. `lang` only changes the language of `overview`,
+//! `rationale` and `explanation`; every structural field stays as-is.
+use super::request::RequestError;
+use axum::http::StatusCode;
+use std::env;
+
+const DEFAULT_SUPPORTED_LANGS: &str = "en,es,fr,de,it,pt,ja,zh,ko";
+
+/// BCP 47-style tags compare lowercase with `-` separators: `pt_BR` is `pt-br`.
+pub fn normalize_lang(lang: &str) -> String {
+    lang.trim().to_lowercase().replace('_', "-")
+}
+
+/// Supported locales from `LIBRARIAN_SUPPORTED_LANGS` (comma-separated).
+pub fn supported_langs() -> Vec<String> {
+    env::var("LIBRARIAN_SUPPORTED_LANGS")
+        .unwrap_or_else(|_| DEFAULT_SUPPORTED_LANGS.to_string())
+        .split(',')
+        .map(normalize_lang)
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Resolves the requested language: a supported tag as-is, or a regional tag
+/// whose base language is supported (`fr-ca` when `fr` is). Unknown locales are
+/// a `400`.
+pub fn resolve_lang(lang: Option<&str>) -> Result<Option<String>, RequestError> {
+    let Some(lang) = lang.map(normalize_lang).filter(|l| !l.is_empty()) else {
+        return Ok(None);
+    };
+    let supported = supported_langs();
+    let base = lang.split('-').next().unwrap_or_default();
+    if supported.iter().any(|s| *s == lang || s == base) {
+        return Ok(Some(lang));
+    }
+    Err(RequestError {
+        status: StatusCode::BAD_REQUEST,
+        field: Some("lang".to_string()),
+        message: format!(
+            "Unsupported locale {:?}; supported: {}",
+            lang,
+            supported.join(", ")
+        ),
+    })
+}
+
+pub fn directive(lang: &str) -> String {
+    format!(
+        "\nLanguage: write \"overview\", \"rationale\" and any \"explanation\" in the language with \
+         BCP 47 tag \"{}\". Keep every other field, all JSON keys, names, endpoints, enum values and the \
+         service_acknowledgement exactly as specified, in English.",
+        lang
+    )
+}
@@ -0,0 +1,338 @@
+// src/backend/lint.rs
+//! Catalog quality warnings. None of these stop a catalog from loading; they
+//! flag entries that are likely to embed and retrieve poorly.
+//!
+//! Clusters of entries whose capabilities (or, once embedded, vectors) overlap
+//! almost completely are reported too: retrieval can't tell them apart, and
+//! the model gets several look-alikes as context. Entries with no capabilities
+//! are left to the `EmptyCapabilities` warning rather than clustered together.
+use super::McpEntry;
+use super::capindex::cosine_similarity;
+use super::catalog::levenshtein;
+use rig::OneOrMany;
+use rig::embeddings::Embedding;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::env;
+
+const DEFAULT_MIN_DESC_LEN: usize = 40;
+/// Jaccard overlap of two capability sets above which they count as near-duplicates.
+const DEFAULT_CAPABILITY_OVERLAP: f64 = 0.8;
+/// Cosine similarity of two entry vectors above which they count as near-duplicates.
+const DEFAULT_EMBEDDING_OVERLAP: f64 = 0.97;
+/// The embedding comparison is quadratic; larger catalogs skip it.
+const MAX_EMBEDDING_OVERLAP_ENTRIES: usize = 2000;
+/// Name and description within this fraction of edits of each other count as
+/// near-identical.
+const NEAR_IDENTICAL_RATIO: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    ShortDescription,
+    EmptyCapabilities,
+    NameMatchesDescription,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintWarning {
+    pub name: String,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+/// Minimum `desc` length from `LIBRARIAN_LINT_MIN_DESC_LEN` (default 40 chars).
+pub fn min_desc_len() -> usize {
+    env::var("LIBRARIAN_LINT_MIN_DESC_LEN")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MIN_DESC_LEN)
+}
+
+/// Capability overlap threshold from `LIBRARIAN_LINT_CAPABILITY_OVERLAP` (default 0.8).
+pub fn capability_overlap() -> f64 {
+    env::var("LIBRARIAN_LINT_CAPABILITY_OVERLAP")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| (0.0..=1.0).contains(v))
+        .unwrap_or(DEFAULT_CAPABILITY_OVERLAP)
+}
+
+/// Embedding overlap threshold from `LIBRARIAN_LINT_EMBEDDING_OVERLAP` (default 0.97).
+pub fn embedding_overlap() -> f64 {
+    env::var("LIBRARIAN_LINT_EMBEDDING_OVERLAP")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| (0.0..=1.0).contains(v))
+        .unwrap_or(DEFAULT_EMBEDDING_OVERLAP)
+}
+
+/// `LIBRARIAN_MERGE_DUPLICATE_CAPABILITIES=true` folds exact duplicates
+/// together at load; see `merge_exact_duplicates`.
+pub fn merge_duplicates_enabled() -> bool {
+    env::var("LIBRARIAN_MERGE_DUPLICATE_CAPABILITIES").is_ok_and(|v| v == "1" || v == "true")
+}
+
+fn comparable(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+pub fn lint_catalog(entries: &[McpEntry], min_desc_len: usize) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    for entry in entries {
+        let mut warn = |kind, message: String| {
+            warnings.push(LintWarning {
+                name: entry.name.clone(),
+                kind,
+                message,
+            })
+        };
+
+        let desc_len = entry.desc.trim().chars().count();
+        if desc_len < min_desc_len {
+            warn(
+                LintKind::ShortDescription,
+                format!(
+                    "desc is {} chars, below the {} minimum",
+                    desc_len, min_desc_len
+                ),
+            );
+        }
+        if entry.capabilities.iter().all(|c| c.trim().is_empty()) {
+            warn(
+                LintKind::EmptyCapabilities,
+                "capabilities is empty".to_string(),
+            );
+        }
+        let (name, desc) = (comparable(&entry.name), comparable(&entry.desc));
+        let longest = name.chars().count().max(desc.chars().count());
+        if longest > 0 && levenshtein(&name, &desc) as f64 <= NEAR_IDENTICAL_RATIO * longest as f64
+        {
+            warn(
+                LintKind::NameMatchesDescription,
+                "desc is near-identical to name and adds nothing to the embedding".to_string(),
+            );
+        }
+    }
+    warnings
+}
+
+/// Entries whose capabilities or vectors overlap above the threshold.
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapCluster {
+    pub names: Vec<String>,
+    /// Lowest pairwise overlap that joined the cluster.
+    pub overlap: f64,
+    /// Every member has the very same capability set.
+    pub exact: bool,
+}
+
+impl OverlapCluster {
+    pub fn message(&self) -> String {
+        if self.exact {
+            format!(
+                "{} share an identical capability set",
+                self.names.join(", ")
+            )
+        } else {
+            format!(
+                "{} overlap by at least {:.2} and may be hard to tell apart",
+                self.names.join(", "),
+                self.overlap
+            )
+        }
+    }
+}
+
+/// Lowercased, trimmed and deduplicated, so ordering and casing don't matter.
+fn capability_set(entry: &McpEntry) -> BTreeSet<String> {
+    entry
+        .capabilities
+        .iter()
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Groups `0..n` by the pairs `similarity` puts at or above `threshold`,
+/// transitively; only groups of two or more are returned, in index order.
+fn cluster(
+    n: usize,
+    threshold: f64,
+    similarity: impl Fn(usize, usize) -> Option<f64>,
+) -> Vec<(Vec<usize>, f64)> {
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut lowest = vec![f64::INFINITY; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let Some(score) = similarity(i, j).filter(|score| *score >= threshold) else {
+                continue;
+            };
+            let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+            let (keep, merged) = (a.min(b), a.max(b));
+            parent[merged] = keep;
+            lowest[keep] = lowest[keep].min(lowest[merged]).min(score);
+        }
+    }
+    let roots: Vec<usize> = (0..n).map(|i| root(&mut parent, i)).collect();
+    (0..n)
+        .map(|r| {
+            (
+                (0..n).filter(|&i| roots[i] == r).collect::<Vec<_>>(),
+                lowest[r],
+            )
+        })
+        .filter(|(members, _)| members.len() > 1)
+        .collect()
+}
+
+/// Enabled entries whose capability sets overlap by at least `threshold`
+/// (Jaccard). Needs no embeddings, so `validate` reports it too.
+pub fn capability_clusters(entries: &[McpEntry], threshold: f64) -> Vec<OverlapCluster> {
+    let entries: Vec<(&McpEntry, BTreeSet<String>)> = entries
+        .iter()
+        .filter(|e| e.enabled)
+        .map(|e| (e, capability_set(e)))
+        .filter(|(_, set)| !set.is_empty())
+        .collect();
+    cluster(entries.len(), threshold, |i, j| {
+        Some(jaccard(&entries[i].1, &entries[j].1))
+    })
+    .into_iter()
+    .map(|(members, overlap)| OverlapCluster {
+        exact: members
+            .iter()
+            .all(|&i| entries[i].1 == entries[members[0]].1),
+        names: members.iter().map(|&i| entries[i].0.name.clone()).collect(),
+        overlap,
+    })
+    .collect()
+}
+
+/// Embedded entries whose vectors are at least `threshold` cosine-similar.
+/// Skipped (empty) above `MAX_EMBEDDING_OVERLAP_ENTRIES` entries.
+pub fn embedding_clusters(
+    embeddings: &[(McpEntry, OneOrMany<Embedding>)],
+    threshold: f64,
+) -> Vec<OverlapCluster> {
+    if embeddings.len() > MAX_EMBEDDING_OVERLAP_ENTRIES {
+        tracing::debug!(
+            entries = embeddings.len(),
+            "Catalog too large for the embedding overlap check; skipping it"
+        );
+        return Vec::new();
+    }
+    let sets: Vec<BTreeSet<String>> = embeddings.iter().map(|(e, _)| capability_set(e)).collect();
+    cluster(embeddings.len(), threshold, |i, j| {
+        let (a, b) = (&embeddings[i].1.first().vec, &embeddings[j].1.first().vec);
+        (a.len() == b.len()).then(|| cosine_similarity(a, b))
+    })
+    .into_iter()
+    .map(|(members, overlap)| OverlapCluster {
+        exact: members
+            .iter()
+            .all(|&i| !sets[i].is_empty() && sets[i] == sets[members[0]]),
+        names: members
+            .iter()
+            .map(|&i| embeddings[i].0.name.clone())
+            .collect(),
+        overlap,
+    })
+    .collect()
+}
+
+/// Folds entries with an identical capability set and the same endpoint into
+/// the first of them, which gains the others' tags. Entries at different
+/// endpoints are distinct servers and are only ever warned about.
+pub fn merge_exact_duplicates(entries: Vec<McpEntry>) -> Vec<McpEntry> {
+    let mut merged: Vec<(McpEntry, BTreeSet<String>)> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let set = capability_set(&entry);
+        let duplicate_of = (!set.is_empty() && entry.enabled)
+            .then(|| {
+                merged.iter_mut().find(|(kept, kept_set)| {
+                    kept.enabled && kept.endpoint == entry.endpoint && *kept_set == set
+                })
+            })
+            .flatten();
+        match duplicate_of {
+            Some((kept, _)) => {
+                tracing::info!(kept = %kept.name, merged = %entry.name, "Merged duplicate catalog entry");
+                for tag in entry.tags {
+                    if !kept.tags.contains(&tag) {
+                        kept.tags.push(tag);
+                    }
+                }
+            }
+            None => merged.push((entry, set)),
+        }
+    }
+    merged.into_iter().map(|(entry, _)| entry).collect()
+}
+
+fn log_clusters(kind: &str, clusters: &[OverlapCluster]) {
+    if clusters.is_empty() {
+        return;
+    }
+    for cluster in clusters {
+        tracing::debug!(kind, "Catalog overlap: {}", cluster.message());
+    }
+    tracing::warn!(
+        exact = clusters.iter().filter(|c| c.exact).count(),
+        entries = clusters.iter().map(|c| c.names.len()).sum::<usize>(),
+        "Catalog has {} cluster(s) of entries with overlapping {}",
+        clusters.len(),
+        kind
+    );
+}
+
+/// Logs clusters of embedded entries with near-identical vectors.
+pub fn log_embedding_overlap(embeddings: &[(McpEntry, OneOrMany<Embedding>)]) {
+    log_clusters(
+        "embeddings",
+        &embedding_clusters(embeddings, embedding_overlap()),
+    );
+}
+
+/// Logs one line per warning plus a per-kind summary.
+pub fn log_lint_summary(entries: &[McpEntry]) {
+    log_clusters(
+        "capabilities",
+        &capability_clusters(entries, capability_overlap()),
+    );
+    let warnings = lint_catalog(entries, min_desc_len());
+    if warnings.is_empty() {
+        return;
+    }
+    for warning in &warnings {
+        tracing::debug!(entry = %warning.name, kind = ?warning.kind, "Catalog lint: {}", warning.message);
+    }
+    let count = |kind| warnings.iter().filter(|w| w.kind == kind).count();
+    tracing::warn!(
+        short_description = count(LintKind::ShortDescription),
+        empty_capabilities = count(LintKind::EmptyCapabilities),
+        name_matches_description = count(LintKind::NameMatchesDescription),
+        "Catalog lint found {} warning(s) across {} entries",
+        warnings.len(),
+        entries.len()
+    );
+}
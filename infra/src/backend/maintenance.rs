@@ -0,0 +1,156 @@
+// src/backend/maintenance.rs
+//! Maintenance mode, for catalog migrations and incidents: the routes that
+//! call the model or embedding API (`/discover`, `/search`, `/embed`,
+//! `/discover/estimate`, `/discover/explain`) answer `503` with code
+//! `maintenance`, a message and `Retry-After`, before any payment is asked
+//! for, while introspection (`/catalog`, `/health`, `/meta`, ...) keeps
+//! serving. `MAINTENANCE_MODE=true` starts the server in it; admins switch it
+//! at runtime with `POST /admin/maintenance` `{"enabled", "message"}` and read
+//! it with `GET /admin/maintenance`. Entering and leaving are logged.
+use super::clock;
+use super::health::DEGRADED_HEADER;
+use super::request::ApiJson;
+use super::store::{self, Store};
+use axum::{
+    Extension,
+    extract::{Request, State},
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+/// How long clients are told to wait during maintenance.
+const MAINTENANCE_RETRY_AFTER_SECS: u64 = 60;
+const DEFAULT_MESSAGE: &str = "The Librarian is down for maintenance; retry later.";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// When maintenance started, RFC 3339.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+}
+
+struct Active {
+    message: String,
+    since: DateTime<Utc>,
+}
+
+#[derive(Clone, Default)]
+pub struct Maintenance(Arc<Mutex<Option<Active>>>);
+
+impl Maintenance {
+    /// On from the start when `MAINTENANCE_MODE` is set.
+    pub fn from_env() -> Self {
+        let maintenance = Maintenance::default();
+        if env::var("MAINTENANCE_MODE").is_ok_and(|v| v == "1" || v == "true") {
+            maintenance.set(true, None, "MAINTENANCE_MODE");
+        }
+        maintenance
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        match &*self.0.lock().unwrap_or_else(|p| p.into_inner()) {
+            Some(active) => MaintenanceStatus {
+                enabled: true,
+                message: Some(active.message.clone()),
+                since: Some(clock::timestamp(active.since)),
+            },
+            None => MaintenanceStatus {
+                enabled: false,
+                message: None,
+                since: None,
+            },
+        }
+    }
+
+    /// Enters or leaves maintenance, logging the change. `source` names who
+    /// asked, for the log. Entering again only replaces the message.
+    pub fn set(&self, enabled: bool, message: Option<String>, source: &str) {
+        let mut active = self.0.lock().unwrap_or_else(|p| p.into_inner());
+        match (enabled, active.as_mut()) {
+            (true, Some(current)) => {
+                if let Some(message) = message {
+                    current.message = message;
+                }
+            }
+            (true, None) => {
+                let message = message.unwrap_or_else(|| DEFAULT_MESSAGE.to_string());
+                tracing::warn!(source, message = %message, "Entering maintenance mode: model and embedding routes refused");
+                *active = Some(Active {
+                    message,
+                    since: Utc::now(),
+                });
+            }
+            (false, Some(current)) => {
+                let minutes = (Utc::now() - current.since).num_minutes();
+                tracing::warn!(source, minutes, "Leaving maintenance mode");
+                *active = None;
+            }
+            (false, None) => {}
+        }
+    }
+}
+
+/// Placed outside the x402 layer, like `readiness::readiness_guard`.
+pub async fn maintenance_guard(
+    State(maintenance): State<Maintenance>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let status = maintenance.status();
+    if !status.enabled {
+        return next.run(request).await;
+    }
+    let retry_after = MAINTENANCE_RETRY_AFTER_SECS.to_string();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [
+            (RETRY_AFTER.as_str(), retry_after.as_str()),
+            (DEGRADED_HEADER, "maintenance"),
+        ],
+        AxumJson(json!({
+            "error": {
+                "code": "maintenance",
+                "message": status.message,
+                "since": status.since,
+            }
+        })),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    /// Shown to refused callers; defaults to a generic notice.
+    pub message: Option<String>,
+}
+
+/// `GET /admin/maintenance`.
+pub async fn status_handler(Extension(maintenance): Extension<Maintenance>) -> impl IntoResponse {
+    AxumJson(maintenance.status())
+}
+
+/// `POST /admin/maintenance`: enters or leaves maintenance; answers the new state.
+pub async fn set_handler(
+    Extension(maintenance): Extension<Maintenance>,
+    Extension(audit): Extension<Arc<dyn Store>>,
+    ApiJson(req): ApiJson<MaintenanceRequest>,
+) -> impl IntoResponse {
+    let message = req
+        .message
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    maintenance.set(req.enabled, message, "admin API");
+    let status = maintenance.status();
+    store::audit(audit.as_ref(), "maintenance", json!(status));
+    AxumJson(status)
+}
@@ -0,0 +1,26 @@
+// src/backend/meta.rs
+use super::LibrarianHandle;
+use super::signing::PublicKeyInfo;
+use axum::{
+    Extension,
+    extract::State,
+    response::{IntoResponse, Json as AxumJson},
+};
+use serde_json::json;
+
+/// `GET /meta`: what this server speaks, so clients can check compatibility,
+/// and the key `/discover` responses are signed with, if any.
+pub async fn meta_handler(
+    State(handle): State<LibrarianHandle>,
+    Extension(signing): Extension<Option<PublicKeyInfo>>,
+) -> impl IntoResponse {
+    let librarian = handle.current();
+    AxumJson(json!({
+        "service": "librarian",
+        "version": env!("CARGO_PKG_VERSION"),
+        "supported_protocol_versions": librarian.protocol_versions.as_slice(),
+        "retrieval_model": librarian.retrieval_model,
+        "completion_model": librarian.completion_model,
+        "signing": signing,
+    }))
+}
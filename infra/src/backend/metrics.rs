@@ -0,0 +1,349 @@
+// src/backend/metrics.rs
+//! Process-wide counters and gauges, rendered in the Prometheus text format at
+//! `GET /metrics`. Plain atomics: every update is lock-free.
+use super::urls::endpoint_key;
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, RwLock};
+
+#[derive(Default)]
+pub struct Metrics {
+    pub agent_in_flight: AtomicU64,
+    pub agent_queue_depth: AtomicU64,
+    pub agent_queue_rejected_total: AtomicU64,
+    pub agent_queue_timeouts_total: AtomicU64,
+    pub discover_reprompts_total: AtomicU64,
+    pub discover_no_candidates_total: AtomicU64,
+    pub agent_breaker_open: AtomicU64,
+    pub agent_breaker_trips_total: AtomicU64,
+    pub agent_breaker_short_circuits_total: AtomicU64,
+    pub settlement_failures_total: AtomicU64,
+    pub payment_unavailable_total: AtomicU64,
+    pub payment_bypassed_total: AtomicU64,
+    pub query_cache_hits_total: AtomicU64,
+    pub query_cache_misses_total: AtomicU64,
+    pub idempotency_cache_hits_total: AtomicU64,
+    pub idempotency_cache_misses_total: AtomicU64,
+    pub discover_cache_hits_total: AtomicU64,
+    pub discover_cache_misses_total: AtomicU64,
+    pub embedding_cache_hits_total: AtomicU64,
+    pub embedding_cache_misses_total: AtomicU64,
+    pub embedding_dimension_mismatches_total: AtomicU64,
+    pub index_entries: AtomicU64,
+    pub embedding_dimension: AtomicU64,
+}
+
+pub static METRICS: Metrics = Metrics {
+    agent_in_flight: AtomicU64::new(0),
+    agent_queue_depth: AtomicU64::new(0),
+    agent_queue_rejected_total: AtomicU64::new(0),
+    agent_queue_timeouts_total: AtomicU64::new(0),
+    discover_reprompts_total: AtomicU64::new(0),
+    discover_no_candidates_total: AtomicU64::new(0),
+    agent_breaker_open: AtomicU64::new(0),
+    agent_breaker_trips_total: AtomicU64::new(0),
+    agent_breaker_short_circuits_total: AtomicU64::new(0),
+    settlement_failures_total: AtomicU64::new(0),
+    payment_unavailable_total: AtomicU64::new(0),
+    payment_bypassed_total: AtomicU64::new(0),
+    query_cache_hits_total: AtomicU64::new(0),
+    query_cache_misses_total: AtomicU64::new(0),
+    idempotency_cache_hits_total: AtomicU64::new(0),
+    idempotency_cache_misses_total: AtomicU64::new(0),
+    discover_cache_hits_total: AtomicU64::new(0),
+    discover_cache_misses_total: AtomicU64::new(0),
+    embedding_cache_hits_total: AtomicU64::new(0),
+    embedding_cache_misses_total: AtomicU64::new(0),
+    embedding_dimension_mismatches_total: AtomicU64::new(0),
+    index_entries: AtomicU64::new(0),
+    embedding_dimension: AtomicU64::new(0),
+};
+
+impl Metrics {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: &AtomicU64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        };
+        metric(
+            "librarian_agent_in_flight",
+            "gauge",
+            "Discover requests currently holding an agent slot.",
+            &self.agent_in_flight,
+        );
+        metric(
+            "librarian_agent_queue_depth",
+            "gauge",
+            "Discover requests waiting for an agent slot.",
+            &self.agent_queue_depth,
+        );
+        metric(
+            "librarian_agent_queue_rejected_total",
+            "counter",
+            "Discover requests rejected because the wait queue was full.",
+            &self.agent_queue_rejected_total,
+        );
+        metric(
+            "librarian_agent_queue_timeouts_total",
+            "counter",
+            "Discover requests that waited past the queue's max wait.",
+            &self.agent_queue_timeouts_total,
+        );
+        metric(
+            "librarian_discover_reprompts_total",
+            "counter",
+            "Discover requests whose first agent output failed parsing or schema validation.",
+            &self.discover_reprompts_total,
+        );
+        metric(
+            "librarian_discover_no_candidates_total",
+            "counter",
+            "Discover requests answered empty without a model call because no candidate survived retrieval.",
+            &self.discover_no_candidates_total,
+        );
+        metric(
+            "librarian_agent_breaker_open",
+            "gauge",
+            "1 while the agent circuit breaker is open or half-open.",
+            &self.agent_breaker_open,
+        );
+        metric(
+            "librarian_agent_breaker_trips_total",
+            "counter",
+            "Times the agent circuit breaker opened.",
+            &self.agent_breaker_trips_total,
+        );
+        metric(
+            "librarian_agent_breaker_short_circuits_total",
+            "counter",
+            "Discover requests answered catalog-only because the breaker was open.",
+            &self.agent_breaker_short_circuits_total,
+        );
+        metric(
+            "librarian_settlement_failures_total",
+            "counter",
+            "Paid requests served but not settled by the facilitator.",
+            &self.settlement_failures_total,
+        );
+        metric(
+            "librarian_payment_unavailable_total",
+            "counter",
+            "Paid requests refused with 503 because the facilitator was unreachable.",
+            &self.payment_unavailable_total,
+        );
+        metric(
+            "librarian_payment_bypassed_total",
+            "counter",
+            "Paid requests served unpaid because the facilitator was unreachable (fail-open).",
+            &self.payment_bypassed_total,
+        );
+        metric(
+            "librarian_query_cache_hits_total",
+            "counter",
+            "Cacheable requests answered 304 Not Modified from a matching ETag.",
+            &self.query_cache_hits_total,
+        );
+        metric(
+            "librarian_query_cache_misses_total",
+            "counter",
+            "Cacheable requests that produced a full response.",
+            &self.query_cache_misses_total,
+        );
+        metric(
+            "librarian_idempotency_cache_hits_total",
+            "counter",
+            "Discover requests replayed from the idempotency cache without a model call.",
+            &self.idempotency_cache_hits_total,
+        );
+        metric(
+            "librarian_idempotency_cache_misses_total",
+            "counter",
+            "Discover requests with an Idempotency-Key that had no cached response.",
+            &self.idempotency_cache_misses_total,
+        );
+        metric(
+            "librarian_discover_cache_hits_total",
+            "counter",
+            "Discover requests answered from the response cache without a model call.",
+            &self.discover_cache_hits_total,
+        );
+        metric(
+            "librarian_discover_cache_misses_total",
+            "counter",
+            "Discover model answers stored in the response cache.",
+            &self.discover_cache_misses_total,
+        );
+        metric(
+            "librarian_embedding_cache_hits_total",
+            "counter",
+            "Catalog entries whose vectors were reused instead of embedded.",
+            &self.embedding_cache_hits_total,
+        );
+        metric(
+            "librarian_embedding_cache_misses_total",
+            "counter",
+            "Catalog entries sent to the embedding API.",
+            &self.embedding_cache_misses_total,
+        );
+        metric(
+            "librarian_embedding_dimension_mismatches_total",
+            "counter",
+            "Stored vectors discarded for not matching the embedding model's dimension.",
+            &self.embedding_dimension_mismatches_total,
+        );
+        metric(
+            "librarian_index_entries",
+            "gauge",
+            "Entries in the live vector index.",
+            &self.index_entries,
+        );
+        metric(
+            "librarian_embedding_dimension",
+            "gauge",
+            "Dimension of the live index's vectors; 0 when the index is empty.",
+            &self.embedding_dimension,
+        );
+        out
+    }
+}
+
+/// How often each endpoint appeared in a `/discover` response. A counter is
+/// inserted under the write lock the first time an endpoint is seen; after
+/// that, bumps take only the read lock. The counters live outside `Librarian`
+/// so reloads keep them, and `record_index` drops endpoints that left the
+/// catalog.
+#[derive(Default)]
+pub struct RecommendationHits {
+    counters: RwLock<HashMap<String, AtomicU64>>,
+}
+
+pub static RECOMMENDATION_HITS: LazyLock<RecommendationHits> =
+    LazyLock::new(RecommendationHits::default);
+
+impl RecommendationHits {
+    pub fn record(&self, endpoint: &str) {
+        let key = endpoint_key(endpoint);
+        if key.is_empty() {
+            return;
+        }
+        {
+            let counters = self
+                .counters
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some(counter) = counters.get(key) {
+                counter.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        let mut counters = self
+            .counters
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        counters
+            .entry(key.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Starts the counters from totals persisted by an earlier run.
+    pub fn seed(&self, totals: BTreeMap<String, u64>) {
+        let mut counters = self
+            .counters
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (endpoint, count) in totals {
+            counters
+                .entry(endpoint)
+                .or_default()
+                .fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts every recommendation in a `/discover` response body.
+    pub fn record_response(&self, response: &Value) {
+        let recommendations = response
+            .get("recommendations")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for rec in recommendations {
+            if let Some(endpoint) = rec.get("endpoint").and_then(Value::as_str) {
+                self.record(endpoint);
+            }
+        }
+    }
+
+    /// Drops the counters of endpoints not in `endpoints`.
+    pub fn retain<'a>(&self, endpoints: impl IntoIterator<Item = &'a str>) {
+        let live: HashSet<&str> = endpoints.into_iter().map(endpoint_key).collect();
+        let mut counters = self
+            .counters
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        counters.retain(|endpoint, _| live.contains(endpoint.as_str()));
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        let counters = self
+            .counters
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        counters
+            .iter()
+            .map(|(endpoint, counter)| (endpoint.clone(), counter.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    fn render(&self, out: &mut String) {
+        let name = "librarian_recommendations_total";
+        let _ = writeln!(
+            out,
+            "# HELP {} Times each endpoint appeared in a /discover response.",
+            name
+        );
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (endpoint, count) in self.snapshot() {
+            let label = endpoint
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            let _ = writeln!(out, "{}{{endpoint=\"{}\"}} {}", name, label, count);
+        }
+    }
+}
+
+/// Sets the index gauges from the librarian about to serve.
+pub fn record_index(librarian: &super::Librarian) {
+    let dimension = librarian
+        .embeddings
+        .first()
+        .map(|(_, vectors)| vectors.first().vec.len())
+        .unwrap_or_default();
+    METRICS
+        .index_entries
+        .store(librarian.embeddings.len() as u64, Ordering::Relaxed);
+    METRICS
+        .embedding_dimension
+        .store(dimension as u64, Ordering::Relaxed);
+    RECOMMENDATION_HITS.retain(
+        librarian
+            .catalog
+            .iter()
+            .map(|entry| entry.endpoint.as_str()),
+    );
+}
+
+/// `GET /metrics`
+pub async fn metrics_handler() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], {
+        let mut out = METRICS.render();
+        RECOMMENDATION_HITS.render(&mut out);
+        out
+    })
+}
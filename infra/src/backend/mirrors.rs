@@ -0,0 +1,384 @@
+// src/backend/mirrors.rs
+//! Catalog entries deployed behind several equivalent endpoints (regions,
+//! mirrors). An entry keeps its primary `endpoint` and lists the others under
+//! `mirrors`, each a URL or `{endpoint, region}`. A catalog may instead give
+//! every deployment as `endpoints`, primary first; `settle` folds that into
+//! the same shape at load, so a plain `endpoint` string stays a one-deployment
+//! entry and nothing downstream sees `endpoints`.
+//!
+//! Each deployment is verified on its own, and an entry counts as available
+//! when any of its deployments is. A recommendation for a mirrored entry names
+//! one deployment as its `endpoint`, picked by `LIBRARIAN_MIRROR_SELECTION`:
+//! `best` (the default) prefers the request's region, then the highest recent
+//! availability (an unchecked deployment counts as 50%), then catalog order;
+//! `primary` always keeps the catalog's first endpoint. The others are listed
+//! in the recommendation's `mirrors` with their region and availability; any
+//! other value fails startup. `session_plan` and the `instructions` entry are rendered for the chosen
+//! endpoint only: a client failing over sends the same requests, with a fresh
+//! `initialize`, to a mirror's URL instead.
+use super::McpEntry;
+use super::filters::{normalize_region, region_matches};
+use super::settings::select_once;
+use super::urls::same_endpoint;
+use super::verify::VerificationCache;
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::env;
+use std::sync::OnceLock;
+
+/// Availability assumed for a deployment that has never been checked.
+const UNCHECKED_AVAILABILITY: f64 = 50.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "RawMirror")]
+pub struct Mirror {
+    pub endpoint: String,
+    /// Normalized like `McpEntry::region`; `None` is global.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawMirror {
+    Url(String),
+    Full {
+        endpoint: String,
+        #[serde(default)]
+        region: Option<String>,
+    },
+}
+
+impl From<RawMirror> for Mirror {
+    fn from(raw: RawMirror) -> Self {
+        match raw {
+            RawMirror::Url(endpoint) => Mirror {
+                endpoint,
+                region: None,
+            },
+            RawMirror::Full { endpoint, region } => Mirror {
+                endpoint,
+                region: region
+                    .map(|r| normalize_region(&r))
+                    .filter(|r| !r.is_empty()),
+            },
+        }
+    }
+}
+
+/// Folds `endpoints` into `endpoint` and `mirrors`, then drops mirrors that
+/// repeat the primary or each other. Fails for an entry with no endpoint at all.
+pub fn settle(entry: &mut McpEntry) -> Result<()> {
+    if let Some(endpoints) = entry.endpoints.take() {
+        let mut endpoints = endpoints.into_iter();
+        if entry.endpoint.is_empty()
+            && let Some(primary) = endpoints.next()
+        {
+            entry.endpoint = primary.endpoint;
+            if entry.region.is_none() {
+                entry.region = primary.region;
+            }
+        }
+        entry.mirrors.extend(endpoints);
+    }
+    if entry.endpoint.trim().is_empty() {
+        bail!(
+            "{:?} has no endpoint (set endpoint or endpoints)",
+            entry.name
+        );
+    }
+
+    let mut kept: Vec<Mirror> = Vec::with_capacity(entry.mirrors.len());
+    for mirror in std::mem::take(&mut entry.mirrors) {
+        if !same_endpoint(&mirror.endpoint, &entry.endpoint)
+            && !kept
+                .iter()
+                .any(|k| same_endpoint(&k.endpoint, &mirror.endpoint))
+        {
+            kept.push(mirror);
+        }
+    }
+    entry.mirrors = kept;
+    Ok(())
+}
+
+/// Every deployment of `entry`, primary first.
+pub fn deployments(entry: &McpEntry) -> Vec<Mirror> {
+    std::iter::once(Mirror {
+        endpoint: entry.endpoint.clone(),
+        region: entry.region.clone(),
+    })
+    .chain(entry.mirrors.iter().cloned())
+    .collect()
+}
+
+/// Whether `endpoint` is one of `entry`'s deployments.
+pub fn serves(entry: &McpEntry, endpoint: &str) -> bool {
+    same_endpoint(&entry.endpoint, endpoint)
+        || entry
+            .mirrors
+            .iter()
+            .any(|m| same_endpoint(&m.endpoint, endpoint))
+}
+
+/// The entry serving `endpoint`, primary or mirror.
+pub fn find<'a>(catalog: &'a [McpEntry], endpoint: &str) -> Option<&'a McpEntry> {
+    catalog
+        .iter()
+        .find(|e| same_endpoint(&e.endpoint, endpoint))
+        .or_else(|| catalog.iter().find(|e| serves(e, endpoint)))
+}
+
+/// Whether some deployment of `entry` is in `preferred`.
+pub fn in_region(entry: &McpEntry, preferred: &str) -> bool {
+    entry
+        .region
+        .iter()
+        .chain(entry.mirrors.iter().filter_map(|m| m.region.as_ref()))
+        .any(|region| region_matches(preferred, region))
+}
+
+/// Whether some deployment of `entry` is global or in `preferred`, i.e. passes
+/// a strict region filter.
+pub fn reaches_region(entry: &McpEntry, preferred: &str) -> bool {
+    deployments(entry).iter().any(|d| {
+        d.region
+            .as_deref()
+            .is_none_or(|region| region_matches(preferred, region))
+    })
+}
+
+/// The best recent availability among `entry`'s deployments, or `None` when
+/// none has been checked.
+pub fn availability(entry: &McpEntry, cache: &VerificationCache) -> Option<f64> {
+    deployments(entry)
+        .iter()
+        .filter_map(|d| cache.availability(&d.endpoint))
+        .reduce(f64::max)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorSelection {
+    #[default]
+    Best,
+    Primary,
+}
+
+impl MirrorSelection {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "best" => Some(MirrorSelection::Best),
+            "primary" => Some(MirrorSelection::Primary),
+            _ => None,
+        }
+    }
+
+    /// `LIBRARIAN_MIRROR_SELECTION`, `best` when unset.
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_MIRROR_SELECTION") {
+            Err(_) => Ok(MirrorSelection::default()),
+            Ok(raw) => MirrorSelection::parse(raw.trim()).ok_or_else(|| {
+                anyhow!(
+                    "Unknown LIBRARIAN_MIRROR_SELECTION {:?} (expected best or primary)",
+                    raw
+                )
+            }),
+        }
+    }
+}
+
+static SELECTION: OnceLock<MirrorSelection> = OnceLock::new();
+
+/// Fixes the mirror policy; see `settings::select_once`.
+pub fn select(selection: MirrorSelection) -> Result<()> {
+    select_once(&SELECTION, selection, "Mirror selection")
+}
+
+/// The selected policy, or `best` when nothing was selected.
+pub fn active() -> MirrorSelection {
+    SELECTION.get().copied().unwrap_or_default()
+}
+
+/// Index into `deployments` of the one to recommend under `Best`.
+fn best(deployments: &[Mirror], cache: &VerificationCache, region: Option<&str>) -> usize {
+    let rank = |d: &Mirror| {
+        let local = region
+            .zip(d.region.as_deref())
+            .is_some_and(|(preferred, region)| region_matches(preferred, region));
+        let availability = cache
+            .availability(&d.endpoint)
+            .unwrap_or(UNCHECKED_AVAILABILITY);
+        (local, availability)
+    };
+    let mut chosen = 0;
+    for (at, deployment) in deployments.iter().enumerate().skip(1) {
+        if rank(deployment).partial_cmp(&rank(&deployments[chosen]))
+            == Some(std::cmp::Ordering::Greater)
+        {
+            chosen = at;
+        }
+    }
+    chosen
+}
+
+/// Points each recommendation of a mirrored entry at the selected deployment
+/// and lists the others under `mirrors`. Runs before `apply_verification` and
+/// `session::attach`, so both see the chosen endpoint.
+pub fn apply(
+    response: &mut Value,
+    catalog: &[McpEntry],
+    cache: &VerificationCache,
+    region: Option<&str>,
+) {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+
+    for rec in recommendations.iter_mut() {
+        let endpoint = rec
+            .get("endpoint")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if let Some(entry) = find(catalog, endpoint) {
+            pick_endpoint(rec, entry, cache, region);
+        }
+    }
+}
+
+/// Sets `rec`'s `endpoint` and `mirrors` for `entry` from the catalog alone;
+/// whatever `mirrors` the recommendation carried is replaced.
+pub fn pick_endpoint(
+    rec: &mut Value,
+    entry: &McpEntry,
+    cache: &VerificationCache,
+    region: Option<&str>,
+) {
+    if entry.mirrors.is_empty() {
+        if let Some(rec) = rec.as_object_mut() {
+            rec.remove("mirrors");
+        }
+        return;
+    }
+    let deployments = deployments(entry);
+    let chosen = match active() {
+        MirrorSelection::Best => best(&deployments, cache, region),
+        MirrorSelection::Primary => 0,
+    };
+    rec["endpoint"] = json!(deployments[chosen].endpoint);
+    rec["mirrors"] = json!(
+        deployments
+            .iter()
+            .enumerate()
+            .filter(|(at, _)| *at != chosen)
+            .map(|(_, d)| json!({
+                "endpoint": d.endpoint,
+                "region": d.region,
+                "recent_availability": cache.availability(&d.endpoint),
+            }))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::validate::retain_catalog_recommendations;
+
+    const INJECTED: &str = "https://attacker.example/mcp";
+
+    fn entry(value: Value) -> McpEntry {
+        let mut entry: McpEntry = serde_json::from_value(value).unwrap();
+        settle(&mut entry).unwrap();
+        entry
+    }
+
+    fn catalog() -> Vec<McpEntry> {
+        vec![
+            entry(json!({
+                "name": "single",
+                "endpoint": "https://single.example/mcp",
+                "version": "1.0.0",
+                "capabilities": [],
+                "desc": "",
+            })),
+            entry(json!({
+                "name": "mirrored",
+                "endpoints": ["https://eu.example/mcp", {"endpoint": "https://us.example/mcp", "region": "us"}],
+                "version": "1.0.0",
+                "capabilities": [],
+                "desc": "",
+            })),
+        ]
+    }
+
+    fn model_output() -> Value {
+        let injected = json!([{ "endpoint": INJECTED }]);
+        json!({ "recommendations": [
+            { "name": "single", "endpoint": "https://single.example/mcp", "mirrors": injected },
+            { "name": "mirrored", "endpoint": "https://eu.example/mcp", "mirrors": injected },
+        ]})
+    }
+
+    fn deployments_of(rec: &Value) -> Vec<&str> {
+        let mut endpoints: Vec<&str> = std::iter::once(&rec["endpoint"])
+            .chain(
+                rec["mirrors"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|m| &m["endpoint"]),
+            )
+            .filter_map(Value::as_str)
+            .collect();
+        endpoints.sort();
+        endpoints
+    }
+
+    #[test]
+    fn validation_strips_model_mirrors() {
+        let mut response = model_output();
+        retain_catalog_recommendations(&mut response, &catalog());
+        for rec in response["recommendations"].as_array().unwrap() {
+            assert!(rec.get("mirrors").is_none());
+        }
+    }
+
+    #[test]
+    fn apply_rebuilds_mirrors_from_the_catalog() {
+        let catalog = catalog();
+        let mut response = model_output();
+        apply(&mut response, &catalog, &VerificationCache::default(), None);
+
+        let recs = response["recommendations"].as_array().unwrap();
+        assert!(recs[0].get("mirrors").is_none());
+        assert_eq!(
+            deployments_of(&recs[1]),
+            ["https://eu.example/mcp", "https://us.example/mcp"]
+        );
+        assert!(!response.to_string().contains(INJECTED));
+    }
+
+    #[test]
+    fn best_prefers_the_requested_region() {
+        let deployments = deployments(&catalog()[1]);
+        let cache = VerificationCache::default();
+        assert_eq!(best(&deployments, &cache, None), 0);
+        assert_eq!(best(&deployments, &cache, Some("us")), 1);
+    }
+
+    #[test]
+    fn selection_accepts_only_known_policies() {
+        assert_eq!(MirrorSelection::parse("best"), Some(MirrorSelection::Best));
+        assert_eq!(
+            MirrorSelection::parse("primary"),
+            Some(MirrorSelection::Primary)
+        );
+        assert_eq!(MirrorSelection::parse("closest"), None);
+        assert_eq!(MirrorSelection::parse("Primary"), None);
+        assert_eq!(active(), MirrorSelection::Best);
+    }
+}
@@ -1,120 +1,1649 @@
 // src/backend/mod.rs
-use crate::ResponsesCompletionModel;
-use anyhow::{Context as _, Result};
+use crate::utils::AgentParams;
+use anyhow::{Context as _, Result, anyhow};
 use axum::{
-    Router,
-    extract::{Json, State},
-    http::StatusCode,
-    response::{IntoResponse, Json as AxumJson},
+    Extension, Router,
+    extract::{DefaultBodyLimit, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Json as AxumJson, Response},
     routing::{get, post},
 };
-use std::fs::File;
 use opentelemetry::trace::Status;
-use rig::Embed;
-use rig::agent::Agent;
-use rig::completion::Prompt;
+use rig::embeddings::{EmbedError, Embedding, TextEmbedder};
+use rig::providers::openai::EmbeddingModel;
+use rig::vector_store::in_memory_store::InMemoryVectorIndex;
+use rig::{Embed, OneOrMany};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::env;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tower_http::trace::TraceLayer;
 use tracing::{Instrument, info_span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
-use x402_axum::{IntoPriceTag, X402Middleware};
-use x402_rs::network::{Network, USDCDeployment};
-use x402_rs::{address_evm, address_sol};
+use x402_axum::X402Middleware;
+
+pub mod account;
+pub mod admin;
+pub mod agent;
+pub mod apikey;
+pub mod breaker;
+pub mod cache;
+pub mod capindex;
+pub mod catalog;
+pub mod clock;
+pub mod cors;
+pub mod deadline;
+pub mod diff;
+pub mod discover_cache;
+pub mod embed;
+pub mod embed_cache;
+pub mod embed_health;
+pub mod embed_profile;
+pub mod envelope;
+pub mod estimate;
+pub mod exclude;
+pub mod explain;
+pub mod extract;
+pub mod facilitator;
+pub mod feedback;
+pub mod filters;
+pub mod freshness;
+pub mod health;
+pub mod idempotency;
+pub mod lang;
+pub mod lint;
+pub mod maintenance;
+pub mod meta;
+pub mod metrics;
+pub mod mirrors;
+pub mod networks;
+pub mod normalize;
+pub mod payer;
+pub mod payment;
+pub mod payto;
+pub mod perspectives;
+pub mod pins;
+pub mod pretty;
+pub mod pricing;
+pub mod probe;
+pub mod providers;
+pub mod query_context;
+pub mod queue;
+pub mod ratelimit;
+pub mod readiness;
+pub mod redact;
+pub mod refresh;
+pub mod reload;
+pub mod remote;
+pub mod replay;
+pub mod request;
+pub mod rerank;
+pub mod response;
+pub mod sampling;
+pub mod sanitize;
+pub mod schema;
+pub mod search;
+pub mod session;
+pub mod settings;
+pub mod settlement;
+pub mod signals;
+pub mod signing;
+pub mod snapshot;
+pub mod stopwords;
+pub mod store;
+pub mod synonyms;
+pub mod tasks;
+pub mod template;
+pub mod urls;
+pub mod validate;
+pub mod verify;
+pub mod vocab;
+pub mod watcher;
+pub mod webhook;
+
+use filters::{CandidateFilter, EndpointPolicy, FILTER_STATS_HEADER, FilterStats};
+use request::ApiJson;
+use synonyms::SynonymMap;
+use validate::ProtocolVersions;
 
 // placeholder MCP data for now
-#[derive(Embed, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct McpEntry {
-    #[embed]
     pub name: String,
+    /// The primary deployment; see `mirrors` for entries with several.
+    #[serde(default)]
     pub endpoint: String,
+    /// Equivalent deployments besides `endpoint` (other regions, mirrors).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<mirrors::Mirror>,
+    /// Input only: every deployment, primary first. `mirrors::settle` folds it
+    /// into `endpoint` and `mirrors` at load.
+    #[serde(default, skip_serializing)]
+    pub endpoints: Option<Vec<mirrors::Mirror>>,
     pub version: String,
-    #[embed]
     pub capabilities: Vec<String>,
-    #[embed]
     pub desc: String,
+    #[serde(default)]
+    pub auth: AuthInfo,
+    #[serde(default = "default_transport")]
+    pub transport: String,
+    /// Curator-assigned ranking boost, added to the similarity score and
+    /// clamped by `search::featured_boost_cap`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub featured_boost: Option<f32>,
+    /// Curator-assigned categories, e.g. "search" or "filesystem"; stored lowercase.
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    pub tags: Vec<String>,
+    /// Hosting region, e.g. "eu-west-1"; normalized by `filters::normalize_region`.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_region",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub region: Option<String>,
+    /// Disabled entries stay in the catalog file but are never embedded or recommended.
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// File path or URL the entry was loaded from; filled in by the loader
+    /// unless the entry declares its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Curator notes that help retrieval but are never shown to callers or the
+    /// model; see `redact`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_notes: Option<String>,
+    /// Curator-declared price per call in USD, for servers that charge; read by
+    /// the `cost` perspective.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_usd: Option<f64>,
+    /// The server's own request limit, passed through to recommendations so
+    /// clients can self-throttle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+    /// `capabilities` as the catalog wrote them, when `normalize` changed them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_capabilities: Option<Vec<String>>,
+}
+
+fn deserialize_region<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let region = Option::<String>::deserialize(deserializer)?;
+    Ok(region
+        .map(|r| filters::normalize_region(&r))
+        .filter(|r| !r.is_empty()))
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+/// Embedded fields follow the active `embed_profile` and its weights, if any.
+impl Embed for McpEntry {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        for text in embed_profile::texts(self) {
+            embedder.embed(text);
+        }
+        Ok(())
+    }
+}
+
+fn default_transport() -> String {
+    "http".to_string()
+}
+
+fn deserialize_tags<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let tags = Vec::<String>::deserialize(deserializer)?;
+    Ok(normalize::active().tags(&tags))
+}
+
+/// Authentication requirements advertised by a catalog entry.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AuthInfo {
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub schemes: Vec<String>,
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+/// `requests` calls per `per` window ("second", "minute", "hour" or "day").
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(try_from = "RawRateLimit")]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: String,
+}
+
+pub const RATE_LIMIT_WINDOWS: [&str; 4] = ["second", "minute", "hour", "day"];
+
+#[derive(Deserialize)]
+struct RawRateLimit {
+    requests: u32,
+    per: String,
+}
+
+impl TryFrom<RawRateLimit> for RateLimit {
+    type Error = String;
+
+    fn try_from(raw: RawRateLimit) -> Result<Self, Self::Error> {
+        if raw.requests == 0 {
+            return Err("rate_limit.requests must be positive".to_string());
+        }
+        let per = raw.per.trim().to_lowercase();
+        if !RATE_LIMIT_WINDOWS.contains(&per.as_str()) {
+            return Err(format!(
+                "rate_limit.per must be one of {}, got {:?}",
+                RATE_LIMIT_WINDOWS.join(", "),
+                raw.per
+            ));
+        }
+        Ok(RateLimit {
+            requests: raw.requests,
+            per,
+        })
+    }
 }
 
 pub fn load_mcps_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<McpEntry>> {
-    let file = File::open(&path)
-        .with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
-    let entries: Vec<McpEntry> = serde_json::from_reader(file)
-        .with_context(|| "Failed to parse mcps.json into Vec<McpEntry>")?;
+    let bytes =
+        std::fs::read(&path).with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
+    parse_mcps(&bytes, &path.as_ref().display().to_string())
+}
+
+/// Parses a catalog document, tagging entries without a `source` with `source`
+/// (a file path or URL), settling multi-endpoint entries (see `mirrors`) and
+/// normalizing capabilities and tags.
+pub fn parse_mcps(bytes: &[u8], source: &str) -> Result<Vec<McpEntry>> {
+    let mut entries: Vec<McpEntry> = serde_json::from_slice(bytes)
+        .with_context(|| format!("Failed to parse {} into Vec<McpEntry>", source))?;
+    for entry in &mut entries {
+        mirrors::settle(entry).with_context(|| format!("Invalid entry in {}", source))?;
+        entry.source.get_or_insert_with(|| source.to_string());
+        normalize::active().apply(entry);
+    }
     Ok(entries)
 }
 
+pub type CatalogIndex = InMemoryVectorIndex<EmbeddingModel, McpEntry>;
+
+/// The agent together with the catalog and vector index it recommends from.
+pub struct Librarian {
+    pub agent: agent::LibrarianAgent,
+    pub index: CatalogIndex,
+    pub catalog: Vec<McpEntry>,
+    /// Entries with `enabled: false`, kept for auditing via `/catalog`.
+    pub disabled: Vec<McpEntry>,
+    pub embeddings: Arc<Vec<(McpEntry, OneOrMany<Embedding>)>>,
+    /// Embeds queries for ranking outside `index`; see `search::retrieve_filtered`.
+    pub embedding_model: EmbeddingModel,
+    pub capability_index: Arc<capindex::CapabilityIndex>,
+    pub policy: Arc<EndpointPolicy>,
+    pub synonyms: Arc<SynonymMap>,
+    /// Operator rules forcing servers into matching queries' recommendations.
+    pub pins: Arc<pins::PinRules>,
+    pub vocabulary: Arc<vocab::CatalogVocabulary>,
+    pub protocol_versions: Arc<ProtocolVersions>,
+    /// Shared across reloads; see `utils::reload_librarian`.
+    pub verification: Arc<verify::VerificationCache>,
+    pub prompt_template: Arc<template::PromptTemplate>,
+    pub reranker: rerank::Reranker,
+    pub webhook: Option<Arc<webhook::Webhook>>,
+    /// See `cache::catalog_hash`; the basis of the read-only endpoints' `ETag`.
+    pub catalog_hash: String,
+    /// Set when the catalog comes from `MCPS_URL`; carried across reloads.
+    pub remote: Option<Arc<remote::RemoteCatalog>>,
+    pub params: AgentParams,
+    pub retrieval_model: String,
+    pub completion_model: String,
+    /// Answers when the primary is rate-limited; see `prompt_with_fallback`.
+    pub fallback_agent: Option<agent::LibrarianAgent>,
+    pub fallback_model: Option<String>,
+    /// When this catalog was read; see `freshness`.
+    pub loaded_at: chrono::DateTime<chrono::Utc>,
+    /// Shared across reloads; marked when a refresh finds the source unchanged.
+    pub source_check: Arc<freshness::SourceCheck>,
+}
+
+pub const RETRIEVAL_MODEL_HEADER: &str = "x-librarian-retrieval-model";
+pub const COMPLETION_MODEL_HEADER: &str = "x-librarian-completion-model";
+pub const QUERY_EXPANSIONS_HEADER: &str = "x-librarian-query-expansions";
+pub const CANDIDATE_SCORES_HEADER: &str = "x-librarian-candidate-scores";
+/// Debug-only; see `filters::SimilarityStats`.
+pub const SIMILARITY_HEADER: &str = "x-librarian-similarity";
+/// Set when `LIBRARIAN_FALLBACK_MODEL` answered instead of the primary.
+pub const FALLBACK_MODEL_HEADER: &str = "x-librarian-fallback-model";
+
+impl Librarian {
+    /// Headers attributing a response to the live embedding/completion model pair.
+    pub fn model_headers(&self) -> [(&'static str, String); 2] {
+        [
+            (RETRIEVAL_MODEL_HEADER, self.retrieval_model.clone()),
+            (COMPLETION_MODEL_HEADER, self.completion_model.clone()),
+        ]
+    }
+
+    /// Prompts the primary agent; if it is rate-limited and a fallback model is
+    /// configured, retries once on the fallback. Returns the output and, when
+    /// the fallback answered, its model name.
+    pub async fn prompt_with_fallback(
+        &self,
+        prompt: &str,
+    ) -> Result<(String, Option<&str>), rig::completion::PromptError> {
+        let error = match self.agent.prompt(prompt).await {
+            Ok(output) => return Ok((output, None)),
+            Err(e) => e,
+        };
+        let (Some(fallback), Some(model)) = (&self.fallback_agent, self.fallback_model.as_deref())
+        else {
+            return Err(error);
+        };
+        if !crate::utils::is_rate_limit_error(&error) {
+            return Err(error);
+        }
+        tracing::warn!(
+            primary = %self.completion_model,
+            fallback = %model,
+            "Primary completion model rate-limited, retrying on fallback: {}",
+            error
+        );
+        let output = fallback.prompt(prompt).await?;
+        Ok((output, Some(model)))
+    }
+
+    /// Synonym expansion followed by catalog-vocabulary expansion. Returns the
+    /// query to embed and every term that was appended.
+    pub fn expand_query(&self, query: &str) -> (String, Vec<String>) {
+        let (expanded, mut expansions) = self.synonyms.expand(query);
+        let vocabulary = self
+            .vocabulary
+            .expand(&expanded)
+            .into_iter()
+            .filter(|term| !expansions.contains(term))
+            .collect::<Vec<_>>();
+        if vocabulary.is_empty() {
+            return (expanded, expansions);
+        }
+        let expanded = format!("{} {}", expanded, vocabulary.join(" "));
+        expansions.extend(vocabulary);
+        (expanded, expansions)
+    }
+}
+
+/// Shared, swappable handle to the live `Librarian`. Handlers take a snapshot with
+/// `current()` so a catalog reload never affects a request already in flight.
+#[derive(Clone)]
+pub struct LibrarianHandle(Arc<RwLock<Arc<Librarian>>>);
+
+impl LibrarianHandle {
+    pub fn new(librarian: Librarian) -> Self {
+        metrics::record_index(&librarian);
+        LibrarianHandle(Arc::new(RwLock::new(Arc::new(librarian))))
+    }
+
+    pub fn current(&self) -> Arc<Librarian> {
+        let guard = self
+            .0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Arc::clone(&guard)
+    }
+
+    pub fn swap(&self, librarian: Librarian) {
+        metrics::record_index(&librarian);
+        let mut guard = self
+            .0
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *guard = Arc::new(librarian);
+    }
+}
+
 #[derive(Deserialize)]
 pub struct DiscoverRequest {
     pub query: String,
     pub filters: Option<Value>,
     pub client_type: Option<String>,
+    /// Lifts the no-auth policy for callers that hold credentials.
+    #[serde(default)]
+    pub allow_auth: bool,
+    /// Same as `?format=compact`.
+    #[serde(default)]
+    pub compact: bool,
+    /// Same as `filters.preferred_region`; this one wins if both are set.
+    pub preferred_region: Option<String>,
+    /// Locale for the free-text fields; validated by `lang::resolve_lang`.
+    pub lang: Option<String>,
+    /// Catalog entry names to rank instead of running vector retrieval; see
+    /// `restricted_candidates`.
+    pub restrict_to: Option<Vec<String>>,
+    /// Drops recommendations scoring below this (0–100); overrides
+    /// `LIBRARIAN_MIN_SCORE`.
+    pub min_score: Option<u64>,
+    /// Token budget for the model call; see `estimate` for how it is counted.
+    /// Over budget, the request is refused before the model is called.
+    pub max_cost: Option<u64>,
+    /// Ranks live-verified recommendations above unverified ones scoring up
+    /// to `LIBRARIAN_PREFER_VERIFIED_MARGIN` points higher.
+    #[serde(default)]
+    pub prefer_verified: bool,
+    /// Catalog names or endpoints the caller already knows, kept out of the
+    /// candidates and the recommendations; see `exclude`.
+    pub exclude: Option<Vec<String>>,
+    /// At most this many recommendations (1–3, default 3). Also picks the
+    /// price tier; see `pricing::DiscoverPricing`.
+    pub max_results: Option<u64>,
+}
+
+/// Query parameters accepted by `/discover`.
+#[derive(Deserialize, Default)]
+pub struct DiscoverParams {
+    /// Adds the candidate-score headers and per-recommendation `signals`.
+    #[serde(default)]
+    pub debug: bool,
+    /// `compact` returns only `name`, `endpoint` and `score` per recommendation;
+    /// `ndjson` returns one recommendation per line plus a summary line;
+    /// `script` returns a runnable bash script (see `response::script`).
+    pub format: Option<String>,
+    /// With `debug`, asks for a longer free-text `explanation` per recommendation.
+    #[serde(default)]
+    pub explain: bool,
+    /// Admin-only: include the raw model output when validation fails.
+    /// See `admin::raw_output_allowed`.
+    #[serde(default)]
+    pub raw: bool,
+    /// Comma-separated extra orderings of the candidates; see `perspectives`.
+    pub perspectives: Option<String>,
+}
+
+/// The catalog entries named in `restrict_to`, in the order given and with a
+/// neutral score. Every name must match a live entry exactly.
+pub(crate) fn restricted_candidates(
+    librarian: &Librarian,
+    names: &[String],
+) -> Result<Vec<(f64, McpEntry)>, request::RequestError> {
+    let invalid = |message: String| request::RequestError {
+        status: StatusCode::BAD_REQUEST,
+        field: Some("restrict_to".to_string()),
+        message,
+    };
+    if names.is_empty() {
+        return Err(invalid(
+            "restrict_to must name at least one catalog entry".to_string(),
+        ));
+    }
+
+    let mut candidates: Vec<(f64, McpEntry)> = Vec::with_capacity(names.len());
+    let mut unknown = Vec::new();
+    for name in names {
+        if candidates.iter().any(|(_, e)| e.name == *name) {
+            continue;
+        }
+        match librarian.catalog.iter().find(|e| e.name == *name) {
+            Some(entry) => candidates.push((1.0, entry.clone())),
+            None => unknown.push(name.as_str()),
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(invalid(format!(
+            "Unknown catalog entries: {}",
+            unknown.join(", ")
+        )));
+    }
+    Ok(candidates)
+}
+
+/// The request's filters, with `preferred_region` folded in.
+pub(crate) fn candidate_filter(librarian: &Librarian, req: &DiscoverRequest) -> CandidateFilter {
+    let mut filter = CandidateFilter::from_request(
+        req.filters.as_ref(),
+        req.allow_auth,
+        Arc::clone(&librarian.policy),
+    );
+    if let Some(region) = &req.preferred_region {
+        filter.region = Some(filters::normalize_region(region));
+    }
+    filter
+}
+
+/// The text embedded for retrieval: the query minus noise words (see
+/// `stopwords`), synonym expansion, then request context (see
+/// `query_context`). Returns it with the expansions that were applied.
+pub(crate) fn retrieval_query(
+    librarian: &Librarian,
+    req: &DiscoverRequest,
+) -> (String, Vec<String>) {
+    let (expanded, expansions) = librarian.expand_query(&stopwords::strip(&req.query));
+    (query_context::contextualize(&expanded, req), expansions)
+}
+
+/// How many entries vector search fetches: enough for the reranker, widened
+/// when filters are about to discard some.
+pub(crate) fn retrieval_fetch_k(librarian: &Librarian, filter: &CandidateFilter) -> usize {
+    let top_k = search::DEFAULT_TOP_K;
+    let fetch_k = librarian.reranker.fetch_k(top_k);
+    if filter.is_active() {
+        let widened = filters::filtered_fetch_k(top_k);
+        if widened > fetch_k {
+            tracing::debug!(fetch_k, widened, "Filters active: widening retrieval");
+            return widened;
+        }
+    }
+    fetch_k
+}
+
+/// Retrieval half of `/discover`: `retrieval_query`, vector search, filters,
+/// availability threshold, re-ranking, then region and featured boosts.
+/// Returns the surviving candidates, per-stage filter stats and the
+/// expansions that were applied.
+pub(crate) async fn discover_candidates(
+    librarian: &Librarian,
+    req: &DiscoverRequest,
+) -> Result<(Vec<(f64, McpEntry)>, FilterStats, Vec<String>)> {
+    let filters = req.filters.as_ref();
+    let filter = candidate_filter(librarian, req);
+    let exclusions =
+        exclude::Exclusions::parse(req.exclude.as_deref()).map_err(|e| anyhow!(e.message))?;
+
+    // named candidates skip retrieval, reranking and boosts, but never the policy
+    if let Some(names) = &req.restrict_to {
+        let named = restricted_candidates(librarian, names).map_err(|e| anyhow!(e.message))?;
+        let (mut candidates, mut stats) = filter.apply(named);
+        stats.excluded = exclusions.retain_candidates(&mut candidates);
+        stats.kept = candidates.len();
+        return Ok((candidates, stats, Vec::new()));
+    }
+
+    let (retrieval_query, expansions) = retrieval_query(librarian, req);
+    // fetch past the excluded entries so the caller still gets a full list
+    let fetch_k = retrieval_fetch_k(librarian, &filter) + exclusions.len();
+    let candidates =
+        search::retrieve_filtered(librarian, &retrieval_query, &filter, fetch_k).await?;
+    let similarity = filters::SimilarityStats::of(&candidates);
+    let (mut candidates, mut stats) = filter.apply(candidates);
+    stats.similarity = similarity;
+    stats.excluded = exclusions.retain_candidates(&mut candidates);
+    // servers that turned out to want credentials count with the declared ones
+    stats.auth +=
+        verify::retain_unchallenged(&mut candidates, &librarian.verification, req.allow_auth);
+    stats.kept = candidates.len();
+    if let Some(threshold) = verify::min_availability(filters) {
+        stats.availability =
+            verify::retain_available(&mut candidates, &librarian.verification, threshold);
+        stats.kept = candidates.len();
+    }
+    librarian
+        .reranker
+        .rerank(&req.query, &mut candidates, search::DEFAULT_TOP_K);
+    search::apply_region_preference(&mut candidates, filter.region.as_deref());
+    search::apply_featured_boosts(&mut candidates, search::featured_boost_cap());
+    Ok((candidates, stats, expansions))
+}
+
+/// `discover_prompt`, dropping the lowest-ranked candidates until preamble plus
+/// prompt fit `estimate::max_prompt_tokens`. Returns the prompt and how many
+/// candidates were dropped.
+pub(crate) fn fit_discover_prompt(
+    librarian: &Librarian,
+    req: &DiscoverRequest,
+    query: &str,
+    candidates: &mut Vec<(f64, McpEntry)>,
+    explain: bool,
+) -> (String, usize) {
+    fit_prompt_within(
+        librarian,
+        req,
+        query,
+        candidates,
+        explain,
+        estimate::max_prompt_tokens(),
+    )
+}
+
+fn fit_prompt_within(
+    librarian: &Librarian,
+    req: &DiscoverRequest,
+    query: &str,
+    candidates: &mut Vec<(f64, McpEntry)>,
+    explain: bool,
+    budget: usize,
+) -> (String, usize) {
+    let preamble = estimate::count_tokens(crate::utils::LIBRARIAN_PREAMBLE);
+    let mut dropped = 0;
+    loop {
+        let prompt = discover_prompt(librarian, req, query, candidates, explain);
+        let total = preamble + estimate::count_tokens(&prompt);
+        if total <= budget || candidates.is_empty() {
+            if dropped > 0 {
+                tracing::warn!(
+                    dropped,
+                    kept = candidates.len(),
+                    prompt_tokens = total,
+                    budget,
+                    "Dropped lowest-ranked candidates to fit the prompt budget"
+                );
+            }
+            return (prompt, dropped);
+        }
+        candidates.pop();
+        dropped += 1;
+    }
+}
+
+/// Assembles the user prompt sent to the agent for `/discover`. `query` must
+/// already be sanitized.
+pub(crate) fn discover_prompt(
+    librarian: &Librarian,
+    req: &DiscoverRequest,
+    query: &str,
+    candidates: &[(f64, McpEntry)],
+    explain: bool,
+) -> String {
+    let now = chrono::Utc::now();
+    let prompt_time = clock::active();
+    // availability from verification feeds the rubric's reliability component,
+    // and with the current time, the check time feeds freshness
+    let context: Vec<Value> = candidates
+        .iter()
+        .map(|(_, entry)| {
+            let mut item = redact::public_value(entry);
+            item["recent_availability"] =
+                serde_json::json!(mirrors::availability(entry, &librarian.verification));
+            if prompt_time != clock::PromptTime::Off
+                && let Some(verified) = librarian.verification.get(&entry.endpoint).filter(|v| v.ok)
+            {
+                item["last_checked"] = serde_json::json!(clock::timestamp(verified.checked_at));
+                item["checked_hours_ago"] =
+                    serde_json::json!(clock::hours_since(verified.checked_at, now));
+            }
+            item
+        })
+        .collect();
+    let context = serde_json::to_string_pretty(&context).unwrap_or_else(|_| "[]".to_string());
+
+    let query = sanitize::delimit("user_query", query);
+    let filters = req
+        .filters
+        .as_ref()
+        .map(|f| sanitize::delimit("filters", &f.to_string()))
+        .unwrap_or_else(|| "none".to_string());
+    let client_type = req
+        .client_type
+        .as_deref()
+        .map(|c| sanitize::sanitize_query(c).text)
+        .unwrap_or_else(|| "unspecified".to_string());
+    let mut prompt = librarian.prompt_template.render(&template::PromptValues {
+        candidates: &context,
+        query: &query,
+        client_type: &client_type,
+        filters: &filters,
+    });
+    if let Some(directive) = prompt_time.directive(now) {
+        prompt.push_str(&directive);
+    }
+    if explain {
+        prompt.push_str(
+            "\nExplain mode: add an \"explanation\" string to each recommendation with a few \
+             sentences on why it was chosen and how it compares to the other candidates. Keep \
+             \"rationale\" to one sentence; do not add any other fields.",
+        );
+    }
+    if *session::INSTRUCTIONS_SOURCE == session::InstructionsSource::Generated {
+        prompt.push_str(
+            "\nInstructions override: the server writes the \"instructions\" block itself. Return \
+             \"instructions\": {} and spend no output on curl commands or next steps.",
+        );
+    }
+    if let Ok(Some(lang)) = lang::resolve_lang(req.lang.as_deref()) {
+        prompt.push_str(&lang::directive(&lang));
+    }
+    if req.allow_auth {
+        prompt.push_str(
+            "\nPolicy override: the caller holds credentials, so servers with auth.required = true \
+             are eligible. For those, set auth.required, auth.schemes and auth.header from the catalog \
+             entry so the caller knows which credentials to present.",
+        );
+    }
+    prompt
+}
+
+/// The canonical empty response when retrieval and the prompt budget left no
+/// candidates: with nothing in its context the model could only invent
+/// servers, so it isn't asked. `None` when there is something to choose from.
+fn empty_candidates_response(query: &str, candidates: &[(f64, McpEntry)]) -> Option<Value> {
+    candidates
+        .is_empty()
+        .then(|| response::empty_response(query))
+}
+
+/// Parses the agent's output and cross-checks it against the catalog, the
+/// supported protocol versions, verification data and the published schema,
+/// ordering recommendations with `validate::sort_recommendations`. `region`
+/// steers the choice among an entry's mirrors; `query` replaces the model's
+/// echo of it (see `validate::enforce_query_echo`). Returns a description of
+/// the problem when the output is unusable.
+fn check_discover_output(
+    librarian: &Librarian,
+    query: &str,
+    output: &str,
+    candidates: &[(f64, McpEntry)],
+    region: Option<&str>,
+    explain: bool,
+) -> Result<Value, String> {
+    process_discover_output(
+        query,
+        output,
+        &librarian.catalog,
+        candidates,
+        &librarian.protocol_versions,
+        &librarian.verification,
+        region,
+        explain,
+    )
+}
+
+/// `check_discover_output` without a live `Librarian`, so `golden` can run it
+/// offline.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn process_discover_output(
+    query: &str,
+    output: &str,
+    catalog: &[McpEntry],
+    candidates: &[(f64, McpEntry)],
+    protocol_versions: &ProtocolVersions,
+    verification: &verify::VerificationCache,
+    region: Option<&str>,
+    explain: bool,
+) -> Result<Value, String> {
+    let mut parsed = extract::parse_agent_json(output)?;
+    if validate::enforce_query_echo_enabled() {
+        validate::enforce_query_echo(&mut parsed, query);
+    }
+    validate::retain_catalog_recommendations(&mut parsed, catalog);
+    validate::retain_supported_versions(&mut parsed, protocol_versions);
+    mirrors::apply(&mut parsed, catalog, verification, region);
+    // tool lists come from verification, never from the model's guess
+    verify::apply_verification(&mut parsed, catalog, verification);
+    session::attach(&mut parsed);
+    validate::sort_recommendations(&mut parsed, candidates);
+    let conformance = if explain {
+        schema::validate_discover_explained(&parsed)
+    } else {
+        schema::validate_discover(&parsed)
+    };
+    conformance
+        .map_err(|errors| format!("does not match the discover schema: {}", errors.join("; ")))?;
+    Ok(parsed)
+}
+
+/// Forces matching operator pins that pass `filter` into `response`; see `pins`.
+fn apply_pins(librarian: &Librarian, query: &str, filter: &CandidateFilter, response: &mut Value) {
+    if librarian.pins.is_empty() {
+        return;
+    }
+    let protocol_version = librarian
+        .protocol_versions
+        .as_slice()
+        .first()
+        .map(String::as_str)
+        .unwrap_or(validate::DEFAULT_PROTOCOL_VERSION);
+    librarian.pins.apply(
+        query,
+        response,
+        &librarian.catalog,
+        filter,
+        &librarian.verification,
+        protocol_version,
+    );
+}
+
+/// Flags a response the fallback model answered.
+fn mark_fallback(headers: &mut HeaderMap, fallback: Option<&str>) {
+    let Some(model) = fallback else {
+        return;
+    };
+    headers.insert(
+        health::DEGRADED_HEADER,
+        HeaderValue::from_static("fallback-model"),
+    );
+    if let Ok(value) = HeaderValue::from_str(model) {
+        headers.insert(FALLBACK_MODEL_HEADER, value);
+    }
+}
+
+/// Adds the requested `perspectives` lists, built from the candidates the model
+/// saw; no extra model call.
+fn attach_perspectives(
+    response: &mut Value,
+    requested: &[perspectives::Perspective],
+    candidates: &[(f64, McpEntry)],
+    librarian: &Librarian,
+) {
+    if requested.is_empty() {
+        return;
+    }
+    if let Some(map) = response.as_object_mut() {
+        map.insert(
+            "perspectives".to_string(),
+            perspectives::build(requested, candidates, &librarian.verification),
+        );
+    }
+}
+
+/// Keeps the first `max_results` recommendations; what was paid for is all
+/// that is returned.
+fn truncate_recommendations(response: &mut Value, max_results: Option<u64>) {
+    if let Some(max) = max_results
+        && let Some(recommendations) = response
+            .get_mut("recommendations")
+            .and_then(Value::as_array_mut)
+    {
+        recommendations.truncate(max as usize);
+    }
+}
+
+/// Registers `response` for `/feedback` under `query_id` and stamps the id on
+/// the body and headers.
+fn tag_query_id(
+    store: &feedback::FeedbackStore,
+    query_id: &str,
+    response: &mut Value,
+    headers: &mut HeaderMap,
+) {
+    store.issue(query_id, response);
+    if let Ok(value) = HeaderValue::from_str(query_id) {
+        headers.insert(feedback::QUERY_ID_HEADER, value);
+    }
+    if let Some(map) = response.as_object_mut() {
+        map.insert("query_id".to_string(), Value::String(query_id.to_string()));
+    }
 }
 
 #[tracing::instrument(skip_all)]
 async fn discover_handler(
-    State(agent): State<Arc<Agent<ResponsesCompletionModel>>>,
-    Json(req): Json<DiscoverRequest>,
-) -> impl IntoResponse {
-    let query = req.query;
-
-    let prompt = format!(
-        "User query: {}. As Librarian, recommend a tool match and explain briefly.",
-        query
-    );
+    State(handle): State<LibrarianHandle>,
+    Query(params): Query<DiscoverParams>,
+    api_caller: Option<Extension<apikey::ApiKeyCaller>>,
+    Extension(breaker): Extension<Arc<breaker::CircuitBreaker>>,
+    Extension(feedback): Extension<Arc<feedback::FeedbackStore>>,
+    Extension(replay): Extension<Arc<replay::ReplayStore>>,
+    Extension(store): Extension<Arc<dyn store::Store>>,
+    Extension(discover_cache): Extension<Arc<discover_cache::DiscoverCache>>,
+    request_started: Option<Extension<deadline::RequestStarted>>,
+    headers: HeaderMap,
+    ApiJson(req): ApiJson<DiscoverRequest>,
+) -> Response {
+    let deadline = deadline::Deadline::start(deadline::timeouts())
+        .within(request_started.map(|Extension(started)| started));
+    let librarian = handle.current();
+    // issued up front so sampled model calls (see `sampling`) carry it too
+    let query_id = uuid::Uuid::new_v4().to_string();
+    // the only caller `GET /discover/{query_id}` shows the result to, once it
+    // presents the same key or this payer's settled `X-PAYMENT`
+    let caller = match &api_caller {
+        Some(Extension(caller)) => Some(account::Caller::ApiKey(caller.0.clone())),
+        None => payer::payer_from_headers(&headers).map(account::Caller::Payer),
+    };
+    let compact = req.compact || params.format.as_deref() == Some("compact");
+    // explanations cost extra tokens, so they are a debug-only affordance
+    let explain = params.debug && params.explain;
+    let query = req.query.clone();
+    if let Err(e) = sanitize::check_query_length(&query) {
+        return e.into_response();
+    }
+    if let Err(e) = lang::resolve_lang(req.lang.as_deref()) {
+        return e.into_response();
+    }
+    if let Some(names) = &req.restrict_to
+        && let Err(e) = restricted_candidates(&librarian, names)
+    {
+        return e.into_response();
+    }
+    if req.min_score.is_some_and(|s| s > 100) {
+        return request::RequestError {
+            status: StatusCode::BAD_REQUEST,
+            field: Some("min_score".to_string()),
+            message: "min_score must be within 0-100".to_string(),
+        }
+        .into_response();
+    }
+    if req
+        .max_results
+        .is_some_and(|n| n == 0 || n > pricing::MAX_DISCOVER_RESULTS as u64)
+    {
+        return request::RequestError {
+            status: StatusCode::BAD_REQUEST,
+            field: Some("max_results".to_string()),
+            message: format!(
+                "max_results must be within 1-{}",
+                pricing::MAX_DISCOVER_RESULTS
+            ),
+        }
+        .into_response();
+    }
+    if let Err(e) = estimate::check_max_cost(req.max_cost) {
+        return e.into_response();
+    }
+    let exclusions = match exclude::Exclusions::parse(req.exclude.as_deref()) {
+        Ok(exclusions) => exclusions,
+        Err(e) => return e.into_response(),
+    };
+    let min_score = req.min_score.unwrap_or_else(validate::min_score_from_env);
+    let perspectives = match params.perspectives.as_deref().map(perspectives::parse) {
+        Some(Ok(perspectives)) => perspectives,
+        Some(Err(e)) => return e.into_response(),
+        None => Vec::new(),
+    };
 
-    match agent.as_ref().prompt(&prompt).await {
-        Ok(response) => {
-            let json_resp = Value::String(format!(
-                "Discovered via RAG: {} (Agent response: {})",
-                query, response
-            ));
-            (StatusCode::OK, AxumJson(json_resp))
+    let sanitized = sanitize::sanitize_query(&query);
+    if !sanitized.findings.is_empty() {
+        tracing::warn!(findings = ?sanitized.findings, "Neutralized prompt-injection patterns in query");
+        if sanitized.is_egregious() && sanitize::reject_injections() {
+            let json_resp = Value::String("Query rejected: prompt-injection attempt".to_string());
+            return (StatusCode::BAD_REQUEST, AxumJson(json_resp)).into_response();
         }
-        Err(e) => {
-            let json_resp = Value::String(format!("Agent error: {}", e));
-            (StatusCode::INTERNAL_SERVER_ERROR, AxumJson(json_resp))
+    }
+    // every answer (fresh, cached, empty or catalog-only) gets its own query_id,
+    // replay entry, hits, webhook event and output format
+    let respond = |mut parsed: Value, mut stats_header: HeaderMap| -> Response {
+        tag_query_id(&feedback, &query_id, &mut parsed, &mut stats_header);
+        replay.store(&parsed, caller.clone());
+        metrics::RECOMMENDATION_HITS.record_response(&parsed);
+        store::record_response_hits(store.as_ref(), &parsed);
+
+        if let Some(webhook) = &librarian.webhook {
+            let payer = api_caller
+                .as_ref()
+                .map(|Extension(caller)| caller.0.clone())
+                .or_else(|| payer::payer_from_headers(&headers));
+            webhook.notify(webhook::RecommendationEvent::new(&query, &parsed, payer));
         }
+
+        if params.format.as_deref() == Some("script") {
+            return (
+                stats_header,
+                librarian.model_headers(),
+                response::script(&parsed),
+            )
+                .into_response();
+        }
+        if params.format.as_deref() == Some("ndjson") {
+            let body = if compact {
+                response::compact(&parsed)
+            } else {
+                parsed
+            };
+            return (
+                stats_header,
+                librarian.model_headers(),
+                response::ndjson(&body, "recommendations"),
+            )
+                .into_response();
+        }
+        if compact {
+            return (
+                StatusCode::OK,
+                stats_header,
+                librarian.model_headers(),
+                AxumJson(response::compact(&parsed)),
+            )
+                .into_response();
+        }
+        (
+            StatusCode::OK,
+            stats_header,
+            librarian.model_headers(),
+            AxumJson(parsed),
+        )
+            .into_response()
+    };
+    let cache_key = discover_cache::key(&req, &params);
+    if let Some(mut cached) = discover_cache.get(&cache_key, &librarian.catalog_hash) {
+        tracing::debug!("Answering discover from the response cache");
+        metrics::METRICS
+            .discover_cache_hits_total
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        cached["query"] = Value::String(query.clone());
+        let mut cache_header = HeaderMap::new();
+        cache_header.insert(
+            discover_cache::CACHE_HEADER,
+            HeaderValue::from_static("hit"),
+        );
+        return respond(cached, cache_header);
+    }
+
+    let (mut candidates, stats, expansions) = match deadline
+        .retrieval(discover_candidates(&librarian, &req))
+        .await
+    {
+        Err(timeout) => return timeout.into_response(),
+        Ok(Ok(retrieved)) => retrieved,
+        Ok(Err(e)) => {
+            let json_resp = Value::String(format!("Retrieval error: {}", e));
+            return (StatusCode::INTERNAL_SERVER_ERROR, AxumJson(json_resp)).into_response();
+        }
+    };
+    tracing::debug!(filter_stats = %stats.header_value(), "Filtered discover candidates");
+    let mut stats_header = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&stats.header_value()) {
+        stats_header.insert(FILTER_STATS_HEADER, value);
     }
+    if params.debug {
+        if let Ok(value) = HeaderValue::from_str(&expansions.join(",")) {
+            stats_header.insert(QUERY_EXPANSIONS_HEADER, value);
+        }
+        let scores = candidates
+            .iter()
+            .map(|(score, entry)| format!("{}={:.4}", entry.name, score))
+            .collect::<Vec<_>>()
+            .join(",");
+        if let Ok(value) = HeaderValue::from_str(&scores) {
+            stats_header.insert(CANDIDATE_SCORES_HEADER, value);
+        }
+        if let Some(similarity) = &stats.similarity
+            && let Ok(value) = HeaderValue::from_str(&similarity.header_value())
+        {
+            stats_header.insert(SIMILARITY_HEADER, value);
+        }
+    }
+    let (prompt, _) =
+        fit_discover_prompt(&librarian, &req, &sanitized.text, &mut candidates, explain);
+    let filter = candidate_filter(&librarian, &req);
+    let region = filter.region.clone();
+
+    if let Some(mut empty) = empty_candidates_response(&query, &candidates) {
+        tracing::info!(filter_stats = %stats.header_value(), "No discover candidates, answering empty without the model");
+        metrics::METRICS
+            .discover_no_candidates_total
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        apply_pins(&librarian, &query, &filter, &mut empty);
+        exclusions.retain_recommendations(&mut empty, &query, &librarian.catalog);
+        truncate_recommendations(&mut empty, req.max_results);
+        return respond(empty, stats_header);
+    }
+
+    if !breaker.allow() {
+        let protocol_version = librarian
+            .protocol_versions
+            .as_slice()
+            .first()
+            .map(String::as_str)
+            .unwrap_or(validate::DEFAULT_PROTOCOL_VERSION);
+        let mut fallback = response::catalog_only(&query, &candidates, protocol_version);
+        mirrors::apply(
+            &mut fallback,
+            &librarian.catalog,
+            &librarian.verification,
+            region.as_deref(),
+        );
+        verify::apply_verification(&mut fallback, &librarian.catalog, &librarian.verification);
+        session::attach(&mut fallback);
+        validate::retain_min_score(&mut fallback, &query, min_score);
+        if req.prefer_verified {
+            verify::prefer_verified(&mut fallback, verify::prefer_verified_margin());
+        }
+        apply_pins(&librarian, &query, &filter, &mut fallback);
+        exclusions.retain_recommendations(&mut fallback, &query, &librarian.catalog);
+        truncate_recommendations(&mut fallback, req.max_results);
+        if params.debug {
+            signals::attach(&mut fallback, &query, &candidates, &librarian.catalog);
+        }
+        attach_perspectives(&mut fallback, &perspectives, &candidates, &librarian);
+        stats_header.insert(
+            health::DEGRADED_HEADER,
+            HeaderValue::from_static("agent-circuit-open"),
+        );
+        return respond(fallback, stats_header);
+    }
+
+    // the catalog-only fallback above costs no tokens, so only this path is budgeted
+    if let Err(e) = estimate::check_budget(
+        req.max_cost,
+        estimate::estimated_cost(&prompt, librarian.params.max_tokens),
+    ) {
+        return (stats_header, e).into_response();
+    }
+
+    let agent_error = |e: rig::completion::PromptError, headers: HeaderMap| {
+        breaker.record_failure();
+        let json_resp = Value::String(format!("Agent error: {}", e));
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            headers,
+            librarian.model_headers(),
+            AxumJson(json_resp),
+        )
+            .into_response()
+    };
+
+    let output = match deadline
+        .model(librarian.prompt_with_fallback(&prompt))
+        .await
+    {
+        Err(timeout) => {
+            breaker.record_failure();
+            return timeout.into_response();
+        }
+        Ok(Ok((output, fallback))) => {
+            let model = fallback.unwrap_or(&librarian.completion_model);
+            sampling::log_call(&query_id, "initial", model, &prompt, &output);
+            mark_fallback(&mut stats_header, fallback);
+            output
+        }
+        Ok(Err(e)) => return agent_error(e, stats_header),
+    };
+    breaker.record_success();
+    let mut parsed = match check_discover_output(
+        &librarian,
+        &query,
+        &output,
+        &candidates,
+        region.as_deref(),
+        explain,
+    ) {
+        Ok(parsed) => parsed,
+        Err(problem) => {
+            // one corrective retry on bad output only; API errors are not retried
+            tracing::warn!(problem = %problem, "Discover output invalid, reprompting once");
+            metrics::METRICS
+                .discover_reprompts_total
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let corrective = format!(
+                "{}\n\nYour previous output was:\n{}\n\nYour previous output was invalid JSON ({}); \
+                 return only the schema-conformant JSON.",
+                prompt, output, problem
+            );
+            let retried = match deadline
+                .model(librarian.prompt_with_fallback(&corrective))
+                .await
+            {
+                Err(timeout) => {
+                    breaker.record_failure();
+                    return timeout.into_response();
+                }
+                Ok(Ok((output, fallback))) => {
+                    let model = fallback.unwrap_or(&librarian.completion_model);
+                    sampling::log_call(&query_id, "reprompt", model, &corrective, &output);
+                    mark_fallback(&mut stats_header, fallback);
+                    output
+                }
+                Ok(Err(e)) => return agent_error(e, stats_header),
+            };
+            match check_discover_output(
+                &librarian,
+                &query,
+                &retried,
+                &candidates,
+                region.as_deref(),
+                explain,
+            ) {
+                Ok(parsed) => parsed,
+                Err(problem) => {
+                    tracing::error!(problem = %problem, "Discover output still invalid after reprompt");
+                    // raw output stays admin-only; see `admin::raw_output_allowed`
+                    let mut json_resp = serde_json::json!({
+                        "error": "model_output_invalid",
+                        "message": "Agent returned a response that does not match the discover schema",
+                    });
+                    if params.raw && admin::raw_output_allowed(&headers) {
+                        json_resp["problem"] = Value::String(problem);
+                        json_resp["raw_output"] = serde_json::json!([output, retried]);
+                    }
+                    return (
+                        StatusCode::BAD_GATEWAY,
+                        stats_header,
+                        librarian.model_headers(),
+                        AxumJson(json_resp),
+                    )
+                        .into_response();
+                }
+            }
+        }
+    };
+
+    validate::retain_min_score(&mut parsed, &query, min_score);
+    if req.prefer_verified {
+        verify::prefer_verified(&mut parsed, verify::prefer_verified_margin());
+    }
+    apply_pins(&librarian, &query, &filter, &mut parsed);
+    exclusions.retain_recommendations(&mut parsed, &query, &librarian.catalog);
+    truncate_recommendations(&mut parsed, req.max_results);
+    if params.debug {
+        signals::attach(&mut parsed, &query, &candidates, &librarian.catalog);
+    }
+    attach_perspectives(&mut parsed, &perspectives, &candidates, &librarian);
+    if discover_cache.enabled() {
+        metrics::METRICS
+            .discover_cache_misses_total
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        discover_cache.insert(cache_key, &librarian.catalog_hash, &parsed);
+        stats_header.insert(
+            discover_cache::CACHE_HEADER,
+            HeaderValue::from_static("miss"),
+        );
+    }
+    respond(parsed, stats_header)
 }
 
 pub struct Backend {
     pub app: Router,
-    pub agent: Arc<Agent<ResponsesCompletionModel>>,
+    pub librarian: LibrarianHandle,
+    pub route_prices: pricing::RoutePrices,
+    pub facilitator: Arc<facilitator::FacilitatorHealth>,
+    pub payment_failure_mode: facilitator::PaymentFailureMode,
+    pub embedding_health: Arc<embed_health::EmbeddingHealth>,
+    /// Shared by every MCP verification request.
+    pub verify_http: Arc<verify::VerifyHttp>,
+    /// Every catalog reload goes through here: admin, watcher and refresh timer.
+    pub reloader: Arc<reload::Reloader>,
+    /// Flipped by `launch` once the startup index build is swapped in.
+    pub readiness: readiness::Readiness,
+    /// Feedback, recommendation hits and the admin audit trail; see `store`.
+    pub store: Arc<dyn store::Store>,
 }
 
 impl Backend {
-    pub fn new(agent: Agent<ResponsesCompletionModel>) -> Self {
+    pub fn new(librarian: Librarian) -> Result<Self> {
+        // before `launch` loads the catalog, which is normalized as it is parsed
+        normalize::select(normalize::NormalizeRules::from_env()?)?;
+        clock::select(clock::PromptTime::from_env()?)?;
+        networks::select(networks::NetworkNames::from_env()?)?;
+        mirrors::select(mirrors::MirrorSelection::from_env()?)?;
+        query_context::select(query_context::QueryContext::from_env()?)?;
+        stopwords::select(stopwords::Stopwords::from_env()?)?;
         let facilitator_url = env::var("FACILITATOR_URL")
             .unwrap_or_else(|_| "https://facilitator.x402.rs".to_string());
 
-        let base_url = env::var("API_BASE_URL")
-            .unwrap_or_else(|_| "http://localhost:8080/".to_string());
         let x402_base = X402Middleware::try_from(facilitator_url.clone())
-            .expect("Failed to create X402 middleware")
-            .with_base_url(url::Url::parse(&base_url).expect("Invalid base URL"));
+            .map_err(|e| anyhow!("Failed to create X402 middleware: {}", e))?
+            .with_base_url(urls::base_url_from_env()?);
+
+        let route_prices = pricing::RoutePrices::from_env()?;
+        route_prices.validate()?;
+        let discover_price = route_prices.get("/discover");
+        let discover_pricing = pricing::DiscoverPricing::from_env(&route_prices)?;
+        discover_pricing.validate()?;
+        if !discover_pricing.is_flat() {
+            tracing::info!(tiers = ?discover_pricing.tiers(), "Pricing /discover per requested result");
+        }
+        let search_price = route_prices.get("/search");
+        let embed_price = route_prices.get("/embed");
+        let route_challenges = pricing::RouteChallenges::from_env()?;
+        let discover_challenge = route_challenges.get("/discover");
+        let search_challenge = route_challenges.get("/search");
+        let embed_challenge = route_challenges.get("/embed");
+        let pay_to = Arc::new(payto::PayToPool::from_env()?);
+
+        let librarian = LibrarianHandle::new(librarian);
+        let readiness = readiness::Readiness::default();
+        let maintenance = maintenance::Maintenance::from_env();
+        let idempotency_cache = Arc::new(idempotency::IdempotencyCache::from_env());
+        let cors = cors::cors_layer_from_env()?;
+        let facilitator = Arc::new(facilitator::FacilitatorHealth::new(facilitator_url.clone()));
+        let payment_failure_mode = facilitator::PaymentFailureMode::from_env()?;
+        let embedding_health = Arc::new(embed_health::EmbeddingHealth::new());
+        let agent_queue = Arc::new(queue::AgentQueue::from_env());
+        let breaker = Arc::new(breaker::CircuitBreaker::from_env());
+        let store = store::from_env()?;
+        let feedback = Arc::new(feedback::FeedbackStore::open(Arc::clone(&store))?);
+        metrics::RECOMMENDATION_HITS.seed(store.hits()?);
+        let replay = Arc::new(replay::ReplayStore::from_env());
+        let discover_cache = Arc::new(discover_cache::DiscoverCache::from_env());
+        let signer = signing::ResponseSigner::from_env()?.map(Arc::new);
+        let freshness = freshness::CatalogFreshness {
+            handle: librarian.clone(),
+            max_age: freshness::max_catalog_age_from_env()?,
+        };
+        let reloader = Arc::new(reload::Reloader::new(
+            librarian.clone(),
+            reload::ConcurrentReload::from_env()?,
+        ));
+        let queued =
+            || middleware::from_fn_with_state(Arc::clone(&agent_queue), queue::agent_queue_layer);
+        let cached = |private: bool| {
+            middleware::from_fn_with_state(
+                cache::CachePolicy {
+                    handle: librarian.clone(),
+                    private,
+                },
+                cache::cache_layer,
+            )
+        };
+
+        // inside the x402 layer, so `payment_state_layer` knows the payment verified
+        let served = || middleware::from_fn(settlement::served_layer);
+        // one router per pay_to variant; see `payto` for why this is settlement-safe
+        let discover_variants = |price: f64| {
+            (0..pay_to.variants())
+                .map(|k| {
+                    let layer = pay_to.layer(&x402_base, k, price, &discover_challenge)?;
+                    Ok(Router::new()
+                        .route(
+                            "/discover",
+                            post(discover_handler)
+                                .layer(queued())
+                                .layer(served())
+                                .layer(layer),
+                        )
+                        .with_state(librarian.clone()))
+                })
+                .collect::<Result<Vec<Router>>>()
+        };
+        // and, when priced per result, one set of variants per tier
+        let discover_tiers = if discover_pricing.is_flat() {
+            vec![discover_pricing.price(pricing::MAX_DISCOVER_RESULTS)]
+        } else {
+            discover_pricing.tiers()
+        }
+        .into_iter()
+        .map(|price| {
+            Ok(Router::new().route(
+                "/discover",
+                payto::paid_route(discover_variants(price)?, Arc::clone(&pay_to)),
+            ))
+        })
+        .collect::<Result<Vec<Router>>>()?;
+        let search_variants = (0..pay_to.variants())
+            .map(|k| {
+                let layer = pay_to.layer(&x402_base, k, search_price, &search_challenge)?;
+                Ok(Router::new()
+                    .route(
+                        "/search",
+                        post(search::search_handler)
+                            .layer(cached(true))
+                            .layer(served())
+                            .layer(layer),
+                    )
+                    .with_state(librarian.clone()))
+            })
+            .collect::<Result<Vec<Router>>>()?;
+        let embed_limiter = Arc::new(ratelimit::RateLimiter::from_env(
+            "/embed",
+            "LIBRARIAN_EMBED_RATE_LIMIT",
+        ));
+        let limited = || {
+            middleware::from_fn_with_state(Arc::clone(&embed_limiter), ratelimit::rate_limit_layer)
+        };
+        // free, but each estimate embeds the query
+        let estimate_limiter = Arc::new(ratelimit::RateLimiter::from_env(
+            "/discover/estimate",
+            "LIBRARIAN_ESTIMATE_RATE_LIMIT",
+        ));
+        let embed_variants = (0..pay_to.variants())
+            .map(|k| {
+                let layer = pay_to.layer(&x402_base, k, embed_price, &embed_challenge)?;
+                Ok(Router::new()
+                    .route(
+                        "/embed",
+                        post(embed::embed_handler)
+                            .layer(limited())
+                            .layer(served())
+                            .layer(layer),
+                    )
+                    .with_state(librarian.clone()))
+            })
+            .collect::<Result<Vec<Router>>>()?;
+
+        let discover_unpaid = Router::new()
+            .route("/discover", post(discover_handler).layer(queued()))
+            .with_state(librarian.clone());
+        let search_unpaid = Router::new()
+            .route("/search", post(search::search_handler).layer(cached(true)))
+            .with_state(librarian.clone());
+        let embed_unpaid = Router::new()
+            .route("/embed", post(embed::embed_handler).layer(limited()))
+            .with_state(librarian.clone());
+
+        let api_keys = Arc::new(apikey::ApiKeys::from_env());
+        let discover_bypass = apikey::ApiKeyBypass {
+            keys: Arc::clone(&api_keys),
+            unpaid: discover_unpaid.clone(),
+        };
+        let search_bypass = apikey::ApiKeyBypass {
+            keys: Arc::clone(&api_keys),
+            unpaid: search_unpaid.clone(),
+        };
+        let embed_bypass = apikey::ApiKeyBypass {
+            keys: Arc::clone(&api_keys),
+            unpaid: embed_unpaid.clone(),
+        };
+
+        let failure_policy = |unpaid: Router| {
+            middleware::from_fn_with_state(
+                facilitator::PaymentFailurePolicy {
+                    health: Arc::clone(&facilitator),
+                    mode: payment_failure_mode,
+                    unpaid,
+                },
+                facilitator::payment_failure_layer,
+            )
+        };
+
+        let ledger = Arc::new(account::UsageLedger::from_env());
+        let usage = |price: f64| {
+            middleware::from_fn_with_state(
+                account::UsageTracking {
+                    ledger: Arc::clone(&ledger),
+                    keys: Arc::clone(&api_keys),
+                    price,
+                },
+                account::usage_layer,
+            )
+        };
+        let account_routes = Router::new()
+            .route("/account", get(account::account_handler))
+            .with_state(account::AccountState {
+                ledger: Arc::clone(&ledger),
+                keys: Arc::clone(&api_keys),
+            });
+        let replay_routes = Router::new()
+            .route("/discover/{query_id}", get(replay::replay_handler))
+            .with_state(replay::ReplayState {
+                store: Arc::clone(&replay),
+                keys: Arc::clone(&api_keys),
+            });
 
-        let usdc_base_sepolia = USDCDeployment::by_network(Network::BaseSepolia)
-            .pay_to(address_evm!("0xf2757Fe8Ba90ad98dAed8e6254bA9A677069826a"));
-        let usdc_solana = USDCDeployment::by_network(Network::Solana)
-            .pay_to(address_sol!("11111111111111111111111111111112"));
+        let payment_routes = Router::new()
+            .route("/payment/info", get(payment::payment_info_handler))
+            .with_state(payment::PaymentInfo {
+                pool: Arc::clone(&pay_to),
+                prices: route_prices.clone(),
+                discover: discover_pricing,
+                challenges: route_challenges.clone(),
+                facilitator_url: facilitator_url.clone(),
+            });
 
-        let agent_arc = Arc::new(agent);
+        let admin_routes = Router::new()
+            .route("/admin/snapshot", get(snapshot::snapshot_handler))
+            .route(
+                "/admin/catalog",
+                post(catalog::add_entry_handler).layer(Extension(Arc::clone(&reloader))),
+            )
+            .route(
+                "/discover/explain",
+                post(explain::explain_handler).layer(middleware::from_fn_with_state(
+                    maintenance.clone(),
+                    maintenance::maintenance_guard,
+                )),
+            )
+            .route(
+                "/admin/reload",
+                post(reload::reload_handler).layer(Extension(Arc::clone(&reloader))),
+            )
+            .route(
+                "/admin/maintenance",
+                get(maintenance::status_handler)
+                    .post(maintenance::set_handler)
+                    .layer(Extension(maintenance.clone())),
+            )
+            .route("/admin/audit", get(store::audit_handler))
+            .route_layer(middleware::from_fn(admin::require_admin));
 
         let app = Router::new()
-            .route("/health", get(|| async { "OK" }))
+            .route(
+                "/health",
+                get(health::health_handler)
+                    .layer(Extension(Arc::clone(&facilitator)))
+                    .layer(Extension(maintenance.clone()))
+                    .layer(Extension(Arc::clone(&embedding_health)))
+                    .layer(Extension(freshness.clone())),
+            )
+            .route("/health/live", get(readiness::live_handler))
+            .route(
+                "/health/ready",
+                get(readiness::ready_handler).layer(Extension(readiness.clone())),
+            )
+            .route(
+                "/meta",
+                get(meta::meta_handler)
+                    .layer(Extension(signer.as_ref().map(|s| s.public_key())))
+                    .layer(cached(false)),
+            )
+            .route("/metrics", get(metrics::metrics_handler))
+            .route(
+                "/schema/discover",
+                get(schema::discover_schema_handler).layer(cached(false)),
+            )
+            .route(
+                "/catalog",
+                get(catalog::catalog_handler).layer(cached(false)),
+            )
+            .route(
+                "/catalog/by-tag/{tag}",
+                get(catalog::by_tag_handler).layer(cached(false)),
+            )
+            .route("/catalog/stats", get(catalog::stats_handler))
+            // free and unpaid: catalog metadata, not the discovery product
+            .route("/mcps", get(catalog::mcps_handler).layer(cached(false)))
+            .route("/feedback", post(feedback::feedback_handler))
+            // catalog names contain slashes, e.g. `com.example/server`
+            .route("/mcp/{*name}", get(catalog::mcp_handler))
+            .route(
+                "/discover/estimate",
+                post(estimate::estimate_handler)
+                    .layer(Extension(discover_pricing))
+                    .layer(middleware::from_fn_with_state(
+                        estimate_limiter,
+                        ratelimit::rate_limit_layer,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        maintenance.clone(),
+                        maintenance::maintenance_guard,
+                    )),
+            )
+            .merge(admin_routes)
+            .merge(account_routes)
+            .merge(replay_routes)
+            .merge(payment_routes)
             .route(
                 "/discover",
-                post(discover_handler).layer(
-                    x402_base
-                        .clone()
-                        .with_description("MCP Discovery Service")
-                        .with_mime_type("application/json")
-                        .with_price_tag(usdc_solana.amount(0.001).unwrap())
-                        .or_price_tag(usdc_base_sepolia.amount(0.001).unwrap()),
-                ),
+                pricing::tiered_route(discover_tiers)
+                    // facilitator outages only matter to callers that would pay
+                    .layer(failure_policy(discover_unpaid))
+                    // API-key callers are checked before the payment challenge
+                    .layer(middleware::from_fn_with_state(
+                        discover_bypass,
+                        apikey::api_key_layer,
+                    ))
+                    .layer(middleware::from_fn(settlement::payment_state_layer))
+                    .layer(usage(discover_price))
+                    .layer(middleware::from_fn_with_state(
+                        discover_pricing,
+                        pricing::quote_layer,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        librarian.clone(),
+                        health::empty_catalog_guard,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        freshness.clone(),
+                        freshness::stale_catalog_guard,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        readiness.clone(),
+                        readiness::readiness_guard,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        maintenance.clone(),
+                        maintenance::maintenance_guard,
+                    ))
+                    // covers payment verification but never cuts a settlement short; not replays
+                    .layer(middleware::from_fn(deadline::request_deadline_layer))
+                    // outermost: a replayed key is answered before the payment challenge
+                    .layer(middleware::from_fn_with_state(
+                        idempotency_cache,
+                        idempotency::idempotency_layer,
+                    ))
+                    // one body limit for the handler and every layer that reads the body first
+                    .layer(DefaultBodyLimit::max(request::MAX_BODY_BYTES))
+                    // outside idempotency, so a replayed body is wrapped per request
+                    .layer(middleware::from_fn_with_state(
+                        librarian.clone(),
+                        envelope::envelope_layer,
+                    ))
+                    // inside signing, so a signed body is signed as sent
+                    .layer(middleware::from_fn(pretty::pretty_layer))
+                    // covers replayed bodies too
+                    .layer(middleware::from_fn_with_state(
+                        signer,
+                        signing::signing_layer,
+                    ))
+                    .layer(middleware::from_fn(cache::no_store_layer)),
+            )
+            .route(
+                "/search",
+                payto::paid_route(search_variants, Arc::clone(&pay_to))
+                    .layer(failure_policy(search_unpaid))
+                    .layer(middleware::from_fn_with_state(
+                        search_bypass,
+                        apikey::api_key_layer,
+                    ))
+                    .layer(middleware::from_fn(settlement::payment_state_layer))
+                    .layer(usage(search_price))
+                    .layer(middleware::from_fn_with_state(
+                        readiness.clone(),
+                        readiness::readiness_guard,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        maintenance.clone(),
+                        maintenance::maintenance_guard,
+                    )),
+            )
+            .route(
+                "/embed",
+                payto::paid_route(embed_variants, Arc::clone(&pay_to))
+                    .layer(failure_policy(embed_unpaid))
+                    .layer(middleware::from_fn_with_state(
+                        embed_bypass,
+                        apikey::api_key_layer,
+                    ))
+                    .layer(middleware::from_fn(settlement::payment_state_layer))
+                    .layer(usage(embed_price))
+                    .layer(middleware::from_fn_with_state(
+                        maintenance.clone(),
+                        maintenance::maintenance_guard,
+                    )),
             )
+            // read by `/discover` and reported by `/health`
+            .layer(Extension(breaker))
+            // query ids are issued by `/discover`, read by `/feedback` and `/catalog/stats`
+            .layer(Extension(feedback))
+            // written by `/discover` and the admin routes, read by `/admin/audit`
+            .layer(Extension(Arc::clone(&store)))
+            // filled by `/discover`, read by `GET /discover/{query_id}`
+            .layer(Extension(replay))
+            // model answers reused by identical `/discover` requests
+            .layer(Extension(discover_cache))
+            .layer(middleware::from_fn(pretty::pretty_layer))
+            .layer(cors)
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(|request: &axum::http::Request<_>| {
@@ -160,46 +1689,520 @@ impl Backend {
                         },
                     ),
             )
-            // attach only the agent as shared state
-            .with_state(Arc::clone(&agent_arc));
+            // attach the agent and its catalog as shared state
+            .with_state(librarian.clone());
 
-        Backend {
+        Ok(Backend {
             app,
-            agent: agent_arc,
-        }
+            librarian,
+            route_prices,
+            facilitator,
+            payment_failure_mode,
+            embedding_health,
+            verify_http: Arc::new(verify::VerifyHttp::from_env()?),
+            reloader,
+            readiness,
+            store,
+        })
     }
 
-    pub async fn launch(self) -> Result<()> {
-        // Test the agent via arc reference
-        let test_prompt = "Test launch: Confirm Librarian ready.";
-        match self.agent.as_ref().prompt(test_prompt).await {
-            Ok(resp) => tracing::info!("Agent launched successfully: {}", resp),
-            Err(e) => tracing::warn!("Agent launch test failed: {}", e),
-        }
-        let facilitator_url =
-        env::var("FACILITATOR_URL").unwrap_or_else(|_| "https://facilitator.x402.rs".to_string());
-        let base_url = env::var("API_BASE_URL")
-            .unwrap_or_else(|_| "http://localhost:8080/".to_string());
-        let port = env::var("API_PORT").unwrap_or_else(|_| "8080".to_string());
-        let bind_addr = format!("0.0.0.0:{}", port);
+    /// Logs the fully resolved configuration as one flat, structured event.
+    /// Secrets are masked with `utils::mask_secret` and never logged in full.
+    fn log_startup_config(
+        librarian: &Librarian,
+        route_prices: &pricing::RoutePrices,
+        base_url: &str,
+        bind_addr: &str,
+        watch_catalog: bool,
+        payment_failure_mode: facilitator::PaymentFailureMode,
+    ) {
+        let secret = |key: &str| {
+            env::var(key)
+                .map(|v| crate::utils::mask_secret(&v))
+                .unwrap_or_else(|_| "<unset>".to_string())
+        };
+        let route_prices = route_prices
+            .iter()
+            .map(|(route, price)| format!("{}={}", route, price))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        tracing::info!(
+            completion_provider = providers::active().completion.provider.kind.name(),
+            completion_model = %librarian.completion_model,
+            embedding_provider = providers::active().embedding.provider.kind.name(),
+            retrieval_model = %librarian.retrieval_model,
+            temperature = librarian.params.temperature,
+            max_tokens = librarian.params.max_tokens,
+            seed = ?librarian.params.seed,
+            completion_api = librarian.params.api.name(),
+            fallback_model = ?librarian.fallback_model,
+            catalog_path = crate::utils::CATALOG_PATH,
+            catalog_url = ?librarian.remote.as_ref().map(|r| r.url()),
+            catalog_entries = librarian.catalog.len(),
+            max_catalog_age_secs = ?freshness::max_catalog_age_from_env().ok().flatten().map(|age| age.num_seconds()),
+            top_k = search::DEFAULT_TOP_K,
+            verify_interval_secs = ?verify::verify_interval_from_env().map(|interval| interval.as_secs()),
+            allowlist_patterns = librarian.policy.allow.len(),
+            denylist_patterns = librarian.policy.deny.len(),
+            deny_private_endpoints = librarian.policy.deny_private,
+            redacted_fields = %redact::redacted_fields().join(","),
+            embed_query_context = %query_context::describe(),
+            prompt_time = clock::active().name(),
+            query_stopwords = %stopwords::describe(),
+            normalize = normalize::active().mode.name(),
+            normalize_aliases = normalize::active().aliases.len(),
+            instructions = session::INSTRUCTIONS_SOURCE.name(),
+            synonym_groups = librarian.synonyms.len(),
+            pin_rules = librarian.pins.len(),
+            vocab_expansion = librarian.vocabulary.is_enabled(),
+            protocol_versions = %librarian.protocol_versions.as_slice().join(","),
+            payment_failure_mode = payment_failure_mode.name(),
+            store = %env::var("LIBRARIAN_STORE").unwrap_or_else(|_| "file".to_string()),
+            facilitator_url = %env::var("FACILITATOR_URL")
+                .unwrap_or_else(|_| "https://facilitator.x402.rs".to_string()),
+            base_url = %base_url,
+            bind_addr = %bind_addr,
+            route_prices = %route_prices,
+            cors_origins = %env::var("LIBRARIAN_CORS_ORIGINS").unwrap_or_default(),
+            watch_catalog,
+            openai_api_key = %secret("OPENAI_API_KEY"),
+            admin_token = %secret("LIBRARIAN_ADMIN_TOKEN"),
+            "Resolved startup configuration"
+        );
+    }
+
+    /// Binds right away and serves while `build` embeds the catalog; the built
+    /// `Librarian` is swapped in and `readiness` flipped when it finishes. A
+    /// failed build exits the process, as a failed startup did before. On
+    /// Ctrl-C or `SIGTERM` the server stops accepting connections, finishes
+    /// in-flight requests, then drains background tasks (see `tasks`).
+    pub async fn launch<F>(self, build: F) -> Result<()>
+    where
+        F: Future<Output = Result<Librarian>> + Send + 'static,
+    {
+        let facilitator_url = env::var("FACILITATOR_URL")
+            .unwrap_or_else(|_| "https://facilitator.x402.rs".to_string());
+        let base_url = urls::base_url_from_env()?;
+        let bind_addr = crate::utils::bind_addr_from_env()?.to_string();
 
         let x402_base = X402Middleware::try_from(facilitator_url)
-            .expect("Failed to create X402 middleware")
-            .with_base_url(url::Url::parse(&base_url).expect("Invalid base URL"));
+            .map_err(|e| anyhow!("Failed to create X402 middleware: {}", e))?
+            .with_base_url(base_url.clone());
+        let base_url = base_url.to_string();
 
         tracing::info!("Using facilitator on {}", x402_base.facilitator_url());
+        // a facilitator outage may be transient, so warn loudly but keep starting
+        let status = self.facilitator.probe().await;
+        if !status.probe.reachable {
+            tracing::warn!(
+                facilitator = %status.url,
+                error = status.probe.error.as_deref().unwrap_or_default(),
+                payment_failure_mode = self.payment_failure_mode.name(),
+                "FACILITATOR UNREACHABLE: paid requests are refused (closed) or served unpaid (open) until it recovers"
+            );
+        }
+        self.facilitator.spawn_monitor();
+
+        let watch_catalog =
+            env::var("LIBRARIAN_WATCH_CATALOG").is_ok_and(|v| v == "1" || v == "true");
+        let refresh_interval = refresh::refresh_interval_from_env()?;
 
         let listener = tokio::net::TcpListener::bind(&bind_addr)
             .await
             .with_context(|| format!("Failed to bind to {}", bind_addr))?;
-        tracing::info!("Listening on {}", listener.local_addr().unwrap());
+        tracing::info!(
+            "Listening on {}; /discover waits for the index",
+            listener.local_addr().unwrap()
+        );
+
+        let handle = self.librarian.clone();
+        let verify_http = Arc::clone(&self.verify_http);
+        let verify_interval = verify::verify_interval_from_env();
+        let readiness = self.readiness.clone();
+        let reloader = Arc::clone(&self.reloader);
+        let route_prices = self.route_prices.clone();
+        let payment_failure_mode = self.payment_failure_mode;
+        let embedding_health = Arc::clone(&self.embedding_health);
+        tasks::TASKS.spawn("startup_index", |cancel| async move {
+            let built = tokio::select! {
+                built = build => built,
+                _ = cancel.cancelled() => {
+                    tracing::info!("Shutting down before the catalog index was built");
+                    return;
+                }
+            };
+            let librarian = match built {
+                Ok(librarian) => librarian,
+                Err(e) => {
+                    tracing::error!("Failed to build the catalog index: {:#}", e);
+                    std::process::exit(1);
+                }
+            };
+            let remote = librarian.remote.clone();
+            handle.swap(librarian);
+            if let Some(remote) = &remote {
+                remote.commit();
+            }
+            readiness.mark_ready();
+            embedding_health.spawn_monitor(handle.clone());
+            let librarian = handle.current();
+            tracing::info!(
+                catalog_entries = librarian.catalog.len(),
+                "Index ready, serving /discover"
+            );
+            Self::log_startup_config(
+                &librarian,
+                &route_prices,
+                &base_url,
+                &bind_addr,
+                watch_catalog,
+                payment_failure_mode,
+            );
+
+            // Test the agent via arc reference
+            if !crate::utils::skip_startup_prompt() {
+                let test_prompt = "Test launch: Confirm Librarian ready.";
+                match librarian.agent.prompt(test_prompt).await {
+                    Ok(resp) => tracing::info!("Agent launched successfully: {}", resp),
+                    Err(e) => tracing::warn!("Agent launch test failed: {}", e),
+                }
+            }
+
+            if watch_catalog
+                && let Err(e) = watcher::spawn_catalog_watcher(
+                    Arc::clone(&reloader),
+                    crate::utils::CATALOG_PATH,
+                )
+            {
+                tracing::error!("Failed to watch the catalog: {:#}", e);
+            }
+            if let Some(interval) = refresh_interval {
+                // a remote catalog is checked by conditional fetch, not by file hash
+                refresh::spawn_catalog_refresh(
+                    reloader,
+                    remote
+                        .is_none()
+                        .then_some(std::path::Path::new(crate::utils::CATALOG_PATH)),
+                    interval,
+                );
+            }
+            if let Some(interval) = verify_interval {
+                verify::spawn_verification(handle, verify_http, interval);
+            }
+        });
 
         // Serve the router that already has state attached
         axum::serve(listener, self.app)
+            .with_graceful_shutdown(tasks::shutdown_signal())
             .into_future()
             .instrument(info_span!("axum_server"))
             .await
             .context("Server failed to run")?;
+        // in-flight requests are done; now the background work
+        tasks::TASKS.shutdown(tasks::drain_window_from_env()).await;
+        tracing::info!("Shutdown complete");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use serde_json::json;
+    use std::time::Duration;
+    use tower::ServiceExt as _;
+
+    fn weather() -> (McpEntry, OneOrMany<Embedding>) {
+        let mut entry: McpEntry = serde_json::from_value(json!({
+            "name": "weather",
+            "endpoint": "https://weather.example/mcp",
+            "version": "1.0.0",
+            "capabilities": ["forecast"],
+            "desc": "Forecasts",
+        }))
+        .unwrap();
+        mirrors::settle(&mut entry).unwrap();
+        let embedding = Embedding {
+            document: "weather".to_string(),
+            vec: vec![1.0, 0.0],
+        };
+        (entry, OneOrMany::one(embedding))
+    }
+
+    /// `/discover` without payment but with the envelope layer, over
+    /// `weather()` and a breaker that is already open when `circuit_open`. No
+    /// test here reaches the model: it has no API key, so a call would answer
+    /// `500`.
+    fn discover_app(circuit_open: bool) -> Router {
+        discover_app_with(
+            circuit_open,
+            discover_cache::DiscoverCache::new(Duration::ZERO),
+        )
+    }
+
+    /// `discover_app` answering from `cache` before it would call the model.
+    fn discover_app_with(circuit_open: bool, cache: discover_cache::DiscoverCache) -> Router {
+        let librarian =
+            crate::utils::assemble_librarian(AgentParams::default(), vec![weather()], Vec::new())
+                .unwrap();
+        let breaker = Arc::new(breaker::CircuitBreaker::new(1, Duration::from_secs(600)));
+        if circuit_open {
+            breaker.record_failure();
+        }
+        let store: Arc<dyn store::Store> = Arc::new(store::MemoryStore::default());
+        let feedback = Arc::new(feedback::FeedbackStore::open(Arc::clone(&store)).unwrap());
+        let handle = LibrarianHandle::new(librarian);
+        let envelope = middleware::from_fn_with_state(handle.clone(), envelope::envelope_layer);
+        Router::new()
+            .route("/discover", post(discover_handler).layer(envelope))
+            .layer(Extension(breaker))
+            .layer(Extension(feedback))
+            .layer(Extension(Arc::new(replay::ReplayStore::new(
+                Duration::from_secs(60),
+            ))))
+            .layer(Extension(store))
+            .layer(Extension(Arc::new(cache)))
+            .with_state(handle)
+    }
+
+    #[test]
+    fn discover_prompt_states_now_and_how_long_ago_candidates_were_checked() {
+        let (entry, embedding) = weather();
+        let librarian = crate::utils::assemble_librarian(
+            AgentParams::default(),
+            vec![(entry.clone(), embedding)],
+            Vec::new(),
+        )
+        .unwrap();
+        let checked_at = chrono::Utc::now() - chrono::Duration::hours(3);
+        librarian.verification.insert(
+            &entry.endpoint,
+            verify::VerificationResult {
+                ok: true,
+                tools: vec!["forecast".to_string()],
+                resources: Vec::new(),
+                prompts: Vec::new(),
+                checked_at,
+                error: None,
+                auth_required: false,
+            },
+        );
+        let req: DiscoverRequest = serde_json::from_value(json!({ "query": "weather" })).unwrap();
+
+        let prompt = discover_prompt(&librarian, &req, "weather", &[(1.0, entry)], false);
+        // unit tests leave `LIBRARIAN_PROMPT_TIME` at its rfc3339 default
+        let stated = prompt
+            .split("Current time (UTC): ")
+            .nth(1)
+            .and_then(|rest| rest.split(". ").next())
+            .unwrap();
+        assert!(clock::PromptTime::Rfc3339.is_well_formed(stated));
+        assert!(prompt.contains(&format!(
+            "\"last_checked\": \"{}\"",
+            clock::timestamp(checked_at)
+        )));
+        assert!(prompt.contains("\"checked_hours_ago\": 3"));
+    }
+
+    #[test]
+    fn oversized_prompts_drop_the_lowest_ranked_candidates() {
+        let librarian =
+            crate::utils::assemble_librarian(AgentParams::default(), vec![weather()], Vec::new())
+                .unwrap();
+        let req: DiscoverRequest = serde_json::from_value(json!({ "query": "weather" })).unwrap();
+        let large = |rank: usize| {
+            let mut entry = weather().0;
+            entry.name = format!("weather-{}", rank);
+            entry.endpoint = format!("https://weather-{}.example/mcp", rank);
+            entry.desc = "Hourly and daily forecasts with radar imagery. ".repeat(400);
+            (1.0 - rank as f64 / 10.0, entry)
+        };
+        let ranked: Vec<(f64, McpEntry)> = (0..5).map(large).collect();
+
+        let preamble = estimate::count_tokens(crate::utils::LIBRARIAN_PREAMBLE);
+        let two = discover_prompt(&librarian, &req, "weather", &ranked[..2], false);
+        // slack for the stated time; one entry is thousands of tokens
+        let budget = preamble + estimate::count_tokens(&two) + 50;
+
+        let mut candidates = ranked.clone();
+        let (prompt, dropped) =
+            fit_prompt_within(&librarian, &req, "weather", &mut candidates, false, budget);
+        assert_eq!(dropped, 3);
+        assert!(preamble + estimate::count_tokens(&prompt) <= budget);
+        let kept: Vec<&str> = candidates
+            .iter()
+            .map(|(_, entry)| entry.name.as_str())
+            .collect();
+        assert_eq!(kept, ["weather-0", "weather-1"]);
+
+        let mut candidates = ranked.clone();
+        assert_eq!(
+            fit_prompt_within(
+                &librarian,
+                &req,
+                "weather",
+                &mut candidates,
+                false,
+                usize::MAX
+            )
+            .1,
+            0
+        );
+        assert_eq!(candidates.len(), 5);
+
+        let mut candidates = ranked;
+        assert_eq!(
+            fit_prompt_within(&librarian, &req, "weather", &mut candidates, false, 0).1,
+            5
+        );
+        assert!(candidates.is_empty());
+    }
+
+    async fn discover(app: Router, uri: &str, body: Value) -> (StatusCode, HeaderMap, String) {
+        send(app, Request::post(uri), body).await
+    }
+
+    async fn send(
+        app: Router,
+        request: axum::http::request::Builder,
+        body: Value,
+    ) -> (StatusCode, HeaderMap, String) {
+        let request = request
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.unwrap();
+        (
+            parts.status,
+            parts.headers,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn no_candidates_answers_empty_without_the_model() {
+        // no catalog entry has this capability, so retrieval returns nothing
+        let request =
+            json!({ "query": "translate text", "filters": { "capability": "translate" } });
+        let (status, headers, body) = discover(discover_app(false), "/discover", request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["recommendations"], json!([]));
+        assert_eq!(body["query"], "translate text");
+        assert!(body["query_id"].is_string());
+        assert!(headers.contains_key(COMPLETION_MODEL_HEADER));
+        assert!(headers.contains_key(feedback::QUERY_ID_HEADER));
+    }
+
+    #[tokio::test]
+    async fn empty_answer_honours_the_output_format() {
+        let request =
+            json!({ "query": "translate text", "filters": { "capability": "translate" } });
+        let (status, headers, body) =
+            discover(discover_app(false), "/discover?format=ndjson", request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["content-type"], response::NDJSON_CONTENT_TYPE);
+        let lines: Vec<Value> = body
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["summary"]["count"], 0);
+    }
+
+    #[tokio::test]
+    async fn open_circuit_answers_from_the_catalog_in_the_requested_format() {
+        let request = json!({ "query": "weather", "restrict_to": ["weather"] });
+        let (status, headers, body) =
+            discover(discover_app(true), "/discover", request.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[health::DEGRADED_HEADER], "agent-circuit-open");
+        assert!(headers.contains_key(COMPLETION_MODEL_HEADER));
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["recommendations"][0]["name"], "weather");
+        assert_eq!(
+            body["recommendations"][0]["verification_status"],
+            "catalog_only"
+        );
+
+        let (status, headers, body) =
+            discover(discover_app(true), "/discover?format=ndjson", request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["content-type"], response::NDJSON_CONTENT_TYPE);
+        assert_eq!(headers[health::DEGRADED_HEADER], "agent-circuit-open");
+        let first: Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+        assert_eq!(first["name"], "weather");
+    }
+
+    async fn enveloped(app: Router, body: Value) -> (StatusCode, Value) {
+        let request = Request::post("/discover").header("accept", envelope::ENVELOPE_MEDIA_TYPE);
+        let (status, _, body) = send(app, request, body).await;
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn envelope_wraps_a_no_match_answer() {
+        let request =
+            json!({ "query": "translate text", "filters": { "capability": "translate" } });
+        let (status, body) = enveloped(discover_app(false), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["recommendations"], json!([]));
+        assert_eq!(body["meta"]["request_id"], body["data"]["query_id"]);
+        assert_eq!(body["meta"]["degraded"], false);
+        assert_eq!(body["meta"]["degraded_reason"], Value::Null);
+        assert_eq!(body["meta"]["cache"], "miss");
+        assert!(body["meta"]["catalog_hash"].is_string());
+    }
+
+    #[tokio::test]
+    async fn envelope_marks_a_degraded_answer() {
+        let request = json!({ "query": "weather", "restrict_to": ["weather"] });
+        let (status, body) = enveloped(discover_app(true), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["recommendations"][0]["name"], "weather");
+        assert_eq!(body["meta"]["degraded"], true);
+        assert_eq!(body["meta"]["degraded_reason"], "agent-circuit-open");
+        assert_eq!(body["meta"]["request_id"], body["data"]["query_id"]);
+    }
+
+    #[tokio::test]
+    async fn envelope_wraps_a_model_answer() {
+        let request = json!({ "query": "weather" });
+        let answer = json!({
+            "recommendations": [{ "name": "weather", "endpoint": "https://weather.example/mcp" }],
+        });
+        let catalog_hash =
+            crate::utils::assemble_librarian(AgentParams::default(), vec![weather()], Vec::new())
+                .unwrap()
+                .catalog_hash;
+        let cache = discover_cache::DiscoverCache::new(Duration::from_secs(60));
+        let key = discover_cache::key(
+            &serde_json::from_value(request.clone()).unwrap(),
+            &DiscoverParams::default(),
+        );
+        cache.insert(key, &catalog_hash, &answer);
+
+        let (status, body) = enveloped(discover_app_with(false, cache), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["recommendations"], answer["recommendations"]);
+        assert_eq!(body["data"]["query"], "weather");
+        assert_eq!(body["meta"]["cache"], "hit");
+        assert_eq!(body["meta"]["degraded"], false);
+        assert_eq!(body["meta"]["catalog_hash"], catalog_hash.as_str());
+        assert_eq!(body["meta"]["request_id"], body["data"]["query_id"]);
+    }
+
+    #[tokio::test]
+    async fn envelope_is_left_off_unless_asked_for() {
+        let request = json!({ "query": "weather", "restrict_to": ["weather"] });
+        let (_, _, body) = discover(discover_app(true), "/discover", request).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert!(body.get("meta").is_none());
+        assert_eq!(body["recommendations"][0]["name"], "weather");
+    }
+}
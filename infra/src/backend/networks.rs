@@ -0,0 +1,186 @@
+// src/backend/networks.rs
+//! Human-readable names and chain ids for payment networks, keyed by the
+//! identifier x402 puts on the wire (`base-sepolia`, `solana`, ...), so a client
+//! can show "Base Sepolia (chain 84532)". `/payment/info` and the `402`
+//! challenge body both read from here. Built-in names cover the networks x402
+//! supports; `LIBRARIAN_NETWORK_NAMES_PATH` points at a JSON object of
+//! `network -> {name, chain_id, testnet}` that adds to or overrides them. The
+//! names are selected once at startup, and a file that doesn't parse fails it.
+use super::settings::select_once;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkName {
+    pub name: String,
+    /// EVM chain id; Solana clusters have none.
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    #[serde(default)]
+    pub testnet: bool,
+}
+
+impl NetworkName {
+    /// "Base Sepolia (chain 84532)", or just the name without a chain id.
+    pub fn label(&self) -> String {
+        match self.chain_id {
+            Some(id) => format!("{} (chain {})", self.name, id),
+            None => self.name.clone(),
+        }
+    }
+}
+
+const BUILTIN: &[(&str, &str, Option<u64>, bool)] = &[
+    ("base", "Base", Some(8453), false),
+    ("base-sepolia", "Base Sepolia", Some(84532), true),
+    ("avalanche", "Avalanche C-Chain", Some(43114), false),
+    ("avalanche-fuji", "Avalanche Fuji", Some(43113), true),
+    ("polygon", "Polygon", Some(137), false),
+    ("polygon-amoy", "Polygon Amoy", Some(80002), true),
+    ("sei", "Sei", Some(1329), false),
+    ("sei-testnet", "Sei Testnet", Some(1328), true),
+    ("xdc", "XDC Network", Some(50), false),
+    ("solana", "Solana", None, false),
+    ("solana-devnet", "Solana Devnet", None, true),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkNames(BTreeMap<String, NetworkName>);
+
+impl Default for NetworkNames {
+    fn default() -> Self {
+        NetworkNames(
+            BUILTIN
+                .iter()
+                .map(|&(network, name, chain_id, testnet)| {
+                    (
+                        network.to_string(),
+                        NetworkName {
+                            name: name.to_string(),
+                            chain_id,
+                            testnet,
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl NetworkNames {
+    /// The built-in names with the file's entries laid over them.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
+        let overrides: BTreeMap<String, NetworkName> = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse {:?} as network names", path.as_ref()))?;
+        let mut names = NetworkNames::default();
+        names.0.extend(overrides);
+        Ok(names)
+    }
+
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_NETWORK_NAMES_PATH") {
+            Ok(path) => NetworkNames::from_file(path),
+            Err(_) => Ok(NetworkNames::default()),
+        }
+    }
+
+    pub fn get(&self, network: &str) -> Option<&NetworkName> {
+        self.0.get(network)
+    }
+
+    /// `{name, chain_id, testnet, label}` for `network`; an unknown network is
+    /// named after its identifier.
+    pub fn describe(&self, network: &str) -> Value {
+        match self.get(network) {
+            Some(known) => json!({
+                "name": known.name,
+                "chain_id": known.chain_id,
+                "testnet": known.testnet,
+                "label": known.label(),
+            }),
+            None => json!({
+                "name": network,
+                "chain_id": null,
+                "testnet": null,
+                "label": network,
+            }),
+        }
+    }
+}
+
+static ACTIVE: OnceLock<NetworkNames> = OnceLock::new();
+
+/// Fixes the network names; see `settings::select_once`.
+pub fn select(names: NetworkNames) -> Result<()> {
+    select_once(&ACTIVE, names, "Network names")
+}
+
+/// The selected names, or the built-in ones when nothing was selected.
+pub fn active() -> &'static NetworkNames {
+    ACTIVE.get_or_init(NetworkNames::default)
+}
+
+/// The wire identifier of `network`, as x402 serializes it.
+pub fn wire_name<T: Serialize>(network: &T) -> String {
+    match serde_json::to_value(network) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(json: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("librarian-networks-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn describes_known_and_unknown_networks() {
+        let names = NetworkNames::default();
+        assert_eq!(
+            names.describe("base-sepolia"),
+            json!({"name": "Base Sepolia", "chain_id": 84532, "testnet": true, "label": "Base Sepolia (chain 84532)"})
+        );
+        assert_eq!(names.describe("solana")["label"], "Solana");
+        assert_eq!(
+            names.describe("made-up"),
+            json!({"name": "made-up", "chain_id": null, "testnet": null, "label": "made-up"})
+        );
+    }
+
+    #[test]
+    fn file_entries_add_to_and_override_the_builtins() {
+        let path = overrides(
+            r#"{"base": {"name": "Base Mainnet", "chain_id": 8453}, "local": {"name": "Anvil", "chain_id": 31337, "testnet": true}}"#,
+        );
+        let names = NetworkNames::from_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            names.get("base").unwrap().label(),
+            "Base Mainnet (chain 8453)"
+        );
+        assert!(names.get("local").unwrap().testnet);
+        assert_eq!(names.get("polygon"), NetworkNames::default().get("polygon"));
+    }
+
+    #[test]
+    fn unparseable_file_is_an_error() {
+        let path = overrides(r#"{"base": "Base"}"#);
+        assert!(NetworkNames::from_file(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+        assert!(NetworkNames::from_file("/nonexistent/networks.json").is_err());
+    }
+}
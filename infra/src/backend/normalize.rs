@@ -0,0 +1,379 @@
+// src/backend/normalize.rs
+//! Load-time normalization of `capabilities` and `tags`, so "Search", " search"
+//! and (with stemming) "searching" filter and embed as one term. Each string
+//! goes through, in order:
+//!
+//! 1. trim, lowercase, and collapse whitespace runs to one space;
+//! 2. the alias map from `LIBRARIAN_NORMALIZE_ALIASES_PATH` (a JSON object of
+//!    `alias -> canonical`, keys and values normalized by step 1); an aliased
+//!    string is final;
+//! 3. under `LIBRARIAN_NORMALIZE=stem`, a light suffix stemmer per word, words
+//!    being split on spaces, `_` and `-` (see `stem_word`);
+//! 4. empties dropped and duplicates removed, keeping the first occurrence
+//!    (tags are also sorted, as before).
+//!
+//! `LIBRARIAN_NORMALIZE` is `basic` (steps 1, 2 and 4; the default), `stem`
+//! or `off` (capabilities as written; tags keep their case-folding). The rules
+//! are selected once at startup, and a bad value fails it. Capability filters
+//! in requests go through the same rules. When normalization changes an
+//! entry's capabilities, the originals are kept in memory in
+//! `original_capabilities` for display; the catalog file keeps the originals
+//! as its `capabilities`.
+use super::McpEntry;
+use super::filters::normalize_tags;
+use super::settings::select_once;
+use anyhow::{Context as _, Result, bail};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizeMode {
+    Off,
+    #[default]
+    Basic,
+    Stem,
+}
+
+impl NormalizeMode {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "off" => Some(NormalizeMode::Off),
+            "basic" => Some(NormalizeMode::Basic),
+            "stem" => Some(NormalizeMode::Stem),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NormalizeMode::Off => "off",
+            NormalizeMode::Basic => "basic",
+            NormalizeMode::Stem => "stem",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct NormalizeRules {
+    #[serde(default)]
+    pub mode: NormalizeMode,
+    /// Already normalized by step 1, on both sides.
+    #[serde(default, deserialize_with = "deserialize_aliases")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+fn fold(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn fold_aliases(raw: HashMap<String, String>) -> BTreeMap<String, String> {
+    raw.into_iter()
+        .map(|(alias, canonical)| (fold(&alias), fold(&canonical)))
+        .filter(|(alias, canonical)| !alias.is_empty() && !canonical.is_empty())
+        .collect()
+}
+
+fn deserialize_aliases<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    HashMap::<String, String>::deserialize(deserializer).map(fold_aliases)
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+/// A deliberately small English stemmer: `-ies` -> `-y`, `-ing` dropped (and a
+/// doubled final consonant other than `l`, `s` or `z` undoubled), `-es` dropped
+/// after `ch`, `sh`, `s`, `x` or `z`, and a plural `-s` dropped unless the word
+/// ends in `ss`, `us` or `is`. Words of four letters or fewer are left alone,
+/// as is anything that isn't ASCII lowercase. Applied until nothing changes,
+/// so stemming a stem is a no-op.
+fn stem_word(word: &str) -> String {
+    let mut stemmed = word.to_string();
+    loop {
+        let next = stem_once(&stemmed);
+        if next == stemmed {
+            return stemmed;
+        }
+        stemmed = next;
+    }
+}
+
+fn stem_once(word: &str) -> String {
+    if word.len() <= 4 || !word.chars().all(|c| c.is_ascii_lowercase()) {
+        return word.to_string();
+    }
+    if let Some(base) = word.strip_suffix("ies") {
+        return format!("{}y", base);
+    }
+    if let Some(base) = word.strip_suffix("ing")
+        && base.len() >= 3
+        && base.chars().any(is_vowel)
+    {
+        let mut chars = base.chars().rev();
+        let (last, prev) = (chars.next(), chars.next());
+        return match (last, prev) {
+            (Some(a), Some(b)) if a == b && !is_vowel(a) && !matches!(a, 'l' | 's' | 'z') => {
+                base[..base.len() - 1].to_string()
+            }
+            _ => base.to_string(),
+        };
+    }
+    if let Some(base) = word.strip_suffix("es")
+        && ["ch", "sh", "s", "x", "z"]
+            .iter()
+            .any(|end| base.ends_with(end))
+    {
+        return base.to_string();
+    }
+    if word.ends_with('s') && !["ss", "us", "is"].iter().any(|end| word.ends_with(end)) {
+        return word[..word.len() - 1].to_string();
+    }
+    word.to_string()
+}
+
+/// Stems every word, keeping the separators between them.
+fn stem(text: &str) -> String {
+    let mut stemmed = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars() {
+        if matches!(c, ' ' | '_' | '-') {
+            stemmed.push_str(&stem_word(&word));
+            stemmed.push(c);
+            word.clear();
+        } else {
+            word.push(c);
+        }
+    }
+    stemmed.push_str(&stem_word(&word));
+    stemmed
+}
+
+impl NormalizeRules {
+    pub fn from_file<P: AsRef<Path>>(mode: NormalizeMode, path: P) -> Result<Self> {
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
+        let raw: HashMap<String, String> = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse {:?} as an alias map", path.as_ref()))?;
+        Ok(NormalizeRules {
+            mode,
+            aliases: fold_aliases(raw),
+        })
+    }
+
+    /// `LIBRARIAN_NORMALIZE` and `LIBRARIAN_NORMALIZE_ALIASES_PATH`.
+    pub fn from_env() -> Result<Self> {
+        let mode = match env::var("LIBRARIAN_NORMALIZE") {
+            Err(_) => NormalizeMode::default(),
+            Ok(raw) => match NormalizeMode::parse(raw.trim()) {
+                Some(mode) => mode,
+                None => bail!(
+                    "Unknown LIBRARIAN_NORMALIZE {:?} (expected off, basic or stem)",
+                    raw
+                ),
+            },
+        };
+        match env::var("LIBRARIAN_NORMALIZE_ALIASES_PATH") {
+            Ok(path) => NormalizeRules::from_file(mode, path),
+            Err(_) => Ok(NormalizeRules {
+                mode,
+                aliases: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Steps 1 to 3 for one string; `off` returns it unchanged.
+    pub fn term(&self, text: &str) -> String {
+        if self.mode == NormalizeMode::Off {
+            return text.to_string();
+        }
+        let folded = fold(text);
+        if let Some(canonical) = self.aliases.get(&folded) {
+            return canonical.clone();
+        }
+        match self.mode {
+            NormalizeMode::Stem => stem(&folded),
+            _ => folded,
+        }
+    }
+
+    pub fn capabilities(&self, capabilities: &[String]) -> Vec<String> {
+        if self.mode == NormalizeMode::Off {
+            return capabilities.to_vec();
+        }
+        let mut normalized: Vec<String> = Vec::with_capacity(capabilities.len());
+        for capability in capabilities {
+            let term = self.term(capability);
+            if !term.trim().is_empty() && !normalized.contains(&term) {
+                normalized.push(term);
+            }
+        }
+        normalized
+    }
+
+    pub fn tags(&self, tags: &[String]) -> Vec<String> {
+        let folded = normalize_tags(tags);
+        if self.mode == NormalizeMode::Off {
+            return folded;
+        }
+        normalize_tags(&folded.iter().map(|tag| self.term(tag)).collect::<Vec<_>>())
+    }
+
+    /// Normalizes `entry` in place, keeping the original capabilities when
+    /// they change. Idempotent, so re-loading a persisted catalog is a no-op.
+    pub fn apply(&self, entry: &mut McpEntry) {
+        let capabilities = self.capabilities(&entry.capabilities);
+        if capabilities != entry.capabilities {
+            let original = std::mem::replace(&mut entry.capabilities, capabilities);
+            entry.original_capabilities.get_or_insert(original);
+        }
+        entry.tags = self.tags(&entry.tags);
+    }
+}
+
+static ACTIVE: OnceLock<NormalizeRules> = OnceLock::new();
+
+/// Fixes the normalization rules; see `settings::select_once`.
+pub fn select(rules: NormalizeRules) -> Result<()> {
+    select_once(&ACTIVE, rules, "Normalization rules")
+}
+
+/// The selected rules, or `basic` with no aliases when nothing was selected.
+pub fn active() -> &'static NormalizeRules {
+    ACTIVE.get_or_init(NormalizeRules::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(value: serde_json::Value) -> NormalizeRules {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn basic_folds_case_and_spacing_and_drops_empties() {
+        let rules = rules(json!({ "mode": "basic" }));
+        let capabilities = strings(&[
+            "Search",
+            " search ",
+            "Get  Forecast",
+            "",
+            "   ",
+            "SEARCH",
+            "get_forecast",
+        ]);
+        assert_eq!(
+            rules.capabilities(&capabilities),
+            ["search", "get forecast", "get_forecast"]
+        );
+        assert_eq!(
+            rules.tags(&strings(&["Weather", " weather", "DOCS", ""])),
+            ["docs", "weather"]
+        );
+    }
+
+    #[test]
+    fn stem_merges_inflections() {
+        let rules = rules(json!({ "mode": "stem" }));
+        let capabilities = strings(&[
+            "Searching",
+            "search",
+            "Searches",
+            "list_issues",
+            "Running-Jobs",
+            "Categories",
+            "process",
+            "status",
+            "get_alerts",
+        ]);
+        assert_eq!(
+            rules.capabilities(&capabilities),
+            [
+                "search",
+                "list_issue",
+                "run-jobs",
+                "category",
+                "process",
+                "status",
+                "get_alert"
+            ]
+        );
+        assert_eq!(
+            rules.tags(&strings(&["Searching", "Search", "Alerts", "alert"])),
+            ["alert", "search"]
+        );
+    }
+
+    #[test]
+    fn aliases_are_final_and_folded_on_both_sides() {
+        let rules = rules(json!({
+            "mode": "stem",
+            "aliases": { "Full Text Search": "search", "lookup": "search", "  Issue  Tracker ": "issues" },
+        }));
+        let capabilities = strings(&[
+            "full  text search",
+            "Lookup",
+            "searching",
+            "Issue Tracker",
+            "issue",
+        ]);
+        // "issues" comes from an alias, so it isn't stemmed into "issue"
+        assert_eq!(
+            rules.capabilities(&capabilities),
+            ["search", "issues", "issue"]
+        );
+        assert_eq!(rules.tags(&strings(&["LOOKUP", "Search"])), ["search"]);
+    }
+
+    #[test]
+    fn off_keeps_capabilities_as_written() {
+        let rules = rules(json!({ "mode": "off", "aliases": { "lookup": "search" } }));
+        let capabilities = strings(&["Search", "search", " Lookup ", ""]);
+        assert_eq!(rules.capabilities(&capabilities), capabilities);
+        assert_eq!(
+            rules.tags(&strings(&["Search", " search", "Lookup"])),
+            ["lookup", "search"]
+        );
+    }
+
+    #[test]
+    fn apply_keeps_the_originals_once() {
+        let rules = rules(json!({ "mode": "stem" }));
+        let mut entry: McpEntry = serde_json::from_value(json!({
+            "name": "issues",
+            "endpoint": "https://issues.example/mcp",
+            "version": "1.0.0",
+            "capabilities": ["List Issues", "search"],
+            "desc": "",
+        }))
+        .unwrap();
+        rules.apply(&mut entry);
+        assert_eq!(entry.capabilities, ["list issue", "search"]);
+        assert_eq!(
+            entry.original_capabilities,
+            Some(strings(&["List Issues", "search"]))
+        );
+
+        rules.apply(&mut entry);
+        assert_eq!(entry.capabilities, ["list issue", "search"]);
+        assert_eq!(
+            entry.original_capabilities,
+            Some(strings(&["List Issues", "search"]))
+        );
+    }
+}
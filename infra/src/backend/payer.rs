@@ -0,0 +1,109 @@
+// src/backend/payer.rs
+use axum::http::HeaderMap;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PAYMENT_HEADER: &str = "x-payment";
+
+/// Extracts the paying address from the base64 JSON `X-PAYMENT` header.
+///
+/// Only EVM `exact` payloads carry the payer explicitly
+/// (`payload.authorization.from`); Solana payloads embed it inside a signed
+/// transaction, so those return `None`. The address is only claimed here;
+/// use `settled_payer` or `SETTLED` where it must be proven.
+pub fn payer_from_headers(headers: &HeaderMap) -> Option<String> {
+    let raw = headers.get(PAYMENT_HEADER)?.to_str().ok()?;
+    let decoded = STANDARD.decode(raw.trim()).ok()?;
+    let payment: Value = serde_json::from_slice(&decoded).ok()?;
+    payment
+        .pointer("/payload/authorization/from")
+        .and_then(Value::as_str)
+        .map(|from| from.to_lowercase())
+}
+
+/// Header the x402 layer adds to a settled response: base64 JSON with the
+/// facilitator's `success`, `payer`, `transaction` and `network`.
+pub const PAYMENT_RESPONSE_HEADER: &str = "x-payment-response";
+/// Settled payments remembered for `verified_payer`.
+const MAX_SETTLED_PAYMENTS: usize = 10_000;
+const SETTLED_PAYMENT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// SHA-256 of the `X-PAYMENT` header exactly as sent.
+pub fn payment_digest(headers: &HeaderMap) -> Option<String> {
+    let raw = headers.get(PAYMENT_HEADER)?;
+    Some(hex::encode(Sha256::digest(raw.as_bytes())))
+}
+
+/// The payer the facilitator settled, read from `X-PAYMENT-RESPONSE`. Unlike
+/// `payer_from_headers` this is only present once x402 has verified and
+/// settled the payment.
+pub fn settled_payer(headers: &HeaderMap) -> Option<String> {
+    let raw = headers.get(PAYMENT_RESPONSE_HEADER)?.to_str().ok()?;
+    let decoded = STANDARD.decode(raw.trim()).ok()?;
+    let settlement: Value = serde_json::from_slice(&decoded).ok()?;
+    if settlement.get("success").and_then(Value::as_bool) != Some(true) {
+        return None;
+    }
+    settlement
+        .get("payer")
+        .and_then(Value::as_str)
+        .map(|payer| payer.to_lowercase())
+}
+
+/// `X-PAYMENT` digests that settled, mapped to the payer the facilitator
+/// reported. Presenting one of those headers again proves the caller holds
+/// that wallet's signed payment, which a forged header doesn't.
+pub struct SettledPayments {
+    inner: Mutex<Settled>,
+}
+
+#[derive(Default)]
+struct Settled {
+    payers: HashMap<String, (String, Instant)>,
+    /// Digests oldest first, for eviction.
+    order: VecDeque<String>,
+}
+
+pub static SETTLED: LazyLock<SettledPayments> = LazyLock::new(|| SettledPayments {
+    inner: Mutex::new(Settled::default()),
+});
+
+impl SettledPayments {
+    /// Remembers the payment on a settled response. Called outside the x402
+    /// layer with the request's `X-PAYMENT` digest.
+    pub fn record(&self, digest: String, payer: String) {
+        let mut settled = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        while settled.order.len() >= MAX_SETTLED_PAYMENTS
+            || settled
+                .order
+                .front()
+                .and_then(|d| settled.payers.get(d))
+                .is_some_and(|(_, at)| at.elapsed() > SETTLED_PAYMENT_TTL)
+        {
+            let Some(oldest) = settled.order.pop_front() else {
+                break;
+            };
+            settled.payers.remove(&oldest);
+        }
+        if settled
+            .payers
+            .insert(digest.clone(), (payer, Instant::now()))
+            .is_none()
+        {
+            settled.order.push_back(digest);
+        }
+    }
+
+    /// The payer whose settled `X-PAYMENT` these headers carry, if any.
+    pub fn verified_payer(&self, headers: &HeaderMap) -> Option<String> {
+        let digest = payment_digest(headers)?;
+        let settled = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        let (payer, at) = settled.payers.get(&digest)?;
+        (at.elapsed() <= SETTLED_PAYMENT_TTL).then(|| payer.clone())
+    }
+}
@@ -0,0 +1,83 @@
+// src/backend/payment.rs
+//! Payment parameters for clients configuring a wallet, read from the same
+//! configuration the x402 layers are built from.
+use super::networks::{self, wire_name};
+use super::payto::PayToPool;
+use super::pricing::{DiscoverPricing, PAYMENT_NETWORKS, RouteChallenges, RoutePrices};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Json as AxumJson},
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use x402_rs::network::{Network, USDCDeployment};
+
+#[derive(Clone)]
+pub struct PaymentInfo {
+    pub pool: Arc<PayToPool>,
+    pub prices: RoutePrices,
+    pub discover: DiscoverPricing,
+    pub challenges: RouteChallenges,
+    pub facilitator_url: String,
+}
+
+impl PaymentInfo {
+    fn pay_to(&self, network: Network) -> Vec<String> {
+        match network {
+            Network::Solana => self
+                .pool
+                .solana
+                .iter()
+                .map(|(a, _)| a.to_string())
+                .collect(),
+            _ => self.pool.evm.iter().map(|(a, _)| a.to_string()).collect(),
+        }
+    }
+}
+
+/// `GET /payment/info`: accepted networks (with display names and chain ids,
+/// see `networks`) and tokens, pay-to addresses and per-route prices (in USDC)
+/// and challenge descriptions.
+pub async fn payment_info_handler(State(info): State<PaymentInfo>) -> impl IntoResponse {
+    let networks: Vec<Value> = PAYMENT_NETWORKS
+        .iter()
+        .map(|&network| {
+            let usdc = USDCDeployment::by_network(network);
+            json!({
+                "network": network,
+                "network_info": networks::active().describe(&wire_name(&network)),
+                "tokens": [{
+                    "symbol": "USDC",
+                    "asset": usdc.asset.address,
+                    "decimals": usdc.decimals,
+                }],
+                "pay_to": info.pay_to(network),
+            })
+        })
+        .collect();
+    let prices: serde_json::Map<String, Value> = info
+        .prices
+        .iter()
+        .map(|(route, price)| (route.to_string(), json!(price)))
+        .collect();
+    let challenges: serde_json::Map<String, Value> = info
+        .challenges
+        .iter()
+        .map(|(route, challenge)| {
+            (
+                route.to_string(),
+                json!({ "description": challenge.description, "mime_type": challenge.mime_type }),
+            )
+        })
+        .collect();
+
+    AxumJson(json!({
+        "scheme": "exact",
+        "facilitator": info.facilitator_url,
+        "networks": networks,
+        "prices": prices,
+        // by `max_results`; all equal unless priced per result
+        "discover_tiers": info.discover.tiers(),
+        "challenges": challenges,
+    }))
+}
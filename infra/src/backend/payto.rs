@@ -0,0 +1,262 @@
+// src/backend/payto.rs
+//! Pools of `pay_to` addresses for spreading settlement across wallets.
+//!
+//! x402 price tags are fixed when the middleware layer is built, so the pool is
+//! realized as one pre-built layer per pool *variant*. Every variant advertises
+//! all pool addresses in its `accepts` list, only reordered so the selected pair
+//! comes first. Clients pay the first acceptable option, and because every
+//! variant accepts every pool address, a paid retry verifies and settles no
+//! matter which variant handles it. Settlement goes to whichever address the
+//! client signed for; the selection log line plus the facilitator receipt are
+//! what reconciliation should join on.
+use super::pricing::RouteChallenge;
+use anyhow::{Context as _, Result, anyhow, bail};
+use axum::{Router, extract::Request, response::Response, routing::MethodRouter};
+use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tower::ServiceExt as _;
+use x402_axum::{IntoPriceTag, X402Middleware};
+use x402_rs::facilitator_client::FacilitatorClient;
+use x402_rs::network::{Network, USDCDeployment};
+use x402_rs::types::{EvmAddress, SolanaAddress};
+
+pub const DEFAULT_EVM_PAY_TO: &str = "0xf2757Fe8Ba90ad98dAed8e6254bA9A677069826a";
+/// Demo placeholder; `validate_solana_pay_to` rejects it, so `PAY_TO_SOLANA`
+/// must be set before the server will start.
+pub const DEFAULT_SOLANA_PAY_TO: &str = "11111111111111111111111111111112";
+
+/// Payout addresses that are never legitimate: funds sent there are lost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayToError {
+    ZeroEvmAddress,
+    SolanaPlaceholder(String),
+}
+
+impl std::fmt::Display for PayToError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayToError::ZeroEvmAddress => {
+                write!(
+                    f,
+                    "EVM pay_to is the zero address; set PAY_TO_EVM to a wallet you control"
+                )
+            }
+            PayToError::SolanaPlaceholder(address) => write!(
+                f,
+                "Solana pay_to {} is a system-program placeholder; set PAY_TO_SOLANA to a wallet you control",
+                address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PayToError {}
+
+pub fn validate_evm_pay_to(address: &alloy::primitives::Address) -> Result<(), PayToError> {
+    if address.is_zero() {
+        return Err(PayToError::ZeroEvmAddress);
+    }
+    Ok(())
+}
+
+/// Rejects the all-ones system program id and its `...112` sibling, which is
+/// what the demo configuration shipped with.
+pub fn validate_solana_pay_to(address: &str) -> Result<(), PayToError> {
+    let head = address.strip_suffix('2').unwrap_or(address);
+    if !head.is_empty() && head.chars().all(|c| c == '1') {
+        return Err(PayToError::SolanaPlaceholder(address.to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    RoundRobin,
+    Weighted,
+}
+
+/// Configured payout addresses per network, from `PAY_TO_EVM` / `PAY_TO_SOLANA`
+/// (comma-separated `address[:weight]`) and `PAY_TO_STRATEGY`
+/// (`round_robin` or `weighted`).
+pub struct PayToPool {
+    pub evm: Vec<(EvmAddress, u32)>,
+    pub solana: Vec<(SolanaAddress, u32)>,
+    pub strategy: SelectionStrategy,
+    schedule: Vec<usize>,
+    cursor: AtomicUsize,
+}
+
+fn parse_weighted(raw: &str) -> Result<Vec<(String, u32)>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|item| match item.split_once(':') {
+            Some((address, weight)) => {
+                let weight: u32 = weight
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid weight in {:?}", item))?;
+                if weight == 0 {
+                    bail!("Weight must be positive in {:?}", item);
+                }
+                Ok((address.trim().to_string(), weight))
+            }
+            None => Ok((item.to_string(), 1)),
+        })
+        .collect()
+}
+
+impl PayToPool {
+    pub fn from_env() -> Result<Self> {
+        let evm = parse_weighted(
+            &env::var("PAY_TO_EVM").unwrap_or_else(|_| DEFAULT_EVM_PAY_TO.to_string()),
+        )?
+        .into_iter()
+        .map(|(address, weight)| {
+            let parsed: alloy::primitives::Address = address
+                .parse()
+                .with_context(|| format!("Invalid EVM pay_to address {:?}", address))?;
+            validate_evm_pay_to(&parsed)?;
+            Ok((EvmAddress::from(parsed), weight))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+        let solana = parse_weighted(
+            &env::var("PAY_TO_SOLANA").unwrap_or_else(|_| DEFAULT_SOLANA_PAY_TO.to_string()),
+        )?
+        .into_iter()
+        .map(|(address, weight)| {
+            validate_solana_pay_to(&address)?;
+            let parsed: SolanaAddress = address
+                .parse()
+                .map_err(|e| anyhow!("Invalid Solana pay_to address {:?}: {}", address, e))?;
+            Ok((parsed, weight))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+        if evm.is_empty() || solana.is_empty() {
+            bail!("PAY_TO_EVM and PAY_TO_SOLANA must each list at least one address");
+        }
+
+        let strategy = match env::var("PAY_TO_STRATEGY").as_deref() {
+            Err(_) | Ok("round_robin") => SelectionStrategy::RoundRobin,
+            Ok("weighted") => SelectionStrategy::Weighted,
+            Ok(other) => bail!("Unknown PAY_TO_STRATEGY {:?}", other),
+        };
+
+        let mut pool = PayToPool {
+            evm,
+            solana,
+            strategy,
+            schedule: Vec::new(),
+            cursor: AtomicUsize::new(0),
+        };
+        pool.schedule = (0..pool.variants())
+            .flat_map(|k| {
+                let repeats = match strategy {
+                    SelectionStrategy::RoundRobin => 1,
+                    SelectionStrategy::Weighted => pool.variant_weight(k),
+                };
+                std::iter::repeat_n(k, repeats as usize)
+            })
+            .collect();
+        Ok(pool)
+    }
+
+    /// Variant `k` prefers `evm[k % n]` and `solana[k % m]`.
+    pub fn variants(&self) -> usize {
+        self.evm.len().max(self.solana.len())
+    }
+
+    /// A variant's weight is the larger of its two preferred addresses' weights.
+    fn variant_weight(&self, k: usize) -> u32 {
+        let evm = self.evm[k % self.evm.len()].1;
+        let solana = self.solana[k % self.solana.len()].1;
+        evm.max(solana)
+    }
+
+    /// Pool addresses for variant `k`, its preferred address first.
+    pub fn ordered_evm(&self, k: usize) -> Vec<EvmAddress> {
+        let n = self.evm.len();
+        (0..n).map(|i| self.evm[(k + i) % n].0.clone()).collect()
+    }
+
+    pub fn ordered_solana(&self, k: usize) -> Vec<SolanaAddress> {
+        let n = self.solana.len();
+        (0..n).map(|i| self.solana[(k + i) % n].0.clone()).collect()
+    }
+
+    pub fn next_variant(&self) -> usize {
+        let slot = self.cursor.fetch_add(1, Ordering::Relaxed) % self.schedule.len();
+        self.schedule[slot]
+    }
+
+    /// Builds the x402 layer for variant `k` at `price` USDC.
+    pub fn layer(
+        &self,
+        x402_base: &X402Middleware<FacilitatorClient>,
+        k: usize,
+        price: f64,
+        challenge: &RouteChallenge,
+    ) -> Result<X402Middleware<FacilitatorClient>> {
+        let price_error = |e| anyhow!("Invalid x402 price: {}", e);
+
+        let mut tags = Vec::new();
+        for address in self.ordered_solana(k) {
+            tags.push(
+                USDCDeployment::by_network(Network::Solana)
+                    .pay_to(address)
+                    .amount(price)
+                    .map_err(price_error)?,
+            );
+        }
+        for address in self.ordered_evm(k) {
+            tags.push(
+                USDCDeployment::by_network(Network::BaseSepolia)
+                    .pay_to(address)
+                    .amount(price)
+                    .map_err(price_error)?,
+            );
+        }
+
+        let mut tags = tags.into_iter();
+        let first = tags.next().context("pay_to pool is empty")?;
+        let mut layer = x402_base
+            .clone()
+            .with_description(&challenge.description)
+            .with_mime_type(&challenge.mime_type)
+            .with_price_tag(first);
+        for tag in tags {
+            layer = layer.or_price_tag(tag);
+        }
+        Ok(layer)
+    }
+}
+
+/// Dispatches each request to one of the per-variant routers, chosen by the pool.
+pub fn paid_route<S>(variants: Vec<Router>, pool: Arc<PayToPool>) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let variants = Arc::new(variants);
+    axum::routing::any(move |request: Request| {
+        let variants = Arc::clone(&variants);
+        let pool = Arc::clone(&pool);
+        async move {
+            let k = pool.next_variant();
+            tracing::info!(
+                pay_to_evm = %pool.evm[k % pool.evm.len()].0,
+                pay_to_solana = %pool.solana[k % pool.solana.len()].0,
+                variant = k,
+                "Selected pay_to for {}",
+                request.uri().path()
+            );
+            let response: Response = match variants[k].clone().oneshot(request).await {
+                Ok(response) => response,
+                Err(never) => match never {},
+            };
+            response
+        }
+    })
+}
@@ -0,0 +1,146 @@
+// src/backend/perspectives.rs
+//! Alternative orderings of one retrieval's candidates, requested with
+//! `/discover?perspectives=cost,capability,reliability`. Each perspective is
+//! computed from catalog metadata and similarity alone, so the model is still
+//! called once and the request is priced like any other `/discover`. Every list
+//! is capped at the same three entries as `recommendations`, and lists may
+//! overlap with it and with each other.
+//!
+//! - `cost`: lowest `price_usd` first; entries with no declared price last.
+//! - `capability`: most tools first, verified tools when known, else declared.
+//! - `reliability`: highest rolling availability first; never-checked last.
+//!
+//! Ties fall back to similarity.
+use super::McpEntry;
+use super::request::RequestError;
+use super::verify::VerificationCache;
+use axum::http::StatusCode;
+use serde_json::{Map, Value, json};
+use std::cmp::Ordering;
+
+/// Same cap the discover schema puts on `recommendations`.
+const PERSPECTIVE_CAP: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perspective {
+    Cost,
+    Capability,
+    Reliability,
+}
+
+impl Perspective {
+    pub fn name(self) -> &'static str {
+        match self {
+            Perspective::Cost => "cost",
+            Perspective::Capability => "capability",
+            Perspective::Reliability => "reliability",
+        }
+    }
+
+    /// Field carrying the dimension value in each ranked item.
+    fn basis_key(self) -> &'static str {
+        match self {
+            Perspective::Cost => "price_usd",
+            Perspective::Capability => "tools",
+            Perspective::Reliability => "availability",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cost" => Some(Perspective::Cost),
+            "capability" => Some(Perspective::Capability),
+            "reliability" => Some(Perspective::Reliability),
+            _ => None,
+        }
+    }
+
+    /// The dimension value for `entry`, higher is better; `None` sorts last.
+    fn metric(self, entry: &McpEntry, verification: &VerificationCache) -> Option<f64> {
+        match self {
+            Perspective::Cost => entry.price_usd.map(|price| -price),
+            Perspective::Capability => Some(
+                verification
+                    .get(&entry.endpoint)
+                    .filter(|result| result.ok)
+                    .map_or(entry.capabilities.len(), |result| result.tools.len())
+                    as f64,
+            ),
+            Perspective::Reliability => verification.availability(&entry.endpoint),
+        }
+    }
+}
+
+/// Parses the comma-separated `perspectives` query parameter, dropping repeats.
+pub fn parse(raw: &str) -> Result<Vec<Perspective>, RequestError> {
+    let mut perspectives = Vec::new();
+    for name in raw.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let Some(perspective) = Perspective::from_name(&name.to_lowercase()) else {
+            return Err(RequestError {
+                status: StatusCode::BAD_REQUEST,
+                field: Some("perspectives".to_string()),
+                message: format!(
+                    "Unknown perspective {:?}; expected cost, capability or reliability",
+                    name
+                ),
+            });
+        };
+        if !perspectives.contains(&perspective) {
+            perspectives.push(perspective);
+        }
+    }
+    Ok(perspectives)
+}
+
+fn rank(
+    perspective: Perspective,
+    candidates: &[(f64, McpEntry)],
+    verification: &VerificationCache,
+) -> Vec<Value> {
+    let mut scored: Vec<(Option<f64>, f64, &McpEntry)> = candidates
+        .iter()
+        .map(|(score, entry)| (perspective.metric(entry, verification), *score, entry))
+        .collect();
+    scored.sort_by(|a, b| match (a.0, b.0) {
+        (Some(x), Some(y)) => y.total_cmp(&x).then(b.1.total_cmp(&a.1)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => b.1.total_cmp(&a.1),
+    });
+    scored
+        .into_iter()
+        .take(PERSPECTIVE_CAP)
+        .map(|(metric, score, entry)| {
+            let basis = match (perspective, metric) {
+                (Perspective::Cost, Some(price)) => json!(-price),
+                (_, metric) => json!(metric),
+            };
+            let mut item = json!({
+                "name": entry.name,
+                "endpoint": entry.endpoint,
+                "score": (score.clamp(0.0, 1.0) * 100.0).round() as u64,
+            });
+            item[perspective.basis_key()] = basis;
+            item
+        })
+        .collect()
+}
+
+/// `{ "<perspective>": [ {name, endpoint, score, <basis>}, ... ] }` for each
+/// requested perspective.
+pub fn build(
+    perspectives: &[Perspective],
+    candidates: &[(f64, McpEntry)],
+    verification: &VerificationCache,
+) -> Value {
+    let lists: Map<String, Value> = perspectives
+        .iter()
+        .map(|p| {
+            (
+                p.name().to_string(),
+                Value::Array(rank(*p, candidates, verification)),
+            )
+        })
+        .collect();
+    Value::Object(lists)
+}
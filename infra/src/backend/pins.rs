@@ -0,0 +1,309 @@
+// src/backend/pins.rs
+//! Operator pins: queries matching a rule always get a given catalog server
+//! in their `/discover` recommendations, whatever the model chose. The file at
+//! `LIBRARIAN_PINS_PATH` (default `pins.json`, optional) is a list of
+//!
+//! ```json
+//! { "pattern": "gdpr", "endpoint": "https://dpo.example.com/mcp", "first": true }
+//! ```
+//!
+//! `pattern` is a case-insensitive substring of the query, or a regex with
+//! `"regex": true`. `first` moves the pinned server to the top. Pins apply
+//! after the model output is validated and scored, so `min_score` never drops
+//! them; if the response would exceed three recommendations, unpinned ones are
+//! dropped from the end. Pinned recommendations carry `"pinned": true`.
+//!
+//! A pin never overrides the request's hard filters: a pinned server that
+//! `CandidateFilter::matches` rejects (missing a required capability, needing
+//! auth the caller didn't allow, ...) is left out, so it can't appear on top
+//! of an answer that was empty because nothing met them.
+use super::filters::CandidateFilter;
+use super::urls::same_endpoint;
+use super::verify::{self, VerificationCache};
+use super::{McpEntry, mirrors, response, session};
+use anyhow::{Context as _, Result, bail};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::env;
+use std::fs::File;
+use std::path::Path;
+
+const DEFAULT_PINS_PATH: &str = "pins.json";
+const MAX_RECOMMENDATIONS: usize = 3;
+const PINNED_RATIONALE: &str = "Pinned by an operator rule for queries like this one.";
+
+#[derive(Deserialize)]
+struct RawPin {
+    pattern: String,
+    #[serde(default)]
+    regex: bool,
+    endpoint: String,
+    #[serde(default)]
+    first: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// Lowercased.
+    Substring(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub struct Pin {
+    matcher: Matcher,
+    pub pattern: String,
+    pub endpoint: String,
+    pub first: bool,
+}
+
+impl Pin {
+    fn matches(&self, query: &str) -> bool {
+        match &self.matcher {
+            Matcher::Substring(needle) => query.to_lowercase().contains(needle),
+            Matcher::Regex(regex) => regex.is_match(query),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PinRules {
+    pins: Vec<Pin>,
+}
+
+impl PinRules {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
+        let raw: Vec<RawPin> = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse {:?} as pin rules", path.as_ref()))?;
+
+        let mut pins = Vec::with_capacity(raw.len());
+        for pin in raw {
+            if pin.pattern.trim().is_empty() {
+                bail!("Pin for {} has an empty pattern", pin.endpoint);
+            }
+            let matcher = if pin.regex {
+                Matcher::Regex(
+                    Regex::new(&pin.pattern)
+                        .with_context(|| format!("Invalid pin regex {:?}", pin.pattern))?,
+                )
+            } else {
+                Matcher::Substring(pin.pattern.trim().to_lowercase())
+            };
+            pins.push(Pin {
+                matcher,
+                pattern: pin.pattern,
+                endpoint: pin.endpoint,
+                first: pin.first,
+            });
+        }
+        Ok(PinRules { pins })
+    }
+
+    /// Loads `LIBRARIAN_PINS_PATH` (default `pins.json`). A missing default
+    /// file means no pins; an explicitly configured one must load.
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_PINS_PATH") {
+            Ok(path) => PinRules::from_file(path),
+            Err(_) if Path::new(DEFAULT_PINS_PATH).exists() => {
+                PinRules::from_file(DEFAULT_PINS_PATH)
+            }
+            Err(_) => Ok(PinRules::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Warns about pins whose endpoint isn't in `catalog`; they never fire.
+    pub fn check_catalog(&self, catalog: &[McpEntry]) {
+        for pin in &self.pins {
+            if !catalog
+                .iter()
+                .any(|e| same_endpoint(&e.endpoint, &pin.endpoint))
+            {
+                tracing::warn!(
+                    pattern = %pin.pattern,
+                    endpoint = %pin.endpoint,
+                    "Pinned endpoint is not in the catalog; the pin will never fire"
+                );
+            }
+        }
+    }
+
+    /// Forces the servers of every pin matching `query` into `response`,
+    /// returning the pinned endpoints. New recommendations are built from the
+    /// catalog, verified and given a session plan like the rest. Pins whose
+    /// server `filter` rejects don't fire.
+    pub fn apply(
+        &self,
+        query: &str,
+        response: &mut Value,
+        catalog: &[McpEntry],
+        filter: &CandidateFilter,
+        verification: &VerificationCache,
+        protocol_version: &str,
+    ) -> Vec<String> {
+        let Some(recommendations) = response
+            .get_mut("recommendations")
+            .and_then(Value::as_array_mut)
+        else {
+            return Vec::new();
+        };
+        // only the server marks pins
+        for rec in recommendations.iter_mut() {
+            if let Some(rec) = rec.as_object_mut() {
+                rec.remove("pinned");
+            }
+        }
+
+        let mut fired = Vec::new();
+        let mut added = false;
+        // `first` pins keep their rule order at the top
+        let mut front = 0;
+        for pin in self.pins.iter().filter(|pin| pin.matches(query)) {
+            let Some(entry) = catalog
+                .iter()
+                .find(|e| same_endpoint(&e.endpoint, &pin.endpoint))
+            else {
+                continue;
+            };
+            if let Some(stage) = filter.rejection(entry) {
+                tracing::debug!(pattern = %pin.pattern, endpoint = %entry.endpoint, ?stage, "Pin skipped by request filters");
+                continue;
+            }
+            let at = recommendations.iter().position(|rec| {
+                rec.get("endpoint")
+                    .and_then(Value::as_str)
+                    .is_some_and(|endpoint| mirrors::serves(entry, endpoint))
+            });
+            let mut rec = match at {
+                Some(at) => recommendations.remove(at),
+                None => {
+                    added = true;
+                    let mut rec = response::catalog_recommendation(
+                        entry,
+                        1.0,
+                        protocol_version,
+                        PINNED_RATIONALE,
+                    );
+                    mirrors::pick_endpoint(&mut rec, entry, verification, None);
+                    rec
+                }
+            };
+            rec["pinned"] = json!(true);
+            match (pin.first, at) {
+                (true, _) => {
+                    recommendations.insert(front.min(recommendations.len()), rec);
+                    front += 1;
+                }
+                (false, Some(at)) => recommendations.insert(at, rec),
+                (false, None) => recommendations.push(rec),
+            }
+            tracing::info!(pattern = %pin.pattern, endpoint = %entry.endpoint, first = pin.first, "Pin fired");
+            fired.push(entry.endpoint.clone());
+        }
+        if fired.is_empty() {
+            return fired;
+        }
+
+        while recommendations.len() > MAX_RECOMMENDATIONS {
+            let Some(unpinned) = recommendations
+                .iter()
+                .rposition(|rec| rec.get("pinned").is_none())
+            else {
+                break;
+            };
+            recommendations.remove(unpinned);
+        }
+        recommendations.truncate(MAX_RECOMMENDATIONS);
+        let names: Vec<String> = recommendations
+            .iter()
+            .filter_map(|rec| rec.get("name").and_then(Value::as_str).map(str::to_string))
+            .collect();
+        if let Some(instructions) = response
+            .get_mut("instructions")
+            .and_then(Value::as_object_mut)
+        {
+            instructions.retain(|name, _| names.contains(name));
+        }
+        if added {
+            verify::apply_verification(response, catalog, verification);
+            session::attach(response);
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::filters::EndpointPolicy;
+    use std::sync::Arc;
+
+    fn rules(json: &str) -> PinRules {
+        let path = env::temp_dir().join(format!("librarian-pins-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json).unwrap();
+        let rules = PinRules::from_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        rules
+    }
+
+    fn catalog() -> Vec<McpEntry> {
+        vec![
+            serde_json::from_value(json!({
+                "name": "example/weather",
+                "endpoint": "https://weather.example.com/mcp",
+                "version": "1.0.0",
+                "capabilities": ["get_forecast", "get_alerts"],
+                "desc": "",
+            }))
+            .unwrap(),
+        ]
+    }
+
+    fn pin(filters: Value) -> (Vec<String>, Value) {
+        let pins =
+            rules(r#"[{ "pattern": "forecast", "endpoint": "https://weather.example.com/mcp" }]"#);
+        let filter = CandidateFilter::from_request(
+            Some(&filters),
+            false,
+            Arc::new(EndpointPolicy::default()),
+        );
+        let mut response = json!({ "recommendations": [] });
+        let fired = pins.apply(
+            "Forecast for Lyon",
+            &mut response,
+            &catalog(),
+            &filter,
+            &VerificationCache::default(),
+            "2025-06-18",
+        );
+        (fired, response)
+    }
+
+    #[test]
+    fn pin_fires_when_its_server_passes_the_filters() {
+        let (fired, response) =
+            pin(json!({ "require_all_capabilities": ["get_forecast", "get_alerts"] }));
+        assert_eq!(fired, ["https://weather.example.com/mcp"]);
+        assert_eq!(response["recommendations"][0]["pinned"], true);
+    }
+
+    #[test]
+    fn pin_is_skipped_when_its_server_fails_the_filters() {
+        let (fired, response) = pin(json!({ "require_any_capabilities": ["geocode"] }));
+        assert!(fired.is_empty());
+        assert_eq!(response["recommendations"], json!([]));
+
+        let (fired, _) = pin(json!({ "require_all_capabilities": ["get_forecast", "geocode"] }));
+        assert!(fired.is_empty());
+    }
+}
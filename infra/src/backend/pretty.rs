@@ -0,0 +1,136 @@
+// src/backend/pretty.rs
+//! `?pretty=true` on any route re-serializes a JSON response body with
+//! indentation, for reading `curl` output. Compact stays the default. The body
+//! is replaced whole and `Content-Length` dropped so it is recomputed; a body
+//! over `MAX_PRETTY_BYTES`, or of unknown length, goes out as it came. Nothing
+//! in-process compresses responses, so encoding always sees the final bytes.
+//! Signed `/discover` responses are prettified inside the signing layer, and
+//! the app-wide pass leaves them alone, so the signature covers what is sent.
+use axum::{
+    body::{Body, HttpBody as _, to_bytes},
+    extract::Request,
+    http::{
+        StatusCode,
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+/// Largest body re-indented.
+const MAX_PRETTY_BYTES: u64 = 1024 * 1024;
+
+/// Marks a response already prettified, so an outer pass skips it.
+#[derive(Clone, Copy)]
+struct Prettified;
+
+/// `pretty=true`, `pretty=1` or a bare `pretty` in the query string.
+fn wants_pretty(request: &Request) -> bool {
+    request.uri().query().is_some_and(|query| {
+        query.split('&').any(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+            key == "pretty" && matches!(value, "true" | "1")
+        })
+    })
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(';')
+                .next()
+                .is_some_and(|mime| mime.trim() == "application/json")
+        })
+}
+
+pub async fn pretty_layer(request: Request, next: Next) -> Response {
+    if !wants_pretty(&request) {
+        return next.run(request).await;
+    }
+    let response = next.run(request).await;
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|len| len <= MAX_PRETTY_BYTES);
+    if !is_json(&response) || !fits || response.extensions().get::<Prettified>().is_some() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_PRETTY_BYTES as usize).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to read response to prettify: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    // anything that isn't JSON after all goes out untouched
+    let Some(pretty) = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|value| serde_json::to_vec_pretty(&value).ok())
+    else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.extensions.insert(Prettified);
+    Response::from_parts(parts, Body::from(pretty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::get};
+    use tower::ServiceExt as _;
+
+    fn app(body: String) -> Router {
+        let answer = move || {
+            let body = body.clone();
+            async move {
+                Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(CONTENT_LENGTH, body.len())
+                    .body(Body::from(body))
+                    .unwrap()
+            }
+        };
+        Router::new()
+            .route("/", get(answer))
+            .layer(middleware::from_fn(pretty_layer))
+    }
+
+    /// The `Content-Length` sent, and the body.
+    async fn fetch(app: Router, uri: &str) -> (String, String) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.unwrap();
+        let length = parts.headers[CONTENT_LENGTH].to_str().unwrap().to_string();
+        (length, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn indents_json_and_sends_the_new_length() {
+        let (length, body) = fetch(app(r#"{"a":[1,2]}"#.to_string()), "/?pretty=true").await;
+        assert_eq!(body, "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+        assert_eq!(length, body.len().to_string());
+        let (_, body) = fetch(app(r#"{"a":1}"#.to_string()), "/?pretty").await;
+        assert!(body.contains('\n'));
+    }
+
+    #[tokio::test]
+    async fn leaves_compact_and_oversized_bodies_alone() {
+        let (_, body) = fetch(app(r#"{"a":1}"#.to_string()), "/?pretty=false").await;
+        assert_eq!(body, r#"{"a":1}"#);
+        let big = format!("[{}0]", "0,".repeat(MAX_PRETTY_BYTES as usize));
+        let (length, body) = fetch(app(big.clone()), "/?pretty=true").await;
+        assert_eq!(length, big.len().to_string());
+        assert_eq!(body, big);
+    }
+}
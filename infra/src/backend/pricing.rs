@@ -0,0 +1,443 @@
+// src/backend/pricing.rs
+use super::request::{self, RequestError};
+use anyhow::{Context as _, Result, bail};
+use axum::{
+    Router,
+    body::Body,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+use tower::ServiceExt as _;
+use x402_rs::network::{Network, USDCDeployment};
+
+pub const DISCOVER_PRICE: f64 = 0.001;
+pub const SEARCH_PRICE: f64 = 0.0001;
+/// Embedding-only; no retrieval or LLM call.
+pub const EMBED_PRICE: f64 = 0.00005;
+
+/// Networks every paid route accepts payment on.
+pub const PAYMENT_NETWORKS: [Network; 2] = [Network::Solana, Network::BaseSepolia];
+
+/// Per-route USDC prices. Defaults reflect compute cost (the LLM path is the
+/// expensive one) and can be overridden with `LIBRARIAN_ROUTE_PRICES`, e.g.
+/// `/discover=0.002,/search=0.0001,/embed=0.00005`.
+#[derive(Debug, Clone)]
+pub struct RoutePrices(BTreeMap<String, f64>);
+
+impl Default for RoutePrices {
+    fn default() -> Self {
+        RoutePrices(BTreeMap::from([
+            ("/discover".to_string(), DISCOVER_PRICE),
+            ("/embed".to_string(), EMBED_PRICE),
+            ("/search".to_string(), SEARCH_PRICE),
+        ]))
+    }
+}
+
+impl RoutePrices {
+    pub fn from_env() -> Result<Self> {
+        let mut prices = RoutePrices::default();
+        let Ok(raw) = env::var("LIBRARIAN_ROUTE_PRICES") else {
+            return Ok(prices);
+        };
+
+        for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (route, amount) = pair.split_once('=').with_context(|| {
+                format!(
+                    "Expected route=price in LIBRARIAN_ROUTE_PRICES, got {:?}",
+                    pair
+                )
+            })?;
+            let route = route.trim();
+            if !prices.0.contains_key(route) {
+                bail!("Unknown paid route {:?} in LIBRARIAN_ROUTE_PRICES", route);
+            }
+            let amount: f64 = amount
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid price for {}: {:?}", route, amount))?;
+            prices.0.insert(route.to_string(), amount);
+        }
+        Ok(prices)
+    }
+
+    /// Price for a paid route; routes are always present since unknown ones are rejected at load.
+    pub fn get(&self, route: &str) -> f64 {
+        self.0.get(route).copied().unwrap_or(DISCOVER_PRICE)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.0.iter().map(|(route, price)| (route.as_str(), *price))
+    }
+
+    /// Checks every route price on every accepted network.
+    pub fn validate(&self) -> Result<()> {
+        for (route, price) in self.iter() {
+            for network in PAYMENT_NETWORKS {
+                validate_price(network, price)
+                    .with_context(|| format!("Invalid price for {}", route))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a route's x402 challenge tells wallets about the purchase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteChallenge {
+    pub description: String,
+    pub mime_type: String,
+}
+
+impl RouteChallenge {
+    fn new(description: &str) -> Self {
+        RouteChallenge {
+            description: description.to_string(),
+            mime_type: "application/json".to_string(),
+        }
+    }
+}
+
+/// Fields of one `LIBRARIAN_ROUTE_CHALLENGES` entry; unset ones keep the default.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChallengeOverride {
+    description: Option<String>,
+    mime_type: Option<String>,
+}
+
+/// Per-route challenge description and mime type. Overridden with
+/// `LIBRARIAN_ROUTE_CHALLENGES`, a JSON object keyed by route, e.g.
+/// `{"/search": {"description": "Catalog search, no LLM"}}`.
+#[derive(Debug, Clone)]
+pub struct RouteChallenges(BTreeMap<String, RouteChallenge>);
+
+impl Default for RouteChallenges {
+    fn default() -> Self {
+        RouteChallenges(BTreeMap::from([
+            (
+                "/discover".to_string(),
+                RouteChallenge::new("MCP Discovery Service"),
+            ),
+            ("/embed".to_string(), RouteChallenge::new("Query Embedding")),
+            (
+                "/search".to_string(),
+                RouteChallenge::new("MCP Catalog Search"),
+            ),
+        ]))
+    }
+}
+
+impl RouteChallenges {
+    pub fn from_env() -> Result<Self> {
+        let mut challenges = RouteChallenges::default();
+        let Ok(raw) = env::var("LIBRARIAN_ROUTE_CHALLENGES") else {
+            return Ok(challenges);
+        };
+
+        let overrides: BTreeMap<String, ChallengeOverride> = serde_json::from_str(&raw).context(
+            "LIBRARIAN_ROUTE_CHALLENGES must be a JSON object of route -> {description, mime_type}",
+        )?;
+        for (route, over) in overrides {
+            let Some(challenge) = challenges.0.get_mut(route.trim()) else {
+                bail!(
+                    "Unknown paid route {:?} in LIBRARIAN_ROUTE_CHALLENGES",
+                    route
+                );
+            };
+            if let Some(description) = over.description {
+                if description.trim().is_empty() {
+                    bail!(
+                        "Empty description for {} in LIBRARIAN_ROUTE_CHALLENGES",
+                        route
+                    );
+                }
+                challenge.description = description.trim().to_string();
+            }
+            if let Some(mime_type) = over.mime_type {
+                let mime_type = mime_type.trim();
+                let well_formed = mime_type.split_once('/').is_some_and(|(kind, sub)| {
+                    !kind.is_empty() && !sub.is_empty() && !mime_type.contains(char::is_whitespace)
+                });
+                if !well_formed {
+                    bail!(
+                        "Invalid mime type {:?} for {} in LIBRARIAN_ROUTE_CHALLENGES",
+                        mime_type,
+                        route
+                    );
+                }
+                challenge.mime_type = mime_type.to_string();
+            }
+        }
+        Ok(challenges)
+    }
+
+    /// Challenge for a paid route; routes are always present since unknown ones are rejected at load.
+    pub fn get(&self, route: &str) -> RouteChallenge {
+        self.0
+            .get(route)
+            .cloned()
+            .unwrap_or_else(|| RouteChallenge::new("MCP Discovery Service"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &RouteChallenge)> {
+        self.0
+            .iter()
+            .map(|(route, challenge)| (route.as_str(), challenge))
+    }
+}
+
+/// The most recommendations a `/discover` answer holds, and the default
+/// `max_results`.
+pub const MAX_DISCOVER_RESULTS: usize = 3;
+/// `/discover` priced by how many recommendations the caller asks for:
+/// `base + per_result * max_results`, clamped to `[min, max]`. `base` is the
+/// `/discover` route price; `LIBRARIAN_DISCOVER_PER_RESULT_PRICE` (default 0,
+/// a flat price), `LIBRARIAN_DISCOVER_MIN_PRICE` and
+/// `LIBRARIAN_DISCOVER_MAX_PRICE` set the rest.
+///
+/// The amount is quoted from the request, not the response: an x402 `exact`
+/// payment authorizes the amount in the challenge before the handler runs, so
+/// it can't be lowered once the result is known. A caller expecting few
+/// matches asks for fewer results, or checks `/discover/estimate` first. An
+/// empty catalog is never charged; see `health::empty_catalog_guard`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiscoverPricing {
+    pub base: f64,
+    pub per_result: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// The price `quote_layer` settled on, as a request extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    /// Index into `DiscoverPricing::tiers`.
+    pub tier: usize,
+    pub results: usize,
+    pub price: f64,
+}
+
+impl DiscoverPricing {
+    pub fn from_env(prices: &RoutePrices) -> Result<Self> {
+        let var = |key: &str, default: f64| -> Result<f64> {
+            match env::var(key) {
+                Ok(raw) => raw
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v >= 0.0)
+                    .with_context(|| {
+                        format!("{} must be a non-negative number, got {:?}", key, raw)
+                    }),
+                Err(_) => Ok(default),
+            }
+        };
+        let pricing = DiscoverPricing {
+            base: prices.get("/discover"),
+            per_result: var("LIBRARIAN_DISCOVER_PER_RESULT_PRICE", 0.0)?,
+            min: var("LIBRARIAN_DISCOVER_MIN_PRICE", 0.0)?,
+            max: var("LIBRARIAN_DISCOVER_MAX_PRICE", f64::INFINITY)?,
+        };
+        if pricing.min > pricing.max {
+            bail!(
+                "LIBRARIAN_DISCOVER_MIN_PRICE {} is above LIBRARIAN_DISCOVER_MAX_PRICE {}",
+                pricing.min,
+                pricing.max
+            );
+        }
+        Ok(pricing)
+    }
+
+    /// The price of asking for `results` recommendations, rounded to whole
+    /// atomic units so it reads back unchanged from `USDCDeployment::amount`.
+    pub fn price(&self, results: usize) -> f64 {
+        let decimals = PAYMENT_NETWORKS
+            .iter()
+            .map(|&network| USDCDeployment::by_network(network).decimals)
+            .min()
+            .unwrap_or(6);
+        let scale = 10f64.powi(decimals as i32);
+        let amount = (self.base + self.per_result * results as f64).clamp(self.min, self.max);
+        (amount * scale).round() / scale
+    }
+
+    /// Prices for 1 through `MAX_DISCOVER_RESULTS` results.
+    pub fn tiers(&self) -> Vec<f64> {
+        (1..=MAX_DISCOVER_RESULTS)
+            .map(|results| self.price(results))
+            .collect()
+    }
+
+    /// Whether every tier costs the same, so one set of x402 layers serves all.
+    pub fn is_flat(&self) -> bool {
+        self.tiers().windows(2).all(|pair| pair[0] == pair[1])
+    }
+
+    /// Clamps an out-of-range `max_results` to the nearest tier. On `/discover`
+    /// this is defensive only: `quote_layer` refuses such a value with a `400`
+    /// or `422` (see `requested_results`) before quoting. `/discover/estimate`
+    /// doesn't validate it and reports the clamped quote.
+    pub fn quote(&self, max_results: Option<u64>) -> Quote {
+        let results = max_results.map_or(MAX_DISCOVER_RESULTS, |n| {
+            usize::try_from(n)
+                .unwrap_or(usize::MAX)
+                .clamp(1, MAX_DISCOVER_RESULTS)
+        });
+        let tier = if self.is_flat() { 0 } else { results - 1 };
+        Quote {
+            tier,
+            results,
+            price: self.price(results),
+        }
+    }
+
+    /// Checks every tier on every accepted network.
+    pub fn validate(&self) -> Result<()> {
+        for (i, price) in self.tiers().into_iter().enumerate() {
+            for network in PAYMENT_NETWORKS {
+                validate_price(network, price)
+                    .with_context(|| format!("Invalid /discover price for {} results", i + 1))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `max_results` from a `/discover` body, refused with the handler's field
+/// error when it is not an integer within 1-`MAX_DISCOVER_RESULTS`, so a
+/// malformed value is never quoted. A body that isn't a JSON object is left for
+/// the handler to reject.
+fn requested_results(body: &[u8]) -> Result<Option<u64>, RequestError> {
+    let Ok(Value::Object(fields)) = serde_json::from_slice::<Value>(body) else {
+        return Ok(None);
+    };
+    let out_of_range = |status| RequestError {
+        status,
+        field: Some("max_results".to_string()),
+        message: format!("max_results must be within 1-{}", MAX_DISCOVER_RESULTS),
+    };
+    match fields.get("max_results") {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(n) if (1..=MAX_DISCOVER_RESULTS as u64).contains(&n) => Ok(Some(n)),
+            Some(_) => Err(out_of_range(StatusCode::BAD_REQUEST)),
+            None => Err(out_of_range(StatusCode::UNPROCESSABLE_ENTITY)),
+        },
+    }
+}
+
+/// Reads `max_results` from the body and attaches the `Quote` that
+/// `tiered_route` and the usage ledger charge by. Placed outside both, and
+/// inside the route's `DefaultBodyLimit`, which bounds the read.
+pub async fn quote_layer(
+    State(pricing): State<DiscoverPricing>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, bytes) = match request::buffer(request).await {
+        Ok(buffered) => buffered,
+        Err(e) => return e.into_response(),
+    };
+    let max_results = match requested_results(&bytes) {
+        Ok(max_results) => max_results,
+        Err(e) => return e.into_response(),
+    };
+    let mut request = Request::from_parts(parts, Body::from(bytes));
+    request.extensions_mut().insert(pricing.quote(max_results));
+    next.run(request).await
+}
+
+/// Dispatches each request to the router of its quoted tier, one per entry of
+/// `DiscoverPricing::tiers` (or just one when the price is flat).
+pub fn tiered_route<S>(tiers: Vec<Router>) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let tiers = Arc::new(tiers);
+    axum::routing::any(move |request: Request| {
+        let tiers = Arc::clone(&tiers);
+        async move {
+            let tier = request
+                .extensions()
+                .get::<Quote>()
+                .map_or(tiers.len() - 1, |quote| quote.tier.min(tiers.len() - 1));
+            let response: Response = match tiers[tier].clone().oneshot(request).await {
+                Ok(response) => response,
+                Err(never) => match never {},
+            };
+            response
+        }
+    })
+}
+
+/// Refuses prices that are not positive or that would round to zero atomic units
+/// of the network's USDC deployment (e.g. below 0.000001 for 6 decimals).
+pub fn validate_price(network: Network, amount: f64) -> Result<()> {
+    if !amount.is_finite() || amount <= 0.0 {
+        bail!(
+            "Price {} on {:?} must be a positive number",
+            amount,
+            network
+        );
+    }
+
+    let decimals = USDCDeployment::by_network(network).decimals;
+    let atomic = (amount * 10f64.powi(decimals as i32)).round();
+    if atomic < 1.0 {
+        bail!(
+            "Price {} on {:?} rounds to zero atomic units (token has {} decimals)",
+            amount,
+            network,
+            decimals
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_results_reads_a_valid_max_results() {
+        assert_eq!(
+            requested_results(br#"{"query": "maps", "max_results": 2}"#).unwrap(),
+            Some(2)
+        );
+        assert_eq!(requested_results(br#"{"query": "maps"}"#).unwrap(), None);
+        assert_eq!(
+            requested_results(br#"{"query": "maps", "max_results": null}"#).unwrap(),
+            None
+        );
+        // left for the handler's own rejection
+        assert_eq!(requested_results(b"not json").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_max_results_is_a_field_error() {
+        for (body, status) in [
+            (r#"{"max_results": "3"}"#, StatusCode::UNPROCESSABLE_ENTITY),
+            (r#"{"max_results": 2.5}"#, StatusCode::UNPROCESSABLE_ENTITY),
+            (r#"{"max_results": -1}"#, StatusCode::UNPROCESSABLE_ENTITY),
+            (r#"{"max_results": 0}"#, StatusCode::BAD_REQUEST),
+            (r#"{"max_results": 1000}"#, StatusCode::BAD_REQUEST),
+        ] {
+            let e = requested_results(body.as_bytes()).unwrap_err();
+            assert_eq!(
+                (e.status, e.field.as_deref()),
+                (status, Some("max_results")),
+                "{}",
+                body
+            );
+        }
+    }
+}
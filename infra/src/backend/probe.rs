@@ -0,0 +1,83 @@
+// src/backend/probe.rs
+//! The last result of a background reachability probe. The facilitator and
+//! embedding checks each keep one and `/health` reports it, flattened next to
+//! what was probed (`url` or `model`).
+use serde::Serialize;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeStatus {
+    pub reachable: bool,
+    /// Unix seconds of the probe this status came from.
+    pub checked_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ProbeStatus {
+    /// A probe finishing now, reachable unless it failed with `error`.
+    pub fn now(error: Option<String>) -> Self {
+        let checked_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        ProbeStatus {
+            reachable: error.is_none(),
+            checked_at,
+            error,
+        }
+    }
+}
+
+/// The latest status a monitor recorded; `None` until the first probe finishes.
+pub struct LastProbe<T>(RwLock<Option<T>>);
+
+impl<T> Default for LastProbe<T> {
+    fn default() -> Self {
+        LastProbe(RwLock::new(None))
+    }
+}
+
+impl<T: Clone> LastProbe<T> {
+    pub fn record(&self, status: T) {
+        *self.0.write().unwrap_or_else(|p| p.into_inner()) = Some(status);
+    }
+
+    pub fn get(&self) -> Option<T> {
+        self.0.read().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Clone, Serialize)]
+    struct Probed {
+        url: String,
+        #[serde(flatten)]
+        probe: ProbeStatus,
+    }
+
+    #[test]
+    fn status_flattens_next_to_its_target() {
+        let last = LastProbe::default();
+        assert!(last.get().is_none());
+        last.record(Probed {
+            url: "https://facilitator.example".to_string(),
+            probe: ProbeStatus {
+                checked_at: 1,
+                ..ProbeStatus::now(None)
+            },
+        });
+        assert_eq!(
+            serde_json::to_value(last.get().unwrap()).unwrap(),
+            json!({ "url": "https://facilitator.example", "reachable": true, "checked_at": 1 })
+        );
+        let failed = ProbeStatus::now(Some("timed out".to_string()));
+        assert!(!failed.reachable);
+        assert!(failed.checked_at > 0);
+    }
+}
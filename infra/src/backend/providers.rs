@@ -0,0 +1,254 @@
+// src/backend/providers.rs
+//! Where embeddings and completions come from, chosen independently so, say, a
+//! self-hosted embedding model can sit next to OpenAI completions. Both sides
+//! speak the OpenAI API; per side (`EMBEDDING` or `COMPLETION`):
+//!
+//! - `LIBRARIAN_<SIDE>_PROVIDER`: `openai` (default), `ollama` (a local Ollama
+//!   at `http://localhost:11434/v1`) or `compatible` (any other server with
+//!   the same API, e.g. vLLM or text-embeddings-inference).
+//! - `LIBRARIAN_<SIDE>_BASE_URL`: required for `compatible`, overrides the
+//!   others' default.
+//! - `LIBRARIAN_<SIDE>_API_KEY`: defaults to `OPENAI_API_KEY` for `openai`;
+//!   local servers usually need none.
+//! - `LIBRARIAN_EMBEDDING_MODEL` / `LIBRARIAN_COMPLETION_MODEL`: required
+//!   away from `openai`, where they default to `text-embedding-3-small` and
+//!   `gpt-4o-mini`. Set but empty is an error, not the default.
+//!   `LIBRARIAN_EMBEDDING_DIMS` gives the vector size of a model rig doesn't
+//!   know.
+//!
+//! Stored vectors are tied to `EmbeddingConfig::identity`, provider and model,
+//! since the same model name served elsewhere need not share a vector space.
+use super::settings::select_once_by;
+use anyhow::{Result, bail};
+use rig::embeddings::EmbeddingModel as _;
+use rig::prelude::*;
+use rig::providers::openai::client::Client as OpenAIClient;
+use rig::providers::openai::{EmbeddingModel, TEXT_EMBEDDING_3_SMALL};
+use std::env;
+use std::sync::OnceLock;
+
+pub const DEFAULT_COMPLETION_MODEL: &str = "gpt-4o-mini";
+const OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProviderKind {
+    #[default]
+    OpenAi,
+    Ollama,
+    Compatible,
+}
+
+impl ProviderKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::Compatible => "compatible",
+        }
+    }
+}
+
+/// One side's connection settings.
+#[derive(Debug, Clone)]
+pub struct Provider {
+    pub kind: ProviderKind,
+    pub base_url: Option<String>,
+    api_key: Option<String>,
+}
+
+impl Provider {
+    fn from_env(side: &str) -> Result<Self> {
+        let var = |key: &str| {
+            env::var(format!("LIBRARIAN_{}_{}", side, key))
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let kind = match var("PROVIDER").as_deref() {
+            None | Some("openai") => ProviderKind::OpenAi,
+            Some("ollama") => ProviderKind::Ollama,
+            Some("compatible") => ProviderKind::Compatible,
+            Some(other) => bail!(
+                "Unknown LIBRARIAN_{}_PROVIDER {:?} (expected openai, ollama or compatible)",
+                side,
+                other
+            ),
+        };
+        let base_url = match (kind, var("BASE_URL")) {
+            (_, Some(url)) => {
+                url::Url::parse(&url)
+                    .map_err(|e| anyhow::anyhow!("Invalid LIBRARIAN_{}_BASE_URL: {}", side, e))?;
+                Some(url)
+            }
+            (ProviderKind::Ollama, None) => Some(OLLAMA_BASE_URL.to_string()),
+            (ProviderKind::Compatible, None) => {
+                bail!(
+                    "LIBRARIAN_{}_PROVIDER=compatible needs LIBRARIAN_{}_BASE_URL",
+                    side,
+                    side
+                )
+            }
+            (ProviderKind::OpenAi, None) => None,
+        };
+        let api_key = var("API_KEY").or_else(|| {
+            (kind == ProviderKind::OpenAi)
+                .then(|| env::var("OPENAI_API_KEY").ok())
+                .flatten()
+        });
+        Ok(Provider {
+            kind,
+            base_url,
+            api_key,
+        })
+    }
+
+    /// Whether requests would go out unauthenticated to a provider that
+    /// always wants a key.
+    pub fn missing_key(&self) -> bool {
+        self.kind == ProviderKind::OpenAi
+            && self.api_key.as_deref().is_none_or(|k| k.trim().is_empty())
+    }
+
+    pub fn client(&self) -> OpenAIClient {
+        // local servers ignore the key, but the client sends one regardless
+        let api_key = self.api_key.as_deref().unwrap_or("none");
+        match &self.base_url {
+            Some(url) => OpenAIClient::builder(api_key).base_url(url).build(),
+            None => OpenAIClient::new(api_key),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbeddingConfig {
+    pub provider: Provider,
+    pub model: String,
+    pub dims: Option<usize>,
+}
+
+impl EmbeddingConfig {
+    pub fn model(&self) -> EmbeddingModel {
+        let client = self.provider.client();
+        match self.dims {
+            Some(dims) => client.embedding_model_with_ndims(&self.model, dims),
+            None => client.embedding_model(&self.model),
+        }
+    }
+
+    /// `provider:model`, recorded in snapshots and compared on load.
+    pub fn identity(&self) -> String {
+        format!("{}:{}", self.provider.kind.name(), self.model)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionConfig {
+    pub provider: Provider,
+    pub model: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Providers {
+    pub embedding: EmbeddingConfig,
+    pub completion: CompletionConfig,
+}
+
+impl Default for Providers {
+    fn default() -> Self {
+        let openai = Provider {
+            kind: ProviderKind::OpenAi,
+            base_url: None,
+            api_key: env::var("OPENAI_API_KEY").ok(),
+        };
+        Providers {
+            embedding: EmbeddingConfig {
+                provider: openai.clone(),
+                model: TEXT_EMBEDDING_3_SMALL.to_string(),
+                dims: None,
+            },
+            completion: CompletionConfig {
+                provider: openai,
+                model: DEFAULT_COMPLETION_MODEL.to_string(),
+            },
+        }
+    }
+}
+
+impl Providers {
+    pub fn from_env() -> Result<Self> {
+        let model = |side: &str, provider: &Provider, default: &str| -> Result<String> {
+            match env::var(format!("LIBRARIAN_{}_MODEL", side))
+                .ok()
+                .map(|m| m.trim().to_string())
+            {
+                Some(model) if model.is_empty() => {
+                    bail!("LIBRARIAN_{}_MODEL is set but empty", side)
+                }
+                Some(model) if model.contains(char::is_whitespace) => {
+                    bail!("LIBRARIAN_{}_MODEL {:?} is not a model id", side, model)
+                }
+                Some(model) => Ok(model),
+                None if provider.kind == ProviderKind::OpenAi => Ok(default.to_string()),
+                None => bail!(
+                    "LIBRARIAN_{}_PROVIDER={} needs LIBRARIAN_{}_MODEL",
+                    side,
+                    provider.kind.name(),
+                    side
+                ),
+            }
+        };
+
+        let provider = Provider::from_env("EMBEDDING")?;
+        let dims = match env::var("LIBRARIAN_EMBEDDING_DIMS") {
+            Ok(raw) => match raw.trim().parse::<usize>() {
+                Ok(dims) if dims > 0 => Some(dims),
+                _ => bail!(
+                    "LIBRARIAN_EMBEDDING_DIMS must be a positive integer, got {:?}",
+                    raw
+                ),
+            },
+            Err(_) => None,
+        };
+        let embedding = EmbeddingConfig {
+            model: model("EMBEDDING", &provider, TEXT_EMBEDDING_3_SMALL)?,
+            provider,
+            dims,
+        };
+        if embedding.model().ndims() == 0 {
+            bail!(
+                "Unknown vector size for embedding model {:?}; set LIBRARIAN_EMBEDDING_DIMS",
+                embedding.model
+            );
+        }
+
+        let provider = Provider::from_env("COMPLETION")?;
+        let completion = CompletionConfig {
+            model: model("COMPLETION", &provider, DEFAULT_COMPLETION_MODEL)?,
+            provider,
+        };
+        Ok(Providers {
+            embedding,
+            completion,
+        })
+    }
+}
+
+static ACTIVE: OnceLock<Providers> = OnceLock::new();
+
+/// Fixes the providers; see `settings::select_once`. Two selections are the
+/// same when they name the same embedding identity and completion model.
+pub fn select(providers: Providers) -> Result<()> {
+    select_once_by(&ACTIVE, providers, "Providers", |a, b| {
+        a.embedding.identity() == b.embedding.identity() && a.completion.model == b.completion.model
+    })
+}
+
+/// The selected providers, or OpenAI for both when nothing was selected.
+pub fn active() -> &'static Providers {
+    ACTIVE.get_or_init(Providers::default)
+}
+
+/// The live embedding model; every embedding call goes through this.
+pub fn embedding_model() -> EmbeddingModel {
+    active().embedding.model()
+}
@@ -0,0 +1,217 @@
+// src/backend/query_context.rs
+//! Request context folded into the text embedded for `/discover` retrieval.
+//! A short query says little on its own; `client_type` and structured filters
+//! often carry the traits catalog descriptions mention ("native", "low
+//! latency"). `LIBRARIAN_EMBED_QUERY_CONTEXT` lists what to fold in,
+//! comma-separated: `client_type`, `filters` (every scalar filter) or
+//! `filters.<key>` (one filter). Unset folds nothing; an unknown field fails
+//! startup. Only the embedded text changes; the prompt, reranking and filters
+//! still see the raw query.
+//! Compare with and without using `bench`, e.g. on
+//! `fixtures/bench/context.json` against `fixtures/mcps.json`.
+use super::DiscoverRequest;
+use super::settings::select_once;
+use anyhow::{Result, bail};
+use serde_json::Value;
+use std::env;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextField {
+    ClientType,
+    AllFilters,
+    Filter(String),
+}
+
+impl ContextField {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "client_type" => Some(ContextField::ClientType),
+            "filters" => Some(ContextField::AllFilters),
+            _ => raw
+                .strip_prefix("filters.")
+                .filter(|key| !key.is_empty())
+                .map(|key| ContextField::Filter(key.to_string())),
+        }
+    }
+}
+
+/// The fields to fold in, in the order configured.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryContext(Vec<ContextField>);
+
+impl QueryContext {
+    /// A comma-separated field list; repeats are dropped.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let mut fields = Vec::new();
+        for raw in raw.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match ContextField::parse(raw) {
+                Some(field) if !fields.contains(&field) => fields.push(field),
+                Some(_) => {}
+                None => bail!(
+                    "Unknown LIBRARIAN_EMBED_QUERY_CONTEXT field {:?} (expected client_type, filters or filters.<key>)",
+                    raw
+                ),
+            }
+        }
+        Ok(QueryContext(fields))
+    }
+
+    pub fn from_env() -> Result<Self> {
+        QueryContext::parse(&env::var("LIBRARIAN_EMBED_QUERY_CONTEXT").unwrap_or_default())
+    }
+
+    /// The fields as written, for logs and `bench` output.
+    pub fn describe(&self) -> String {
+        if self.0.is_empty() {
+            return "none".to_string();
+        }
+        self.0
+            .iter()
+            .map(|field| match field {
+                ContextField::ClientType => "client_type".to_string(),
+                ContextField::AllFilters => "filters".to_string(),
+                ContextField::Filter(key) => format!("filters.{}", key),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// `query` followed by one `key: value` line per field present in `req`;
+    /// `query` unchanged when none is.
+    pub fn contextualize(&self, query: &str, req: &DiscoverRequest) -> String {
+        let filters = req.filters.as_ref().and_then(Value::as_object);
+        let mut lines: Vec<String> = Vec::new();
+        let mut push = |key: &str, text: String| {
+            let line = format!("{}: {}", key.replace('_', " "), text);
+            if !lines.contains(&line) {
+                lines.push(line);
+            }
+        };
+
+        for field in &self.0 {
+            match field {
+                ContextField::ClientType => {
+                    if let Some(client) = req
+                        .client_type
+                        .as_deref()
+                        .map(str::trim)
+                        .filter(|c| !c.is_empty())
+                    {
+                        push("client", client.to_string());
+                    }
+                }
+                ContextField::AllFilters => {
+                    // `mode` is how to filter, not what the caller wants
+                    for (key, value) in filters
+                        .into_iter()
+                        .flatten()
+                        .filter(|(key, _)| *key != "mode")
+                    {
+                        if let Some(text) = filter_text(value) {
+                            push(key, text);
+                        }
+                    }
+                }
+                ContextField::Filter(key) => {
+                    if let Some(text) = filters.and_then(|f| f.get(key)).and_then(filter_text) {
+                        push(key, text);
+                    }
+                }
+            }
+        }
+
+        if lines.is_empty() {
+            return query.to_string();
+        }
+        format!("{}\n{}", query, lines.join("\n"))
+    }
+}
+
+/// Strings, numbers, `true` and lists of those; anything else carries no
+/// useful text.
+fn filter_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(true) => Some("yes".to_string()),
+        Value::Array(items) => {
+            let parts: Vec<String> = items.iter().filter_map(filter_text).collect();
+            (!parts.is_empty()).then(|| parts.join(", "))
+        }
+        _ => None,
+    }
+}
+
+static ACTIVE: OnceLock<QueryContext> = OnceLock::new();
+
+/// Fixes the query context fields; see `settings::select_once`.
+pub fn select(context: QueryContext) -> Result<()> {
+    select_once(&ACTIVE, context, "Query context")
+}
+
+/// The selected fields, or none when nothing was selected.
+pub fn active() -> &'static QueryContext {
+    ACTIVE.get_or_init(QueryContext::default)
+}
+
+/// `active().describe()`.
+pub fn describe() -> String {
+    active().describe()
+}
+
+/// `query` with the selected context folded in; see `QueryContext::contextualize`.
+pub fn contextualize(query: &str, req: &DiscoverRequest) -> String {
+    active().contextualize(query, req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(body: Value) -> DiscoverRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn parse_keeps_order_drops_repeats_and_refuses_unknown_fields() {
+        let context =
+            QueryContext::parse(" filters.latency, client_type,filters.latency ,").unwrap();
+        assert_eq!(context.describe(), "filters.latency,client_type");
+        assert_eq!(QueryContext::parse("").unwrap().describe(), "none");
+        assert!(QueryContext::parse("client_type,region").is_err());
+        assert!(QueryContext::parse("filters.").is_err());
+    }
+
+    #[test]
+    fn folds_configured_fields_present_in_the_request() {
+        let req = request(json!({
+            "query": "weather",
+            "client_type": " ios ",
+            "filters": {"mode": "strict", "latency": "low", "tags": ["native", 3, {}], "free": true},
+        }));
+        let all = QueryContext::parse("client_type,filters").unwrap();
+        assert_eq!(
+            all.contextualize("weather", &req),
+            "weather\nclient: ios\nfree: yes\nlatency: low\ntags: native, 3"
+        );
+        let one = QueryContext::parse("filters.latency,filters.latency_ms").unwrap();
+        assert_eq!(one.contextualize("weather", &req), "weather\nlatency: low");
+    }
+
+    #[test]
+    fn query_is_unchanged_without_context() {
+        let req = request(json!({"query": "weather", "filters": {"mode": "strict"}}));
+        assert_eq!(
+            QueryContext::default().contextualize("weather", &req),
+            "weather"
+        );
+        assert_eq!(
+            QueryContext::parse("client_type,filters")
+                .unwrap()
+                .contextualize("weather", &req),
+            "weather"
+        );
+    }
+}
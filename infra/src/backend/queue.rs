@@ -0,0 +1,158 @@
+// src/backend/queue.rs
+//! Load shedding for the agent: at most `LIBRARIAN_AGENT_CONCURRENCY` discover
+//! requests run at once, and up to `LIBRARIAN_AGENT_QUEUE_DEPTH` more may wait
+//! `LIBRARIAN_AGENT_QUEUE_WAIT_MS` for a slot. Anything beyond that gets `503`.
+//!
+//! The layer sits inside the x402 middleware, so a shed request is never settled.
+use super::metrics::METRICS;
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+const DEFAULT_AGENT_CONCURRENCY: usize = 16;
+const DEFAULT_QUEUE_WAIT_MS: u64 = 2000;
+
+pub struct AgentQueue {
+    slots: Semaphore,
+    depth: usize,
+    max_wait: Duration,
+}
+
+impl AgentQueue {
+    pub fn new(concurrency: usize, depth: usize, max_wait: Duration) -> Self {
+        AgentQueue {
+            slots: Semaphore::new(concurrency.max(1)),
+            depth,
+            max_wait,
+        }
+    }
+
+    /// Queue depth defaults to 0: without configuration, a saturated agent
+    /// rejects immediately.
+    pub fn from_env() -> Self {
+        let var = |key: &str| env::var(key).ok().and_then(|v| v.parse::<u64>().ok());
+        AgentQueue::new(
+            var("LIBRARIAN_AGENT_CONCURRENCY").map_or(DEFAULT_AGENT_CONCURRENCY, |v| v as usize),
+            var("LIBRARIAN_AGENT_QUEUE_DEPTH").unwrap_or(0) as usize,
+            Duration::from_millis(
+                var("LIBRARIAN_AGENT_QUEUE_WAIT_MS").unwrap_or(DEFAULT_QUEUE_WAIT_MS),
+            ),
+        )
+    }
+}
+
+fn overloaded(reason: &'static str) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(RETRY_AFTER, "1")],
+        reason,
+    )
+        .into_response()
+}
+
+/// One unit of a gauge, given back on drop, so a request dropped while waiting
+/// or running (client gone, deadline hit) doesn't leave the gauge raised.
+struct Held(&'static AtomicU64);
+
+impl Held {
+    /// Raises `gauge`; also returns its value before.
+    fn raise(gauge: &'static AtomicU64) -> (Self, u64) {
+        let before = gauge.fetch_add(1, Ordering::SeqCst);
+        (Held(gauge), before)
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn agent_queue_layer(
+    State(queue): State<Arc<AgentQueue>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let permit = match queue.slots.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            // reserve a queue position, giving it back if the queue is already full
+            let (waiting, ahead) = Held::raise(&METRICS.agent_queue_depth);
+            if ahead as usize >= queue.depth {
+                drop(waiting);
+                METRICS
+                    .agent_queue_rejected_total
+                    .fetch_add(1, Ordering::Relaxed);
+                return overloaded("Librarian is at capacity, retry shortly");
+            }
+            let acquired = tokio::time::timeout(queue.max_wait, queue.slots.acquire()).await;
+            drop(waiting);
+            match acquired {
+                Ok(Ok(permit)) => permit,
+                _ => {
+                    METRICS
+                        .agent_queue_timeouts_total
+                        .fetch_add(1, Ordering::Relaxed);
+                    return overloaded("Timed out waiting for a Librarian slot, retry shortly");
+                }
+            }
+        }
+    };
+
+    let (_running, _) = Held::raise(&METRICS.agent_in_flight);
+    let response = next.run(request).await;
+    drop(permit);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, middleware, routing::get};
+    use tower::ServiceExt as _;
+
+    async fn settle_at(gauge: &AtomicU64, value: u64) {
+        for _ in 0..100 {
+            if gauge.load(Ordering::SeqCst) == value {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!(
+            "gauge stuck at {}, expected {}",
+            gauge.load(Ordering::SeqCst),
+            value
+        );
+    }
+
+    #[tokio::test]
+    async fn gauges_fall_when_requests_are_dropped() {
+        let queue = Arc::new(AgentQueue::new(1, 1, Duration::from_secs(60)));
+        let app = Router::new()
+            .route("/", get(std::future::pending::<&'static str>))
+            .layer(middleware::from_fn_with_state(queue, agent_queue_layer));
+        let request = || Request::get("/").body(Body::empty()).unwrap();
+
+        let running = tokio::spawn(app.clone().oneshot(request()));
+        settle_at(&METRICS.agent_in_flight, 1).await;
+        let waiting = tokio::spawn(app.clone().oneshot(request()));
+        settle_at(&METRICS.agent_queue_depth, 1).await;
+
+        let rejected = app.oneshot(request()).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(METRICS.agent_queue_depth.load(Ordering::SeqCst), 1);
+
+        waiting.abort();
+        settle_at(&METRICS.agent_queue_depth, 0).await;
+        running.abort();
+        settle_at(&METRICS.agent_in_flight, 0).await;
+    }
+}
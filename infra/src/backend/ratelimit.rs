@@ -0,0 +1,148 @@
+// src/backend/ratelimit.rs
+//! Per-caller request limits for the routes that spend on the embedding API
+//! beyond what they charge: `/embed` (`LIBRARIAN_EMBED_RATE_LIMIT`) and the
+//! free `/discover/estimate` (`LIBRARIAN_ESTIMATE_RATE_LIMIT`), each 30
+//! requests per minute by default, `0` to disable. Callers are told apart by
+//! API key, then by paying address; callers with neither (Solana payers, and
+//! in practice every `/discover/estimate` caller) share one allowance.
+use super::apikey::ApiKeyCaller;
+use super::payer::payer_from_headers;
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_RATE_LIMIT: u32 = 30;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Callers sharing the allowance when neither key nor payer identifies them.
+const ANONYMOUS_CALLER: &str = "anonymous";
+
+/// Fixed one-minute windows per caller, for one route.
+pub struct RateLimiter {
+    route: &'static str,
+    per_minute: u32,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(route: &'static str, per_minute: u32) -> Self {
+        RateLimiter {
+            route,
+            per_minute,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The limit for `route` from `var`, defaulting to 30 a minute.
+    pub fn from_env(route: &'static str, var: &str) -> Self {
+        let per_minute = env::var(var)
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_RATE_LIMIT);
+        RateLimiter::new(route, per_minute)
+    }
+
+    /// Counts a request; `Err` carries the time until the caller's window resets.
+    fn check(&self, caller: &str) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut windows = self
+            .windows
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        windows.retain(|_, (started, _)| now.duration_since(*started) < RATE_WINDOW);
+        let (started, count) = windows.entry(caller.to_string()).or_insert((now, 0));
+        if *count >= self.per_minute {
+            return Err(RATE_WINDOW.saturating_sub(now.duration_since(*started)));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+/// On a paid route, placed inside the x402 layer, so only paid (or keyed)
+/// requests count and a limited one is refused before its payment settles.
+pub async fn rate_limit_layer(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let caller = match request.extensions().get::<ApiKeyCaller>() {
+        Some(ApiKeyCaller(id)) => format!("key:{}", id),
+        None => payer_from_headers(request.headers())
+            .map(|payer| format!("payer:{}", payer))
+            .unwrap_or_else(|| ANONYMOUS_CALLER.to_string()),
+    };
+    match limiter.check(&caller) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            tracing::warn!(caller = %caller, route = limiter.route, "Rate-limited request");
+            let secs = retry_after.as_secs().max(1);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, secs.to_string())],
+                AxumJson(json!({
+                    "error": {
+                        "code": "RATE_LIMITED",
+                        "message": format!(
+                            "{} allows {} requests per minute per caller; retry in {}s",
+                            limiter.route, limiter.per_minute, secs
+                        ),
+                    }
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, middleware, routing::post};
+    use tower::ServiceExt as _;
+
+    #[test]
+    fn callers_get_separate_windows() {
+        let limiter = RateLimiter::new("/embed", 2);
+        assert!(limiter.check("key:a").is_ok());
+        assert!(limiter.check("key:a").is_ok());
+        let retry_after = limiter.check("key:a").unwrap_err();
+        assert!(retry_after <= RATE_WINDOW && !retry_after.is_zero());
+        assert!(limiter.check("key:b").is_ok());
+    }
+
+    #[test]
+    fn zero_disables_the_limit() {
+        let limiter = RateLimiter::new("/embed", 0);
+        assert!((0..100).all(|_| limiter.check(ANONYMOUS_CALLER).is_ok()));
+    }
+
+    #[tokio::test]
+    async fn layer_refuses_over_the_limit_with_retry_after() {
+        let limiter = Arc::new(RateLimiter::new("/discover/estimate", 1));
+        let app = Router::new()
+            .route("/discover/estimate", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(limiter, rate_limit_layer));
+        let call = || {
+            app.clone().oneshot(
+                Request::post("/discover/estimate")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        assert_eq!(call().await.unwrap().status(), StatusCode::OK);
+        let limited = call().await.unwrap();
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers().contains_key(RETRY_AFTER));
+    }
+}
@@ -0,0 +1,80 @@
+// src/backend/readiness.rs
+//! The server binds before the catalog is embedded, so a large catalog doesn't
+//! hold up liveness probes. Until the built index is swapped in, `/health/live`
+//! answers, `/health/ready` doesn't, and `/discover` and `/search` are refused
+//! with `503` and `Retry-After` before any payment is asked for.
+use super::health::DEGRADED_HEADER;
+use axum::{
+    Extension,
+    extract::{Request, State},
+    http::{StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How long clients are told to wait while the index builds.
+const BUILDING_RETRY_AFTER_SECS: u64 = 5;
+
+/// Whether the startup index build has finished; flipped once, never back.
+/// Later reloads swap a complete index in one step and don't touch it.
+#[derive(Clone, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Called right after the built `Librarian` is swapped in, so a request
+    /// that sees `ready` also sees the full index.
+    pub fn mark_ready(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// `GET /health/live`: the process is up and serving.
+pub async fn live_handler() -> impl IntoResponse {
+    AxumJson(json!({ "status": "live" }))
+}
+
+/// `GET /health/ready`: `200` once the index is built, `503` before.
+pub async fn ready_handler(Extension(readiness): Extension<Readiness>) -> Response {
+    if readiness.is_ready() {
+        return AxumJson(json!({ "status": "ready" })).into_response();
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(RETRY_AFTER, BUILDING_RETRY_AFTER_SECS.to_string())],
+        AxumJson(json!({ "status": "building" })),
+    )
+        .into_response()
+}
+
+/// Placed outside the x402 layer, like `health::empty_catalog_guard`.
+pub async fn readiness_guard(
+    State(readiness): State<Readiness>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if readiness.is_ready() {
+        return next.run(request).await;
+    }
+    let retry_after = BUILDING_RETRY_AFTER_SECS.to_string();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [
+            (RETRY_AFTER.as_str(), retry_after.as_str()),
+            (DEGRADED_HEADER, "index-building"),
+        ],
+        AxumJson(json!({
+            "error": {
+                "code": "index_building",
+                "message": "The catalog index is still being built; retry shortly.",
+            }
+        })),
+    )
+        .into_response()
+}
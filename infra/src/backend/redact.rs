@@ -0,0 +1,66 @@
+// src/backend/redact.rs
+//! Catalog fields that feed retrieval but must never leave the server. An
+//! entry's `internal_notes` are always withheld; `LIBRARIAN_REDACT_FIELDS`
+//! (comma-separated field names) withholds more. Every path that serializes an
+//! entry for a caller or for the model goes through `public_value`; only the
+//! admin snapshot export keeps them, so it can be re-imported losslessly.
+use super::McpEntry;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+pub const INTERNAL_NOTES_FIELD: &str = "internal_notes";
+/// Without these an entry can't be recommended or connected to.
+const UNREDACTABLE: [&str; 2] = ["name", "endpoint"];
+
+static REDACTED: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let mut fields = vec![INTERNAL_NOTES_FIELD.to_string()];
+    for field in configured()
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+    {
+        if UNREDACTABLE.contains(&field) {
+            tracing::warn!(
+                "LIBRARIAN_REDACT_FIELDS: {:?} cannot be redacted, ignoring",
+                field
+            );
+        } else if !fields.iter().any(|f| f == field) {
+            fields.push(field.to_string());
+        }
+    }
+    fields
+});
+
+#[cfg(not(test))]
+fn configured() -> String {
+    std::env::var("LIBRARIAN_REDACT_FIELDS").unwrap_or_default()
+}
+
+/// Unit tests redact a fixed set, whatever the environment says.
+#[cfg(test)]
+fn configured() -> String {
+    "desc,capabilities".to_string()
+}
+
+pub fn redacted_fields() -> &'static [String] {
+    &REDACTED
+}
+
+pub fn is_redacted(field: &str) -> bool {
+    REDACTED.iter().any(|f| f == field)
+}
+
+/// `entry` as JSON without its redacted fields.
+pub fn public_value(entry: &McpEntry) -> Value {
+    let mut value = serde_json::to_value(entry).unwrap_or(Value::Null);
+    if let Some(map) = value.as_object_mut() {
+        for field in REDACTED.iter() {
+            map.remove(field);
+        }
+    }
+    value
+}
+
+pub fn public_values<'a>(entries: impl IntoIterator<Item = &'a McpEntry>) -> Vec<Value> {
+    entries.into_iter().map(public_value).collect()
+}
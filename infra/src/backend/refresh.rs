@@ -0,0 +1,82 @@
+// src/backend/refresh.rs
+use super::reload::Reloader;
+use super::tasks::TASKS;
+use anyhow::{Context as _, Result};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Polling interval from `CATALOG_REFRESH_INTERVAL` (seconds). Unset or `0`
+/// disables periodic refresh.
+pub fn refresh_interval_from_env() -> Result<Option<Duration>> {
+    let Ok(raw) = env::var("CATALOG_REFRESH_INTERVAL") else {
+        return Ok(None);
+    };
+    let secs: u64 = raw.trim().parse().with_context(|| {
+        format!(
+            "CATALOG_REFRESH_INTERVAL must be a number of seconds, got {:?}",
+            raw
+        )
+    })?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+/// SHA-256 of the raw catalog source, used to skip rebuilds when nothing changed.
+pub fn source_hash(path: impl AsRef<Path>) -> Result<String> {
+    let bytes =
+        std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path.as_ref()))?;
+    Ok(hex::encode(Sha256::digest(&bytes)))
+}
+
+/// Reloads the catalog on a fixed timer, independent of the file watcher. A
+/// catalog file is hashed first and the rebuild skipped when it is unchanged;
+/// with `path` unset (a remote catalog) every tick reloads, relying on the
+/// conditional fetch instead. A failed rebuild is logged and the previous
+/// catalog keeps serving. Stops between ticks on shutdown.
+pub fn spawn_catalog_refresh(reloader: Arc<Reloader>, path: Option<&Path>, interval: Duration) {
+    let path: Option<PathBuf> = path.map(Path::to_path_buf);
+    let mut last_hash = path.as_ref().and_then(|path| source_hash(path).ok());
+    match &path {
+        Some(path) => tracing::info!("Refreshing catalog from {:?} every {:?}", path, interval),
+        None => tracing::info!("Refreshing remote catalog every {:?}", interval),
+    }
+
+    TASKS.spawn("catalog_refresh", |cancel| async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // the first tick fires immediately and the startup build is already fresh
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = cancel.cancelled() => return,
+            }
+
+            let hash = match path.as_ref().map(source_hash).transpose() {
+                Ok(hash) => hash,
+                Err(e) => {
+                    tracing::error!("Catalog refresh failed, keeping previous catalog: {:#}", e);
+                    continue;
+                }
+            };
+            if hash.is_some() && last_hash == hash {
+                tracing::debug!("Catalog refresh skipped: {:?} unchanged", path);
+                reloader.confirm_unchanged();
+                continue;
+            }
+
+            match reloader.reload().await {
+                Ok(count) => {
+                    last_hash = hash;
+                    tracing::info!("Catalog refreshed: {} entries", count);
+                }
+                Err(e) => {
+                    tracing::error!("Catalog refresh failed, keeping previous catalog: {:#}", e);
+                }
+            }
+        }
+    });
+}
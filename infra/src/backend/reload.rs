@@ -0,0 +1,181 @@
+// src/backend/reload.rs
+//! Serialized catalog reloads. Only one rebuild runs at a time; a caller that
+//! arrives while one is in flight either waits for that reload's outcome or is
+//! turned away, per `LIBRARIAN_CONCURRENT_RELOAD`. Other catalog writers (the
+//! admin add) take `Reloader::exclusive` so they never interleave with a reload.
+use super::LibrarianHandle;
+use super::store::{self, Store};
+use super::tasks::TASKS;
+use crate::utils;
+use anyhow::{Result, bail};
+use axum::{
+    Extension,
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::json;
+use std::env;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{MutexGuard, watch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrentReload {
+    /// Share the in-flight reload's result.
+    Wait,
+    /// Answer `ReloadError::InProgress` (`409` over HTTP).
+    Reject,
+}
+
+impl ConcurrentReload {
+    /// `LIBRARIAN_CONCURRENT_RELOAD`: `wait` (default) or `reject`.
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_CONCURRENT_RELOAD").as_deref() {
+            Err(_) | Ok("wait") => Ok(ConcurrentReload::Wait),
+            Ok("reject") => Ok(ConcurrentReload::Reject),
+            Ok(other) => bail!(
+                "Unknown LIBRARIAN_CONCURRENT_RELOAD {:?}; expected wait or reject",
+                other
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ReloadError {
+    InProgress,
+    Failed(String),
+}
+
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReloadError::InProgress => write!(f, "a catalog reload is already in progress"),
+            ReloadError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+/// `Ok` carries the reloaded catalog's entry count.
+pub type ReloadOutcome = Result<usize, ReloadError>;
+
+pub struct Reloader {
+    handle: LibrarianHandle,
+    mode: ConcurrentReload,
+    in_flight: Mutex<Option<watch::Receiver<Option<ReloadOutcome>>>>,
+    /// Held from reading the live catalog to swapping its replacement in.
+    writes: tokio::sync::Mutex<()>,
+}
+
+impl Reloader {
+    pub fn new(handle: LibrarianHandle, mode: ConcurrentReload) -> Self {
+        Reloader {
+            handle,
+            mode,
+            in_flight: Mutex::new(None),
+            writes: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Waits out any reload or other write, then keeps the catalog to the
+    /// caller until the guard drops.
+    pub async fn exclusive(&self) -> MutexGuard<'_, ()> {
+        self.writes.lock().await
+    }
+
+    /// Records that the catalog source was found unchanged without a reload,
+    /// which keeps the live catalog fresh; see `freshness`.
+    pub fn confirm_unchanged(&self) {
+        self.handle.current().source_check.mark();
+    }
+
+    /// Re-reads the catalog and swaps it in on success; on failure, or when a
+    /// remote catalog is unchanged, the previous catalog keeps serving. The rebuild runs on its own task, so a caller
+    /// going away never strands the waiters.
+    pub async fn reload(self: &Arc<Self>) -> ReloadOutcome {
+        let mut rx = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.as_ref() {
+                Some(_) if self.mode == ConcurrentReload::Reject => {
+                    return Err(ReloadError::InProgress);
+                }
+                Some(rx) => rx.clone(),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    *in_flight = Some(rx.clone());
+                    let this = Arc::clone(self);
+                    TASKS.spawn("catalog_reload", |_| async move {
+                        let writes = this.exclusive().await;
+                        let current = this.handle.current();
+                        let outcome = match utils::reload_librarian(&current).await {
+                            Ok(Some(librarian)) => {
+                                let count = librarian.catalog.len();
+                                let remote = librarian.remote.clone();
+                                this.handle.swap(librarian);
+                                // only now may the next fetch skip this body
+                                if let Some(remote) = remote {
+                                    remote.commit();
+                                }
+                                Ok(count)
+                            }
+                            Ok(None) => {
+                                current.source_check.mark();
+                                Ok(current.catalog.len())
+                            }
+                            Err(e) => Err(ReloadError::Failed(format!("{:#}", e))),
+                        };
+                        drop(writes);
+                        // clear before publishing so a follow-up reload starts fresh
+                        *this.in_flight.lock().unwrap() = None;
+                        let _ = tx.send(Some(outcome));
+                    });
+                    rx
+                }
+            }
+        };
+
+        match rx.wait_for(Option::is_some).await {
+            Ok(outcome) => outcome.clone().unwrap_or(Err(ReloadError::InProgress)),
+            Err(_) => Err(ReloadError::Failed(
+                "reload task ended without a result".to_string(),
+            )),
+        }
+    }
+}
+
+/// `POST /admin/reload`: `200` with the new entry count, `409` when rejected as
+/// concurrent, `500` when the rebuild failed and the old catalog is still live.
+pub async fn reload_handler(
+    Extension(reloader): Extension<Arc<Reloader>>,
+    Extension(audit): Extension<Arc<dyn Store>>,
+) -> Response {
+    let outcome = reloader.reload().await;
+    store::audit(
+        audit.as_ref(),
+        "catalog_reload",
+        match &outcome {
+            Ok(count) => json!({ "reloaded": true, "catalog_entries": count }),
+            Err(e) => json!({ "reloaded": false, "error": e.to_string() }),
+        },
+    );
+    match outcome {
+        Ok(count) => {
+            tracing::info!("Catalog reloaded via admin API: {} entries", count);
+            AxumJson(json!({ "reloaded": true, "catalog_entries": count })).into_response()
+        }
+        Err(ReloadError::InProgress) => (
+            StatusCode::CONFLICT,
+            AxumJson(json!({ "error": ReloadError::InProgress.to_string() })),
+        )
+            .into_response(),
+        Err(ReloadError::Failed(message)) => {
+            tracing::error!("Admin reload failed, keeping previous catalog: {}", message);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({ "reloaded": false, "error": message })),
+            )
+                .into_response()
+        }
+    }
+}
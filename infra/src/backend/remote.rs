@@ -0,0 +1,237 @@
+// src/backend/remote.rs
+//! A catalog served over HTTP instead of read from `mcps.json`, so one catalog
+//! service can feed many instances. `MCPS_URL` takes precedence over the file.
+//! Re-fetches are conditional (`If-None-Match` / `If-Modified-Since`), and an
+//! unchanged body is detected by hash as well, so an unchanged catalog is never
+//! re-embedded. A fetched body's validators only count once the catalog built
+//! from it is live (`commit`), so a failed rebuild is retried in full. Bodies
+//! over `MCPS_URL_MAX_BYTES` (default 16 MiB) are refused.
+use super::{McpEntry, parse_mcps};
+use anyhow::{Context as _, Result, anyhow, bail};
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_MCPS_URL_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MCPS_URL_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Validators of one response.
+#[derive(Debug, Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body_hash: Option<String>,
+}
+
+pub struct RemoteCatalog {
+    url: String,
+    client: reqwest::Client,
+    max_bytes: usize,
+    /// From the response the live catalog was built from.
+    last: Mutex<Validators>,
+    /// From the last fetched body, until `commit`.
+    pending: Mutex<Option<Validators>>,
+}
+
+impl RemoteCatalog {
+    /// `None` unless `MCPS_URL` is set. `MCPS_URL_TIMEOUT_SECS` bounds each fetch
+    /// (default 30) and `MCPS_URL_MAX_BYTES` each body.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(url) = env::var("MCPS_URL") else {
+            return Ok(None);
+        };
+        url::Url::parse(&url).map_err(|e| anyhow!("Invalid MCPS_URL: {}", e))?;
+        let timeout = env::var("MCPS_URL_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MCPS_URL_TIMEOUT_SECS);
+        let max_bytes = env::var("MCPS_URL_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MCPS_URL_MAX_BYTES);
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout))
+            .build()?;
+        Ok(Some(RemoteCatalog {
+            url,
+            client,
+            max_bytes,
+            last: Mutex::new(Validators::default()),
+            pending: Mutex::new(None),
+        }))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Fetches and parses the catalog; `None` when it is unchanged since the
+    /// last committed fetch. Call `commit` once the returned entries are live.
+    pub async fn fetch(&self) -> Result<Option<Vec<McpEntry>>> {
+        let mut request = self.client.get(&self.url);
+        {
+            let last = self.last.lock().unwrap_or_else(|p| p.into_inner());
+            if let Some(etag) = &last.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &last.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch catalog from {}", self.url))?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!(
+                "Fetching catalog from {} returned {}",
+                self.url,
+                response.status()
+            );
+        }
+        let header = |name: reqwest::header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        let body = self.read_body(response).await?;
+
+        let body_hash = hex::encode(Sha256::digest(&body));
+        let validators = Validators {
+            etag,
+            last_modified,
+            body_hash: Some(body_hash),
+        };
+        {
+            let mut last = self.last.lock().unwrap_or_else(|p| p.into_inner());
+            if last.body_hash == validators.body_hash {
+                // a server without validators still gets skipped when nothing changed
+                *last = validators;
+                return Ok(None);
+            }
+        }
+        let entries = parse_mcps(&body, &self.url)?;
+        *self.pending.lock().unwrap_or_else(|p| p.into_inner()) = Some(validators);
+        Ok(Some(entries))
+    }
+
+    /// Makes the last fetch's validators the ones sent next time. Called after
+    /// the catalog built from it is swapped in.
+    pub fn commit(&self) {
+        if let Some(validators) = self
+            .pending
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .take()
+        {
+            *self.last.lock().unwrap_or_else(|p| p.into_inner()) = validators;
+        }
+    }
+
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>> {
+        let too_large = || {
+            anyhow!(
+                "Catalog from {} is larger than {} bytes (MCPS_URL_MAX_BYTES)",
+                self.url,
+                self.max_bytes
+            )
+        };
+        if response
+            .content_length()
+            .is_some_and(|len| len > self.max_bytes as u64)
+        {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| format!("Failed to read catalog from {}", self.url))?
+        {
+            if body.len() + chunk.len() > self.max_bytes {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::HeaderMap, routing::get};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ETAG_VALUE: &str = "\"v1\"";
+
+    /// Serves `body` with an `ETag`, answering `304` to a matching
+    /// `If-None-Match`; counts the conditional requests it sees.
+    async fn serve(body: String, conditional: Arc<AtomicUsize>) -> String {
+        let app = Router::new().route(
+            "/mcps.json",
+            get(move |headers: HeaderMap| async move {
+                if headers
+                    .get("if-none-match")
+                    .is_some_and(|v| v == ETAG_VALUE)
+                {
+                    conditional.fetch_add(1, Ordering::SeqCst);
+                    return (
+                        axum::http::StatusCode::NOT_MODIFIED,
+                        [("etag", ETAG_VALUE)],
+                        String::new(),
+                    );
+                }
+                (axum::http::StatusCode::OK, [("etag", ETAG_VALUE)], body)
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/mcps.json", addr)
+    }
+
+    fn remote(url: String, max_bytes: usize) -> RemoteCatalog {
+        RemoteCatalog {
+            url,
+            client: reqwest::Client::new(),
+            max_bytes,
+            last: Mutex::new(Validators::default()),
+            pending: Mutex::new(None),
+        }
+    }
+
+    #[tokio::test]
+    async fn validators_wait_for_commit() {
+        let conditional = Arc::new(AtomicUsize::new(0));
+        let url = serve("[]".to_string(), Arc::clone(&conditional)).await;
+        let remote = remote(url, DEFAULT_MCPS_URL_MAX_BYTES);
+
+        assert!(remote.fetch().await.unwrap().is_some());
+        // the rebuild failed, so nothing was committed: fetched in full again
+        assert!(remote.fetch().await.unwrap().is_some());
+        assert_eq!(conditional.load(Ordering::SeqCst), 0);
+
+        remote.commit();
+        assert!(remote.fetch().await.unwrap().is_none());
+        assert_eq!(conditional.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_refused() {
+        let url = serve(format!("[{}]", " ".repeat(64)), Arc::default()).await;
+        let error = remote(url, 16).fetch().await.unwrap_err();
+        assert!(error.to_string().contains("MCPS_URL_MAX_BYTES"));
+    }
+}
@@ -0,0 +1,301 @@
+// src/backend/replay.rs
+//! `GET /discover/{query_id}`: the stored result of an earlier `/discover`,
+//! without calling the model or charging again. Results are kept in memory for
+//! `LIBRARIAN_REPLAY_TTL_SECS` (default one hour; `0` disables replay) and are
+//! visible only to the caller who made the original request, authenticated
+//! the same way as for `/account`: by API key, or by presenting the settled
+//! `X-PAYMENT` the result was paid with. A request made with neither an API
+//! key nor an identifiable payer can't be replayed.
+use super::account::{Caller, identify};
+use super::apikey::ApiKeys;
+use super::request::RequestError;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const REPLAYED_HEADER: &str = "x-librarian-replayed";
+const DEFAULT_REPLAY_TTL_SECS: u64 = 3600;
+/// Stored ids, expired or not; the oldest are forgotten first, after which
+/// their id answers `404` instead of `410`.
+const MAX_REPLAY_ENTRIES: usize = 10_000;
+
+struct StoredDiscover {
+    caller: Option<Caller>,
+    stored_at: Instant,
+    /// Dropped once expired; the id stays behind so it can answer `410`.
+    response: Option<Value>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, StoredDiscover>,
+    /// Every stored id, oldest first.
+    order: VecDeque<String>,
+    /// Ids still holding a response, oldest first.
+    live: VecDeque<String>,
+}
+
+pub enum Lookup {
+    Found(Value),
+    Expired,
+    Missing,
+}
+
+pub struct ReplayStore {
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl ReplayStore {
+    pub fn new(ttl: Duration) -> Self {
+        ReplayStore {
+            ttl,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Reads `LIBRARIAN_REPLAY_TTL_SECS`, defaulting to one hour.
+    pub fn from_env() -> Self {
+        let ttl = env::var("LIBRARIAN_REPLAY_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_REPLAY_TTL_SECS);
+        ReplayStore::new(Duration::from_secs(ttl))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Keeps `response` under its `query_id` for `caller`. Responses without
+    /// a `query_id` are not stored.
+    pub fn store(&self, response: &Value, caller: Option<Caller>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let Some(query_id) = response.get("query_id").and_then(Value::as_str) else {
+            return;
+        };
+
+        let mut inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        let Inner {
+            entries,
+            order,
+            live,
+        } = &mut *inner;
+        while let Some(oldest) = live.front() {
+            match entries.get_mut(oldest) {
+                Some(stored) if stored.stored_at.elapsed() <= self.ttl => break,
+                Some(stored) => stored.response = None,
+                None => {}
+            }
+            live.pop_front();
+        }
+        if order.len() >= MAX_REPLAY_ENTRIES
+            && let Some(oldest) = order.pop_front()
+        {
+            entries.remove(&oldest);
+        }
+        order.push_back(query_id.to_string());
+        live.push_back(query_id.to_string());
+        entries.insert(
+            query_id.to_string(),
+            StoredDiscover {
+                caller,
+                stored_at: Instant::now(),
+                response: Some(response.clone()),
+            },
+        );
+    }
+
+    /// An id stored for another caller, or for nobody, is reported `Missing`,
+    /// so its existence isn't revealed.
+    pub fn lookup(&self, query_id: &str, caller: &Caller) -> Lookup {
+        let inner = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(stored) = inner.entries.get(query_id) else {
+            return Lookup::Missing;
+        };
+        if stored.caller.as_ref() != Some(caller) {
+            return Lookup::Missing;
+        }
+        match &stored.response {
+            Some(response) if stored.stored_at.elapsed() <= self.ttl => {
+                Lookup::Found(response.clone())
+            }
+            _ => Lookup::Expired,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReplayState {
+    pub store: Arc<ReplayStore>,
+    pub keys: Arc<ApiKeys>,
+}
+
+/// `GET /discover/{query_id}`: `200` with the stored response, `401` without
+/// credentials, `404` for an unknown id (or one made by another caller), `410`
+/// once its TTL has passed.
+pub async fn replay_handler(
+    State(state): State<ReplayState>,
+    Path(query_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let not_found = |message: String| RequestError {
+        status: StatusCode::NOT_FOUND,
+        field: Some("query_id".to_string()),
+        message,
+    };
+    if !state.store.is_enabled() {
+        return not_found("Discover replay is disabled".to_string()).into_response();
+    }
+
+    let Some(caller) = identify(&headers, &state.keys) else {
+        return RequestError {
+            status: StatusCode::UNAUTHORIZED,
+            field: None,
+            message: "Present an API key, or the X-PAYMENT header the result was paid with"
+                .to_string(),
+        }
+        .into_response();
+    };
+    match state.store.lookup(&query_id, &caller) {
+        Lookup::Found(response) => (
+            [(REPLAYED_HEADER, HeaderValue::from_static("true"))],
+            AxumJson(response),
+        )
+            .into_response(),
+        Lookup::Expired => RequestError {
+            status: StatusCode::GONE,
+            field: Some("query_id".to_string()),
+            message: format!(
+                "query_id {:?} expired; results are kept for {}s",
+                query_id,
+                state.store.ttl().as_secs()
+            ),
+        }
+        .into_response(),
+        Lookup::Missing => not_found(format!("Unknown query_id {:?}", query_id)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::payer::{PAYMENT_HEADER, SETTLED, payment_digest};
+    use axum::{Router, body::Body, extract::Request, routing::get};
+    use base64::Engine as _;
+    use base64::engine::general_purpose::STANDARD;
+    use serde_json::json;
+    use tower::ServiceExt as _;
+
+    fn payer(address: &str) -> Caller {
+        Caller::Payer(address.to_string())
+    }
+
+    fn store_with(query_id: &str, owner: Option<Caller>) -> Arc<ReplayStore> {
+        let store = Arc::new(ReplayStore::new(Duration::from_secs(60)));
+        store.store(&json!({ "query_id": query_id }), owner);
+        store
+    }
+
+    #[test]
+    fn only_the_owner_sees_a_result() {
+        let store = store_with("q1", Some(payer("0xa")));
+        assert!(matches!(
+            store.lookup("q1", &payer("0xa")),
+            Lookup::Found(_)
+        ));
+        assert!(matches!(store.lookup("q1", &payer("0xb")), Lookup::Missing));
+        assert!(matches!(
+            store.lookup("q1", &Caller::ApiKey("0xa".to_string())),
+            Lookup::Missing
+        ));
+    }
+
+    #[test]
+    fn results_without_an_owner_are_not_replayable() {
+        let store = store_with("q1", None);
+        assert!(matches!(store.lookup("q1", &payer("0xa")), Lookup::Missing));
+    }
+
+    #[test]
+    fn expired_results_answer_gone_and_oldest_ids_are_forgotten() {
+        let store = ReplayStore::new(Duration::from_millis(1));
+        store.store(&json!({ "query_id": "old" }), Some(payer("0xa")));
+        std::thread::sleep(Duration::from_millis(5));
+        store.store(&json!({ "query_id": "new" }), Some(payer("0xa")));
+        assert!(matches!(
+            store.lookup("old", &payer("0xa")),
+            Lookup::Expired
+        ));
+
+        let store = store_with("first", Some(payer("0xa")));
+        for i in 0..MAX_REPLAY_ENTRIES {
+            store.store(&json!({ "query_id": i.to_string() }), Some(payer("0xa")));
+        }
+        assert!(matches!(
+            store.lookup("first", &payer("0xa")),
+            Lookup::Missing
+        ));
+        assert!(matches!(store.lookup("1", &payer("0xa")), Lookup::Found(_)));
+    }
+
+    fn app(store: Arc<ReplayStore>) -> Router {
+        Router::new()
+            .route("/discover/{query_id}", get(replay_handler))
+            .with_state(ReplayState {
+                store,
+                keys: Arc::new(ApiKeys::default()),
+            })
+    }
+
+    /// A replay presenting an `X-PAYMENT` from `from`, settled as `settled_as`.
+    fn replay(from: &str, signature: &str, settled_as: Option<&str>) -> Request {
+        let payment = json!({"payload": {"authorization": {"from": from}, "signature": signature}});
+        let request = Request::get("/discover/q1")
+            .header(PAYMENT_HEADER, STANDARD.encode(payment.to_string()))
+            .body(Body::empty())
+            .unwrap();
+        if let Some(payer) = settled_as {
+            SETTLED.record(
+                payment_digest(request.headers()).unwrap(),
+                payer.to_string(),
+            );
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn other_callers_cannot_replay_a_result() {
+        let owner = "0xreplay-owner";
+        let store = store_with("q1", Some(payer(owner)));
+
+        // claims the owner's address, but no such payment settled
+        let forged = replay(owner, "forged", None);
+        let response = app(Arc::clone(&store)).oneshot(forged).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let other = replay("0xother", "other", Some("0xother"));
+        let response = app(Arc::clone(&store)).oneshot(other).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app(store)
+            .oneshot(replay(owner, "paid", Some(owner)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(REPLAYED_HEADER));
+    }
+}
@@ -0,0 +1,108 @@
+// src/backend/request.rs
+//! JSON body extractor whose rejections name the offending field, so agent
+//! clients can fix a malformed request programmatically:
+//! `{ "error": { "code": "INVALID_REQUEST", "field": "filters.tags", "message": "..." } }`.
+use axum::{
+    extract::{FromRequest, Request, rejection::BytesRejection},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+pub const INVALID_REQUEST: &str = "INVALID_REQUEST";
+/// Body limit on `/discover`, applied with `DefaultBodyLimit` so the handler
+/// and every layer that reads the body ahead of it reject the same bodies.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub struct RequestError {
+    pub status: StatusCode,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl IntoResponse for RequestError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            AxumJson(json!({
+                "error": {
+                    "code": INVALID_REQUEST,
+                    "field": self.field,
+                    "message": self.message,
+                }
+            })),
+        )
+            .into_response()
+    }
+}
+
+/// serde reports a missing field at the parent's path; recover its name from
+/// the message ("missing field `query`").
+fn missing_field(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("missing field `")?;
+    rest.split_once('`').map(|(field, _)| field)
+}
+
+fn body_rejection(e: BytesRejection) -> RequestError {
+    RequestError {
+        status: e.status(),
+        field: None,
+        message: e.body_text(),
+    }
+}
+
+/// Buffers the body for middleware that must read it before the handler,
+/// under the route's body limit and with the same rejection as `ApiJson`.
+pub async fn buffer(request: Request) -> Result<(Parts, Bytes), RequestError> {
+    let (parts, body) = request.into_parts();
+    let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), &())
+        .await
+        .map_err(body_rejection)?;
+    Ok((parts, bytes))
+}
+
+/// Like `axum::Json`, but malformed bodies are rejected with a structured error:
+/// `400` for syntax errors, `422` for wrong types or missing fields.
+pub struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = RequestError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(body_rejection)?;
+
+        let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+        serde_path_to_error::deserialize(deserializer)
+            .map(ApiJson)
+            .map_err(|e| {
+                let path = e.path().to_string();
+                let inner = e.into_inner();
+                let message = inner.to_string();
+                let status = if inner.is_data() {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                } else {
+                    StatusCode::BAD_REQUEST
+                };
+                let field = match (path.as_str(), missing_field(&message)) {
+                    (".", Some(field)) => Some(field.to_string()),
+                    (".", None) => None,
+                    (parent, Some(field)) => Some(format!("{}.{}", parent, field)),
+                    (path, None) => Some(path.to_string()),
+                };
+                RequestError {
+                    status,
+                    field,
+                    message,
+                }
+            })
+    }
+}
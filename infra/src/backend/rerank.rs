@@ -0,0 +1,96 @@
+// src/backend/rerank.rs
+//! Optional post-retrieval re-ranking. When enabled, retrieval fetches a wider
+//! `LIBRARIAN_RERANK_FETCH_K` candidates, the selected strategy re-orders them,
+//! and only the best `top_k` go to the agent. `LIBRARIAN_RERANK` picks the
+//! strategy; the default `none` keeps cosine order and the narrow fetch.
+use super::McpEntry;
+use super::search::rank_order;
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+use std::env;
+
+const DEFAULT_RERANK_FETCH_K: usize = 10;
+/// Weight of the lexical overlap relative to the cosine similarity in `jaccard`.
+pub const JACCARD_WEIGHT: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RerankStrategy {
+    #[default]
+    None,
+    /// Blends similarity with the Jaccard overlap between query terms and the
+    /// entry's capability and description terms.
+    Jaccard,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reranker {
+    pub strategy: RerankStrategy,
+    pub fetch_k: usize,
+}
+
+impl Reranker {
+    pub fn from_env() -> Result<Self> {
+        let strategy = match env::var("LIBRARIAN_RERANK").as_deref() {
+            Err(_) | Ok("none") => RerankStrategy::None,
+            Ok("jaccard") => RerankStrategy::Jaccard,
+            Ok(other) => bail!(
+                "Unknown LIBRARIAN_RERANK {:?} (expected none or jaccard)",
+                other
+            ),
+        };
+        let fetch_k = env::var("LIBRARIAN_RERANK_FETCH_K")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_RERANK_FETCH_K);
+        Ok(Reranker { strategy, fetch_k })
+    }
+
+    /// How many candidates to pull from the index for a final set of `top_k`.
+    pub fn fetch_k(&self, top_k: usize) -> usize {
+        match self.strategy {
+            RerankStrategy::None => top_k,
+            _ => self.fetch_k.max(top_k),
+        }
+    }
+
+    /// Re-orders `candidates` for `query` and keeps the best `top_k`.
+    pub fn rerank(&self, query: &str, candidates: &mut Vec<(f64, McpEntry)>, top_k: usize) {
+        match self.strategy {
+            RerankStrategy::None => {}
+            RerankStrategy::Jaccard => {
+                let query_terms = terms(query);
+                for (score, entry) in candidates.iter_mut() {
+                    *score += JACCARD_WEIGHT * overlap(&query_terms, entry);
+                }
+                candidates.sort_by(rank_order);
+            }
+        }
+        candidates.truncate(top_k);
+    }
+}
+
+/// Lowercased words of more than two characters.
+pub fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.len() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Jaccard overlap between `query_terms` and the terms of the entry's
+/// description and capabilities.
+pub fn overlap(query_terms: &HashSet<String>, entry: &McpEntry) -> f64 {
+    let mut entry_terms = terms(&entry.desc);
+    for capability in &entry.capabilities {
+        entry_terms.extend(terms(capability));
+    }
+    jaccard(query_terms, &entry_terms)
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
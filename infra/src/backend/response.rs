@@ -0,0 +1,259 @@
+// src/backend/response.rs
+use super::McpEntry;
+use super::{redact, session};
+use axum::{
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+pub const SERVICE_ACKNOWLEDGEMENT: &str = "Thank you for using the Librarian Service.";
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// The canonical no-match response the preamble specifies.
+pub fn empty_response(query: &str) -> Value {
+    json!({
+        "service_acknowledgement": SERVICE_ACKNOWLEDGEMENT,
+        "query": query,
+        "recommendations": [],
+        "instructions": {},
+    })
+}
+
+/// Minimal shape for callers that only need where to connect: `name`,
+/// `endpoint` and `score` per recommendation, without `instructions`. A
+/// `query_id` is kept so compact callers can still send feedback.
+pub fn compact(response: &Value) -> Value {
+    let recommendations: Vec<Value> = response
+        .get("recommendations")
+        .and_then(Value::as_array)
+        .map(|recs| {
+            recs.iter()
+                .map(|rec| {
+                    json!({
+                        "name": rec.get("name").cloned().unwrap_or(Value::Null),
+                        "endpoint": rec.get("endpoint").cloned().unwrap_or(Value::Null),
+                        "score": rec.get("score").cloned().unwrap_or(Value::Null),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut body = json!({
+        "query": response.get("query").cloned().unwrap_or(Value::Null),
+        "recommendations": recommendations,
+    });
+    if let Some(query_id) = response.get("query_id") {
+        body["query_id"] = query_id.clone();
+    }
+    body
+}
+
+/// One recommendation built from the catalog alone, without the model. Read
+/// from `redact::public_value`, so a redacted field is left out (or empty where
+/// the shape requires it).
+pub fn catalog_recommendation(
+    entry: &McpEntry,
+    score: f64,
+    protocol_version: &str,
+    rationale: &str,
+) -> Value {
+    let public = redact::public_value(entry);
+    let field = |name: &str| public.get(name).cloned();
+    let mut rec = json!({
+        "name": entry.name,
+        "endpoint": entry.endpoint,
+        "protocol_version": protocol_version,
+        "capabilities": {
+            "tools": field("capabilities").unwrap_or_else(|| json!([])),
+            "resources": [],
+            "prompts": [],
+        },
+        "score": (score.clamp(0.0, 1.0) * 100.0).round() as u64,
+        "rationale": rationale,
+        "overview": field("desc").unwrap_or_else(|| json!("")),
+        "verification_status": "catalog_only",
+        "last_checked": "",
+    });
+    for name in ["transport", "auth", "version", "rate_limit"] {
+        if let Some(value) = field(name) {
+            rec[name] = value;
+        }
+    }
+    rec
+}
+
+/// Recommendations straight from retrieval, used when the agent is unavailable.
+/// Scores are the similarity as a percentage; `instructions` are left to
+/// `session::attach`.
+pub fn catalog_only(query: &str, candidates: &[(f64, McpEntry)], protocol_version: &str) -> Value {
+    let recommendations: Vec<Value> = candidates
+        .iter()
+        .take(3)
+        .map(|(score, entry)| {
+            catalog_recommendation(
+                entry,
+                *score,
+                protocol_version,
+                "Ranked by catalog similarity; the recommendation model is currently unavailable.",
+            )
+        })
+        .collect();
+
+    json!({
+        "service_acknowledgement": SERVICE_ACKNOWLEDGEMENT,
+        "query": query,
+        "recommendations": recommendations,
+        "instructions": {},
+    })
+}
+
+/// Newline-delimited JSON for pipeline consumers: one line per element of
+/// `response[items_key]`, then a terminating `{"summary": ...}` line holding the
+/// remaining top-level fields and the item count. Only successful responses
+/// use this shape; errors and payment challenges stay plain JSON.
+pub fn ndjson(response: &Value, items_key: &str) -> Response {
+    let mut summary = response.as_object().cloned().unwrap_or_default();
+    let items = summary
+        .remove(items_key)
+        .and_then(|v| match v {
+            Value::Array(items) => Some(items),
+            _ => None,
+        })
+        .unwrap_or_default();
+    summary.insert("count".to_string(), json!(items.len()));
+
+    let mut body = String::new();
+    for item in items
+        .iter()
+        .chain(std::iter::once(&json!({ "summary": summary })))
+    {
+        body.push_str(&item.to_string());
+        body.push('\n');
+    }
+    ([(CONTENT_TYPE, NDJSON_CONTENT_TYPE)], body).into_response()
+}
+
+pub const SCRIPT_CONTENT_TYPE: &str = "text/x-shellscript";
+
+/// Single-quotes `value` for POSIX shells; embedded quotes become `'\''`.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Collapses control characters so text can't break out of a `#` comment.
+fn comment_text(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// A bash script that runs each recommended server's `session::plan`:
+/// initialize, list its tools, close. Commands come from the structured fields
+/// with every value shell-quoted; the example `tools/call` is left commented
+/// out, since its arguments need filling in. Servers requiring auth read the
+/// header value from `MCP_TOKEN_<n>`.
+pub fn script(response: &Value) -> Response {
+    let mut out = String::from("#!/usr/bin/env bash\n");
+    let query = response
+        .get("query")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    out.push_str(&format!(
+        "# Librarian recommendations for: {}\n",
+        comment_text(query)
+    ));
+    out.push_str("set -euo pipefail\n\nWORKDIR=$(mktemp -d)\ntrap 'rm -rf \"$WORKDIR\"' EXIT\n");
+
+    let recommendations = response
+        .get("recommendations")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    if recommendations.is_empty() {
+        out.push_str("\necho 'No recommendations for this query.' >&2\n");
+    }
+    for (i, rec) in recommendations.iter().enumerate() {
+        let n = i + 1;
+        let field = |key: &str| rec.get(key).and_then(Value::as_str).unwrap_or_default();
+        out.push_str(&format!(
+            "\n# {}. {} (score {})\nENDPOINT_{n}={}\n",
+            n,
+            comment_text(field("name")),
+            rec.get("score").and_then(Value::as_u64).unwrap_or_default(),
+            shell_quote(field("endpoint")),
+        ));
+
+        let steps = session::plan(rec);
+        if steps.iter().any(|step| {
+            step.headers
+                .values()
+                .any(|v| v.contains(session::TOKEN_PLACEHOLDER))
+        }) {
+            out.push_str(&format!(
+                ": \"${{MCP_TOKEN_{n}:?set MCP_TOKEN_{n} to the credential for server {n}}}\"\n"
+            ));
+        }
+        let (endpoint, session_var, token_var) = (
+            format!("\"$ENDPOINT_{n}\""),
+            format!("SESSION_{n}"),
+            format!("MCP_TOKEN_{n}"),
+        );
+        let headers_file = format!("\"$WORKDIR/{n}.headers\"");
+        let vars = session::ShellVars {
+            endpoint: &endpoint,
+            session: &session_var,
+            token: &token_var,
+            headers_file: &headers_file,
+        };
+        for step in &steps {
+            let command = session::curl(step, &vars);
+            if step.example {
+                out.push_str(&format!("# {}\n", command));
+                continue;
+            }
+            out.push_str(&command);
+            out.push('\n');
+            if step.capture_header.is_some() {
+                out.push_str(&session::extract_session(&vars));
+                out.push('\n');
+            }
+        }
+    }
+    ([(CONTENT_TYPE, SCRIPT_CONTENT_TYPE)], out).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // unit tests redact `desc` and `capabilities` (see `redact::configured`)
+    fn entry() -> McpEntry {
+        serde_json::from_value(json!({
+            "name": "weather",
+            "endpoint": "https://weather.example/mcp",
+            "version": "1.2.0",
+            "capabilities": ["forecast"],
+            "desc": "Private description",
+            "internal_notes": "internal only",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn catalog_recommendation_leaves_out_redacted_fields() {
+        let rec = catalog_recommendation(&entry(), 0.5, "2025-06-18", "because");
+        let text = rec.to_string();
+        assert!(!text.contains("forecast"));
+        assert!(!text.contains("Private description"));
+        assert!(!text.contains("internal only"));
+        assert_eq!(rec["capabilities"]["tools"], json!([]));
+        assert_eq!(rec["overview"], "");
+        assert_eq!(rec["name"], "weather");
+        assert_eq!(rec["endpoint"], "https://weather.example/mcp");
+        assert_eq!(rec["version"], "1.2.0");
+        assert_eq!(rec["score"], 50);
+    }
+}
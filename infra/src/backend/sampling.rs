@@ -0,0 +1,176 @@
+// src/backend/sampling.rs
+//! Quality-audit logging of what the model saw and said. For a sampled
+//! fraction of `/discover` requests, every model call's assembled prompt and
+//! raw output are logged at `info` under the `librarian::prompt_sample` target,
+//! with the request's `query_id` so a sample joins up with its response,
+//! feedback and metrics. Route that target to its own sink with `RUST_LOG` /
+//! the subscriber's filters.
+//!
+//! - `LIBRARIAN_PROMPT_SAMPLE_RATE`: fraction of requests, `0`–`1` (default
+//!   `0`, off).
+//! - `LIBRARIAN_PROMPT_SAMPLE_MAX_BYTES`: each logged text is cut to this
+//!   many bytes (default 16384); the full length is logged alongside.
+//!
+//! The decision hashes the request id, so a request is either sampled for all
+//! of its calls (a corrective reprompt, the fallback model) or for none, and
+//! it's reproducible from the id alone. Email addresses and runs of 9 or more
+//! digits (phone, card and account numbers) are masked before anything is
+//! logged.
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::LazyLock;
+
+pub const SAMPLE_TARGET: &str = "librarian::prompt_sample";
+const DEFAULT_MAX_BYTES: usize = 16 * 1024;
+
+/// `LIBRARIAN_PROMPT_SAMPLE_RATE`, read once and clamped to `0..=1`.
+static RATE: LazyLock<f64> = LazyLock::new(|| {
+    env::var("LIBRARIAN_PROMPT_SAMPLE_RATE")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|rate| rate.is_finite())
+        .map(|rate| rate.clamp(0.0, 1.0))
+        .unwrap_or(0.0)
+});
+
+static MAX_BYTES: LazyLock<usize> = LazyLock::new(|| {
+    env::var("LIBRARIAN_PROMPT_SAMPLE_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_BYTES)
+});
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").expect("valid email pattern")
+});
+/// Digits optionally split by spaces or dashes, as numbers are usually written.
+static LONG_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d(?:[ -]?\d){8,}").expect("valid number pattern"));
+
+pub fn rate() -> f64 {
+    *RATE
+}
+
+/// Whether `request_id` falls in the sample: the first 8 bytes of its SHA-256,
+/// as a fraction of `u64::MAX`, below the rate.
+pub fn is_sampled_at(request_id: &str, rate: f64) -> bool {
+    if rate <= 0.0 {
+        return false;
+    }
+    if rate >= 1.0 {
+        return true;
+    }
+    let digest = Sha256::digest(request_id.as_bytes());
+    let mut head = [0u8; 8];
+    head.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(head) as f64 / u64::MAX as f64) < rate
+}
+
+pub fn is_sampled(request_id: &str) -> bool {
+    is_sampled_at(request_id, rate())
+}
+
+/// `text` with emails and long numbers masked, cut to `max_bytes` on a
+/// character boundary.
+pub fn scrub(text: &str, max_bytes: usize) -> String {
+    let masked = EMAIL.replace_all(text, "[email]");
+    let masked = LONG_NUMBER.replace_all(&masked, "[number]");
+    if masked.len() <= max_bytes {
+        return masked.into_owned();
+    }
+    let mut cut = max_bytes;
+    while !masked.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}…[truncated]", &masked[..cut])
+}
+
+/// Logs one model call of a sampled request; does nothing otherwise.
+/// `attempt` is `initial` or `reprompt`.
+pub fn log_call(request_id: &str, attempt: &str, model: &str, prompt: &str, output: &str) {
+    if !is_sampled(request_id) {
+        return;
+    }
+    tracing::info!(
+        target: SAMPLE_TARGET,
+        query_id = request_id,
+        attempt,
+        model,
+        prompt_bytes = prompt.len(),
+        output_bytes = output.len(),
+        prompt = %scrub(prompt, *MAX_BYTES),
+        output = %scrub(output, *MAX_BYTES),
+        "Sampled discover model call"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_is_decided_by_the_request_id_alone() {
+        let ids = [
+            "0b6f3c2e-8d7a-4b51-9f0e-2a7c1d9e4f10",
+            "5e2a9c71-3b4d-4e8f-a1c2-7d6b0e9f3a24",
+            "c4d8e1f0-7a2b-4c39-8e5d-1f0a9b6c2d37",
+            "9a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d",
+            "e7f60a5b-2c91-4d38-b7e4-6a5f0c1d2e98",
+        ];
+        let sampled: Vec<bool> = ids.iter().map(|id| is_sampled_at(id, 0.1)).collect();
+        assert_eq!(sampled, [false, false, true, true, true]);
+        // a lower rate only ever drops requests a higher one kept
+        assert!(
+            ids.iter()
+                .all(|id| !is_sampled_at(id, 0.01) || is_sampled_at(id, 0.1))
+        );
+        assert!(
+            ids.iter()
+                .all(|id| !is_sampled_at(id, 0.0) && is_sampled_at(id, 1.0))
+        );
+    }
+
+    #[test]
+    fn rate_is_the_sampled_fraction() {
+        let sampled = (0..1000)
+            .filter(|i| is_sampled_at(&format!("request-{}", i), 0.1))
+            .count();
+        assert_eq!(sampled, 92);
+    }
+
+    #[test]
+    fn scrub_masks_emails_and_long_numbers() {
+        assert_eq!(
+            scrub("weather forecast for Paris", 64),
+            "weather forecast for Paris"
+        );
+        assert_eq!(
+            scrub(
+                "email me at jane.doe+mcp@example.co.uk or call +33 6 12 34 56 78",
+                64
+            ),
+            "email me at [email] or call +[number]"
+        );
+        // eight digits may be an order number; nine or more are masked
+        assert_eq!(
+            scrub("card 4111-1111-1111-1111, order 12345678 stays", 64),
+            "card [number], order 12345678 stays"
+        );
+    }
+
+    #[test]
+    fn scrub_cuts_on_a_character_boundary() {
+        let text = format!("candidates: {}", "é".repeat(40));
+        // 12 ASCII bytes, then 2-byte characters: 63 bytes would split one
+        assert_eq!(
+            scrub(&text, 63),
+            format!("candidates: {}…[truncated]", "é".repeat(25))
+        );
+        assert_eq!(
+            scrub(&text, 64),
+            format!("candidates: {}…[truncated]", "é".repeat(26))
+        );
+        assert_eq!(scrub(&text, text.len()), text);
+    }
+}
@@ -0,0 +1,65 @@
+// src/backend/schema.rs
+//! The published contract for `/discover` output. The model's JSON is checked
+//! against this before it is returned, so extra or missing fields and
+//! out-of-range values (e.g. `score` outside 0–100) never reach callers.
+use axum::response::Json as AxumJson;
+use jsonschema::Validator;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+pub const DISCOVER_SCHEMA: &str = include_str!("../../schemas/discover_response.schema.json");
+
+static DISCOVER_SCHEMA_VALUE: LazyLock<Value> = LazyLock::new(|| {
+    serde_json::from_str(DISCOVER_SCHEMA).expect("embedded discover schema is valid JSON")
+});
+
+static DISCOVER_VALIDATOR: LazyLock<Validator> = LazyLock::new(|| {
+    jsonschema::validator_for(&DISCOVER_SCHEMA_VALUE).expect("embedded discover schema compiles")
+});
+
+/// Validates a `/discover` response, returning one message per violation
+/// (`<instance path>: <error>`).
+pub fn validate_discover(response: &Value) -> Result<(), Vec<String>> {
+    let errors: Vec<String> = DISCOVER_VALIDATOR
+        .iter_errors(response)
+        .map(|e| format!("{}: {}", e.instance_path, e))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Explain mode adds a free-text `explanation` to each recommendation; it must be
+/// a string, and everything else is held to the strict schema.
+pub fn validate_discover_explained(response: &Value) -> Result<(), Vec<String>> {
+    let mut stripped = response.clone();
+    let mut errors = Vec::new();
+    if let Some(recommendations) = stripped
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    {
+        for (i, rec) in recommendations.iter_mut().enumerate() {
+            let explanation = rec.as_object_mut().and_then(|r| r.remove("explanation"));
+            if explanation.is_some_and(|e| !e.is_string()) {
+                errors.push(format!(
+                    "/recommendations/{}/explanation: is not a string",
+                    i
+                ));
+            }
+        }
+    }
+    if let Err(schema_errors) = validate_discover(&stripped) {
+        errors.extend(schema_errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+pub async fn discover_schema_handler() -> AxumJson<Value> {
+    AxumJson(DISCOVER_SCHEMA_VALUE.clone())
+}
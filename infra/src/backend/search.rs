@@ -0,0 +1,283 @@
+// src/backend/search.rs
+use super::filters::{CandidateFilter, FILTER_STATS_HEADER, FilterStage};
+use super::{CatalogIndex, Librarian, LibrarianHandle, McpEntry, capindex, mirrors};
+use anyhow::Result;
+use axum::{
+    extract::{Json, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use rig::embeddings::EmbeddingModel as _;
+use rig::vector_store::{VectorSearchRequest, VectorStoreIndex};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+use std::env;
+use std::sync::Arc;
+
+pub const DEFAULT_TOP_K: usize = 3;
+const MAX_SEARCH_LIMIT: usize = 20;
+pub const DEFAULT_FEATURED_BOOST_CAP: f64 = 0.05;
+/// Similarity bonus for entries in the caller's preferred region.
+pub const REGION_BOOST: f64 = 0.03;
+
+/// Best score first; equal scores fall back to the endpoint, so ties come out
+/// in the same order on every run.
+pub fn rank_order(a: &(f64, McpEntry), b: &(f64, McpEntry)) -> Ordering {
+    b.0.total_cmp(&a.0)
+        .then_with(|| a.1.endpoint.cmp(&b.1.endpoint))
+}
+
+/// Queries the vector index and returns `(similarity, entry)` pairs, best match first.
+pub async fn retrieve(index: &CatalogIndex, query: &str, k: usize) -> Result<Vec<(f64, McpEntry)>> {
+    let request = VectorSearchRequest::builder()
+        .query(query)
+        .samples(k as u64)
+        .build()?;
+
+    let results = index.top_n::<McpEntry>(request).await?;
+    let mut scored: Vec<(f64, McpEntry)> = results
+        .into_iter()
+        .map(|(score, _id, entry)| (score, entry))
+        .collect();
+    // don't rely on the store's ordering; callers threshold on these scores
+    scored.sort_by(rank_order);
+    Ok(scored)
+}
+
+/// `retrieve`, except that a capability filter first narrows the universe via
+/// the capability index and only those entries are ranked, so the filter no
+/// longer discards most of what was fetched. Without one, or with a soft one,
+/// this is `retrieve`.
+pub async fn retrieve_filtered(
+    librarian: &Librarian,
+    query: &str,
+    filter: &CandidateFilter,
+    k: usize,
+) -> Result<Vec<(f64, McpEntry)>> {
+    let Some(capability) = filter
+        .capability
+        .as_ref()
+        .filter(|_| !filter.is_soft(FilterStage::Capability))
+    else {
+        return retrieve(&librarian.index, query, k).await;
+    };
+    let ids = librarian.capability_index.lookup(capability);
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let embedded = librarian.embedding_model.embed_text(query).await?;
+    Ok(capindex::rank(
+        &embedded.vec,
+        &librarian.embeddings,
+        &ids,
+        k,
+    ))
+}
+
+/// Upper bound on any `featured_boost`, from `LIBRARIAN_FEATURED_BOOST_CAP`.
+/// A featured entry can overtake a non-featured one only when their
+/// similarities are within this margin.
+pub fn featured_boost_cap() -> f64 {
+    env::var("LIBRARIAN_FEATURED_BOOST_CAP")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|cap| cap.is_finite() && *cap >= 0.0)
+        .unwrap_or(DEFAULT_FEATURED_BOOST_CAP)
+}
+
+/// Adds each entry's `featured_boost`, clamped to `[0, cap]`, to its score and
+/// re-sorts best first.
+pub fn apply_featured_boosts(candidates: &mut [(f64, McpEntry)], cap: f64) {
+    for (score, entry) in candidates.iter_mut() {
+        let Some(boost) = entry.featured_boost else {
+            continue;
+        };
+        let applied = f64::from(boost).clamp(0.0, cap);
+        if applied > 0.0 {
+            tracing::debug!(
+                name = %entry.name,
+                similarity = *score,
+                boost = applied,
+                requested = boost,
+                "Applied featured boost"
+            );
+            *score += applied;
+        }
+    }
+    candidates.sort_by(rank_order);
+}
+
+/// Adds `REGION_BOOST` to entries whose region matches `preferred` and re-sorts.
+pub fn apply_region_preference(candidates: &mut [(f64, McpEntry)], preferred: Option<&str>) {
+    let Some(preferred) = preferred else {
+        return;
+    };
+    for (score, entry) in candidates.iter_mut() {
+        if mirrors::in_region(entry, preferred) {
+            *score += REGION_BOOST;
+        }
+    }
+    candidates.sort_by(rank_order);
+}
+
+#[derive(Deserialize)]
+pub struct SearchRequest {
+    pub query: String,
+    pub filters: Option<Value>,
+    #[serde(default)]
+    pub allow_auth: bool,
+    pub limit: Option<usize>,
+}
+
+/// `?debug=true` adds retrieval diagnostics to the response; `?format=ndjson`
+/// returns one hit per line plus a summary line.
+#[derive(Deserialize, Default)]
+pub struct DebugParams {
+    #[serde(default)]
+    pub debug: bool,
+    pub format: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub score: f64,
+    /// The entry after `redact::public_value`.
+    #[serde(flatten)]
+    pub entry: Value,
+}
+
+/// Retrieval-only lookup: ranks catalog entries by similarity without calling the model.
+#[tracing::instrument(skip_all)]
+pub async fn search_handler(
+    State(handle): State<LibrarianHandle>,
+    Query(params): Query<DebugParams>,
+    Json(req): Json<SearchRequest>,
+) -> Response {
+    if let Err(e) = super::sanitize::check_query_length(&req.query) {
+        return e.into_response();
+    }
+    let librarian = handle.current();
+    let limit = req
+        .limit
+        .unwrap_or(DEFAULT_TOP_K)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    let filter = CandidateFilter::from_request(
+        req.filters.as_ref(),
+        req.allow_auth,
+        Arc::clone(&librarian.policy),
+    );
+
+    let (retrieval_query, expansions) = librarian.expand_query(&req.query);
+
+    match retrieve_filtered(&librarian, &retrieval_query, &filter, limit).await {
+        Ok(candidates) => {
+            let (mut kept, stats) = filter.apply(candidates);
+            apply_region_preference(&mut kept, filter.region.as_deref());
+            apply_featured_boosts(&mut kept, featured_boost_cap());
+            let results: Vec<SearchHit> = kept
+                .into_iter()
+                .map(|(score, entry)| SearchHit {
+                    score,
+                    entry: super::redact::public_value(&entry),
+                })
+                .collect();
+            let mut body = json!({ "query": req.query, "results": results });
+            if params.debug {
+                body["debug"] = json!({
+                    "retrieval_query": retrieval_query,
+                    "expansions": expansions,
+                    "filter_stats": stats,
+                });
+            }
+            if params.format.as_deref() == Some("ndjson") {
+                return (
+                    [(FILTER_STATS_HEADER, stats.header_value())],
+                    librarian.model_headers(),
+                    super::response::ndjson(&body, "results"),
+                )
+                    .into_response();
+            }
+            (
+                StatusCode::OK,
+                [(FILTER_STATS_HEADER, stats.header_value())],
+                librarian.model_headers(),
+                AxumJson(body),
+            )
+                .into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            AxumJson(json!({ "error": format!("Search failed: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> McpEntry {
+        serde_json::from_value(json!({
+            "name": name,
+            "endpoint": format!("https://{}.example.com/mcp", name),
+            "version": "1.0.0",
+            "capabilities": [],
+            "desc": "",
+        }))
+        .unwrap()
+    }
+
+    fn names(candidates: &[(f64, McpEntry)]) -> Vec<&str> {
+        candidates
+            .iter()
+            .map(|(_, entry)| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn results_sort_descending_by_score() {
+        let mut scored = vec![
+            (0.41, entry("maps")),
+            (0.87, entry("weather")),
+            (0.41, entry("geocode")),
+            (0.63, entry("alerts")),
+        ];
+        scored.sort_by(rank_order);
+        assert!(scored.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+        // equal scores come out by endpoint
+        assert_eq!(names(&scored), ["weather", "alerts", "geocode", "maps"]);
+    }
+
+    fn featured(name: &str, boost: f32) -> McpEntry {
+        McpEntry {
+            featured_boost: Some(boost),
+            ..entry(name)
+        }
+    }
+
+    #[test]
+    fn featured_boost_lifts_a_close_match() {
+        let mut candidates = vec![(0.80, entry("weather")), (0.78, featured("forecast", 0.04))];
+        apply_featured_boosts(&mut candidates, DEFAULT_FEATURED_BOOST_CAP);
+        assert_eq!(names(&candidates), ["forecast", "weather"]);
+        assert!((candidates[0].0 - 0.82).abs() < 1e-9);
+    }
+
+    #[test]
+    fn featured_boost_is_capped_below_a_strong_match() {
+        let mut candidates = vec![(0.90, entry("weather")), (0.40, featured("forecast", 10.0))];
+        apply_featured_boosts(&mut candidates, DEFAULT_FEATURED_BOOST_CAP);
+        assert_eq!(names(&candidates), ["weather", "forecast"]);
+        assert!((candidates[1].0 - (0.40 + DEFAULT_FEATURED_BOOST_CAP)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn negative_featured_boost_is_ignored() {
+        let mut candidates = vec![(0.50, featured("forecast", -1.0)), (0.45, entry("weather"))];
+        apply_featured_boosts(&mut candidates, DEFAULT_FEATURED_BOOST_CAP);
+        assert_eq!(candidates[0].0, 0.50);
+        assert_eq!(names(&candidates), ["forecast", "weather"]);
+    }
+}
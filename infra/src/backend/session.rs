@@ -0,0 +1,401 @@
+// src/backend/session.rs
+//! The MCP session lifecycle for one recommendation as typed steps:
+//! `initialize`, the `initialized` notification, `tools/list`, an example
+//! `tools/call` and the closing `DELETE`. The `session_plan` on each
+//! recommendation, its `instructions` entry and `?format=script` are all
+//! rendered from `plan`, so they can't disagree.
+//!
+//! `LIBRARIAN_INSTRUCTIONS` picks who writes the `instructions` block:
+//! `generated` (default) builds every entry here and asks the model to leave it
+//! empty, saving the tokens it would spend on boilerplate; `model` keeps the
+//! model's entries and only re-renders their `curl` strings.
+use super::response::shell_quote;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::LazyLock;
+
+/// Stand-ins a client substitutes before sending: the `Mcp-Session-Id` the
+/// initialize response returned, and the full value of the auth header.
+pub const SESSION_ID_PLACEHOLDER: &str = "{session_id}";
+pub const TOKEN_PLACEHOLDER: &str = "{token}";
+pub const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStep {
+    /// JSON-RPC method, or `close` for the session `DELETE`.
+    pub method: String,
+    pub http_method: &'static str,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Response header later steps need.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture_header: Option<&'static str>,
+    /// Illustrative: fill in the arguments before sending.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub example: bool,
+}
+
+/// The lifecycle for a recommendation, from its `endpoint`,
+/// `protocol_version`, `auth` and (verified) `capabilities.tools`.
+pub fn plan(rec: &Value) -> Vec<SessionStep> {
+    let field = |key: &str| rec.get(key).and_then(Value::as_str).unwrap_or_default();
+    let url = field("endpoint").to_string();
+
+    let mut auth = BTreeMap::new();
+    if rec.pointer("/auth/required").and_then(Value::as_bool) == Some(true) {
+        let header = rec
+            .pointer("/auth/header")
+            .and_then(Value::as_str)
+            .filter(|h| !h.is_empty())
+            .unwrap_or("Authorization");
+        auth.insert(header.to_string(), TOKEN_PLACEHOLDER.to_string());
+    }
+    let mut json_headers = auth.clone();
+    json_headers.insert("Content-Type".to_string(), "application/json".to_string());
+    json_headers.insert(
+        "Accept".to_string(),
+        "application/json, text/event-stream".to_string(),
+    );
+    let with_session = |mut headers: BTreeMap<String, String>| {
+        headers.insert(
+            SESSION_HEADER.to_string(),
+            SESSION_ID_PLACEHOLDER.to_string(),
+        );
+        headers
+    };
+    let post = |method: &str, body: Value| SessionStep {
+        method: method.to_string(),
+        http_method: "POST",
+        url: url.clone(),
+        headers: with_session(json_headers.clone()),
+        body: Some(body),
+        capture_header: None,
+        example: false,
+    };
+
+    let mut steps = vec![
+        SessionStep {
+            headers: json_headers.clone(),
+            capture_header: Some(SESSION_HEADER),
+            ..post(
+                "initialize",
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": "init-1",
+                    "method": "initialize",
+                    "params": {
+                        "protocolVersion": field("protocol_version"),
+                        "capabilities": {},
+                        "clientInfo": { "name": "Agent", "version": "1.0" },
+                    },
+                }),
+            )
+        },
+        post(
+            "notifications/initialized",
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        ),
+        post(
+            "tools/list",
+            json!({ "jsonrpc": "2.0", "id": "2", "method": "tools/list" }),
+        ),
+    ];
+    if let Some(tool) = rec.pointer("/capabilities/tools/0").and_then(Value::as_str) {
+        steps.push(SessionStep {
+            example: true,
+            ..post(
+                "tools/call",
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": "3",
+                    "method": "tools/call",
+                    "params": { "name": tool, "arguments": {} },
+                }),
+            )
+        });
+    }
+    steps.push(SessionStep {
+        method: "close".to_string(),
+        http_method: "DELETE",
+        url: url.clone(),
+        headers: with_session(auth),
+        body: None,
+        capture_header: None,
+        example: false,
+    });
+    steps
+}
+
+/// Shell spellings for a rendered step: `endpoint` is a shell word (a quoted
+/// URL or `"$VAR"`), `session` and `token` are variable names.
+pub struct ShellVars<'a> {
+    pub endpoint: &'a str,
+    pub session: &'a str,
+    pub token: &'a str,
+    /// Where the capturing step dumps response headers.
+    pub headers_file: &'a str,
+}
+
+/// `text` as one shell word, with the placeholders expanded from `vars` and
+/// everything else single-quoted.
+fn shell_word(text: &str, vars: &ShellVars) -> String {
+    let mut word = String::new();
+    let mut rest = text;
+    loop {
+        let next = [
+            (SESSION_ID_PLACEHOLDER, vars.session),
+            (TOKEN_PLACEHOLDER, vars.token),
+        ]
+        .into_iter()
+        .filter_map(|(placeholder, var)| rest.find(placeholder).map(|at| (at, placeholder, var)))
+        .min_by_key(|(at, _, _)| *at);
+        let Some((at, placeholder, var)) = next else {
+            if !rest.is_empty() || word.is_empty() {
+                word.push_str(&shell_quote(rest));
+            }
+            return word;
+        };
+        if at > 0 {
+            word.push_str(&shell_quote(&rest[..at]));
+        }
+        word.push_str(&format!("\"${}\"", var));
+        rest = &rest[at + placeholder.len()..];
+    }
+}
+
+pub fn curl(step: &SessionStep, vars: &ShellVars) -> String {
+    let mut command = String::from("curl -sS");
+    if step.capture_header.is_some() {
+        command.push_str(&format!(" -D {}", vars.headers_file));
+    }
+    command.push_str(&format!(" -X {} {}", step.http_method, vars.endpoint));
+    for (name, value) in &step.headers {
+        command.push_str(&format!(
+            " -H {}",
+            shell_word(&format!("{}: {}", name, value), vars)
+        ));
+    }
+    if let Some(body) = &step.body {
+        command.push_str(&format!(" --data {}", shell_quote(&body.to_string())));
+    }
+    command
+}
+
+/// Reads the captured session id into `vars.session`.
+pub fn extract_session(vars: &ShellVars) -> String {
+    format!(
+        "{}=$(awk 'tolower($1) == \"{}:\" {{print $2}}' {} | tr -d '\\r')",
+        vars.session,
+        SESSION_HEADER.to_lowercase(),
+        vars.headers_file
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstructionsSource {
+    #[default]
+    Generated,
+    Model,
+}
+
+impl InstructionsSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            InstructionsSource::Generated => "generated",
+            InstructionsSource::Model => "model",
+        }
+    }
+}
+
+pub static INSTRUCTIONS_SOURCE: LazyLock<InstructionsSource> =
+    LazyLock::new(|| match env::var("LIBRARIAN_INSTRUCTIONS").as_deref() {
+        Err(_) | Ok("generated") => InstructionsSource::Generated,
+        Ok("model") => InstructionsSource::Model,
+        Ok(other) => {
+            tracing::warn!(
+                "Unknown LIBRARIAN_INSTRUCTIONS {:?}, generating instructions",
+                other
+            );
+            InstructionsSource::Generated
+        }
+    });
+
+/// `instructions.<name>.curl` key for a step.
+fn curl_key(method: &str) -> &str {
+    match method {
+        "notifications/initialized" => "initialized",
+        "tools/list" => "list_tools",
+        "tools/call" => "call_example",
+        other => other,
+    }
+}
+
+fn next_step(step: &SessionStep) -> String {
+    match step.method.as_str() {
+        "initialize" => format!(
+            "POST initialize and capture the {} response header",
+            SESSION_HEADER
+        ),
+        "tools/list" => "POST tools/list to discover the available tools".to_string(),
+        "tools/call" => "POST tools/call with the tool's arguments filled in".to_string(),
+        "close" => "DELETE to close the session when done".to_string(),
+        other => format!("POST {}", other),
+    }
+}
+
+/// A complete `instructions` entry for `steps`, around the already rendered
+/// `curl` commands.
+fn instructions(steps: &[SessionStep], commands: serde_json::Map<String, Value>) -> Value {
+    let initialize = steps.first();
+    let mut next_steps: Vec<String> = Vec::new();
+    if let Some(header) = initialize.and_then(|step| {
+        step.headers
+            .iter()
+            .find(|(_, value)| value.contains(TOKEN_PLACEHOLDER))
+            .map(|(name, _)| name)
+    }) {
+        next_steps.push(format!(
+            "Set MCP_TOKEN to the full value of the {} header",
+            header
+        ));
+    }
+    next_steps.extend(steps.iter().map(next_step));
+    json!({
+        "http_only": true,
+        "headers": initialize.map(|step| json!(step.headers)).unwrap_or_else(|| json!({})),
+        "initialize_call": initialize.and_then(|step| step.body.clone()).unwrap_or_else(|| json!({})),
+        "curl": commands,
+        "next_steps": next_steps,
+    })
+}
+
+/// Adds `session_plan` to every recommendation and renders its `instructions`
+/// entry from the same plan: the whole entry by default, or only the `curl`
+/// strings of the model's entry under `LIBRARIAN_INSTRUCTIONS=model`.
+pub fn attach(response: &mut Value) {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    let mut rendered: Vec<(String, Vec<SessionStep>, serde_json::Map<String, Value>)> = Vec::new();
+    for rec in recommendations.iter_mut() {
+        let steps = plan(rec);
+        let endpoint = shell_quote(
+            rec.get("endpoint")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+        );
+        let vars = ShellVars {
+            endpoint: &endpoint,
+            session: "SESSION",
+            token: "MCP_TOKEN",
+            headers_file: "init.headers",
+        };
+        let mut commands = serde_json::Map::new();
+        for step in &steps {
+            commands.insert(curl_key(&step.method).to_string(), json!(curl(step, &vars)));
+            if step.capture_header.is_some() {
+                commands.insert("extract_session".to_string(), json!(extract_session(&vars)));
+            }
+        }
+        rec["session_plan"] = json!(steps);
+        if let Some(name) = rec.get("name").and_then(Value::as_str) {
+            rendered.push((name.to_string(), steps, commands));
+        }
+    }
+
+    if *INSTRUCTIONS_SOURCE == InstructionsSource::Generated {
+        let generated: serde_json::Map<String, Value> = rendered
+            .into_iter()
+            .map(|(name, steps, commands)| (name, instructions(&steps, commands)))
+            .collect();
+        response["instructions"] = Value::Object(generated);
+        return;
+    }
+    let Some(instructions) = response
+        .get_mut("instructions")
+        .and_then(Value::as_object_mut)
+    else {
+        return;
+    };
+    for (name, _, commands) in rendered {
+        if let Some(entry) = instructions.get_mut(&name).and_then(Value::as_object_mut) {
+            entry.insert("curl".to_string(), Value::Object(commands));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather() -> Value {
+        json!({
+            "name": "weather",
+            "endpoint": "https://weather.example.com/mcp",
+            "protocol_version": "2025-06-18",
+            "auth": { "required": true, "header": "X-Api-Key" },
+            "capabilities": { "tools": ["forecast"] },
+        })
+    }
+
+    fn generated_curl(rec: Value) -> Value {
+        let mut response = json!({ "recommendations": [rec], "instructions": {} });
+        attach(&mut response);
+        response["instructions"]["weather"]["curl"].clone()
+    }
+
+    #[test]
+    fn curl_for_a_sample_endpoint() {
+        let curl = generated_curl(weather());
+        assert_eq!(
+            curl["initialize"],
+            "curl -sS -D init.headers -X POST 'https://weather.example.com/mcp' \
+             -H 'Accept: application/json, text/event-stream' -H 'Content-Type: application/json' \
+             -H 'X-Api-Key: '\"$MCP_TOKEN\" \
+             --data '{\"id\":\"init-1\",\"jsonrpc\":\"2.0\",\"method\":\"initialize\",\"params\":{\"capabilities\":{},\"clientInfo\":{\"name\":\"Agent\",\"version\":\"1.0\"},\"protocolVersion\":\"2025-06-18\"}}'"
+        );
+        assert_eq!(
+            curl["extract_session"],
+            "SESSION=$(awk 'tolower($1) == \"mcp-session-id:\" {print $2}' init.headers | tr -d '\\r')"
+        );
+        assert_eq!(
+            curl["list_tools"],
+            "curl -sS -X POST 'https://weather.example.com/mcp' \
+             -H 'Accept: application/json, text/event-stream' -H 'Content-Type: application/json' \
+             -H 'Mcp-Session-Id: '\"$SESSION\" -H 'X-Api-Key: '\"$MCP_TOKEN\" \
+             --data '{\"id\":\"2\",\"jsonrpc\":\"2.0\",\"method\":\"tools/list\"}'"
+        );
+        assert_eq!(
+            curl["close"],
+            "curl -sS -X DELETE 'https://weather.example.com/mcp' \
+             -H 'Mcp-Session-Id: '\"$SESSION\" -H 'X-Api-Key: '\"$MCP_TOKEN\""
+        );
+        assert!(
+            curl["call_example"]
+                .as_str()
+                .unwrap()
+                .contains("\"name\":\"forecast\"")
+        );
+    }
+
+    #[test]
+    fn curl_quotes_an_endpoint_with_shell_characters() {
+        let mut rec = weather();
+        rec["endpoint"] = json!("https://example.com/mcp?q=it's&x=$(id)");
+        rec["auth"] = json!({ "required": false });
+        let curl = generated_curl(rec);
+        let close = curl["close"].as_str().unwrap();
+        assert_eq!(
+            close,
+            "curl -sS -X DELETE 'https://example.com/mcp?q=it'\\''s&x=$(id)' -H 'Mcp-Session-Id: '\"$SESSION\""
+        );
+        assert!(!curl["initialize"].as_str().unwrap().contains("MCP_TOKEN"));
+    }
+}
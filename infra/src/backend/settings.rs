@@ -0,0 +1,57 @@
+// src/backend/settings.rs
+//! Process-wide settings read once at startup: the prompt clock, embedding
+//! profile and weights, mirror policy, network names, normalization rules,
+//! providers, query context fields and stopwords. Each module keeps its own in
+//! a `OnceLock`, with `select` to fix it and `active` to read it; `select` is
+//! `select_once` over that cell.
+use anyhow::{Result, bail};
+use std::sync::OnceLock;
+
+/// Fixes `cell` to `value` for the life of the process. Selecting the same
+/// value again is a no-op; a different one is an error, since requests may
+/// already have been served under the first.
+pub fn select_once<T: PartialEq>(cell: &OnceLock<T>, value: T, what: &str) -> Result<()> {
+    select_once_by(cell, value, what, T::eq)
+}
+
+/// `select_once`, with `same` deciding whether two values are one selection.
+pub fn select_once_by<T>(
+    cell: &OnceLock<T>,
+    value: T,
+    what: &str,
+    same: impl Fn(&T, &T) -> bool,
+) -> Result<()> {
+    match cell.set(value) {
+        Ok(()) => Ok(()),
+        Err(value) if cell.get().is_some_and(|active| same(active, &value)) => Ok(()),
+        Err(_) => bail!("{} already selected; restart to change it", what),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_selection_sticks() {
+        let cell = OnceLock::new();
+        select_once(&cell, "rfc3339", "Prompt time format").unwrap();
+        select_once(&cell, "rfc3339", "Prompt time format").unwrap();
+        let error = select_once(&cell, "unix", "Prompt time format").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Prompt time format already selected; restart to change it"
+        );
+        assert_eq!(cell.get(), Some(&"rfc3339"));
+    }
+
+    #[test]
+    fn same_decides_what_counts_as_a_change() {
+        let cell = OnceLock::new();
+        let same_len = |a: &String, b: &String| a.len() == b.len();
+        select_once_by(&cell, "abc".to_string(), "Providers", same_len).unwrap();
+        select_once_by(&cell, "xyz".to_string(), "Providers", same_len).unwrap();
+        assert!(select_once_by(&cell, "abcd".to_string(), "Providers", same_len).is_err());
+        assert_eq!(cell.get().map(String::as_str), Some("abc"));
+    }
+}
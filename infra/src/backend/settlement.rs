@@ -0,0 +1,293 @@
+// src/backend/settlement.rs
+//! Tells x402 clients *why* a paid route answered `402`. The x402 layer uses
+//! one status for several situations, told apart by how far the request got:
+//!
+//! - `PAYMENT_REQUIRED`: no `X-PAYMENT` was sent. Pay one of `accepts`.
+//! - `PAYMENT_REJECTED`: a payment was sent but failed verification (bad
+//!   signature, expired authorization, insufficient balance), so the handler
+//!   never ran. Nothing was charged; fix or fund the payment and send a new one.
+//! - `SETTLEMENT_FAILED`: the payment verified and the request was served, but
+//!   the facilitator refused to settle it with a known x402 error reason, so
+//!   the response was withheld. Nothing was charged.
+//! - `SETTLEMENT_INDETERMINATE`: the request was served but settlement ended
+//!   without a refusal the facilitator explained (a timeout, a transport
+//!   error). The payment may still land, so don't blindly pay again; check the
+//!   payer's balance or transaction first.
+//!
+//! "Served" is observed, not guessed: `served_layer` sits between the x402
+//! layer and the handler and flags the request when it gets there.
+//!
+//! The `402` body keeps the x402 fields (`accepts`, `x402Version`, `error`) and
+//! gains `code`, `action` (`pay`, `top_up`, `fix_payment`, `give_up` or
+//! `check_before_retry`), the facilitator's `reason` and `networks`, a
+//! display name and chain id for each network in `accepts` (see `networks`).
+//! The state is also in the `x-librarian-payment-state` header.
+use super::metrics::METRICS;
+use super::networks;
+use super::payer::{PAYMENT_HEADER, SETTLED, payer_from_headers, payment_digest, settled_payer};
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    http::{HeaderValue, StatusCode, header::CONTENT_LENGTH},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const PAYMENT_STATE_HEADER: &str = "x-librarian-payment-state";
+/// x402 challenges are small; anything larger is passed through untouched.
+const MAX_CHALLENGE_BYTES: usize = 64 * 1024;
+
+/// x402 facilitator error reasons that are a definitive refusal, with what the
+/// client should do about each. Nothing was charged when one of these is given.
+const KNOWN_REASONS: [(&str, &str); 6] = [
+    ("insufficient_funds", "top_up"),
+    ("invalid_network", "give_up"),
+    ("invalid_scheme", "give_up"),
+    ("unsupported_scheme", "give_up"),
+    ("invalid_payment_requirements", "give_up"),
+    ("invalid_signature", "fix_payment"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentState {
+    Required,
+    Rejected,
+    SettlementFailed,
+    SettlementIndeterminate,
+}
+
+impl PaymentState {
+    pub fn code(self) -> &'static str {
+        match self {
+            PaymentState::Required => "PAYMENT_REQUIRED",
+            PaymentState::Rejected => "PAYMENT_REJECTED",
+            PaymentState::SettlementFailed => "SETTLEMENT_FAILED",
+            PaymentState::SettlementIndeterminate => "SETTLEMENT_INDETERMINATE",
+        }
+    }
+}
+
+/// Request extension flagged by `served_layer` once the handler is reached.
+#[derive(Clone, Default)]
+pub struct Served(Arc<AtomicBool>);
+
+impl Served {
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Placed directly around the handler, inside the x402 layer: a request that
+/// gets here has a verified payment, so a later `402` is about settlement.
+pub async fn served_layer(request: Request, next: Next) -> Response {
+    if let Some(served) = request.extensions().get::<Served>() {
+        served.0.store(true, Ordering::Relaxed);
+    }
+    next.run(request).await
+}
+
+/// The `KNOWN_REASONS` code the x402 error string carries, matched as a whole
+/// word so free-form text can't trip it.
+fn known_reason(reason: &str) -> Option<&'static str> {
+    let reason = reason.to_lowercase();
+    let words: Vec<&str> = reason
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .collect();
+    KNOWN_REASONS
+        .iter()
+        .find(|(code, _)| words.contains(code))
+        .map(|(_, action)| *action)
+}
+
+/// Sorts a `402` by whether a payment was presented, whether the handler ran
+/// (see `Served`) and the facilitator's reason, and suggests what the client
+/// should do next.
+pub fn classify(
+    payment_presented: bool,
+    served: bool,
+    reason: &str,
+) -> (PaymentState, &'static str) {
+    match (payment_presented, served, known_reason(reason)) {
+        (false, _, _) => (PaymentState::Required, "pay"),
+        (true, false, action) => (PaymentState::Rejected, action.unwrap_or("fix_payment")),
+        (true, true, Some(action)) => (PaymentState::SettlementFailed, action),
+        (true, true, None) => (PaymentState::SettlementIndeterminate, "check_before_retry"),
+    }
+}
+
+/// Route middleware placed outside the x402 layer on paid routes. Settled
+/// payments are recorded in `SETTLED`; only `402` responses are rewritten.
+pub async fn payment_state_layer(mut request: Request, next: Next) -> Response {
+    // `request_deadline_layer` may already be watching one
+    let served = match request.extensions().get::<Served>() {
+        Some(served) => served.clone(),
+        None => {
+            let served = Served::default();
+            request.extensions_mut().insert(served.clone());
+            served
+        }
+    };
+    let presented = request.headers().contains_key(PAYMENT_HEADER);
+    let payer = payer_from_headers(request.headers());
+    let digest = payment_digest(request.headers());
+    let path = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+    if response.status().is_success()
+        && let (Some(digest), Some(settled)) = (digest, settled_payer(response.headers()))
+    {
+        SETTLED.record(digest, settled);
+    }
+    if response.status() != StatusCode::PAYMENT_REQUIRED {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_CHALLENGE_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("Could not read 402 body on {}: {}", path, e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let Ok(Value::Object(mut challenge)) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    let reason = challenge
+        .get("error")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let (state, action) = classify(presented, served.get(), &reason);
+    match state {
+        PaymentState::Required => {}
+        PaymentState::Rejected => {
+            tracing::warn!(payer = ?payer, reason = %reason, "Payment rejected on {}", path);
+        }
+        PaymentState::SettlementFailed | PaymentState::SettlementIndeterminate => {
+            METRICS
+                .settlement_failures_total
+                .fetch_add(1, Ordering::Relaxed);
+            tracing::error!(
+                payer = ?payer,
+                reason = %reason,
+                state = state.code(),
+                "Settlement failed on {} after serving; response withheld",
+                path
+            );
+        }
+    }
+
+    challenge.insert("code".to_string(), json!(state.code()));
+    challenge.insert("action".to_string(), json!(action));
+    if !reason.is_empty() {
+        challenge.insert("reason".to_string(), json!(reason));
+    }
+    let networks: serde_json::Map<String, Value> = challenge
+        .get("accepts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|accept| accept.get("network").and_then(Value::as_str))
+        .map(|network| (network.to_string(), networks::active().describe(network)))
+        .collect();
+    if !networks.is_empty() {
+        challenge.insert("networks".to_string(), Value::Object(networks));
+    }
+    let body = Value::Object(challenge).to_string();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(PAYMENT_STATE_HEADER, HeaderValue::from_static(state.code()));
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::post};
+    use tower::ServiceExt as _;
+
+    #[test]
+    fn classify_by_stage_and_reason() {
+        assert_eq!(classify(false, false, ""), (PaymentState::Required, "pay"));
+        assert_eq!(
+            classify(true, false, "insufficient_funds"),
+            (PaymentState::Rejected, "top_up")
+        );
+        assert_eq!(
+            classify(true, false, "Verification failed"),
+            (PaymentState::Rejected, "fix_payment")
+        );
+        assert_eq!(
+            classify(true, true, "Settlement failed: invalid_network"),
+            (PaymentState::SettlementFailed, "give_up")
+        );
+        assert_eq!(
+            classify(true, true, "error sending request: operation timed out"),
+            (PaymentState::SettlementIndeterminate, "check_before_retry")
+        );
+        // a substring of a known reason is not that reason
+        assert_eq!(
+            classify(true, true, "not_insufficient_funds_related"),
+            (PaymentState::SettlementIndeterminate, "check_before_retry")
+        );
+    }
+
+    /// Stands in for the x402 layer: rejects unless `x-verified` is set, and
+    /// otherwise serves the request and then fails settlement with `reason`.
+    async fn fake_x402(request: Request, next: Next) -> Response {
+        let reason = if request.headers().contains_key("x-verified") {
+            next.run(request).await;
+            "Settlement failed: facilitator timed out"
+        } else {
+            "invalid signature"
+        };
+        Response::builder()
+            .status(StatusCode::PAYMENT_REQUIRED)
+            .body(Body::from(
+                json!({ "error": reason, "accepts": [] }).to_string(),
+            ))
+            .unwrap()
+    }
+
+    async fn state(verified: bool) -> (String, Value) {
+        let app = Router::new().route(
+            "/paid",
+            post(|| async { "served" })
+                .layer(axum::middleware::from_fn(served_layer))
+                .layer(axum::middleware::from_fn(fake_x402))
+                .layer(axum::middleware::from_fn(payment_state_layer)),
+        );
+        let mut request = Request::post("/paid").header(PAYMENT_HEADER, "payment");
+        if verified {
+            request = request.header("x-verified", "1");
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = response.headers()[PAYMENT_STATE_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (header, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn served_flag_separates_rejection_from_settlement() {
+        let (header, body) = state(false).await;
+        assert_eq!(header, "PAYMENT_REJECTED");
+        assert_eq!(body["action"], "fix_payment");
+
+        let (header, body) = state(true).await;
+        assert_eq!(header, "SETTLEMENT_INDETERMINATE");
+        assert_eq!(body["action"], "check_before_retry");
+        assert_eq!(body["reason"], "Settlement failed: facilitator timed out");
+    }
+}
@@ -0,0 +1,149 @@
+// src/backend/signals.rs
+//! `/discover?debug=true` annotates each recommendation with the deterministic
+//! signals behind it, next to the model's `rationale`, so it shows when the
+//! model's reasoning and the retrieval disagree:
+//!
+//! - `similarity`: the entry's retrieval score; `null` when it was not a
+//!   candidate (a pin, say).
+//! - `lexical_overlap`: Jaccard overlap of query terms with the entry's
+//!   description and capability terms, as `LIBRARIAN_RERANK=jaccard` uses it.
+//! - `relevance`: that rerank blend of the two, on the model's 0–100 `score`
+//!   scale.
+//! - `capability_matches`: which query terms matched which catalog capabilities.
+//! - `verification_status`: what `verify::apply_verification` settled on.
+//!
+//! Production responses carry none of this.
+use super::rerank::{self, JACCARD_WEIGHT};
+use super::{McpEntry, mirrors};
+use serde_json::{Value, json};
+
+/// Rounded like the debug score headers.
+fn round4(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// Adds `signals` to every recommendation matched to the catalog.
+pub fn attach(
+    response: &mut Value,
+    query: &str,
+    candidates: &[(f64, McpEntry)],
+    catalog: &[McpEntry],
+) {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    let query_terms = rerank::terms(query);
+
+    for rec in recommendations.iter_mut() {
+        let endpoint = rec
+            .get("endpoint")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let Some(entry) = mirrors::find(catalog, endpoint) else {
+            continue;
+        };
+        let similarity = candidates
+            .iter()
+            .find(|(_, candidate)| candidate.name == entry.name)
+            .map(|(score, _)| *score);
+        let overlap = rerank::overlap(&query_terms, entry);
+        let relevance = similarity.map(|similarity| {
+            (100.0 * (similarity + JACCARD_WEIGHT * overlap) / (1.0 + JACCARD_WEIGHT))
+                .clamp(0.0, 100.0)
+                .round() as u64
+        });
+        let capability_matches: Vec<Value> = entry
+            .capabilities
+            .iter()
+            .filter_map(|capability| {
+                let mut matched: Vec<&String> = rerank::terms(capability)
+                    .into_iter()
+                    .filter_map(|term| query_terms.get(&term))
+                    .collect();
+                matched.sort();
+                (!matched.is_empty()).then(|| json!({ "capability": capability, "terms": matched }))
+            })
+            .collect();
+        let verification_status = rec
+            .get("verification_status")
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        rec["signals"] = json!({
+            "similarity": similarity.map(round4),
+            "lexical_overlap": round4(overlap),
+            "relevance": relevance,
+            "capability_matches": capability_matches,
+            "verification_status": verification_status,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather() -> McpEntry {
+        serde_json::from_value(json!({
+            "name": "example/weather",
+            "endpoint": "https://weather.example.com/mcp",
+            "version": "1.2.0",
+            "capabilities": ["get_forecast", "get_alerts"],
+            "desc": "Weather forecasts and alerts.",
+        }))
+        .unwrap()
+    }
+
+    fn response() -> Value {
+        json!({
+            "recommendations": [
+                {
+                    "name": "example/weather",
+                    "endpoint": "https://weather.example.com/mcp",
+                    "verification_status": "catalog_only",
+                },
+                { "name": "example/climate", "endpoint": "https://climate.example.org/mcp" },
+            ]
+        })
+    }
+
+    #[test]
+    fn candidates_carry_their_retrieval_signals() {
+        let mut response = response();
+        attach(
+            &mut response,
+            "weather forecast for Paris",
+            &[(0.8, weather())],
+            &[weather()],
+        );
+        assert_eq!(
+            response["recommendations"][0]["signals"],
+            json!({
+                "similarity": 0.8,
+                "lexical_overlap": 0.25,
+                "relevance": 62,
+                "capability_matches": [{ "capability": "get_forecast", "terms": ["forecast"] }],
+                "verification_status": "catalog_only",
+            })
+        );
+    }
+
+    #[test]
+    fn non_candidates_have_no_similarity_and_unknown_servers_no_signals() {
+        let mut response = response();
+        attach(
+            &mut response,
+            "weather forecast for Paris",
+            &[],
+            &[weather()],
+        );
+        let signals = &response["recommendations"][0]["signals"];
+        assert_eq!(signals["similarity"], Value::Null);
+        assert_eq!(signals["relevance"], Value::Null);
+        assert_eq!(signals["lexical_overlap"], 0.25);
+        assert!(response["recommendations"][1].get("signals").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+// src/backend/signing.rs
+//! Optional Ed25519 signatures over `/discover` responses, so clients acting on
+//! a recommendation can check it came from this server unmodified.
+//!
+//! Canonicalization: none. The signature covers the response body exactly as
+//! sent on the wire (the raw bytes of the JSON, before any transfer or content
+//! encoding), so a client verifies the bytes it received without re-serializing.
+//! The public key is published at `GET /meta` under `signing`.
+//!
+//! Signing runs after payment has settled, so a body it can't sign (over
+//! `MAX_SIGNED_BYTES`, or of unknown length) is still sent, unsigned, with
+//! `x-librarian-signature-skipped: too-large` in place of the signature.
+use anyhow::{Context as _, Result, bail};
+use axum::{
+    body::{Body, HttpBody as _, to_bytes},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signer as _, SigningKey};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::Arc;
+
+pub const SIGNATURE_HEADER: &str = "x-librarian-signature";
+pub const KEY_ID_HEADER: &str = "x-librarian-key-id";
+pub const SIGNATURE_SKIPPED_HEADER: &str = "x-librarian-signature-skipped";
+/// Largest body signed; a bigger or unsized one goes out unsigned.
+const MAX_SIGNED_BYTES: u64 = 1024 * 1024;
+
+pub struct ResponseSigner {
+    key: SigningKey,
+    key_id: String,
+}
+
+/// What `/meta` publishes for verification.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicKeyInfo {
+    pub algorithm: &'static str,
+    pub key_id: String,
+    /// Base64 of the 32-byte Ed25519 public key.
+    pub public_key: String,
+}
+
+impl ResponseSigner {
+    /// `LIBRARIAN_SIGNING_KEY` is the base64 32-byte Ed25519 seed; signing is off
+    /// when it is unset. `LIBRARIAN_SIGNING_KEY_ID` defaults to the first 16 hex
+    /// digits of the public key's SHA-256.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(raw) = env::var("LIBRARIAN_SIGNING_KEY") else {
+            return Ok(None);
+        };
+        let seed = STANDARD
+            .decode(raw.trim())
+            .context("LIBRARIAN_SIGNING_KEY must be base64")?;
+        let Ok(seed) = <[u8; 32]>::try_from(seed.as_slice()) else {
+            bail!(
+                "LIBRARIAN_SIGNING_KEY must decode to a 32-byte Ed25519 seed, got {} bytes",
+                seed.len()
+            );
+        };
+        let key = SigningKey::from_bytes(&seed);
+        let key_id = env::var("LIBRARIAN_SIGNING_KEY_ID").unwrap_or_else(|_| {
+            let digest = Sha256::digest(key.verifying_key().as_bytes());
+            hex::encode(digest)[..16].to_string()
+        });
+        Ok(Some(ResponseSigner { key, key_id }))
+    }
+
+    pub fn public_key(&self) -> PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: "ed25519",
+            key_id: self.key_id.clone(),
+            public_key: STANDARD.encode(self.key.verifying_key().as_bytes()),
+        }
+    }
+
+    /// Base64 of the 64-byte signature over `body`.
+    pub fn sign(&self, body: &[u8]) -> String {
+        STANDARD.encode(self.key.sign(body).to_bytes())
+    }
+}
+
+/// Signs the body of every response passing through; a no-op without a key.
+pub async fn signing_layer(
+    State(signer): State<Option<Arc<ResponseSigner>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let Some(signer) = signer else {
+        return response;
+    };
+
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|len| len <= MAX_SIGNED_BYTES);
+    if !fits {
+        tracing::warn!("Response body too large to sign; sending it unsigned");
+        let mut response = response;
+        response.headers_mut().insert(
+            SIGNATURE_SKIPPED_HEADER,
+            HeaderValue::from_static("too-large"),
+        );
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_SIGNED_BYTES as usize).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to read response body to sign: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if let Ok(value) = HeaderValue::from_str(&signer.sign(&body)) {
+        parts.headers.insert(SIGNATURE_HEADER, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&signer.key_id) {
+        parts.headers.insert(KEY_ID_HEADER, value);
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::get};
+    use tower::ServiceExt as _;
+
+    /// `/discover` answering `len` bytes behind the signing layer.
+    async fn fetch(len: usize) -> Response {
+        let signer = ResponseSigner {
+            key: SigningKey::from_bytes(&[7; 32]),
+            key_id: "test".to_string(),
+        };
+        let app = Router::new()
+            .route("/discover", get(move || async move { "x".repeat(len) }))
+            .layer(middleware::from_fn_with_state(
+                Some(Arc::new(signer)),
+                signing_layer,
+            ));
+        let request = Request::get("/discover").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn small_bodies_are_signed() {
+        let response = fetch(64).await;
+        assert_eq!(response.headers()[KEY_ID_HEADER], "test");
+        assert!(response.headers().contains_key(SIGNATURE_HEADER));
+        assert!(!response.headers().contains_key(SIGNATURE_SKIPPED_HEADER));
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_sent_unsigned() {
+        let len = MAX_SIGNED_BYTES as usize + 1;
+        let response = fetch(len).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[SIGNATURE_SKIPPED_HEADER], "too-large");
+        assert!(!response.headers().contains_key(SIGNATURE_HEADER));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), len);
+    }
+}
@@ -0,0 +1,152 @@
+// src/backend/snapshot.rs
+use super::embed_profile::{self, EmbedProfile};
+use super::providers::{self, EmbeddingConfig, ProviderKind};
+use super::{LibrarianHandle, McpEntry};
+use anyhow::{Context as _, Result, anyhow, bail};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Json as AxumJson},
+};
+use rig::OneOrMany;
+use rig::embeddings::Embedding;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A catalog together with its embedding vectors, so a new instance can be
+/// seeded without re-embedding. Only valid for the provider and model that
+/// produced it.
+#[derive(Serialize, Deserialize)]
+pub struct CatalogSnapshot {
+    /// Snapshots from before providers were selectable came from OpenAI.
+    #[serde(default = "default_provider_name")]
+    pub embedding_provider: String,
+    pub embedding_model: String,
+    /// Snapshots from before profiles existed used the default profile.
+    #[serde(default = "default_profile_name")]
+    pub embedding_profile: String,
+    /// `EmbedWeights::key()` when the weighted scheme built the vectors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_weights: Option<String>,
+    pub created_at: u64,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub entry: McpEntry,
+    pub embeddings: Vec<Embedding>,
+}
+
+fn default_provider_name() -> String {
+    ProviderKind::OpenAi.name().to_string()
+}
+
+fn default_profile_name() -> String {
+    EmbedProfile::Default.name().to_string()
+}
+
+impl CatalogSnapshot {
+    pub fn new(embedding_model: &str, embeddings: &[(McpEntry, OneOrMany<Embedding>)]) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        CatalogSnapshot {
+            embedding_provider: providers::active()
+                .embedding
+                .provider
+                .kind
+                .name()
+                .to_string(),
+            embedding_model: embedding_model.to_string(),
+            embedding_profile: embed_profile::active().name().to_string(),
+            embedding_weights: embed_profile::active_weights().map(|w| w.key()),
+            created_at,
+            entries: embeddings
+                .iter()
+                .map(|(entry, vectors)| SnapshotEntry {
+                    entry: entry.clone(),
+                    embeddings: vectors.iter().cloned().collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Reads a snapshot written by `GET /admin/snapshot`, refusing one built by a
+/// different embedding provider or model than `expected` or with another
+/// embedding profile or weighting.
+pub fn load_snapshot<P: AsRef<Path>>(
+    path: P,
+    expected: &EmbeddingConfig,
+) -> Result<Vec<(McpEntry, OneOrMany<Embedding>)>> {
+    let file = File::open(&path)
+        .with_context(|| format!("Failed to open snapshot {:?}", path.as_ref()))?;
+    let snapshot: CatalogSnapshot = serde_json::from_reader(file)
+        .with_context(|| format!("Failed to parse snapshot {:?}", path.as_ref()))?;
+
+    let built_with = format!(
+        "{}:{}",
+        snapshot.embedding_provider, snapshot.embedding_model
+    );
+    if built_with != expected.identity() {
+        bail!(
+            "Snapshot was built with embedding model {:?} but the server uses {:?}",
+            built_with,
+            expected.identity()
+        );
+    }
+
+    let active = embed_profile::active();
+    if EmbedProfile::from_name(&snapshot.embedding_profile) != Some(active) {
+        bail!(
+            "Snapshot was built with embedding profile {:?} but the server uses {:?}",
+            snapshot.embedding_profile,
+            active.name()
+        );
+    }
+
+    let weights = embed_profile::active_weights().map(|w| w.key());
+    if snapshot.embedding_weights != weights {
+        bail!(
+            "Snapshot was built with embedding weights {:?} but the server uses {:?}",
+            snapshot.embedding_weights,
+            weights
+        );
+    }
+
+    snapshot
+        .entries
+        .into_iter()
+        .map(|e| {
+            let vectors = OneOrMany::many(e.embeddings)
+                .map_err(|_| anyhow!("Snapshot entry {:?} has no embeddings", e.entry.name))?;
+            Ok((e.entry, vectors))
+        })
+        .collect()
+}
+
+/// Names of the entries holding a vector whose length is not `expected`, the
+/// live embedding model's dimension. Comparing vectors of different lengths
+/// doesn't fail, it just ranks garbage, so stored vectors are checked before use.
+pub fn mismatched_dimensions(
+    embeddings: &[(McpEntry, OneOrMany<Embedding>)],
+    expected: usize,
+) -> Vec<&str> {
+    embeddings
+        .iter()
+        .filter(|(_, vectors)| vectors.iter().any(|e| e.vec.len() != expected))
+        .map(|(entry, _)| entry.name.as_str())
+        .collect()
+}
+
+/// `GET /admin/snapshot`: the live catalog and its embedding vectors.
+pub async fn snapshot_handler(State(handle): State<LibrarianHandle>) -> impl IntoResponse {
+    let librarian = handle.current();
+    AxumJson(CatalogSnapshot::new(
+        &librarian.retrieval_model,
+        &librarian.embeddings,
+    ))
+}
@@ -0,0 +1,218 @@
+// src/backend/stopwords.rs
+//! Opt-in noise removal for the text embedded for `/discover` retrieval.
+//! Queries from other agents often wrap a few substantive terms in
+//! boilerplate ("Hi, I need a tool that can please ..."), which pulls the
+//! query vector toward every description written the same way.
+//! `LIBRARIAN_QUERY_STOPWORDS` is unset or `off` (the default), `default` for
+//! the built-in list, or the path to a JSON array of words and phrases; a list
+//! that can't be read fails startup.
+//! Entries match whole words, case-insensitively and ignoring surrounding
+//! punctuation; phrases match consecutive words. Only the embedded text
+//! changes; the prompt, reranking and filters still see the raw query. A query
+//! that is nothing but noise is embedded as written. Compare with and without
+//! using `bench`, e.g. on `fixtures/bench/verbose.json`.
+use super::settings::select_once;
+use anyhow::{Context as _, Result};
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const DEFAULT_STOPWORDS: &[&str] = &[
+    "a",
+    "an",
+    "the",
+    "some",
+    "any",
+    "i",
+    "me",
+    "my",
+    "we",
+    "our",
+    "you",
+    "it",
+    "is",
+    "are",
+    "be",
+    "to",
+    "of",
+    "for",
+    "that",
+    "which",
+    "with",
+    "can",
+    "please",
+    "kindly",
+    "hi",
+    "hello",
+    "hey",
+    "thanks",
+    "thank you",
+    "i need",
+    "we need",
+    "i want",
+    "i'd like",
+    "i would like",
+    "i'm looking for",
+    "looking for",
+    "can you",
+    "could you",
+    "would you",
+    "help me",
+    "something",
+    "anything",
+    "tool",
+    "tools",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stopwords {
+    /// Lowercased words per entry, longest first so phrases win over their parts.
+    phrases: Vec<Vec<String>>,
+    source: String,
+}
+
+/// Lowercased, without the punctuation around it ("please," -> "please").
+fn word_key(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+        .to_lowercase()
+}
+
+impl Stopwords {
+    fn new<S: AsRef<str>>(entries: &[S], source: String) -> Self {
+        let mut phrases: Vec<Vec<String>> = entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .as_ref()
+                    .split_whitespace()
+                    .map(word_key)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|phrase| !phrase.is_empty() && phrase.iter().all(|w| !w.is_empty()))
+            .collect();
+        phrases.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        phrases.dedup();
+        Stopwords { phrases, source }
+    }
+
+    pub fn builtin() -> Self {
+        Stopwords::new(DEFAULT_STOPWORDS, "default".to_string())
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
+        let entries: Vec<String> = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse {:?} as a stopword list", path.as_ref()))?;
+        Ok(Stopwords::new(
+            &entries,
+            path.as_ref().display().to_string(),
+        ))
+    }
+
+    /// `None` when `LIBRARIAN_QUERY_STOPWORDS` is unset or `off`.
+    pub fn from_env() -> Result<Option<Self>> {
+        match env::var("LIBRARIAN_QUERY_STOPWORDS")
+            .as_deref()
+            .map(str::trim)
+        {
+            Err(_) | Ok("") | Ok("off") => Ok(None),
+            Ok("default") => Ok(Some(Stopwords::builtin())),
+            Ok(path) => Stopwords::from_file(path).map(Some),
+        }
+    }
+
+    /// `query` with every stopword and stopword phrase removed and the rest
+    /// joined by single spaces; `query` unchanged if nothing would be left.
+    pub fn strip(&self, query: &str) -> String {
+        let words: Vec<&str> = query.split_whitespace().collect();
+        let keys: Vec<String> = words.iter().map(|w| word_key(w)).collect();
+        let mut kept: Vec<&str> = Vec::with_capacity(words.len());
+        let mut i = 0;
+        while i < words.len() {
+            let matched = self
+                .phrases
+                .iter()
+                .find(|phrase| keys[i..].starts_with(phrase))
+                .map(Vec::len);
+            match matched {
+                Some(len) => i += len,
+                None => {
+                    kept.push(words[i]);
+                    i += 1;
+                }
+            }
+        }
+        if kept.is_empty() {
+            return query.to_string();
+        }
+        kept.join(" ")
+    }
+}
+
+static ACTIVE: OnceLock<Option<Stopwords>> = OnceLock::new();
+
+/// Fixes the stopword list; see `settings::select_once`.
+pub fn select(stopwords: Option<Stopwords>) -> Result<()> {
+    select_once(&ACTIVE, stopwords, "Query stopwords")
+}
+
+/// The selected list; `None` when filtering is off or nothing was selected.
+fn active() -> Option<&'static Stopwords> {
+    ACTIVE.get_or_init(|| None).as_ref()
+}
+
+/// The query to embed: stripped when filtering is on, as written otherwise.
+pub fn strip(query: &str) -> String {
+    match active() {
+        Some(stopwords) => stopwords.strip(query),
+        None => query.to_string(),
+    }
+}
+
+/// `off`, `default` or the list's path, for logs and `bench` output.
+pub fn describe() -> String {
+    match active() {
+        Some(stopwords) => format!("{} ({} entries)", stopwords.source, stopwords.phrases.len()),
+        None => "off".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_words_and_phrases_ignoring_case_and_punctuation() {
+        let stopwords = Stopwords::builtin();
+        assert_eq!(
+            stopwords.strip("Hi, I'm looking for a tool that can convert PDF to text, please!"),
+            "convert PDF text,"
+        );
+        assert_eq!(
+            stopwords.strip("Could you   translate invoices"),
+            "translate invoices"
+        );
+    }
+
+    #[test]
+    fn a_query_of_only_noise_is_kept() {
+        assert_eq!(
+            Stopwords::builtin().strip("Hello, can you help me?"),
+            "Hello, can you help me?"
+        );
+    }
+
+    #[test]
+    fn file_lists_are_read_and_bad_ones_refused() {
+        let path =
+            std::env::temp_dir().join(format!("librarian-stopwords-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"["Per Se", "um"]"#).unwrap();
+        let stopwords = Stopwords::from_file(&path).unwrap();
+        assert_eq!(stopwords.strip("um, a weather api per se"), "a weather api");
+        std::fs::write(&path, r#"{"um": true}"#).unwrap();
+        assert!(Stopwords::from_file(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,675 @@
+// src/backend/store.rs
+//! Persistence for what the server learns while running: feedback reports,
+//! per-endpoint recommendation hits and admin audit events. All of it goes
+//! through `Store`; `Backend` holds one `Arc<dyn Store>`, picked by
+//! `LIBRARIAN_STORE`:
+//!
+//! - `file` (the default): append-only JSON lines, one file per kind:
+//!   `LIBRARIAN_FEEDBACK_PATH` (default `feedback.jsonl`, in the format it
+//!   always had), `LIBRARIAN_HITS_PATH` (default `hits.jsonl`) and
+//!   `LIBRARIAN_AUDIT_PATH` (default `audit.jsonl`). Writes are queued to one
+//!   writer thread, so the request path never waits on the disk; a write that
+//!   fails there is logged. The hits file is compacted into per-endpoint
+//!   totals once it passes `MAX_HITS_BYTES`, and the latest audit events are
+//!   kept in memory for `GET /admin/audit`. Reads replay the files, skipping
+//!   malformed lines with a warning.
+//! - `memory`: kept in the process only and lost on restart.
+//!
+//! Another backend (a database, say) implements `Store` and gets a name in
+//! `from_env`. Writes happen on the request path, so they should be quick.
+use super::feedback::FeedbackRecord;
+use super::urls::endpoint_key;
+use anyhow::{Context as _, Result, anyhow, bail};
+use axum::{
+    Extension,
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Json as AxumJson, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+
+const DEFAULT_FEEDBACK_PATH: &str = "feedback.jsonl";
+const DEFAULT_HITS_PATH: &str = "hits.jsonl";
+const DEFAULT_AUDIT_PATH: &str = "audit.jsonl";
+/// Events `GET /admin/audit` answers with unless `?limit=` says otherwise.
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+/// Audit events `MemoryStore` keeps; the oldest are dropped first.
+const MAX_MEMORY_AUDIT_EVENTS: usize = 10_000;
+/// Latest audit events `FileStore` answers from memory; a larger `limit`
+/// reads the file.
+const MAX_RECENT_AUDIT_EVENTS: usize = 1_000;
+/// Size past which the hits file is rewritten as one line of totals.
+const MAX_HITS_BYTES: u64 = 4 * 1024 * 1024;
+
+/// One admin action worth a trail: a reload, a catalog change, maintenance.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEvent {
+    pub at: DateTime<Utc>,
+    pub action: String,
+    #[serde(default)]
+    pub detail: Value,
+}
+
+impl AuditEvent {
+    pub fn now(action: &str, detail: Value) -> Self {
+        AuditEvent {
+            at: Utc::now(),
+            action: action.to_string(),
+            detail,
+        }
+    }
+}
+
+/// One `/discover` response's worth of recommendation hits, or, after a
+/// compaction, the `totals` of every record before it.
+#[derive(Debug, Serialize, Deserialize)]
+struct HitsRecord {
+    at: DateTime<Utc>,
+    endpoints: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    totals: BTreeMap<String, u64>,
+}
+
+pub trait Store: Send + Sync {
+    /// `file`, `memory`, ...; for logs.
+    fn name(&self) -> &'static str;
+    fn record_feedback(&self, record: &FeedbackRecord) -> Result<()>;
+    /// Every stored report, oldest first.
+    fn feedback(&self) -> Result<Vec<FeedbackRecord>>;
+    /// One more recommendation of each of `endpoints`.
+    fn record_hits(&self, endpoints: &[String], at: DateTime<Utc>) -> Result<()>;
+    /// Recommendations so far per `urls::endpoint_key`.
+    fn hits(&self) -> Result<BTreeMap<String, u64>>;
+    fn record_audit(&self, event: &AuditEvent) -> Result<()>;
+    /// The latest `limit` events, oldest first.
+    fn audit(&self, limit: usize) -> Result<Vec<AuditEvent>>;
+}
+
+/// The store `LIBRARIAN_STORE` names.
+pub fn from_env() -> Result<Arc<dyn Store>> {
+    let path = |key: &str, default: &str| {
+        PathBuf::from(env::var(key).unwrap_or_else(|_| default.to_string()))
+    };
+    match env::var("LIBRARIAN_STORE").as_deref().map(str::trim) {
+        Err(_) | Ok("file") => Ok(Arc::new(FileStore::open(
+            path("LIBRARIAN_FEEDBACK_PATH", DEFAULT_FEEDBACK_PATH),
+            path("LIBRARIAN_HITS_PATH", DEFAULT_HITS_PATH),
+            path("LIBRARIAN_AUDIT_PATH", DEFAULT_AUDIT_PATH),
+        )?)),
+        Ok("memory") => Ok(Arc::new(MemoryStore::default())),
+        Ok(other) => bail!(
+            "Unknown LIBRARIAN_STORE {:?}; expected file or memory",
+            other
+        ),
+    }
+}
+
+/// Records an admin action; a failed write is logged, never surfaced, so the
+/// action itself still succeeds.
+pub fn audit(store: &dyn Store, action: &str, detail: Value) {
+    if let Err(e) = store.record_audit(&AuditEvent::now(action, detail)) {
+        tracing::warn!(action, "Failed to record audit event: {:#}", e);
+    }
+}
+
+/// Records the endpoints a `/discover` response recommended; failures are
+/// logged like `audit`'s.
+pub fn record_response_hits(store: &dyn Store, response: &Value) {
+    let endpoints: Vec<String> = response
+        .get("recommendations")
+        .and_then(Value::as_array)
+        .map(|recs| {
+            recs.iter()
+                .filter_map(|rec| rec.get("endpoint").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    if endpoints.is_empty() {
+        return;
+    }
+    if let Err(e) = store.record_hits(&endpoints, Utc::now()) {
+        tracing::warn!("Failed to record recommendation hits: {:#}", e);
+    }
+}
+
+fn add_hits(totals: &mut BTreeMap<String, u64>, endpoints: &[String]) {
+    for endpoint in endpoints {
+        let key = endpoint_key(endpoint);
+        if !key.is_empty() {
+            *totals.entry(key.to_string()).or_default() += 1;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Memory {
+    feedback: Vec<FeedbackRecord>,
+    hits: BTreeMap<String, u64>,
+    audit: VecDeque<AuditEvent>,
+}
+
+#[derive(Default)]
+pub struct MemoryStore(Mutex<Memory>);
+
+impl Store for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn record_feedback(&self, record: &FeedbackRecord) -> Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .feedback
+            .push(record.clone());
+        Ok(())
+    }
+
+    fn feedback(&self) -> Result<Vec<FeedbackRecord>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .feedback
+            .clone())
+    }
+
+    fn record_hits(&self, endpoints: &[String], _at: DateTime<Utc>) -> Result<()> {
+        add_hits(
+            &mut self.0.lock().unwrap_or_else(|p| p.into_inner()).hits,
+            endpoints,
+        );
+        Ok(())
+    }
+
+    fn hits(&self) -> Result<BTreeMap<String, u64>> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .hits
+            .clone())
+    }
+
+    fn record_audit(&self, event: &AuditEvent) -> Result<()> {
+        let mut memory = self.0.lock().unwrap_or_else(|p| p.into_inner());
+        if memory.audit.len() >= MAX_MEMORY_AUDIT_EVENTS {
+            memory.audit.pop_front();
+        }
+        memory.audit.push_back(event.clone());
+        Ok(())
+    }
+
+    fn audit(&self, limit: usize) -> Result<Vec<AuditEvent>> {
+        let memory = self.0.lock().unwrap_or_else(|p| p.into_inner());
+        Ok(memory
+            .audit
+            .iter()
+            .skip(memory.audit.len().saturating_sub(limit))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Work for the `FileStore` writer thread.
+enum Job {
+    Append {
+        path: PathBuf,
+        line: String,
+    },
+    /// Answered once every job queued before it is done.
+    Flush(mpsc::Sender<()>),
+}
+
+/// The newest audit events, and whether they are all the file holds.
+struct RecentAudit {
+    events: VecDeque<AuditEvent>,
+    complete: bool,
+}
+
+pub struct FileStore {
+    pub feedback: PathBuf,
+    pub hits: PathBuf,
+    pub audit: PathBuf,
+    /// The writer thread; one writer also keeps appends from interleaving.
+    jobs: mpsc::Sender<Job>,
+    recent_audit: Mutex<RecentAudit>,
+}
+
+impl FileStore {
+    /// Reads the audit trail's tail and starts the writer thread.
+    pub fn open(feedback: PathBuf, hits: PathBuf, audit: PathBuf) -> Result<Self> {
+        let events = FileStore::read::<AuditEvent>(&audit)?;
+        let complete = events.len() <= MAX_RECENT_AUDIT_EVENTS;
+        let skip = events.len().saturating_sub(MAX_RECENT_AUDIT_EVENTS);
+        let recent_audit = RecentAudit {
+            events: events.into_iter().skip(skip).collect(),
+            complete,
+        };
+        let (jobs, queue) = mpsc::channel();
+        let hits_path = hits.clone();
+        std::thread::Builder::new()
+            .name("librarian-store".to_string())
+            .spawn(move || write_jobs(queue, &hits_path))
+            .context("Failed to start the store writer")?;
+        Ok(FileStore {
+            feedback,
+            hits,
+            audit,
+            jobs,
+            recent_audit: Mutex::new(recent_audit),
+        })
+    }
+
+    /// A store keeping its three files in `dir`, under their default names.
+    pub fn in_dir(dir: &Path) -> Result<Self> {
+        FileStore::open(
+            dir.join(DEFAULT_FEEDBACK_PATH),
+            dir.join(DEFAULT_HITS_PATH),
+            dir.join(DEFAULT_AUDIT_PATH),
+        )
+    }
+
+    fn append<T: Serialize>(&self, path: &Path, record: &T) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        let job = Job::Append {
+            path: path.to_path_buf(),
+            line,
+        };
+        self.jobs
+            .send(job)
+            .map_err(|_| anyhow!("The store writer has stopped"))
+    }
+
+    /// Waits for every write queued so far.
+    fn flush(&self) -> Result<()> {
+        let (done, wait) = mpsc::channel();
+        self.jobs
+            .send(Job::Flush(done))
+            .map_err(|_| anyhow!("The store writer has stopped"))?;
+        wait.recv().context("The store writer has stopped")
+    }
+
+    /// Every well-formed line of `path`; nothing when it doesn't exist yet.
+    fn read<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut records = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read {:?}", path))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<T>(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => tracing::warn!("Skipping malformed line {:?}:{}: {}", path, i + 1, e),
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// A dropped store finishes what it queued, so nothing is lost on shutdown.
+impl Drop for FileStore {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// The writer thread: runs `jobs` in order until the store is dropped.
+fn write_jobs(jobs: mpsc::Receiver<Job>, hits: &Path) {
+    let mut hits_bytes = std::fs::metadata(hits).map(|m| m.len()).unwrap_or(0);
+    for job in jobs {
+        match job {
+            Job::Append { path, line } => {
+                let written = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .and_then(|mut file| file.write_all(line.as_bytes()));
+                if let Err(e) = written {
+                    tracing::warn!("Failed to append to {:?}: {}", path, e);
+                    continue;
+                }
+                if path != hits {
+                    continue;
+                }
+                hits_bytes += line.len() as u64;
+                if hits_bytes > MAX_HITS_BYTES {
+                    match compact_hits(hits) {
+                        Ok(len) => hits_bytes = len,
+                        Err(e) => tracing::warn!("Failed to compact {:?}: {:#}", hits, e),
+                    }
+                }
+            }
+            Job::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Per-endpoint totals of every hits record in `path`.
+fn hit_totals(path: &Path) -> Result<BTreeMap<String, u64>> {
+    let mut totals = BTreeMap::new();
+    for record in FileStore::read::<HitsRecord>(path)? {
+        add_hits(&mut totals, &record.endpoints);
+        for (endpoint, count) in record.totals {
+            *totals.entry(endpoint).or_default() += count;
+        }
+    }
+    Ok(totals)
+}
+
+/// Rewrites `path` as a single record of its totals, through a temporary file
+/// so a crash leaves the old file whole; returns the new size.
+fn compact_hits(path: &Path) -> Result<u64> {
+    let record = HitsRecord {
+        at: Utc::now(),
+        endpoints: Vec::new(),
+        totals: hit_totals(path)?,
+    };
+    let mut line = serde_json::to_string(&record)?;
+    line.push('\n');
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, &line).with_context(|| format!("Failed to write {:?}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {:?}", path))?;
+    Ok(line.len() as u64)
+}
+
+impl Store for FileStore {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn record_feedback(&self, record: &FeedbackRecord) -> Result<()> {
+        self.append(&self.feedback, record)
+    }
+
+    fn feedback(&self) -> Result<Vec<FeedbackRecord>> {
+        self.flush()?;
+        FileStore::read(&self.feedback)
+    }
+
+    fn record_hits(&self, endpoints: &[String], at: DateTime<Utc>) -> Result<()> {
+        if endpoints.is_empty() {
+            return Ok(());
+        }
+        let record = HitsRecord {
+            at,
+            endpoints: endpoints.to_vec(),
+            totals: BTreeMap::new(),
+        };
+        self.append(&self.hits, &record)
+    }
+
+    fn hits(&self) -> Result<BTreeMap<String, u64>> {
+        self.flush()?;
+        hit_totals(&self.hits)
+    }
+
+    fn record_audit(&self, event: &AuditEvent) -> Result<()> {
+        let mut recent = self.recent_audit.lock().unwrap_or_else(|p| p.into_inner());
+        if recent.events.len() >= MAX_RECENT_AUDIT_EVENTS {
+            recent.events.pop_front();
+            recent.complete = false;
+        }
+        recent.events.push_back(event.clone());
+        self.append(&self.audit, event)
+    }
+
+    fn audit(&self, limit: usize) -> Result<Vec<AuditEvent>> {
+        {
+            let recent = self.recent_audit.lock().unwrap_or_else(|p| p.into_inner());
+            if limit <= recent.events.len() || recent.complete {
+                let skip = recent.events.len().saturating_sub(limit);
+                return Ok(recent.events.iter().skip(skip).cloned().collect());
+            }
+        }
+        self.flush()?;
+        let events = FileStore::read::<AuditEvent>(&self.audit)?;
+        let skip = events.len().saturating_sub(limit);
+        Ok(events.into_iter().skip(skip).collect())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuditParams {
+    pub limit: Option<usize>,
+}
+
+/// `GET /admin/audit?limit=`: the latest admin actions, oldest first. A
+/// `limit` past what the store keeps in memory reads its file, off the runtime.
+pub async fn audit_handler(
+    Extension(store): Extension<Arc<dyn Store>>,
+    Query(params): Query<AuditParams>,
+) -> Response {
+    let limit = params.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+    let reader = Arc::clone(&store);
+    let events = tokio::task::spawn_blocking(move || reader.audit(limit))
+        .await
+        .unwrap_or_else(|e| Err(anyhow!("Audit read panicked: {}", e)));
+    match events {
+        Ok(events) => AxumJson(json!({ "store": store.name(), "events": events })).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to read audit events: {:#}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AxumJson(json!({ "error": "Failed to read audit events" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("librarian-store-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn at(raw: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(raw)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn feedback(query_id: &str, endpoint: &str, useful: bool) -> FeedbackRecord {
+        FeedbackRecord {
+            query_id: query_id.to_string(),
+            endpoint: endpoint.to_string(),
+            useful,
+            notes: None,
+            recorded_at: at("2026-10-01T09:00:00Z"),
+        }
+    }
+
+    fn event(action: &str, minute: u32) -> AuditEvent {
+        AuditEvent {
+            at: at(&format!("2026-10-01T09:{:02}:00Z", minute)),
+            action: action.to_string(),
+            detail: json!({ "n": minute }),
+        }
+    }
+
+    /// The same writes to any store.
+    fn write(store: &dyn Store) {
+        let weather = "https://weather.example.com/mcp".to_string();
+        let geo = "https://geo.example.com/mcp".to_string();
+        store
+            .record_feedback(&feedback("q-1", &weather, true))
+            .unwrap();
+        store
+            .record_hits(&[weather.clone(), geo.clone()], at("2026-10-01T09:00:00Z"))
+            .unwrap();
+        store.record_audit(&event("maintenance", 5)).unwrap();
+        // the same endpoint with a trailing slash counts as the same entry
+        store
+            .record_hits(&[format!("{}/", weather)], at("2026-10-01T09:10:00Z"))
+            .unwrap();
+        store.record_hits(&[], at("2026-10-01T09:11:00Z")).unwrap();
+        store
+            .record_feedback(&feedback("q-2", &geo, false))
+            .unwrap();
+        store.record_audit(&event("catalog_add", 20)).unwrap();
+        store.record_audit(&event("catalog_reload", 30)).unwrap();
+    }
+
+    fn contents(store: &dyn Store) -> Value {
+        json!({
+            "feedback": store.feedback().unwrap(),
+            "hits": store.hits().unwrap(),
+            "audit": store.audit(2).unwrap(),
+        })
+    }
+
+    #[test]
+    fn memory_store_keeps_order_and_counts_hits_per_endpoint() {
+        let store = MemoryStore::default();
+        write(&store);
+        let feedback: Vec<String> = store
+            .feedback()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.query_id)
+            .collect();
+        assert_eq!(feedback, ["q-1", "q-2"]);
+        assert_eq!(
+            store.hits().unwrap(),
+            BTreeMap::from([
+                ("https://geo.example.com/mcp".to_string(), 1),
+                ("https://weather.example.com/mcp".to_string(), 2),
+            ])
+        );
+        let actions: Vec<String> = store
+            .audit(2)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(actions, ["catalog_add", "catalog_reload"]);
+        assert_eq!(store.audit(100).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn file_store_reads_back_what_it_wrote_after_reopening() {
+        let dir = Scratch::new();
+        let memory = MemoryStore::default();
+        write(&memory);
+        {
+            let file = FileStore::in_dir(&dir.0).unwrap();
+            write(&file);
+            assert_eq!(contents(&file), contents(&memory));
+        }
+        let reopened = FileStore::in_dir(&dir.0).unwrap();
+        assert_eq!(contents(&reopened), contents(&memory));
+        assert_eq!(reopened.audit(100).unwrap(), memory.audit(100).unwrap());
+    }
+
+    #[test]
+    fn file_store_skips_malformed_lines() {
+        let dir = Scratch::new();
+        let store = FileStore::in_dir(&dir.0).unwrap();
+        store
+            .record_feedback(&feedback("q-1", "https://a.example/mcp", true))
+            .unwrap();
+        store.flush().unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&store.feedback)
+            .unwrap();
+        file.write_all(b"not json\n\n").unwrap();
+        store
+            .record_feedback(&feedback("q-2", "https://a.example/mcp", false))
+            .unwrap();
+        assert_eq!(store.feedback().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn compacted_hits_keep_their_totals() {
+        let dir = Scratch::new();
+        let store = FileStore::in_dir(&dir.0).unwrap();
+        write(&store);
+        let before = store.hits().unwrap();
+        assert_eq!(
+            compact_hits(&store.hits).unwrap(),
+            std::fs::metadata(&store.hits).unwrap().len()
+        );
+        assert_eq!(
+            std::fs::read_to_string(&store.hits)
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+        assert_eq!(store.hits().unwrap(), before);
+
+        store
+            .record_hits(&["https://geo.example.com/mcp".to_string()], Utc::now())
+            .unwrap();
+        assert_eq!(store.hits().unwrap()["https://geo.example.com/mcp"], 2);
+    }
+
+    #[test]
+    fn audit_past_the_recent_tail_reads_the_file() {
+        let dir = Scratch::new();
+        let store = FileStore::in_dir(&dir.0).unwrap();
+        for i in 0..MAX_RECENT_AUDIT_EVENTS + 5 {
+            store
+                .record_audit(&event(&format!("action-{}", i), 0))
+                .unwrap();
+        }
+        let recent = store.audit(3).unwrap();
+        assert_eq!(
+            recent.last().unwrap().action,
+            format!("action-{}", MAX_RECENT_AUDIT_EVENTS + 4)
+        );
+        let all = store.audit(usize::MAX).unwrap();
+        assert_eq!(all.len(), MAX_RECENT_AUDIT_EVENTS + 5);
+        assert_eq!(all[0].action, "action-0");
+    }
+
+    #[test]
+    fn response_hits_are_the_recommended_endpoints() {
+        let store = MemoryStore::default();
+        record_response_hits(
+            &store,
+            &json!({ "recommendations": [{ "endpoint": "https://a.example/mcp" }, { "name": "no endpoint" }] }),
+        );
+        record_response_hits(&store, &json!({ "recommendations": [] }));
+        assert_eq!(
+            store.hits().unwrap(),
+            BTreeMap::from([("https://a.example/mcp".to_string(), 1)])
+        );
+    }
+}
@@ -0,0 +1,80 @@
+// src/backend/synonyms.rs
+use anyhow::{Context as _, Result};
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::path::Path;
+
+const DEFAULT_SYNONYMS_PATH: &str = "synonyms.json";
+
+/// Capability taxonomy used to widen retrieval queries. The file maps a term to
+/// its aliases; each key and its aliases form one group, and a query mentioning
+/// any member of a group is expanded with the remaining members.
+#[derive(Debug, Clone, Default)]
+pub struct SynonymMap {
+    groups: Vec<Vec<String>>,
+}
+
+impl SynonymMap {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {:?}", path.as_ref()))?;
+        let raw: HashMap<String, Vec<String>> = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse {:?} as a synonym map", path.as_ref()))?;
+
+        let groups = raw
+            .into_iter()
+            .map(|(term, aliases)| {
+                std::iter::once(term)
+                    .chain(aliases)
+                    .map(|t| t.trim().to_lowercase())
+                    .filter(|t| !t.is_empty())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|g| g.len() > 1)
+            .collect();
+        Ok(SynonymMap { groups })
+    }
+
+    /// Loads `LIBRARIAN_SYNONYMS_PATH` (default `synonyms.json`). A missing default
+    /// file simply disables expansion; an explicitly configured one must load.
+    pub fn from_env() -> Result<Self> {
+        match env::var("LIBRARIAN_SYNONYMS_PATH") {
+            Ok(path) => SynonymMap::from_file(path),
+            Err(_) if Path::new(DEFAULT_SYNONYMS_PATH).exists() => {
+                SynonymMap::from_file(DEFAULT_SYNONYMS_PATH)
+            }
+            Err(_) => Ok(SynonymMap::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Returns the query to embed and the expansion terms that were appended.
+    pub fn expand(&self, query: &str) -> (String, Vec<String>) {
+        let lowered = query.to_lowercase();
+        let mut expansions: Vec<String> = Vec::new();
+
+        for group in &self.groups {
+            if !group.iter().any(|term| lowered.contains(term.as_str())) {
+                continue;
+            }
+            for term in group {
+                if !lowered.contains(term.as_str()) && !expansions.contains(term) {
+                    expansions.push(term.clone());
+                }
+            }
+        }
+
+        if expansions.is_empty() {
+            return (query.to_string(), expansions);
+        }
+        (format!("{} {}", query, expansions.join(" ")), expansions)
+    }
+}
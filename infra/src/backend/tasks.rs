@@ -0,0 +1,117 @@
+// src/backend/tasks.rs
+//! Every background task (catalog watcher and refresh, reload rebuilds, the
+//! startup index build, webhook deliveries) is spawned through `TASKS`, so a
+//! graceful shutdown can cancel them together and wait for them. Long-running
+//! loops stop at their next wait once the shared token is cancelled; one-shot
+//! work (a rebuild, a webhook delivery) is left to finish. Whatever is still
+//! running after `LIBRARIAN_SHUTDOWN_DRAIN_SECS` (default 10) is aborted.
+use std::env;
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_SHUTDOWN_DRAIN_SECS: u64 = 10;
+
+/// How long shutdown waits for background tasks, from
+/// `LIBRARIAN_SHUTDOWN_DRAIN_SECS`.
+pub fn drain_window_from_env() -> Duration {
+    let secs = env::var("LIBRARIAN_SHUTDOWN_DRAIN_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SHUTDOWN_DRAIN_SECS);
+    Duration::from_secs(secs)
+}
+
+#[derive(Default)]
+pub struct BackgroundTasks {
+    token: CancellationToken,
+    handles: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+}
+
+impl BackgroundTasks {
+    /// Cancelled when shutdown starts.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Spawns `task`, handing it the shared token. Finished tasks are
+    /// forgotten here, so short-lived ones don't accumulate.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task(self.token()));
+        let mut handles = self.handles.lock().unwrap_or_else(|p| p.into_inner());
+        handles.retain(|(_, handle)| !handle.is_finished());
+        handles.push((name, handle));
+    }
+
+    /// Cancels the token and waits up to `drain` for every registered task,
+    /// aborting the rest. Logs each task's outcome.
+    pub async fn shutdown(&self, drain: Duration) {
+        self.token.cancel();
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap_or_else(|p| p.into_inner()));
+        if handles.is_empty() {
+            return;
+        }
+        tracing::info!(
+            tasks = handles.len(),
+            drain_secs = drain.as_secs(),
+            "Draining background tasks"
+        );
+
+        let deadline = Instant::now() + drain;
+        for (name, mut handle) in handles {
+            match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(())) => tracing::info!(task = name, "Background task finished"),
+                Ok(Err(e)) if e.is_panic() => {
+                    tracing::error!(task = name, "Background task panicked")
+                }
+                Ok(Err(_)) => tracing::warn!(task = name, "Background task was cancelled"),
+                Err(_) => {
+                    handle.abort();
+                    tracing::warn!(
+                        task = name,
+                        "Background task aborted after the drain window"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl-C or, on Unix, `SIGTERM`.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutdown signal received, no longer accepting connections");
+}
+
+pub static TASKS: LazyLock<BackgroundTasks> = LazyLock::new(BackgroundTasks::default);
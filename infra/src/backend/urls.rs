@@ -0,0 +1,127 @@
+// src/backend/urls.rs
+//! URL joins and endpoint comparison in one place. A base URL is kept in
+//! directory form (trailing `/`), so `https://api.example.com/librarian` and
+//! `https://api.example.com/librarian/` both resolve `/discover` to
+//! `https://api.example.com/librarian/discover`: neither a doubled nor a
+//! missing slash, and the base path is never dropped. Endpoints compare with
+//! surrounding whitespace and trailing slashes ignored.
+use anyhow::{Context as _, Result, bail};
+use std::env;
+use url::Url;
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:8080/";
+
+/// `url` with its path ending in exactly one `/`.
+pub fn directory(mut url: Url) -> Url {
+    let path = format!("{}/", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    url
+}
+
+/// Parses an `http(s)` base URL into directory form.
+pub fn parse_base(raw: &str) -> Result<Url> {
+    let url = Url::parse(raw.trim()).with_context(|| format!("Invalid base URL {:?}", raw))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("Base URL {:?} must be http or https", raw);
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        bail!("Base URL {:?} must not carry a query or fragment", raw);
+    }
+    Ok(directory(url))
+}
+
+/// `API_BASE_URL` (default `http://localhost:8080/`), in directory form.
+pub fn base_url_from_env() -> Result<Url> {
+    let raw = env::var("API_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+    parse_base(&raw).context("Invalid API_BASE_URL")
+}
+
+/// `route` resolved under `base`, whichever side carries the slash.
+pub fn join(base: &Url, route: &str) -> Result<Url> {
+    directory(base.clone())
+        .join(route.trim_start_matches('/'))
+        .with_context(|| format!("Failed to join {:?} onto {}", route, base))
+}
+
+/// `join` for plain strings that need not parse, e.g. a configured service URL.
+pub fn join_str(base: &str, route: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim().trim_end_matches('/'),
+        route.trim_start_matches('/')
+    )
+}
+
+/// The form endpoints are compared and keyed in.
+pub fn endpoint_key(endpoint: &str) -> &str {
+    endpoint.trim().trim_end_matches('/')
+}
+
+pub fn same_endpoint(a: &str, b: &str) -> bool {
+    endpoint_key(a) == endpoint_key(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(base: &str, route: &str) -> String {
+        join(&parse_base(base).unwrap(), route).unwrap().to_string()
+    }
+
+    #[test]
+    fn join_keeps_one_slash_and_the_base_path() {
+        for base in ["http://localhost:8080/", "http://localhost:8080"] {
+            assert_eq!(joined(base, "/discover"), "http://localhost:8080/discover");
+            assert_eq!(joined(base, "discover"), "http://localhost:8080/discover");
+        }
+        for base in [
+            "https://api.example.com/librarian",
+            "https://api.example.com/librarian/",
+            "https://api.example.com/librarian//",
+        ] {
+            assert_eq!(
+                joined(base, "/discover"),
+                "https://api.example.com/librarian/discover"
+            );
+            assert_eq!(
+                joined(base, "//discover"),
+                "https://api.example.com/librarian/discover"
+            );
+        }
+        assert_eq!(
+            joined(" https://api.example.com/v1/ ", "/schema/discover"),
+            "https://api.example.com/v1/schema/discover"
+        );
+    }
+
+    #[test]
+    fn parse_base_refuses_what_cannot_be_a_base() {
+        let error = |raw: &str| format!("{:#}", parse_base(raw).unwrap_err());
+        assert_eq!(
+            error("https://api.example.com/?x=1"),
+            "Base URL \"https://api.example.com/?x=1\" must not carry a query or fragment"
+        );
+        assert_eq!(
+            error("ftp://files.example.com/"),
+            "Base URL \"ftp://files.example.com/\" must be http or https"
+        );
+        assert!(error("not a url").starts_with("Invalid base URL \"not a url\""));
+    }
+
+    #[test]
+    fn endpoints_compare_without_trailing_slashes_or_spaces() {
+        assert!(same_endpoint(
+            " https://a.example/mcp/ ",
+            "https://a.example/mcp"
+        ));
+        assert!(!same_endpoint(
+            "https://a.example/mcp",
+            "https://a.example/mcp2"
+        ));
+        assert_eq!(
+            join_str("https://a.example/ ", "/health"),
+            "https://a.example/health"
+        );
+    }
+}
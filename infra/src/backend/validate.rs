@@ -0,0 +1,406 @@
+// src/backend/validate.rs
+use super::McpEntry;
+use super::mirrors;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::env;
+
+pub const DEFAULT_PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// MCP protocol versions the Librarian is willing to recommend, from the
+/// comma-separated `MCP_PROTOCOL_VERSIONS` (default: the version the preamble pins).
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolVersions(Vec<String>);
+
+impl Default for ProtocolVersions {
+    fn default() -> Self {
+        ProtocolVersions(vec![DEFAULT_PROTOCOL_VERSION.to_string()])
+    }
+}
+
+impl ProtocolVersions {
+    pub fn from_env() -> Self {
+        let versions: Vec<String> = env::var("MCP_PROTOCOL_VERSIONS")
+            .unwrap_or_default()
+            .split(',')
+            .map(normalize_protocol_version)
+            .filter(|v| !v.is_empty())
+            .collect();
+        if versions.is_empty() {
+            return ProtocolVersions::default();
+        }
+        ProtocolVersions(versions)
+    }
+
+    pub fn supports(&self, version: &str) -> bool {
+        let version = normalize_protocol_version(version);
+        self.0.iter().any(|v| *v == version)
+    }
+
+    pub fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+}
+
+/// Versions are `YYYY-MM-DD` dates; strip whitespace and quotes the model may add.
+pub fn normalize_protocol_version(version: &str) -> String {
+    version.trim().trim_matches('"').to_string()
+}
+
+/// On unless `LIBRARIAN_ENFORCE_QUERY_ECHO=false`.
+pub fn enforce_query_echo_enabled() -> bool {
+    !env::var("LIBRARIAN_ENFORCE_QUERY_ECHO").is_ok_and(|v| v == "0" || v == "false")
+}
+
+/// Replaces the model's echo of the query, which it may paraphrase, truncate
+/// or leave out, with the caller's exact `query`. Returns whether it differed.
+pub fn enforce_query_echo(response: &mut Value, query: &str) -> bool {
+    let Some(map) = response.as_object_mut() else {
+        return false;
+    };
+    if map.get("query").and_then(Value::as_str) == Some(query) {
+        return false;
+    }
+    let echoed = map.get("query").and_then(Value::as_str);
+    tracing::debug!(
+        ?echoed,
+        "Model did not echo the query exactly; restoring it"
+    );
+    map.insert("query".to_string(), Value::String(query.to_string()));
+    true
+}
+
+/// Drops recommendations whose `protocol_version` is missing or unsupported,
+/// normalizing the ones that are kept. Returns the number dropped.
+pub fn retain_supported_versions(response: &mut Value, versions: &ProtocolVersions) -> usize {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return 0;
+    };
+
+    let before = recommendations.len();
+    recommendations.retain_mut(|rec| {
+        let version = rec
+            .get("protocol_version")
+            .and_then(Value::as_str)
+            .map(normalize_protocol_version)
+            .unwrap_or_default();
+        if versions.supports(&version) {
+            rec["protocol_version"] = Value::String(version);
+            true
+        } else {
+            let name = rec.get("name").and_then(Value::as_str).unwrap_or_default();
+            tracing::warn!(
+                "Dropping recommendation {:?} with unsupported protocol_version {:?}",
+                name,
+                version
+            );
+            false
+        }
+    });
+    before - recommendations.len()
+}
+
+/// Enforces policy rule 5 in code: drops every recommendation whose endpoint is
+/// not in the loaded catalog, along with its `instructions` block. A known
+/// endpoint under the wrong name is corrected to the catalog name. Returns the
+/// number of recommendations dropped.
+pub fn retain_catalog_recommendations(response: &mut Value, catalog: &[McpEntry]) -> usize {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return 0;
+    };
+
+    let mut dropped_names = Vec::new();
+    recommendations.retain_mut(|rec| {
+        let endpoint = rec
+            .get("endpoint")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let name = rec
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        match mirrors::find(catalog, endpoint) {
+            Some(entry) => {
+                if entry.name != name {
+                    tracing::warn!(
+                        "Model named catalog endpoint {} as {:?}; using catalog name {:?}",
+                        entry.endpoint,
+                        name,
+                        entry.name
+                    );
+                    rec["name"] = Value::String(entry.name.clone());
+                }
+                // deployments are rebuilt from the catalog by `mirrors::apply`
+                if let Some(map) = rec.as_object_mut() {
+                    map.remove("mirrors");
+                }
+                // curator metadata, never the model's guess
+                match &entry.rate_limit {
+                    Some(limit) => rec["rate_limit"] = json!(limit),
+                    None => {
+                        if let Some(map) = rec.as_object_mut() {
+                            map.remove("rate_limit");
+                        }
+                    }
+                }
+                true
+            }
+            None => {
+                tracing::warn!(
+                    "Dropping hallucinated recommendation {:?} with endpoint {:?}",
+                    name,
+                    endpoint
+                );
+                dropped_names.push(name);
+                false
+            }
+        }
+    });
+
+    if let Some(instructions) = response
+        .get_mut("instructions")
+        .and_then(Value::as_object_mut)
+    {
+        for name in &dropped_names {
+            instructions.remove(name);
+        }
+    }
+    dropped_names.len()
+}
+
+/// Default `min_score` from `LIBRARIAN_MIN_SCORE` (0–100). Unset or `0` keeps
+/// every recommendation.
+pub fn min_score_from_env() -> u64 {
+    env::var("LIBRARIAN_MIN_SCORE")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|v| v.min(100))
+        .unwrap_or(0)
+}
+
+/// Drops recommendations scoring below `min_score`, with their `instructions`
+/// blocks. If none survive, `response` becomes the canonical no-match response.
+/// Returns the number dropped.
+pub fn retain_min_score(response: &mut Value, query: &str, min_score: u64) -> usize {
+    if min_score == 0 {
+        return 0;
+    }
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return 0;
+    };
+
+    let mut dropped_names = Vec::new();
+    recommendations.retain(|rec| {
+        let score = rec.get("score").and_then(Value::as_u64).unwrap_or(0);
+        if score >= min_score {
+            return true;
+        }
+        dropped_names.push(
+            rec.get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        );
+        false
+    });
+    if recommendations.is_empty() && !dropped_names.is_empty() {
+        tracing::debug!("All recommendations scored below min_score {}", min_score);
+        *response = super::response::empty_response(query);
+        return dropped_names.len();
+    }
+
+    if let Some(instructions) = response
+        .get_mut("instructions")
+        .and_then(Value::as_object_mut)
+    {
+        for name in &dropped_names {
+            instructions.remove(name);
+        }
+    }
+    dropped_names.len()
+}
+
+/// Orders recommendations best first: by `score`, then by the retrieval
+/// similarity of the matching candidate, then by endpoint, so equal scores
+/// always come out in the same order.
+pub fn sort_recommendations(response: &mut Value, candidates: &[(f64, McpEntry)]) {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    let key = |rec: &Value| {
+        let endpoint = rec
+            .get("endpoint")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let similarity = candidates
+            .iter()
+            .find(|(_, entry)| mirrors::serves(entry, endpoint))
+            .map_or(f64::NEG_INFINITY, |(similarity, _)| *similarity);
+        let score = rec.get("score").and_then(Value::as_u64).unwrap_or(0);
+        (score, similarity, endpoint.to_string())
+    };
+    recommendations.sort_by(|a, b| {
+        let ((score_a, similarity_a, endpoint_a), (score_b, similarity_b, endpoint_b)) =
+            (key(a), key(b));
+        score_b
+            .cmp(&score_a)
+            .then_with(|| similarity_b.total_cmp(&similarity_a))
+            .then_with(|| endpoint_a.cmp(&endpoint_b))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::response::empty_response;
+
+    fn catalog() -> Vec<McpEntry> {
+        let entry: McpEntry = serde_json::from_value(json!({
+            "name": "weather",
+            "endpoint": "https://weather.example.com/mcp",
+            "version": "1.0.0",
+            "capabilities": ["forecast"],
+            "desc": "Forecasts",
+        }))
+        .unwrap();
+        vec![entry]
+    }
+
+    #[test]
+    fn hallucinated_endpoints_are_dropped_with_their_instructions() {
+        let mut agent_output = json!({
+            "recommendations": [
+                { "name": "weather", "endpoint": "https://weather.example.com/mcp" },
+                { "name": "weather-pro", "endpoint": "https://weather-pro.example.com/mcp" },
+            ],
+            "instructions": { "weather": {}, "weather-pro": {} },
+        });
+        assert_eq!(
+            retain_catalog_recommendations(&mut agent_output, &catalog()),
+            1
+        );
+        let recommendations = agent_output["recommendations"].as_array().unwrap();
+        assert_eq!(recommendations.len(), 1);
+        assert_eq!(
+            recommendations[0]["endpoint"],
+            "https://weather.example.com/mcp"
+        );
+        assert_eq!(agent_output["instructions"], json!({ "weather": {} }));
+    }
+
+    #[test]
+    fn a_known_endpoint_under_the_wrong_name_is_renamed() {
+        let mut agent_output = json!({
+            "recommendations": [{ "name": "Weather API", "endpoint": "https://weather.example.com/mcp/" }],
+        });
+        assert_eq!(
+            retain_catalog_recommendations(&mut agent_output, &catalog()),
+            0
+        );
+        assert_eq!(agent_output["recommendations"][0]["name"], "weather");
+    }
+
+    fn scored(scores: &[(&str, u64)]) -> Value {
+        let recommendations: Vec<Value> = scores
+            .iter()
+            .map(|(name, score)| json!({ "name": name, "score": score }))
+            .collect();
+        let instructions: serde_json::Map<String, Value> = scores
+            .iter()
+            .map(|(name, _)| (name.to_string(), json!({})))
+            .collect();
+        json!({ "query": "weather", "recommendations": recommendations, "instructions": instructions })
+    }
+
+    #[test]
+    fn min_score_drops_low_scores_with_their_instructions() {
+        let mut response = scored(&[("weather", 92), ("alerts", 20), ("radar", 60)]);
+        assert_eq!(retain_min_score(&mut response, "weather", 60), 1);
+        let names: Vec<&str> = response["recommendations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|rec| rec["name"].as_str())
+            .collect();
+        assert_eq!(names, ["weather", "radar"]);
+        assert!(response["instructions"].get("alerts").is_none());
+    }
+
+    #[test]
+    fn min_score_that_drops_everything_answers_no_match() {
+        let mut response = scored(&[("weather", 35), ("alerts", 20)]);
+        assert_eq!(retain_min_score(&mut response, "weather", 50), 2);
+        assert_eq!(response, empty_response("weather"));
+    }
+
+    #[test]
+    fn zero_min_score_keeps_everything() {
+        let mut response = scored(&[("weather", 0), ("alerts", 20)]);
+        assert_eq!(retain_min_score(&mut response, "weather", 0), 0);
+        assert_eq!(response["recommendations"].as_array().unwrap().len(), 2);
+    }
+
+    fn endpoints(response: &Value) -> Vec<&str> {
+        response["recommendations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|rec| rec["endpoint"].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn equal_scores_break_ties_by_similarity_then_endpoint() {
+        let rec = |endpoint: &str, score: u64| json!({ "endpoint": endpoint, "score": score });
+        let candidate = |endpoint: &str, similarity: f64| {
+            let mut entry = catalog().remove(0);
+            entry.endpoint = endpoint.to_string();
+            (similarity, entry)
+        };
+        let candidates = [
+            candidate("https://b.example/mcp", 0.9),
+            candidate("https://c.example/mcp", 0.4),
+        ];
+        let mut response = json!({ "recommendations": [
+            rec("https://d.example/mcp", 80),
+            rec("https://a.example/mcp", 80),
+            rec("https://c.example/mcp", 80),
+            rec("https://e.example/mcp", 95),
+            rec("https://b.example/mcp", 80),
+        ]});
+        sort_recommendations(&mut response, &candidates);
+        assert_eq!(
+            endpoints(&response),
+            [
+                "https://e.example/mcp",
+                "https://b.example/mcp",
+                "https://c.example/mcp",
+                "https://a.example/mcp",
+                "https://d.example/mcp",
+            ]
+        );
+
+        // any input order comes out the same
+        let mut reversed = response.clone();
+        reversed["recommendations"]
+            .as_array_mut()
+            .unwrap()
+            .reverse();
+        sort_recommendations(&mut reversed, &candidates);
+        assert_eq!(reversed, response);
+    }
+}
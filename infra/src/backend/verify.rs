@@ -0,0 +1,793 @@
+// src/backend/verify.rs
+//! Cached results of live MCP verification (initialize + list calls) and the
+//! rolling availability derived from them. The cache outlives catalog reloads
+//! and is keyed by normalized endpoint. `VerifyHttp::check` runs the handshake
+//! itself.
+//!
+//! `spawn_verification` sweeps every deployment of the live catalog once the
+//! index is ready and then every `LIBRARIAN_VERIFY_INTERVAL_SECS` (default
+//! 900; `0` disables), `LIBRARIAN_VERIFY_CONCURRENCY` (default 8) servers at a
+//! time. Each server gets `VERIFY_ENDPOINT_TIMEOUT_SECS` (default 30) for the
+//! whole handshake, so one that hangs only costs its own slot. A server that
+//! answers `401`/`403` is recorded as requiring auth and kept out of
+//! `/discover` candidates unless the caller sets `allow_auth`.
+use super::LibrarianHandle;
+use super::McpEntry;
+use super::clock;
+use super::mirrors;
+use super::redact;
+use super::session;
+use super::tasks::TASKS;
+use super::urls::endpoint_key;
+use super::validate::DEFAULT_PROTOCOL_VERSION;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationResult {
+    /// `initialize` and the list calls all succeeded.
+    pub ok: bool,
+    pub tools: Vec<String>,
+    pub resources: Vec<String>,
+    pub prompts: Vec<String>,
+    pub checked_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The server answered `401`/`403`: up, but not usable without credentials.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auth_required: bool,
+}
+
+const DEFAULT_AVAILABILITY_WINDOW: usize = 20;
+const DEFAULT_VERIFY_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_VERIFY_POOL_IDLE_PER_HOST: usize = 4;
+const DEFAULT_VERIFY_ENDPOINT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_VERIFY_INTERVAL_SECS: u64 = 900;
+const DEFAULT_VERIFY_CONCURRENCY: usize = 8;
+
+/// The one HTTP client every verification call goes through, so connections
+/// are pooled across the whole catalog sweep. `VERIFY_TIMEOUT_SECS` bounds each
+/// call; `VERIFY_POOL_IDLE_PER_HOST` caps idle connections kept per server;
+/// `VERIFY_ENDPOINT_TIMEOUT_SECS` bounds one server's whole handshake.
+#[derive(Debug, Clone)]
+pub struct VerifyHttp {
+    pub client: reqwest::Client,
+    pub timeout: Duration,
+    pub endpoint_timeout: Duration,
+}
+
+impl VerifyHttp {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |key: &str| env::var(key).ok().and_then(|v| v.parse::<u64>().ok());
+        let timeout =
+            Duration::from_secs(var("VERIFY_TIMEOUT_SECS").unwrap_or(DEFAULT_VERIFY_TIMEOUT_SECS));
+        let idle_per_host = var("VERIFY_POOL_IDLE_PER_HOST")
+            .map_or(DEFAULT_VERIFY_POOL_IDLE_PER_HOST, |v| v as usize);
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout)
+            .pool_max_idle_per_host(idle_per_host)
+            .build()?;
+        let endpoint_timeout = Duration::from_secs(
+            var("VERIFY_ENDPOINT_TIMEOUT_SECS").unwrap_or(DEFAULT_VERIFY_ENDPOINT_TIMEOUT_SECS),
+        );
+        Ok(VerifyHttp {
+            client,
+            timeout,
+            endpoint_timeout,
+        })
+    }
+}
+
+/// How one live check ended. An auth challenge means the server is up but
+/// wants credentials the checker doesn't have.
+#[derive(Debug, Clone)]
+pub enum LiveCheck {
+    Reachable(VerificationResult),
+    AuthChallenge { status: u16, scheme: Option<String> },
+    Failed(VerificationResult),
+}
+
+enum Step {
+    Auth { status: u16, scheme: Option<String> },
+    Error(String),
+}
+
+/// A JSON-RPC reply: the `Mcp-Session-Id` it set, if any, and its message.
+struct Reply {
+    session: Option<String>,
+    body: String,
+    event_stream: bool,
+}
+
+impl Reply {
+    /// The message in the body, plain JSON or the first `data:` event of an
+    /// SSE stream.
+    fn message(&self) -> Result<Value, String> {
+        if self.event_stream {
+            self.body
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .find_map(|data| serde_json::from_str(data.trim()).ok())
+                .ok_or_else(|| "no JSON-RPC message in the event stream".to_string())
+        } else {
+            serde_json::from_str(&self.body)
+                .map_err(|e| format!("invalid JSON-RPC response: {}", e))
+        }
+    }
+}
+
+fn rpc_result(message: &Value) -> Result<Value, String> {
+    if let Some(error) = message.get("error") {
+        return Err(format!("JSON-RPC error: {}", error));
+    }
+    message
+        .get("result")
+        .cloned()
+        .ok_or_else(|| "JSON-RPC response without a result".to_string())
+}
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// `key` of each object in `result[field]`, e.g. the tool names of `tools/list`.
+fn listed_names(result: &Value, field: &str, key: &str) -> Vec<String> {
+    result
+        .get(field)
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.get(key).and_then(Value::as_str).map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl VerifyHttp {
+    async fn post(
+        &self,
+        endpoint: &str,
+        session: Option<&str>,
+        body: Value,
+    ) -> Result<Reply, Step> {
+        let mut request = self
+            .client
+            .post(endpoint)
+            .header(
+                reqwest::header::ACCEPT,
+                "application/json, text/event-stream",
+            )
+            .json(&body);
+        if let Some(session) = session {
+            request = request.header(session::SESSION_HEADER, session);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Step::Error(e.to_string()))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            let scheme = response
+                .headers()
+                .get(reqwest::header::WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split_whitespace().next())
+                .map(str::to_string);
+            return Err(Step::Auth {
+                status: status.as_u16(),
+                scheme,
+            });
+        }
+        if !status.is_success() {
+            return Err(Step::Error(format!("HTTP {}", status)));
+        }
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let session = header(session::SESSION_HEADER);
+        let event_stream =
+            header("content-type").is_some_and(|t| t.starts_with("text/event-stream"));
+        let body = response
+            .text()
+            .await
+            .map_err(|e| Step::Error(e.to_string()))?;
+        Ok(Reply {
+            session,
+            body,
+            event_stream,
+        })
+    }
+
+    /// `method`'s result, or `None` when the server doesn't implement it.
+    async fn list(
+        &self,
+        endpoint: &str,
+        session: Option<&str>,
+        method: &str,
+    ) -> Result<Option<Value>, Step> {
+        let reply = self
+            .post(
+                endpoint,
+                session,
+                json!({ "jsonrpc": "2.0", "id": method, "method": method }),
+            )
+            .await?;
+        let failed = |e: String| Step::Error(format!("{}: {}", method, e));
+        let message = reply.message().map_err(failed)?;
+        if message.pointer("/error/code").and_then(Value::as_i64) == Some(METHOD_NOT_FOUND) {
+            return Ok(None);
+        }
+        rpc_result(&message).map(Some).map_err(failed)
+    }
+
+    async fn handshake(
+        &self,
+        endpoint: &str,
+        protocol_version: &str,
+    ) -> Result<VerificationResult, Step> {
+        let init = self
+            .post(
+                endpoint,
+                None,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": "init-1",
+                    "method": "initialize",
+                    "params": {
+                        "protocolVersion": protocol_version,
+                        "capabilities": {},
+                        "clientInfo": { "name": "librarian-verify", "version": env!("CARGO_PKG_VERSION") },
+                    },
+                }),
+            )
+            .await?;
+        init.message()
+            .and_then(|message| rpc_result(&message))
+            .map_err(|e| Step::Error(format!("initialize: {}", e)))?;
+        let session = init.session.as_deref();
+        self.post(
+            endpoint,
+            session,
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        )
+        .await?;
+
+        let tools = self.list(endpoint, session, "tools/list").await?;
+        let resources = self.list(endpoint, session, "resources/list").await?;
+        let prompts = self.list(endpoint, session, "prompts/list").await?;
+        if let Some(session) = session {
+            self.close(endpoint, session).await;
+        }
+        let names = |result: Option<Value>, field: &str, key: &str| {
+            result
+                .map(|r| listed_names(&r, field, key))
+                .unwrap_or_default()
+        };
+        Ok(VerificationResult {
+            ok: true,
+            tools: names(tools, "tools", "name"),
+            resources: names(resources, "resources", "uri"),
+            prompts: names(prompts, "prompts", "name"),
+            checked_at: Utc::now(),
+            error: None,
+            auth_required: false,
+        })
+    }
+
+    /// Ends the session with `DELETE`. Best effort: servers may refuse it
+    /// (`405`), and the lists already succeeded.
+    async fn close(&self, endpoint: &str, session: &str) {
+        let result = self
+            .client
+            .delete(endpoint)
+            .header(session::SESSION_HEADER, session)
+            .send()
+            .await;
+        if let Err(e) = result {
+            tracing::debug!(endpoint, "Closing the verification session failed: {}", e);
+        }
+    }
+
+    /// One live check of `endpoint`, a single deployment (see `mirrors`):
+    /// `initialize`, the `initialized` notification, the list calls and the
+    /// closing `DELETE`, sent without credentials and bounded by
+    /// `endpoint_timeout` as a whole.
+    pub async fn check(&self, endpoint: &str, protocol_version: &str) -> LiveCheck {
+        let handshake = tokio::time::timeout(
+            self.endpoint_timeout,
+            self.handshake(endpoint, protocol_version),
+        );
+        match handshake.await {
+            Ok(Ok(result)) => LiveCheck::Reachable(result),
+            Ok(Err(Step::Auth { status, scheme })) => LiveCheck::AuthChallenge { status, scheme },
+            Ok(Err(Step::Error(error))) => {
+                LiveCheck::Failed(VerificationResult::failed(error, false))
+            }
+            Err(_) => LiveCheck::Failed(VerificationResult::failed(
+                format!("no answer within {}s", self.endpoint_timeout.as_secs()),
+                false,
+            )),
+        }
+    }
+}
+
+impl VerificationResult {
+    fn failed(error: String, auth_required: bool) -> Self {
+        VerificationResult {
+            ok: false,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            checked_at: Utc::now(),
+            error: Some(error),
+            auth_required,
+        }
+    }
+}
+
+impl LiveCheck {
+    /// What the cache keeps of this check.
+    pub fn into_result(self) -> VerificationResult {
+        match self {
+            LiveCheck::Reachable(result) | LiveCheck::Failed(result) => result,
+            LiveCheck::AuthChallenge { status, scheme } => {
+                let scheme = scheme.map(|s| format!(" ({})", s)).unwrap_or_default();
+                VerificationResult::failed(
+                    format!("auth required: HTTP {}{}", status, scheme),
+                    true,
+                )
+            }
+        }
+    }
+}
+
+/// How one sweep went, for the log.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SweepSummary {
+    pub verified: usize,
+    pub auth_required: usize,
+    pub failed: usize,
+}
+
+/// `LIBRARIAN_VERIFY_INTERVAL_SECS`; `None` when set to `0`.
+pub fn verify_interval_from_env() -> Option<Duration> {
+    let secs = env::var("LIBRARIAN_VERIFY_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_VERIFY_INTERVAL_SECS);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+fn verify_concurrency() -> usize {
+    env::var("LIBRARIAN_VERIFY_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_VERIFY_CONCURRENCY)
+}
+
+/// Checks every deployment of every entry in `catalog`, at most `concurrency`
+/// at once, and stores each outcome in `cache` as soon as it lands.
+pub async fn sweep(
+    http: &VerifyHttp,
+    catalog: &[McpEntry],
+    protocol_version: &str,
+    cache: &Arc<VerificationCache>,
+    concurrency: usize,
+) -> SweepSummary {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut checks = JoinSet::new();
+    for endpoint in catalog
+        .iter()
+        .flat_map(mirrors::deployments)
+        .map(|d| d.endpoint)
+    {
+        let (http, permits, cache) = (http.clone(), Arc::clone(&permits), Arc::clone(cache));
+        let protocol_version = protocol_version.to_string();
+        checks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = http.check(&endpoint, &protocol_version).await.into_result();
+            let outcome = (result.ok, result.auth_required);
+            cache.insert(&endpoint, result);
+            outcome
+        });
+    }
+
+    let mut summary = SweepSummary::default();
+    while let Some(outcome) = checks.join_next().await {
+        match outcome {
+            Ok((true, _)) => summary.verified += 1,
+            Ok((false, true)) => summary.auth_required += 1,
+            Ok((false, false)) => summary.failed += 1,
+            Err(e) => {
+                summary.failed += 1;
+                tracing::warn!("Verification task failed: {}", e);
+            }
+        }
+    }
+    summary
+}
+
+/// Verifies the live catalog now and then every `interval`, picking up
+/// reloads between sweeps. Stops between sweeps on shutdown.
+pub fn spawn_verification(handle: LibrarianHandle, http: Arc<VerifyHttp>, interval: Duration) {
+    let concurrency = verify_concurrency();
+    tracing::info!(
+        concurrency,
+        "Verifying catalog servers every {:?}",
+        interval
+    );
+    TASKS.spawn("catalog_verification", |cancel| async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = cancel.cancelled() => return,
+            }
+            let librarian = handle.current();
+            let protocol_version = librarian
+                .protocol_versions
+                .as_slice()
+                .first()
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_PROTOCOL_VERSION.to_string());
+            let started = std::time::Instant::now();
+            let summary = sweep(
+                &http,
+                &librarian.catalog,
+                &protocol_version,
+                &librarian.verification,
+                concurrency,
+            )
+            .await;
+            tracing::info!(
+                verified = summary.verified,
+                auth_required = summary.auth_required,
+                failed = summary.failed,
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "Catalog verification sweep finished"
+            );
+        }
+    });
+}
+
+/// Latest result per endpoint plus a rolling window of recent outcomes, sized
+/// by `LIBRARIAN_AVAILABILITY_WINDOW` (default 20 checks).
+#[derive(Debug)]
+pub struct VerificationCache {
+    latest: RwLock<HashMap<String, VerificationResult>>,
+    outcomes: RwLock<HashMap<String, VecDeque<bool>>>,
+    window: usize,
+}
+
+impl Default for VerificationCache {
+    fn default() -> Self {
+        VerificationCache::with_window(DEFAULT_AVAILABILITY_WINDOW)
+    }
+}
+
+fn key(endpoint: &str) -> String {
+    endpoint_key(endpoint).to_string()
+}
+
+impl VerificationCache {
+    pub fn with_window(window: usize) -> Self {
+        VerificationCache {
+            latest: RwLock::new(HashMap::new()),
+            outcomes: RwLock::new(HashMap::new()),
+            window: window.max(1),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let window = env::var("LIBRARIAN_AVAILABILITY_WINDOW")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_AVAILABILITY_WINDOW);
+        VerificationCache::with_window(window)
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<VerificationResult> {
+        let guard = self
+            .latest
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.get(&key(endpoint)).cloned()
+    }
+
+    /// Stores `result` as the latest and records its outcome in the rolling window.
+    pub fn insert(&self, endpoint: &str, result: VerificationResult) {
+        {
+            let mut outcomes = self
+                .outcomes
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let window = outcomes.entry(key(endpoint)).or_default();
+            window.push_back(result.ok);
+            while window.len() > self.window {
+                window.pop_front();
+            }
+        }
+        let mut guard = self
+            .latest
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.insert(key(endpoint), result);
+    }
+
+    /// Percentage (0–100) of successful checks in the window, or `None` if the
+    /// endpoint has never been checked.
+    pub fn availability(&self, endpoint: &str) -> Option<f64> {
+        let outcomes = self
+            .outcomes
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window = outcomes.get(&key(endpoint)).filter(|w| !w.is_empty())?;
+        let ok = window.iter().filter(|ok| **ok).count();
+        Some(100.0 * ok as f64 / window.len() as f64)
+    }
+
+    pub fn len(&self) -> usize {
+        self.latest
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Minimum availability from the request's `filters.min_availability`, falling
+/// back to `LIBRARIAN_MIN_AVAILABILITY`. `None` disables the check.
+pub fn min_availability(filters: Option<&Value>) -> Option<f64> {
+    filters
+        .and_then(|f| f.get("min_availability"))
+        .and_then(Value::as_f64)
+        .or_else(|| {
+            env::var("LIBRARIAN_MIN_AVAILABILITY")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+        })
+        .filter(|t| t.is_finite())
+}
+
+/// Drops candidates whose known availability is below `threshold`. Endpoints
+/// that were never checked are kept. Returns the number dropped.
+pub fn retain_available(
+    candidates: &mut Vec<(f64, McpEntry)>,
+    cache: &VerificationCache,
+    threshold: f64,
+) -> usize {
+    let before = candidates.len();
+    candidates.retain(|(_, entry)| {
+        mirrors::availability(entry, cache).is_none_or(|availability| availability >= threshold)
+    });
+    before - candidates.len()
+}
+
+/// Drops candidates whose latest check hit an auth challenge, unless the
+/// caller holds credentials. Returns the number dropped.
+pub fn retain_unchallenged(
+    candidates: &mut Vec<(f64, McpEntry)>,
+    cache: &VerificationCache,
+    allow_auth: bool,
+) -> usize {
+    if allow_auth {
+        return 0;
+    }
+    let before = candidates.len();
+    candidates.retain(|(_, entry)| {
+        !mirrors::deployments(entry).iter().all(|d| {
+            cache
+                .get(&d.endpoint)
+                .is_some_and(|result| result.auth_required)
+        })
+    });
+    before - candidates.len()
+}
+
+/// Replaces the model's `capabilities` and `verification_status` on each
+/// recommendation with what we actually know: the cached verified lists when a
+/// successful verification exists, otherwise `catalog_only` with the static
+/// catalog capabilities (empty when they are redacted). `last_checked` is the
+/// time of the latest check, failed or not, and empty for a server never
+/// checked. Expects recommendations already matched to the catalog.
+pub fn apply_verification(response: &mut Value, catalog: &[McpEntry], cache: &VerificationCache) {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+
+    for rec in recommendations.iter_mut() {
+        let endpoint = rec
+            .get("endpoint")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let Some(entry) = mirrors::find(catalog, endpoint) else {
+            continue;
+        };
+        // each mirror is verified on its own, so look up the one recommended
+        let availability = cache.availability(endpoint);
+        let latest = cache.get(endpoint);
+        rec["recent_availability"] = json!(availability);
+
+        match latest.as_ref().filter(|v| v.ok) {
+            Some(verified) => {
+                rec["capabilities"] = json!({
+                    "tools": verified.tools,
+                    "resources": verified.resources,
+                    "prompts": verified.prompts,
+                });
+                rec["verification_status"] = json!("initialized_and_listed");
+            }
+            None => {
+                let tools = redact::public_value(entry).get("capabilities").cloned();
+                rec["capabilities"] = json!({
+                    "tools": tools.unwrap_or_else(|| json!([])),
+                    "resources": [],
+                    "prompts": [],
+                });
+                rec["verification_status"] = json!("catalog_only");
+            }
+        }
+        // never the model's guess
+        rec["last_checked"] = json!(
+            latest
+                .map(|v| clock::timestamp(v.checked_at))
+                .unwrap_or_default()
+        );
+    }
+}
+
+const DEFAULT_PREFER_VERIFIED_MARGIN: u64 = 10;
+
+/// Score points a live-verified recommendation may trail an unverified one by
+/// and still rank above it under `prefer_verified`; from
+/// `LIBRARIAN_PREFER_VERIFIED_MARGIN` (0–100, default 10).
+pub fn prefer_verified_margin() -> u64 {
+    env::var("LIBRARIAN_PREFER_VERIFIED_MARGIN")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|v| v.min(100))
+        .unwrap_or(DEFAULT_PREFER_VERIFIED_MARGIN)
+}
+
+/// Reorders recommendations as if every `initialized_and_listed` one scored
+/// `margin` points higher. Scores themselves are left as reported; ties keep
+/// the model's order. Expects `apply_verification` to have run.
+pub fn prefer_verified(response: &mut Value, margin: u64) {
+    let Some(recommendations) = response
+        .get_mut("recommendations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    let boosted = |rec: &Value| {
+        let score = rec.get("score").and_then(Value::as_u64).unwrap_or(0);
+        match rec.get("verification_status").and_then(Value::as_str) {
+            Some("initialized_and_listed") => score + margin,
+            _ => score,
+        }
+    };
+    recommendations.sort_by_key(|rec| std::cmp::Reverse(boosted(rec)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather() -> McpEntry {
+        let mut entry: McpEntry = serde_json::from_value(json!({
+            "name": "weather",
+            "endpoint": "https://weather.example/mcp",
+            "version": "1.2.0",
+            "capabilities": ["forecast"],
+            "desc": "",
+        }))
+        .unwrap();
+        mirrors::settle(&mut entry).unwrap();
+        entry
+    }
+
+    #[test]
+    fn catalog_only_capabilities_respect_redaction() {
+        // unit tests redact `capabilities` (see `redact::configured`)
+        let mut response = json!({ "recommendations": [{
+            "name": "weather",
+            "endpoint": "https://weather.example/mcp",
+            "capabilities": { "tools": ["forecast"] },
+        }]});
+
+        apply_verification(&mut response, &[weather()], &VerificationCache::default());
+
+        let rec = &response["recommendations"][0];
+        assert_eq!(rec["verification_status"], "catalog_only");
+        assert_eq!(rec["capabilities"]["tools"], json!([]));
+    }
+
+    #[test]
+    fn catalog_only_last_checked_comes_from_the_cache() {
+        let response = || {
+            json!({ "recommendations": [{
+                "name": "weather",
+                "endpoint": "https://weather.example/mcp",
+                "last_checked": "2025-01-01T00:00:00Z",
+            }]})
+        };
+        let cache = VerificationCache::default();
+        let mut unchecked = response();
+        apply_verification(&mut unchecked, &[weather()], &cache);
+        assert_eq!(unchecked["recommendations"][0]["last_checked"], "");
+
+        let checked_at = Utc::now();
+        cache.insert(
+            "https://weather.example/mcp",
+            VerificationResult {
+                ok: false,
+                tools: Vec::new(),
+                resources: Vec::new(),
+                prompts: Vec::new(),
+                checked_at,
+                error: Some("connection refused".to_string()),
+                auth_required: false,
+            },
+        );
+        let mut failed = response();
+        apply_verification(&mut failed, &[weather()], &cache);
+        let rec = &failed["recommendations"][0];
+        assert_eq!(rec["verification_status"], "catalog_only");
+        assert_eq!(rec["last_checked"], clock::timestamp(checked_at));
+    }
+
+    fn ranked(response: &Value) -> Vec<&str> {
+        response["recommendations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|rec| rec["name"].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn prefer_verified_lifts_live_servers_within_the_margin() {
+        let rec = |name: &str, score: u64, status: &str| json!({ "name": name, "score": score, "verification_status": status });
+        let response = json!({ "recommendations": [
+            rec("unverified-top", 90, "catalog_only"),
+            rec("verified-close", 84, "initialized_and_listed"),
+            rec("verified-far", 70, "initialized_and_listed"),
+            rec("failed", 88, "unreachable"),
+        ]});
+
+        let mut preferred = response.clone();
+        prefer_verified(&mut preferred, 10);
+        assert_eq!(
+            ranked(&preferred),
+            ["verified-close", "unverified-top", "failed", "verified-far"]
+        );
+        // scores are reported as the model gave them
+        assert_eq!(preferred["recommendations"][0]["score"], 84);
+
+        let mut unmoved = response.clone();
+        prefer_verified(&mut unmoved, 0);
+        assert_eq!(
+            ranked(&unmoved),
+            ["unverified-top", "failed", "verified-close", "verified-far"]
+        );
+    }
+}
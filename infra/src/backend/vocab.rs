@@ -0,0 +1,176 @@
+// src/backend/vocab.rs
+//! Model-free query expansion from the catalog's own vocabulary: capability
+//! names that co-occur, across catalog entries, with the terms of the query.
+use super::McpEntry;
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+const DEFAULT_EXPANSION_TERMS: usize = 3;
+/// Query terms shorter than this are too ambiguous to match on.
+const MIN_TERM_LEN: usize = 2;
+
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.len() >= MIN_TERM_LEN)
+        .map(str::to_lowercase)
+}
+
+/// Per entry: the terms it is described with and the capabilities it offers.
+#[derive(Debug, Default)]
+pub struct CatalogVocabulary {
+    enabled: bool,
+    max_terms: usize,
+    entries: Vec<(HashSet<String>, Vec<String>)>,
+}
+
+impl CatalogVocabulary {
+    /// Enabled by `LIBRARIAN_VOCAB_EXPANSION=true`; `LIBRARIAN_VOCAB_EXPANSION_TERMS`
+    /// caps how many capability terms are appended (default 3).
+    pub fn from_env(catalog: &[McpEntry]) -> Self {
+        let enabled = env::var("LIBRARIAN_VOCAB_EXPANSION").is_ok_and(|v| v == "1" || v == "true");
+        let max_terms = env::var("LIBRARIAN_VOCAB_EXPANSION_TERMS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_EXPANSION_TERMS);
+        if !enabled {
+            return CatalogVocabulary::default();
+        }
+        CatalogVocabulary::build(catalog, max_terms)
+    }
+
+    pub fn build(catalog: &[McpEntry], max_terms: usize) -> Self {
+        let entries = catalog
+            .iter()
+            .map(|entry| {
+                let mut words: HashSet<String> =
+                    terms(&entry.name).chain(terms(&entry.desc)).collect();
+                words.extend(entry.tags.iter().flat_map(|t| terms(t)));
+                words.extend(entry.capabilities.iter().flat_map(|c| terms(c)));
+                let capabilities = entry
+                    .capabilities
+                    .iter()
+                    .map(|c| c.trim().to_lowercase())
+                    .filter(|c| !c.is_empty())
+                    .collect();
+                (words, capabilities)
+            })
+            .collect();
+        CatalogVocabulary {
+            enabled: true,
+            max_terms,
+            entries,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Capability terms most often offered by entries mentioning any query term
+    /// (as a whole word or a word prefix), most frequent first, ties by name.
+    /// Terms already in the query are skipped.
+    pub fn expand(&self, query: &str) -> Vec<String> {
+        if !self.enabled || self.max_terms == 0 {
+            return Vec::new();
+        }
+        let query_terms: Vec<String> = terms(query).collect();
+        if query_terms.is_empty() {
+            return Vec::new();
+        }
+        let lowered = query.to_lowercase();
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (words, capabilities) in &self.entries {
+            let mentions = query_terms
+                .iter()
+                .any(|q| words.iter().any(|w| w.starts_with(q.as_str())));
+            if !mentions {
+                continue;
+            }
+            for capability in capabilities {
+                if !lowered.contains(capability.as_str()) {
+                    *counts.entry(capability.as_str()).or_default() += 1;
+                }
+            }
+        }
+
+        let mut ranked: Vec<(&str, usize)> = counts.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        ranked
+            .into_iter()
+            .take(self.max_terms)
+            .map(|(term, _)| term.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(name: &str, desc: &str, capabilities: &[&str]) -> McpEntry {
+        serde_json::from_value(json!({
+            "name": name,
+            "endpoint": format!("https://{}.example.com/mcp", name),
+            "version": "1.0.0",
+            "capabilities": capabilities,
+            "desc": desc,
+        }))
+        .unwrap()
+    }
+
+    fn catalog() -> Vec<McpEntry> {
+        vec![
+            entry(
+                "postgres-db",
+                "Postgres database access",
+                &["run_sql", "list_tables"],
+            ),
+            entry("warehouse", "Analytics warehouse", &["run_sql"]),
+            entry("weather", "Forecasts by city", &["get_forecast"]),
+        ]
+    }
+
+    /// Entries sharing a term with `query`: a lexical stand-in for retrieval.
+    fn recalled<'a>(catalog: &'a [McpEntry], query: &str) -> Vec<&'a str> {
+        let query: HashSet<String> = terms(query).collect();
+        catalog
+            .iter()
+            .filter(|entry| {
+                terms(&entry.name)
+                    .chain(terms(&entry.desc))
+                    .chain(entry.capabilities.iter().flat_map(|c| terms(c)))
+                    .any(|term| query.contains(&term))
+            })
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn terse_query_expands_to_co_occurring_capabilities() {
+        let vocabulary = CatalogVocabulary::build(&catalog(), DEFAULT_EXPANSION_TERMS);
+        assert_eq!(vocabulary.expand("db"), ["list_tables", "run_sql"]);
+        assert_eq!(vocabulary.expand("run_sql db"), ["list_tables"]);
+        assert!(vocabulary.expand("x").is_empty());
+    }
+
+    #[test]
+    fn expansion_improves_recall_for_a_terse_query() {
+        let catalog = catalog();
+        let vocabulary = CatalogVocabulary::build(&catalog, DEFAULT_EXPANSION_TERMS);
+        assert_eq!(recalled(&catalog, "db"), ["postgres-db"]);
+        let expanded = format!("db {}", vocabulary.expand("db").join(" "));
+        assert_eq!(recalled(&catalog, &expanded), ["postgres-db", "warehouse"]);
+    }
+
+    #[test]
+    fn disabled_vocabulary_expands_nothing() {
+        assert!(CatalogVocabulary::default().expand("db").is_empty());
+        assert!(
+            CatalogVocabulary::build(&catalog(), 0)
+                .expand("db")
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// src/backend/watcher.rs
+use super::reload::Reloader;
+use super::tasks::TASKS;
+use anyhow::{Context as _, Result};
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches the catalog file and rebuilds the index after a quiet period. A failed
+/// reload is logged and the previous catalog keeps serving. Stops watching on
+/// shutdown.
+pub fn spawn_catalog_watcher(reloader: Arc<Reloader>, path: impl AsRef<Path>) -> Result<()> {
+    let path: PathBuf = path.as_ref().to_path_buf();
+    let file_name = path.file_name().map(|n| n.to_os_string());
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        if !(event.kind.is_modify() || event.kind.is_create()) {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|p| p.file_name().map(|n| n.to_os_string()) == file_name)
+        {
+            let _ = tx.send(());
+        }
+    })
+    .context("Failed to create catalog watcher")?;
+
+    // Watch the directory so editors that replace the file atomically are still seen.
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {:?}", dir))?;
+    tracing::info!("Watching {:?} for catalog changes", path);
+
+    TASKS.spawn("catalog_watcher", |cancel| async move {
+        let _watcher = watcher;
+        loop {
+            tokio::select! {
+                changed = rx.recv() => {
+                    if changed.is_none() {
+                        return;
+                    }
+                }
+                _ = cancel.cancelled() => return,
+            }
+            loop {
+                match tokio::time::timeout(RELOAD_DEBOUNCE, rx.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            match reloader.reload().await {
+                Ok(count) => {
+                    tracing::info!("Catalog reloaded from {:?}: {} entries", path, count);
+                }
+                Err(e) => {
+                    tracing::error!("Catalog reload failed, keeping previous catalog: {:#}", e);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
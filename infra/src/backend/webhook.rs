@@ -0,0 +1,140 @@
+// src/backend/webhook.rs
+//! Opt-in analytics events for served recommendations. After each successful
+//! `/discover` a compact event is POSTed to `LIBRARIAN_WEBHOOK_URL` from a
+//! detached task, so a slow or down receiver never delays or fails the client.
+//!
+//! The body is signed with HMAC-SHA256 over the raw bytes using
+//! `LIBRARIAN_WEBHOOK_SECRET`, hex-encoded in `X-Librarian-Signature` as
+//! `sha256=<hex>`.
+use super::tasks::TASKS;
+use anyhow::{Result, bail};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const SIGNATURE_HEADER: &str = "x-librarian-signature";
+const MAX_ATTEMPTS: u32 = 3;
+const BACKOFF: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub struct RecommendationEvent {
+    /// SHA-256 of the query; the query text itself is never sent.
+    pub query_hash: String,
+    pub recommendations: Vec<RecommendedItem>,
+    pub payer: Option<String>,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecommendedItem {
+    pub name: String,
+    pub score: Option<i64>,
+}
+
+impl RecommendationEvent {
+    pub fn new(query: &str, response: &Value, payer: Option<String>) -> Self {
+        let recommendations = response
+            .get("recommendations")
+            .and_then(Value::as_array)
+            .map(|recs| {
+                recs.iter()
+                    .map(|rec| RecommendedItem {
+                        name: rec
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string(),
+                        score: rec.get("score").and_then(Value::as_i64),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        RecommendationEvent {
+            query_hash: hex::encode(Sha256::digest(query.as_bytes())),
+            recommendations,
+            payer,
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+pub struct Webhook {
+    url: String,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    /// `None` unless `LIBRARIAN_WEBHOOK_URL` is set; a URL without
+    /// `LIBRARIAN_WEBHOOK_SECRET` is a configuration error.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(url) = env::var("LIBRARIAN_WEBHOOK_URL") else {
+            return Ok(None);
+        };
+        let secret = env::var("LIBRARIAN_WEBHOOK_SECRET").unwrap_or_default();
+        if secret.is_empty() {
+            bail!("LIBRARIAN_WEBHOOK_URL is set but LIBRARIAN_WEBHOOK_SECRET is empty");
+        }
+        url::Url::parse(&url)
+            .map_err(|e| anyhow::anyhow!("Invalid LIBRARIAN_WEBHOOK_URL: {}", e))?;
+        Ok(Some(Webhook {
+            url,
+            secret,
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Sends `event` in the background with up to three attempts. On shutdown
+    /// the remaining attempts are made without backoff.
+    pub fn notify(self: &Arc<Self>, event: RecommendationEvent) {
+        let webhook = Arc::clone(self);
+        TASKS.spawn("webhook_delivery", |cancel| async move {
+            let body = match serde_json::to_vec(&event) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!("Failed to serialize webhook event: {}", e);
+                    return;
+                }
+            };
+            let signature = webhook.sign(&body);
+            for attempt in 1..=MAX_ATTEMPTS {
+                let sent = webhook
+                    .client
+                    .post(&webhook.url)
+                    .timeout(REQUEST_TIMEOUT)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, &signature)
+                    .body(body.clone())
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status());
+                match sent {
+                    Ok(_) => return,
+                    Err(e) if attempt < MAX_ATTEMPTS => {
+                        tracing::debug!(attempt, "Webhook delivery failed, retrying: {}", e);
+                        tokio::select! {
+                            _ = tokio::time::sleep(BACKOFF * attempt) => {}
+                            _ = cancel.cancelled() => {}
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Webhook delivery failed after {} attempts: {}", attempt, e)
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,527 @@
+// src/cli.rs
+//! Offline subcommands that run instead of the server:
+//!
+//! - `validate [PATH] [--live]`: parse a catalog (default `mcps.json`) and
+//!   print lint warnings, including clusters of entries with overlapping
+//!   capabilities. `--live` also runs the MCP handshake against every enabled
+//!   entry's endpoint and mirrors, one at a time, and reports each as reachable, asking for auth, or
+//!   failed; any failure makes the exit code non-zero. This one needs the
+//!   network.
+//! - `diff --old A --new B [--json]`: compare two catalogs entry by entry.
+//! - `bench CASES [--catalog PATH]`: retrieval quality (recall@k, MRR) over
+//!   labeled queries, through the same candidate pipeline `/discover` uses.
+//!   Run it with and without `LIBRARIAN_EMBED_WEIGHTS` to compare schemes, or
+//!   `LIBRARIAN_EMBED_QUERY_CONTEXT` to measure folding in request context, or
+//!   `LIBRARIAN_QUERY_STOPWORDS` to measure noise removal (verbose queries in
+//!   `fixtures/bench/verbose.json`).
+//! - `bench-filter [ENTRIES]`: compare capability-filtered retrieval with and
+//!   without the capability index on a synthetic catalog.
+//! - `golden [DIR] [--update]`: run each `DIR/*.case.json` (default
+//!   `fixtures/golden`) through `/discover`'s output handling against
+//!   `fixtures/mcps.json`, with a mock recommender answering the case's fixed
+//!   `model_output`, and compare with `*.expected.json`. `--update` rewrites
+//!   the expected files. No network access.
+use crate::backend::capindex::{self, CapabilityIndex};
+use crate::backend::filters::{CandidateFilter, EndpointPolicy};
+use crate::backend::load_mcps_from_file;
+use crate::backend::mirrors;
+use crate::backend::normalize::{self, NormalizeRules};
+use crate::backend::query_context::{self, QueryContext};
+use crate::backend::stopwords::{self, Stopwords};
+use crate::backend::urls::endpoint_key;
+use crate::backend::validate::{DEFAULT_PROTOCOL_VERSION, ProtocolVersions};
+use crate::backend::verify::{LiveCheck, VerificationCache, VerifyHttp};
+use crate::backend::{
+    DiscoverRequest, McpEntry, diff, discover_candidates, lint, process_discover_output,
+};
+use crate::utils::{self, AgentParams, CATALOG_PATH};
+use anyhow::{Context as _, Result, bail};
+use rig::OneOrMany;
+use rig::embeddings::Embedding;
+use serde::Deserialize;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Runs the subcommand named in `args` (without the program name), or returns
+/// `None` when there is none and the server should start.
+pub async fn run(args: &[String]) -> Option<Result<ExitCode>> {
+    match args.first().map(String::as_str) {
+        Some("validate") => Some(validate(&args[1..]).await),
+        Some("diff") => Some(catalog_diff(&args[1..])),
+        Some("bench") => Some(bench(&args[1..]).await),
+        Some("bench-filter") => Some(bench_filter(&args[1..])),
+        Some("golden") => Some(golden(&args[1..])),
+        _ => None,
+    }
+}
+
+/// The startup configuration catalog reading and retrieval depend on, selected
+/// as `Backend::new` does so a subcommand sees entries and queries the way the
+/// server would.
+fn select_config() -> Result<()> {
+    normalize::select(NormalizeRules::from_env()?)?;
+    query_context::select(QueryContext::from_env()?)?;
+    stopwords::select(Stopwords::from_env()?)
+}
+
+async fn validate(args: &[String]) -> Result<ExitCode> {
+    select_config()?;
+    let (mut path, mut live) = (None, false);
+    for arg in args {
+        match arg.as_str() {
+            "--live" => live = true,
+            other if other.starts_with("--") => bail!("validate: unexpected argument {:?}", other),
+            other if path.is_none() => path = Some(other),
+            other => bail!("validate: unexpected argument {:?}", other),
+        }
+    }
+    let path = path.unwrap_or(CATALOG_PATH);
+    let entries = match load_mcps_from_file(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}: invalid catalog: {:#}", path, e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let warnings = lint::lint_catalog(&entries, lint::min_desc_len());
+    for warning in &warnings {
+        println!("warning: {}: {}", warning.name, warning.message);
+    }
+    let clusters = lint::capability_clusters(&entries, lint::capability_overlap());
+    for cluster in &clusters {
+        println!("warning: {}", cluster.message());
+    }
+    let mergeable = entries.len() - lint::merge_exact_duplicates(entries.clone()).len();
+    if mergeable > 0 {
+        println!(
+            "note: {} entries duplicate another at the same endpoint; \
+             LIBRARIAN_MERGE_DUPLICATE_CAPABILITIES=true merges them at load",
+            mergeable
+        );
+    }
+    println!(
+        "{}: {} entries, {} warning(s), {} overlap cluster(s)",
+        path,
+        entries.len(),
+        warnings.len(),
+        clusters.len()
+    );
+    if live {
+        return validate_live(&entries).await;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The `--live` pass: one handshake per deployment of each enabled entry, in
+/// catalog order.
+async fn validate_live(entries: &[McpEntry]) -> Result<ExitCode> {
+    let http = VerifyHttp::from_env()?;
+    let versions = ProtocolVersions::from_env();
+    let protocol_version = versions
+        .as_slice()
+        .first()
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_PROTOCOL_VERSION);
+
+    let (mut reachable, mut challenged, mut failed) = (0, 0, 0);
+    // each mirror is its own deployment and is checked on its own
+    let deployments = entries.iter().filter(|e| e.enabled).flat_map(|entry| {
+        mirrors::deployments(entry)
+            .into_iter()
+            .map(move |d| (entry, d.endpoint))
+    });
+    for (entry, endpoint) in deployments {
+        match http.check(&endpoint, protocol_version).await {
+            LiveCheck::Reachable(result) => {
+                reachable += 1;
+                println!(
+                    "ok: {} ({}): {} tool(s), {} resource(s), {} prompt(s)",
+                    entry.name,
+                    endpoint,
+                    result.tools.len(),
+                    result.resources.len(),
+                    result.prompts.len()
+                );
+            }
+            LiveCheck::AuthChallenge { status, scheme } => {
+                challenged += 1;
+                let scheme = scheme.map(|s| format!(" ({})", s)).unwrap_or_default();
+                let declared = if entry.auth.required {
+                    ""
+                } else {
+                    "; the catalog says no auth is required"
+                };
+                println!(
+                    "auth: {} ({}): HTTP {}{}{}",
+                    entry.name, endpoint, status, scheme, declared
+                );
+            }
+            LiveCheck::Failed(result) => {
+                failed += 1;
+                println!(
+                    "failed: {} ({}): {}",
+                    entry.name,
+                    endpoint,
+                    result.error.unwrap_or_default()
+                );
+            }
+        }
+    }
+    println!(
+        "live: {} reachable, {} auth challenge(s), {} failed",
+        reachable, challenged, failed
+    );
+    Ok(if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn catalog_diff(args: &[String]) -> Result<ExitCode> {
+    select_config()?;
+    let (mut old, mut new, mut json) = (None, None, false);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--old" => old = args.next(),
+            "--new" => new = args.next(),
+            "--json" => json = true,
+            other => bail!("diff: unexpected argument {:?}", other),
+        }
+    }
+    let (Some(old), Some(new)) = (old, new) else {
+        bail!("usage: diff --old A.json --new B.json [--json]");
+    };
+
+    let mut catalogs = Vec::with_capacity(2);
+    for path in [old, new] {
+        match load_mcps_from_file(path) {
+            Ok(entries) => catalogs.push(entries),
+            Err(e) => {
+                eprintln!("{}: invalid catalog: {:#}", path, e);
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+
+    let changes = diff::diff_catalogs(&catalogs[0], &catalogs[1]);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        print!("{}", changes.render());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+const BENCH_DIMENSIONS: usize = 64;
+const BENCH_CAPABILITIES: usize = 50;
+const BENCH_FETCH_K: usize = 10;
+const BENCH_QUERIES: usize = 100;
+
+/// xorshift64: deterministic, dependency-free vectors for the synthetic catalog.
+fn bench_vector(state: &mut u64) -> Vec<f64> {
+    (0..BENCH_DIMENSIONS)
+        .map(|_| {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            (*state as f64 / u64::MAX as f64) * 2.0 - 1.0
+        })
+        .collect()
+}
+
+fn bench_filter(args: &[String]) -> Result<ExitCode> {
+    let entries: usize = match args.first() {
+        Some(n) => n
+            .parse()
+            .context("bench-filter: ENTRIES must be a number")?,
+        None => 10_000,
+    };
+    let mut state = 0x9E37_79B9_7F4A_7C15;
+    let catalog = (0..entries)
+        .map(|i| {
+            let entry: McpEntry = serde_json::from_value(serde_json::json!({
+                "name": format!("synthetic-{}", i),
+                "endpoint": format!("https://synthetic-{}.example/mcp", i),
+                "version": "1.0.0",
+                "capabilities": [
+                    format!("cap-{}", i % BENCH_CAPABILITIES),
+                    format!("cap-{}", (i * 7 + 3) % BENCH_CAPABILITIES),
+                ],
+                "desc": "Synthetic benchmark entry",
+            }))?;
+            let embedding = Embedding {
+                document: entry.name.clone(),
+                vec: bench_vector(&mut state),
+            };
+            Ok((entry, OneOrMany::one(embedding)))
+        })
+        .collect::<Result<Vec<(McpEntry, OneOrMany<Embedding>)>>>()?;
+
+    let index = CapabilityIndex::build(catalog.iter().map(|(entry, _)| entry));
+    let all_ids: Vec<usize> = (0..catalog.len()).collect();
+    let filter = CandidateFilter::from_request(
+        Some(&serde_json::json!({ "capability": "cap-17" })),
+        true,
+        Arc::new(EndpointPolicy::default()),
+    );
+    let capability = filter.capability.clone().unwrap_or_default();
+    let queries: Vec<Vec<f64>> = (0..BENCH_QUERIES)
+        .map(|_| bench_vector(&mut state))
+        .collect();
+
+    let started = Instant::now();
+    let (mut plain_kept, mut plain_discarded) = (0, 0);
+    for query in &queries {
+        let fetched = capindex::rank(query, &catalog, &all_ids, BENCH_FETCH_K);
+        let (kept, stats) = filter.apply(fetched);
+        plain_kept += kept.len();
+        plain_discarded += stats.capability;
+    }
+    let plain_elapsed = started.elapsed();
+
+    let started = Instant::now();
+    let (mut indexed_kept, mut indexed_discarded) = (0, 0);
+    for query in &queries {
+        let ids = index.lookup(&capability);
+        let fetched = capindex::rank(query, &catalog, &ids, BENCH_FETCH_K);
+        let (kept, stats) = filter.apply(fetched);
+        indexed_kept += kept.len();
+        indexed_discarded += stats.capability;
+    }
+    let indexed_elapsed = started.elapsed();
+
+    println!(
+        "{} entries, {} capabilities, {} queries, fetch_k={}, filter capability={}",
+        entries,
+        index.len(),
+        BENCH_QUERIES,
+        BENCH_FETCH_K,
+        capability
+    );
+    println!(
+        "plain:   kept {:>5}, discarded {:>5}, {:?}",
+        plain_kept, plain_discarded, plain_elapsed
+    );
+    println!(
+        "indexed: kept {:>5}, discarded {:>5}, {:?}",
+        indexed_kept, indexed_discarded, indexed_elapsed
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// One labeled query: any of `expected_endpoints` is a correct retrieval.
+#[derive(Deserialize)]
+struct BenchCase {
+    query: String,
+    expected_endpoints: Vec<String>,
+    #[serde(default)]
+    filters: Option<serde_json::Value>,
+    #[serde(default)]
+    client_type: Option<String>,
+}
+
+async fn bench(args: &[String]) -> Result<ExitCode> {
+    select_config()?;
+    let (mut cases_path, mut catalog_path) = (None, CATALOG_PATH);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--catalog" => {
+                catalog_path = args.next().context("--catalog needs a path")?;
+            }
+            other if cases_path.is_none() => cases_path = Some(other),
+            other => bail!("bench: unexpected argument {:?}", other),
+        }
+    }
+    let Some(cases_path) = cases_path else {
+        bail!("usage: bench CASES.json [--catalog PATH]");
+    };
+    let raw = std::fs::read_to_string(cases_path)
+        .with_context(|| format!("Failed to read {}", cases_path))?;
+    let cases: Vec<BenchCase> = serde_json::from_str(&raw).with_context(|| {
+        format!(
+            "{} must be a JSON array of {{query, expected_endpoints}}",
+            cases_path
+        )
+    })?;
+    if cases.is_empty() {
+        bail!("{} has no cases", cases_path);
+    }
+
+    let librarian = utils::load_librarian(AgentParams::from_env()?, catalog_path).await?;
+    let top_k = crate::backend::search::DEFAULT_TOP_K;
+    let mut recall_at = vec![0.0; top_k];
+    let mut reciprocal_rank = 0.0;
+
+    for case in &cases {
+        let req: DiscoverRequest = serde_json::from_value(serde_json::json!({
+            "query": case.query,
+            "filters": case.filters,
+            "client_type": case.client_type,
+        }))?;
+        let (candidates, _, _) = discover_candidates(&librarian, &req).await?;
+        let expected: Vec<&str> = case
+            .expected_endpoints
+            .iter()
+            .map(|e| endpoint_key(e))
+            .collect();
+        let ranked: Vec<&str> = candidates
+            .iter()
+            .map(|(_, e)| endpoint_key(&e.endpoint))
+            .collect();
+
+        for (k, recall) in recall_at.iter_mut().enumerate() {
+            let hits = expected
+                .iter()
+                .filter(|e| ranked.iter().take(k + 1).any(|r| r == *e))
+                .count();
+            *recall += hits as f64 / expected.len().max(1) as f64;
+        }
+        let first_hit = ranked.iter().position(|r| expected.contains(r));
+        if let Some(rank) = first_hit {
+            reciprocal_rank += 1.0 / (rank + 1) as f64;
+        }
+        println!(
+            "{:>5}  {}",
+            first_hit
+                .map(|r| format!("#{}", r + 1))
+                .unwrap_or_else(|| "miss".to_string()),
+            case.query
+        );
+    }
+
+    let n = cases.len() as f64;
+    println!(
+        "{} cases, top_k={}, embedding={}, query_context={}, stopwords={}",
+        cases.len(),
+        top_k,
+        crate::backend::embed_profile::cache_key(),
+        query_context::describe(),
+        stopwords::describe()
+    );
+    for (k, recall) in recall_at.iter().enumerate() {
+        println!("recall@{}: {:.3}", k + 1, recall / n);
+    }
+    println!("MRR: {:.3}", reciprocal_rank / n);
+    Ok(ExitCode::SUCCESS)
+}
+
+const GOLDEN_DIR: &str = "fixtures/golden";
+const GOLDEN_CATALOG: &str = "fixtures/mcps.json";
+
+/// One golden query. `model_output` is what the mock recommender answers: a
+/// string is used verbatim (to cover fenced or chatty output), anything else
+/// as its JSON text.
+#[derive(Deserialize)]
+struct GoldenCase {
+    query: String,
+    model_output: serde_json::Value,
+}
+
+/// Stands in for the agent: always answers the case's fixed output.
+struct MockRecommender<'a>(&'a serde_json::Value);
+
+impl MockRecommender<'_> {
+    fn prompt(&self, _query: &str) -> String {
+        match self.0 {
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+fn golden(args: &[String]) -> Result<ExitCode> {
+    let (mut dir, mut update) = (GOLDEN_DIR, false);
+    for arg in args {
+        match arg.as_str() {
+            "--update" => update = true,
+            other if dir == GOLDEN_DIR => dir = other,
+            other => bail!("golden: unexpected argument {:?}", other),
+        }
+    }
+    let catalog = load_mcps_from_file(GOLDEN_CATALOG)?;
+    let catalog: Vec<McpEntry> = catalog.into_iter().filter(|entry| entry.enabled).collect();
+    let versions = ProtocolVersions::default();
+
+    let mut cases: Vec<_> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.to_string_lossy().ends_with(".case.json"))
+        .collect();
+    cases.sort();
+    if cases.is_empty() {
+        bail!("{} has no *.case.json files", dir);
+    }
+
+    let mut failed = 0;
+    for path in &cases {
+        let name = path
+            .to_string_lossy()
+            .trim_end_matches(".case.json")
+            .to_string();
+        let expected_path = format!("{}.expected.json", name);
+        let raw =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let case: GoldenCase = serde_json::from_str(&raw)
+            .with_context(|| format!("{:?} must be {{query, model_output}}", path))?;
+
+        // a fresh cache per case: nothing verified, so results are deterministic
+        let verification = VerificationCache::from_env();
+        let output = MockRecommender(&case.model_output).prompt(&case.query);
+        let actual = match process_discover_output(
+            &case.query,
+            &output,
+            &catalog,
+            &[],
+            &versions,
+            &verification,
+            None,
+            false,
+        ) {
+            Ok(parsed) => parsed,
+            Err(problem) => serde_json::json!({ "error": problem }),
+        };
+
+        if update {
+            std::fs::write(
+                &expected_path,
+                serde_json::to_string_pretty(&actual)? + "\n",
+            )
+            .with_context(|| format!("Failed to write {}", expected_path))?;
+            println!("updated  {}", expected_path);
+            continue;
+        }
+        let expected: Option<serde_json::Value> = Path::new(&expected_path)
+            .exists()
+            .then(|| std::fs::read_to_string(&expected_path))
+            .transpose()?
+            .map(|raw| serde_json::from_str(&raw))
+            .transpose()
+            .with_context(|| format!("Failed to parse {}", expected_path))?;
+        match expected {
+            Some(expected) if expected == actual => println!("ok       {}", name),
+            Some(expected) => {
+                failed += 1;
+                println!("MISMATCH {}", name);
+                println!("  expected: {}", expected);
+                println!("  actual:   {}", actual);
+            }
+            None => {
+                failed += 1;
+                println!("MISSING  {} (run with --update)", expected_path);
+            }
+        }
+    }
+
+    println!("{} case(s), {} failed", cases.len(), failed);
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -1,27 +1,40 @@
 // src/main.rs
 use anyhow::Result;
 use dotenv::dotenv;
+use std::process::ExitCode;
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
 pub mod backend;
+pub mod cli;
 pub mod utils;
 
-use rig::providers::openai::responses_api::ResponsesCompletionModel;
-
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     dotenv().ok();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = cli::run(&args).await {
+        return result;
+    }
+
+    // LOG_FORMAT=json for log pipelines; anything else keeps the human format
+    let log_format = std::env::var("LOG_FORMAT").unwrap_or_else(|_| "pretty".to_string());
+    let json_logs = log_format.eq_ignore_ascii_case("json");
     tracing_subscriber::registry()
         .with(EnvFilter::from_default_env())
-        .with(tracing_subscriber::fmt::layer())
+        .with(json_logs.then(|| tracing_subscriber::fmt::layer().json()))
+        .with((!json_logs).then(tracing_subscriber::fmt::layer))
         .init();
+    if !json_logs && !log_format.eq_ignore_ascii_case("pretty") {
+        tracing::warn!("Unknown LOG_FORMAT {:?}, using pretty", log_format);
+    }
 
-    let agent = utils::init_agent().await?;
-    let backend = backend::Backend::new(agent);
-    if let Err(e) = backend.launch().await {
+    let params = utils::AgentParams::from_env()?;
+    utils::preflight(&params)?;
+    let backend = backend::Backend::new(utils::starting_librarian(params)?)?;
+    if let Err(e) = backend.launch(utils::init_agent(params)).await {
         eprintln!("Failed to launch backend: {}", e);
         std::process::exit(1);
     }
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
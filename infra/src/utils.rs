@@ -1,32 +1,931 @@
 // src/utils.rs
-use crate::ResponsesCompletionModel;
-use crate::backend::load_mcps_from_file;
-use anyhow::Result;
-use rig::agent::Agent;
-use rig::completion::Prompt;
-use rig::embeddings::EmbeddingsBuilder;
+use crate::backend::agent::{CompletionApi, LibrarianAgent};
+use crate::backend::cache;
+use crate::backend::capindex::CapabilityIndex;
+use crate::backend::embed_cache::EmbedCache;
+use crate::backend::embed_profile::{self, EmbedProfile, EmbedWeights};
+use crate::backend::filters::EndpointPolicy;
+use crate::backend::lint;
+use crate::backend::metrics::METRICS;
+use crate::backend::pins::PinRules;
+use crate::backend::providers::{self, Providers};
+use crate::backend::remote::RemoteCatalog;
+use crate::backend::rerank::Reranker;
+use crate::backend::snapshot;
+use crate::backend::synonyms::SynonymMap;
+use crate::backend::template::PromptTemplate;
+use crate::backend::validate::ProtocolVersions;
+use crate::backend::verify::VerificationCache;
+use crate::backend::vocab::CatalogVocabulary;
+use crate::backend::webhook::Webhook;
+use crate::backend::{Librarian, McpEntry, load_mcps_from_file};
+use anyhow::{Context as _, Result, bail};
+use rig::OneOrMany;
+use rig::agent::AgentBuilder;
+use rig::completion::{CompletionError, PromptError};
+use rig::embeddings::{Embedding, EmbeddingError, EmbeddingModel, EmbeddingsBuilder};
 use rig::prelude::*;
-use rig::providers::openai::TEXT_EMBEDDING_3_SMALL;
-use rig::providers::openai::client::Client as OpenAIClient;
 use rig::vector_store::in_memory_store::InMemoryVectorStore;
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
 
-pub async fn init_agent() -> Result<Agent<ResponsesCompletionModel>> {
-    let openai_client = OpenAIClient::from_env();
-    let embedding_model = openai_client.embedding_model(TEXT_EMBEDDING_3_SMALL);
+const DEFAULT_EMBED_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_EMBED_BACKOFF_MS: u64 = 500;
+const DEFAULT_EMBED_MAX_FAILED_FRACTION: f64 = 0.1;
+const DEFAULT_EMBED_BATCH_SIZE: usize = 256;
+const DEFAULT_EMBED_CONCURRENCY: usize = 2;
+pub const CATALOG_PATH: &str = "mcps.json";
+/// `source` of entries added over the admin API without `persist`; they last
+/// until the next reload and are never written to the catalog file.
+pub const ADMIN_SOURCE: &str = "admin";
+const DEFAULT_TEMPERATURE: f64 = 0.1;
+const OPENAI_AUTH_ERROR: &str = "OpenAI authentication failed — check OPENAI_API_KEY";
+const DEFAULT_MAX_TOKENS: u64 = 2048;
 
-    let mcps = load_mcps_from_file("mcps.json")?;
-    
-    let embeddings = EmbeddingsBuilder::new(embedding_model.clone())
-        .documents(mcps)?
-        .build()
-        .await?; 
+/// Sampling parameters for the Librarian agent. A low temperature keeps the
+/// strict-JSON output stable; `max_tokens` bounds the response size.
+#[derive(Debug, Clone, Copy)]
+pub struct AgentParams {
+    pub temperature: f64,
+    pub max_tokens: u64,
+    /// Reproducible runs for integration tests and regression comparisons. A
+    /// seed pins temperature to 0 and is forwarded on the chat API; the
+    /// Responses API has no `seed` parameter. See `agent`.
+    pub seed: Option<u64>,
+    pub api: CompletionApi,
+}
+
+impl Default for AgentParams {
+    fn default() -> Self {
+        AgentParams {
+            temperature: DEFAULT_TEMPERATURE,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            seed: None,
+            api: CompletionApi::default(),
+        }
+    }
+}
+
+impl AgentParams {
+    /// Default parameters with `seed` set and temperature 0.
+    pub fn seeded(seed: u64) -> Self {
+        AgentParams {
+            temperature: 0.0,
+            seed: Some(seed),
+            ..AgentParams::default()
+        }
+    }
+
+    /// Reads `LIBRARIAN_TEMPERATURE`, `LIBRARIAN_MAX_TOKENS`, `LIBRARIAN_SEED`
+    /// and `LIBRARIAN_COMPLETION_API`, falling back to defaults. Unset seed is
+    /// the production default.
+    pub fn from_env() -> Result<Self> {
+        let mut params = AgentParams {
+            api: CompletionApi::from_env()?,
+            ..AgentParams::default()
+        };
+        if let Ok(v) = env::var("LIBRARIAN_TEMPERATURE") {
+            params.temperature = v
+                .parse()
+                .with_context(|| format!("Invalid LIBRARIAN_TEMPERATURE {:?}", v))?;
+        }
+        if let Ok(v) = env::var("LIBRARIAN_MAX_TOKENS") {
+            params.max_tokens = v
+                .parse()
+                .with_context(|| format!("Invalid LIBRARIAN_MAX_TOKENS {:?}", v))?;
+        }
+        if let Ok(v) = env::var("LIBRARIAN_SEED") {
+            let seed = v
+                .parse()
+                .with_context(|| format!("Invalid LIBRARIAN_SEED {:?}", v))?;
+            if params.temperature != 0.0 {
+                tracing::warn!(
+                    "LIBRARIAN_SEED set: overriding temperature {} with 0",
+                    params.temperature
+                );
+            }
+            if !params.api.supports_seed() {
+                tracing::info!(
+                    "LIBRARIAN_SEED set: the {} API takes no seed, so only temperature is pinned",
+                    params.api.name()
+                );
+            }
+            params.temperature = 0.0;
+            params.seed = Some(seed);
+        }
+        params.validate()?;
+        Ok(params)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=2.0).contains(&self.temperature) {
+            bail!(
+                "temperature must be within [0, 2], got {}",
+                self.temperature
+            );
+        }
+        if self.max_tokens == 0 {
+            bail!("max_tokens must be positive");
+        }
+        if self.seed.is_some() && self.temperature != 0.0 {
+            bail!(
+                "a seeded agent must use temperature 0, got {}",
+                self.temperature
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Masks a secret for logging: at most a 4-character prefix of keys long enough
+/// that the prefix reveals nothing useful, otherwise fully redacted.
+pub fn mask_secret(value: &str) -> String {
+    if value.chars().count() < 16 {
+        return "****".to_string();
+    }
+    let prefix: String = value.chars().take(4).collect();
+    format!("{}****", prefix)
+}
+
+/// `LIBRARIAN_SKIP_STARTUP_PROMPT` bypasses the live test prompts in `init_agent`
+/// and `Backend::launch`, for offline CI. Combined with `LIBRARIAN_SNAPSHOT_PATH`
+/// the server boots without contacting the model provider.
+pub fn skip_startup_prompt() -> bool {
+    env::var("LIBRARIAN_SKIP_STARTUP_PROMPT").is_ok_and(|v| v == "1" || v == "true")
+}
+
+/// Resolves the listen address from `BIND_ADDR`, which may be a full `host:port`
+/// or just a host (then `API_PORT` supplies the port). Defaults to all
+/// interfaces, `0.0.0.0`.
+pub fn bind_addr_from_env() -> Result<SocketAddr> {
+    let port: u16 = env::var("API_PORT")
+        .unwrap_or_else(|_| "8080".to_string())
+        .parse()
+        .context("API_PORT must be a port number")?;
+    let raw = env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0".to_string());
+    let raw = raw.trim();
+
+    if let Ok(addr) = raw.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let host = raw.trim_start_matches('[').trim_end_matches(']');
+    let ip: IpAddr = match host {
+        "localhost" => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        _ => host
+            .parse()
+            .with_context(|| format!("BIND_ADDR {:?} is not an IP address or ip:port", raw))?,
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Errors that can carry a provider's non-success response: rig passes the
+/// body through verbatim as `ProviderError`.
+pub trait ProviderResponse {
+    fn provider_body(&self) -> Option<&str>;
+}
+
+impl ProviderResponse for EmbeddingError {
+    fn provider_body(&self) -> Option<&str> {
+        match self {
+            EmbeddingError::ProviderError(body) => Some(body),
+            _ => None,
+        }
+    }
+}
+
+impl ProviderResponse for CompletionError {
+    fn provider_body(&self) -> Option<&str> {
+        match self {
+            CompletionError::ProviderError(body) => Some(body),
+            _ => None,
+        }
+    }
+}
+
+impl ProviderResponse for PromptError {
+    fn provider_body(&self) -> Option<&str> {
+        match self {
+            PromptError::CompletionError(e) => e.provider_body(),
+            _ => None,
+        }
+    }
+}
+
+/// The `error.code` and `error.type` of an OpenAI-style error body.
+fn provider_error_kind(err: &impl ProviderResponse) -> Option<(String, String)> {
+    let body: serde_json::Value = serde_json::from_str(err.provider_body()?).ok()?;
+    let field = |key: &str| {
+        body.pointer(&format!("/error/{}", key))
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    Some((field("code"), field("type")))
+}
+
+/// A provider authentication failure (revoked or malformed API key), by the
+/// error code or type of its response.
+pub fn is_auth_error(err: &impl ProviderResponse) -> bool {
+    provider_error_kind(err).is_some_and(|(code, kind)| {
+        matches!(code.as_str(), "invalid_api_key" | "invalid_authentication")
+            || matches!(kind.as_str(), "authentication_error")
+    })
+}
+
+/// A provider rate limit (`429`), by the error code or type of its response.
+/// An exhausted quota is not one: waiting doesn't help.
+pub fn is_rate_limit_error(err: &impl ProviderResponse) -> bool {
+    provider_error_kind(err).is_some_and(|(code, kind)| {
+        code == "rate_limit_exceeded"
+            || matches!(kind.as_str(), "rate_limit_error" | "rate_limit_exceeded")
+    })
+}
+
+/// Why an embedding batch failed: bad credentials fail every batch alike, so
+/// they are never worth isolating per entry.
+enum EmbedFailure {
+    Auth(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+/// Catalog entries per embedding batch (`EMBED_BATCH_SIZE`, default 256) and
+/// batches in flight at once (`EMBED_CONCURRENCY`, default 2).
+fn embed_batching_from_env() -> (usize, usize) {
+    let var = |key: &str| {
+        env::var(key)
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+    };
+    (
+        var("EMBED_BATCH_SIZE").unwrap_or(DEFAULT_EMBED_BATCH_SIZE),
+        var("EMBED_CONCURRENCY").unwrap_or(DEFAULT_EMBED_CONCURRENCY),
+    )
+}
+
+/// Embeds the catalog in batches of `EMBED_BATCH_SIZE`, up to `EMBED_CONCURRENCY`
+/// at a time, retrying transient provider failures (rate limits, timeouts) with
+/// exponential backoff. Attempts and base delay come from `EMBED_MAX_ATTEMPTS`
+/// and `EMBED_BACKOFF_MS`. After the first rate limit the remaining batches go
+/// one at a time.
+///
+/// If a batch still fails, its entries are embedded one at a time so a single
+/// row the provider rejects is skipped instead of failing startup. Startup only
+/// aborts when the failed fraction exceeds `EMBED_MAX_FAILED_FRACTION` (default 0.1).
+pub async fn build_embeddings_with_retry<M: EmbeddingModel + Clone + 'static>(
+    model: &M,
+    mcps: Vec<McpEntry>,
+) -> Result<Vec<(McpEntry, OneOrMany<Embedding>)>> {
+    let max_attempts = env::var("EMBED_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_EMBED_MAX_ATTEMPTS);
+    let base_delay = env::var("EMBED_BACKOFF_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_EMBED_BACKOFF_MS);
+    let max_failed_fraction = env::var("EMBED_MAX_FAILED_FRACTION")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| (0.0..=1.0).contains(v))
+        .unwrap_or(DEFAULT_EMBED_MAX_FAILED_FRACTION);
+    let (batch_size, concurrency) = embed_batching_from_env();
+
+    let total = mcps.len();
+    METRICS
+        .embedding_cache_misses_total
+        .fetch_add(total as u64, Ordering::Relaxed);
+    let started = Instant::now();
+    let rate_limited = Arc::new(AtomicBool::new(false));
+
+    let batches: Vec<Vec<McpEntry>> = mcps.chunks(batch_size).map(<[McpEntry]>::to_vec).collect();
+    let batch_count = batches.len();
+    // kept in catalog order whatever order the batches finish in
+    let mut embedded: Vec<Option<Vec<(McpEntry, OneOrMany<Embedding>)>>> =
+        (0..batch_count).map(|_| None).collect();
+    let mut failed_batches = Vec::new();
+    let mut pending = batches.into_iter().enumerate();
+    let mut in_flight = JoinSet::new();
+    loop {
+        let limit = if rate_limited.load(Ordering::Relaxed) {
+            1
+        } else {
+            concurrency
+        };
+        while in_flight.len() < limit
+            && let Some((i, batch)) = pending.next()
+        {
+            let (model, rate_limited) = (model.clone(), Arc::clone(&rate_limited));
+            in_flight.spawn(async move {
+                let result = embed_batch(
+                    &model,
+                    batch.clone(),
+                    max_attempts,
+                    base_delay,
+                    &rate_limited,
+                )
+                .await;
+                (i, batch, result)
+            });
+        }
+        let Some(joined) = in_flight.join_next().await else {
+            break;
+        };
+        let (i, batch, result) = joined.context("Embedding task panicked")?;
+        match result {
+            Ok(batch) => embedded[i] = Some(batch),
+            // dropping `in_flight` aborts the other batches
+            Err(EmbedFailure::Auth(e)) => return Err(e),
+            Err(EmbedFailure::Other(e)) if total <= 1 => return Err(e),
+            Err(EmbedFailure::Other(e)) => failed_batches.push((batch, e)),
+        }
+    }
+    let mut embeddings: Vec<_> = embedded.into_iter().flatten().flatten().collect();
+
+    let mut failed = Vec::new();
+    for (batch, batch_error) in failed_batches {
+        tracing::warn!(
+            "Embedding a batch of {} catalog entries failed ({:#}); isolating failures per entry",
+            batch.len(),
+            batch_error
+        );
+        for entry in batch {
+            let name = entry.name.clone();
+            match embed_batch(model, vec![entry], 2, base_delay, &rate_limited).await {
+                Ok(isolated) => embeddings.extend(isolated),
+                Err(EmbedFailure::Auth(e)) => return Err(e),
+                Err(EmbedFailure::Other(e)) => {
+                    tracing::warn!(entry = %name, "Excluding catalog entry that failed to embed: {:#}", e);
+                    failed.push(name);
+                }
+            }
+        }
+    }
+
+    let elapsed = started.elapsed();
+    tracing::info!(
+        entries = embeddings.len(),
+        batches = batch_count,
+        batch_size,
+        concurrency,
+        rate_limited = rate_limited.load(Ordering::Relaxed),
+        elapsed_ms = elapsed.as_millis() as u64,
+        entries_per_sec = %format!("{:.1}", embeddings.len() as f64 / elapsed.as_secs_f64().max(0.001)),
+        "Embedded catalog entries"
+    );
+
+    let failed_fraction = failed.len() as f64 / total as f64;
+    if failed_fraction > max_failed_fraction {
+        bail!(
+            "{} of {} catalog entries failed to embed, above EMBED_MAX_FAILED_FRACTION={}: {}",
+            failed.len(),
+            total,
+            max_failed_fraction,
+            failed.join(", ")
+        );
+    }
+    if !failed.is_empty() {
+        tracing::warn!(
+            "Continuing without {} of {} catalog entries: {}",
+            failed.len(),
+            total,
+            failed.join(", ")
+        );
+    }
+    Ok(embeddings)
+}
+
+/// `rate_limited` is raised on the first `429`, so the caller can slow down.
+async fn embed_batch<M: EmbeddingModel + Clone>(
+    model: &M,
+    mcps: Vec<McpEntry>,
+    max_attempts: u32,
+    base_delay: u64,
+    rate_limited: &AtomicBool,
+) -> Result<Vec<(McpEntry, OneOrMany<Embedding>)>, EmbedFailure> {
+    let total = mcps.len();
+    let mut attempt = 1;
+    loop {
+        let builder = EmbeddingsBuilder::new(model.clone())
+            .documents(mcps.clone())
+            .map_err(|e| EmbedFailure::Other(e.into()))?;
+        let result = builder.build().await;
+
+        match result {
+            Ok(embeddings) => {
+                if embeddings.len() != total {
+                    tracing::warn!(
+                        "Embedding batch returned {} of {} catalog entries",
+                        embeddings.len(),
+                        total
+                    );
+                }
+                return Ok(embeddings);
+            }
+            Err(e) if is_auth_error(&e) => {
+                return Err(EmbedFailure::Auth(anyhow::anyhow!(
+                    "{}: {}",
+                    OPENAI_AUTH_ERROR,
+                    e
+                )));
+            }
+            Err(e) if attempt < max_attempts => {
+                if is_rate_limit_error(&e) && !rate_limited.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(
+                        "Embedding provider rate limit hit; embedding the remaining batches one at a time"
+                    );
+                }
+                let delay = Duration::from_millis(
+                    base_delay.saturating_mul(2u64.saturating_pow(attempt - 1)),
+                );
+                tracing::warn!(
+                    "Embedding {} catalog entries failed (attempt {}/{}): {}. Retrying in {}ms",
+                    total,
+                    attempt,
+                    max_attempts,
+                    e,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(EmbedFailure::Other(anyhow::Error::new(e).context(format!(
+                    "Failed to embed {} catalog entries after {} attempts",
+                    total, attempt
+                ))));
+            }
+        }
+    }
+}
 
-    let vector_store = InMemoryVectorStore::from_documents(embeddings);
-    let index = vector_store.index(embedding_model);
+/// Selects the embedding profile, then builds from `LIBRARIAN_SNAPSHOT_PATH`
+/// if set or else by embedding the catalog at `catalog_path`. No model calls.
+/// Snapshot vectors of the wrong dimension are discarded and re-embedded.
+pub async fn load_librarian(params: AgentParams, catalog_path: &str) -> Result<Librarian> {
+    let profile = EmbedProfile::from_env()?;
+    embed_profile::select(profile)?;
+    embed_profile::select_weights(EmbedWeights::from_env()?)?;
+    tracing::info!("Embedding profile: {}", embed_profile::cache_key());
+    providers::select(Providers::from_env()?)?;
+    let embedding = &providers::active().embedding;
 
-    let agent = openai_client
-        .agent("gpt-4o-mini")
-        .preamble("
+    match env::var("LIBRARIAN_SNAPSHOT_PATH") {
+        Ok(path) => {
+            let embeddings = snapshot::load_snapshot(&path, embedding)?;
+            let expected = providers::embedding_model().ndims();
+            let mismatched = snapshot::mismatched_dimensions(&embeddings, expected);
+            if !mismatched.is_empty() {
+                tracing::warn!(
+                    expected,
+                    mismatched = mismatched.len(),
+                    first = mismatched[0],
+                    "Snapshot {} holds vectors of the wrong dimension for {}; discarding its \
+                     embeddings and re-embedding its entries",
+                    path,
+                    embedding.identity()
+                );
+                METRICS
+                    .embedding_dimension_mismatches_total
+                    .fetch_add(mismatched.len() as u64, Ordering::Relaxed);
+                let mcps = embeddings.into_iter().map(|(entry, _)| entry).collect();
+                return build_librarian(params, mcps).await;
+            }
+            check_index_memory(
+                &embeddings.iter().map(|(e, _)| e).collect::<Vec<_>>(),
+                expected,
+            )?;
+            tracing::info!(
+                "Imported {} embedded entries from snapshot {}",
+                embeddings.len(),
+                path
+            );
+            METRICS
+                .embedding_cache_hits_total
+                .fetch_add(embeddings.len() as u64, Ordering::Relaxed);
+            assemble_librarian(params, embeddings, Vec::new())
+        }
+        Err(_) => match RemoteCatalog::from_env()? {
+            Some(remote) => load_remote_librarian(params, remote, catalog_path).await,
+            None => build_librarian(params, load_mcps_from_file(catalog_path)?).await,
+        },
+    }
+}
+
+/// First fetch from `MCPS_URL`. With no last good catalog to keep yet, a
+/// failed fetch falls back to `catalog_path` when that file exists.
+async fn load_remote_librarian(
+    params: AgentParams,
+    remote: RemoteCatalog,
+    catalog_path: &str,
+) -> Result<Librarian> {
+    let mcps = match remote.fetch().await {
+        Ok(Some(mcps)) => {
+            tracing::info!(
+                "Fetched {} catalog entries from {}",
+                mcps.len(),
+                remote.url()
+            );
+            mcps
+        }
+        Ok(None) => bail!(
+            "{} answered Not Modified to an unconditional fetch",
+            remote.url()
+        ),
+        Err(e) if std::path::Path::new(catalog_path).exists() => {
+            tracing::warn!("{:#}; falling back to {}", e, catalog_path);
+            load_mcps_from_file(catalog_path)?
+        }
+        Err(e) => return Err(e),
+    };
+    let mut librarian = build_librarian(params, mcps).await?;
+    librarian.remote = Some(Arc::new(remote));
+    Ok(librarian)
+}
+
+/// The checks that need no network, run before the server binds so a bad
+/// configuration fails fast instead of after a long index build.
+pub fn preflight(params: &AgentParams) -> Result<()> {
+    params.validate()?;
+    let configured = Providers::from_env()?;
+    if configured.embedding.provider.missing_key() || configured.completion.provider.missing_key() {
+        bail!("{}: the variable is not set", OPENAI_AUTH_ERROR);
+    }
+    providers::select(configured)
+}
+
+/// An empty `Librarian` to serve from while `init_agent` builds the real one.
+pub fn starting_librarian(params: AgentParams) -> Result<Librarian> {
+    assemble_librarian(params, Vec::new(), Vec::new())
+}
+
+/// Loads and embeds the catalog and test-prompts the agent; slow for a large
+/// catalog, so `Backend::launch` runs it after binding.
+pub async fn init_agent(params: AgentParams) -> Result<Librarian> {
+    preflight(&params)?;
+    let librarian = load_librarian(params, CATALOG_PATH).await?;
+
+    if librarian.catalog.is_empty() {
+        let allow_empty =
+            env::var("LIBRARIAN_ALLOW_EMPTY_CATALOG").is_ok_and(|v| v == "1" || v == "true");
+        if !allow_empty {
+            bail!(
+                "Catalog {} is empty; set LIBRARIAN_ALLOW_EMPTY_CATALOG=true to start in degraded mode",
+                CATALOG_PATH
+            );
+        }
+        tracing::warn!(
+            "Catalog is empty: starting degraded, /discover will answer 503 without charging"
+        );
+    }
+
+    if skip_startup_prompt() {
+        tracing::info!("LIBRARIAN_SKIP_STARTUP_PROMPT set: skipping the startup test prompt");
+        return Ok(librarian);
+    }
+    let test_prompt = "Test: Librarian ready for queries.";
+    // test call
+    if let Err(e) = librarian.agent.prompt(test_prompt).await {
+        if is_auth_error(&e) {
+            bail!("{}: {}", OPENAI_AUTH_ERROR, e);
+        }
+        let completion = &providers::active().completion;
+        return Err(anyhow::Error::new(e).context(format!(
+            "Startup test prompt to {}:{} failed; check LIBRARIAN_COMPLETION_MODEL",
+            completion.provider.kind.name(),
+            completion.model
+        )));
+    }
+
+    Ok(librarian)
+}
+
+/// Re-reads the catalog (file, or `MCPS_URL` when configured) and builds a
+/// fresh agent/index with the same parameters as `current`. `None` means the
+/// remote catalog is unchanged and nothing was rebuilt. A rebuild that ends
+/// with no entries (an emptied file, or every embedding failing) is refused
+/// while `current` has some, so a reload never empties the live index. The
+/// caller decides whether to swap the result in.
+pub async fn reload_librarian(current: &Librarian) -> Result<Option<Librarian>> {
+    let mcps = match &current.remote {
+        Some(remote) => match remote.fetch().await? {
+            Some(mcps) => mcps,
+            None => {
+                tracing::debug!("Remote catalog {} unchanged", remote.url());
+                return Ok(None);
+            }
+        },
+        None => load_mcps_from_file(CATALOG_PATH)?,
+    };
+    let mut librarian = build_librarian(current.params, mcps).await?;
+    if librarian.catalog.is_empty() && !current.catalog.is_empty() {
+        bail!(
+            "Reloaded catalog has no enabled, embeddable entries; keeping the current {}",
+            current.catalog.len()
+        );
+    }
+    librarian.verification = Arc::clone(&current.verification);
+    librarian.source_check = Arc::clone(&current.source_check);
+    librarian.remote = current.remote.clone();
+    Ok(Some(librarian))
+}
+
+/// Embeds only `entry` and rebuilds the index around it, reusing the vectors
+/// `current` already holds unless their dimension no longer matches the
+/// embedding model, in which case everything is re-embedded. The caller
+/// decides whether to swap it in.
+pub async fn add_catalog_entry(current: &Librarian, entry: McpEntry) -> Result<Librarian> {
+    let embedding_model = providers::embedding_model();
+    let grown: Vec<&McpEntry> = current
+        .embeddings
+        .iter()
+        .map(|(e, _)| e)
+        .chain([&entry])
+        .collect();
+    check_index_memory(&grown, embedding_model.ndims())?;
+    let added = build_embeddings_with_retry(&embedding_model, vec![entry]).await?;
+    if added.is_empty() {
+        bail!("Embedding the new entry failed");
+    }
+
+    let mismatched = snapshot::mismatched_dimensions(&current.embeddings, embedding_model.ndims());
+    if !mismatched.is_empty() {
+        tracing::warn!(
+            expected = embedding_model.ndims(),
+            mismatched = mismatched.len(),
+            first = mismatched[0],
+            "Live index holds vectors of the wrong dimension for {}; re-embedding the whole catalog",
+            providers::active().embedding.identity()
+        );
+        METRICS
+            .embedding_dimension_mismatches_total
+            .fetch_add(mismatched.len() as u64, Ordering::Relaxed);
+        let mut mcps: Vec<McpEntry> = current.embeddings.iter().map(|(e, _)| e.clone()).collect();
+        mcps.extend(current.disabled.iter().cloned());
+        mcps.extend(added.into_iter().map(|(e, _)| e));
+        let mut librarian = build_librarian(current.params, mcps).await?;
+        librarian.verification = Arc::clone(&current.verification);
+        librarian.source_check = Arc::clone(&current.source_check);
+        librarian.remote = current.remote.clone();
+        librarian.loaded_at = current.loaded_at;
+        return Ok(librarian);
+    }
+
+    let mut embeddings = current.embeddings.as_ref().clone();
+    METRICS
+        .embedding_cache_hits_total
+        .fetch_add(embeddings.len() as u64, Ordering::Relaxed);
+    embeddings.extend(added);
+    if let Some(cache) = EmbedCache::open(embedding_model.ndims()) {
+        cache.save(&embeddings).await;
+    }
+    let mut librarian = assemble_librarian(current.params, embeddings, current.disabled.clone())?;
+    librarian.verification = Arc::clone(&current.verification);
+    librarian.source_check = Arc::clone(&current.source_check);
+    librarian.remote = current.remote.clone();
+    // one new entry doesn't refresh the rest; see `freshness`
+    librarian.loaded_at = current.loaded_at;
+    Ok(librarian)
+}
+
+/// Writes the live catalog, disabled entries included, back to `path`, leaving
+/// out entries added with `ADMIN_SOURCE`. The file is replaced atomically so
+/// the watcher never sees a partial write; entries loaded from `path` itself
+/// are written without their `source`, and with the capabilities the curator
+/// wrote rather than their normalized form.
+pub fn persist_catalog(librarian: &Librarian, path: &str) -> Result<()> {
+    let entries: Vec<McpEntry> = librarian
+        .catalog
+        .iter()
+        .chain(&librarian.disabled)
+        .filter(|entry| entry.source.as_deref() != Some(ADMIN_SOURCE))
+        .cloned()
+        .map(|mut entry| {
+            if entry.source.as_deref() == Some(path) {
+                entry.source = None;
+            }
+            if let Some(original) = entry.original_capabilities.take() {
+                entry.capabilities = original;
+            }
+            entry
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&entries).context("Failed to serialize catalog")?;
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path))?;
+    Ok(())
+}
+
+/// Embeds `mcps`, builds the vector index and the agent that recommends from it.
+pub async fn build_librarian(params: AgentParams, mcps: Vec<McpEntry>) -> Result<Librarian> {
+    let embedding_model = providers::embedding_model();
+
+    lint::log_lint_summary(&mcps);
+    let mcps = if lint::merge_duplicates_enabled() {
+        lint::merge_exact_duplicates(mcps)
+    } else {
+        mcps
+    };
+    let (enabled, disabled): (Vec<McpEntry>, Vec<McpEntry>) =
+        mcps.into_iter().partition(|entry| entry.enabled);
+    check_index_memory(&enabled.iter().collect::<Vec<_>>(), embedding_model.ndims())?;
+    let cache = EmbedCache::load(embedding_model.ndims()).await;
+    let (mut embeddings, missing) = match &cache {
+        Some(cache) => cache.split(enabled.clone()),
+        None => (Vec::new(), enabled.clone()),
+    };
+    if !embeddings.is_empty() {
+        tracing::info!(
+            cached = embeddings.len(),
+            missing = missing.len(),
+            "Reusing cached embeddings"
+        );
+        METRICS
+            .embedding_cache_hits_total
+            .fetch_add(embeddings.len() as u64, Ordering::Relaxed);
+    }
+    let fresh = !missing.is_empty();
+    if fresh || embeddings.is_empty() {
+        embeddings.extend(build_embeddings_with_retry(&embedding_model, missing).await?);
+    }
+    // cached and fresh vectors back in catalog order
+    let order: HashMap<&str, usize> = enabled
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, entry)| (entry.name.as_str(), i))
+        .collect();
+    embeddings.sort_by_key(|(entry, _)| {
+        order
+            .get(entry.name.as_str())
+            .copied()
+            .unwrap_or(usize::MAX)
+    });
+    if let Some(cache) = &cache
+        && (fresh || cache.stored() != embeddings.len())
+    {
+        cache.save(&embeddings).await;
+    }
+    assemble_librarian(params, embeddings, disabled)
+}
+
+/// Approximate resident size of the in-memory index over `entries`, known
+/// before they are embedded: an `ndims`-wide vector of `f64`s plus its source
+/// text for each text `embed_profile::texts` yields. Rig keeps one copy in the
+/// store; `Librarian` keeps another for snapshots, hence the factor of two.
+pub fn estimate_index_bytes(entries: &[&McpEntry], ndims: usize) -> usize {
+    let per_copy: usize = entries
+        .iter()
+        .flat_map(|entry| embed_profile::texts(entry))
+        .map(|text| ndims * std::mem::size_of::<f64>() + text.len())
+        .sum();
+    per_copy * 2
+}
+
+/// Logs the estimated index size and refuses catalogs above
+/// `LIBRARIAN_MAX_INDEX_MB`. Runs before embedding, so a refused catalog costs
+/// no API calls.
+fn check_index_memory(entries: &[&McpEntry], ndims: usize) -> Result<()> {
+    let bytes = estimate_index_bytes(entries, ndims);
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+    tracing::info!(
+        entries = entries.len(),
+        dimension = ndims,
+        "Estimated in-memory index size: {:.1} MiB",
+        mb
+    );
+
+    if let Some(max_mb) = env::var("LIBRARIAN_MAX_INDEX_MB")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        && mb > max_mb
+    {
+        bail!(
+            "Catalog index needs ~{:.1} MiB, above LIBRARIAN_MAX_INDEX_MB={}; \
+             use a Qdrant-backed store for catalogs this size",
+            mb,
+            max_mb
+        );
+    }
+    Ok(())
+}
+
+/// Builds the vector index and agent over already-embedded catalog entries,
+/// e.g. from `build_librarian` or an imported snapshot. Embedded entries that
+/// are marked disabled are moved to `disabled` and left out of the index.
+pub fn assemble_librarian(
+    params: AgentParams,
+    embeddings: Vec<(McpEntry, OneOrMany<Embedding>)>,
+    mut disabled: Vec<McpEntry>,
+) -> Result<Librarian> {
+    let providers = providers::active();
+    let embedding_model = providers.embedding.model();
+    let completion_client = providers.completion.provider.client();
+    let completion_model = providers.completion.model.as_str();
+    let (embeddings, embedded_disabled): (Vec<_>, Vec<_>) =
+        embeddings.into_iter().partition(|(entry, _)| entry.enabled);
+    disabled.extend(embedded_disabled.into_iter().map(|(entry, _)| entry));
+    if !disabled.is_empty() {
+        tracing::info!(
+            "{} disabled catalog entries excluded from the index",
+            disabled.len()
+        );
+    }
+    let catalog: Vec<McpEntry> = embeddings.iter().map(|(entry, _)| entry.clone()).collect();
+
+    let policy = EndpointPolicy::from_env();
+    policy.log_private_exclusions(&catalog);
+    tracing::info!(
+        "Endpoint policy: {} allow pattern(s), {} deny pattern(s), {} of {} catalog entries blocked",
+        policy.allow.len(),
+        policy.deny.len(),
+        policy.blocked_count(&catalog),
+        catalog.len()
+    );
+
+    let synonyms = SynonymMap::from_env()?;
+    tracing::info!(
+        "Loaded {} synonym group(s) for query expansion",
+        synonyms.len()
+    );
+    let pins = PinRules::from_env()?;
+    pins.check_catalog(&catalog);
+    tracing::info!("Loaded {} pin rule(s)", pins.len());
+    let vocabulary = CatalogVocabulary::from_env(&catalog);
+
+    lint::log_embedding_overlap(&embeddings);
+    let capability_index = CapabilityIndex::build(embeddings.iter().map(|(entry, _)| entry));
+    let vector_store = InMemoryVectorStore::from_documents(embeddings.clone());
+    let index = vector_store.index(embedding_model.clone());
+
+    let build_agent = |model: &str| match params.api {
+        CompletionApi::Responses => LibrarianAgent::Responses(
+            completion_client
+                .agent(model)
+                .preamble(LIBRARIAN_PREAMBLE)
+                .temperature(params.temperature)
+                .max_tokens(params.max_tokens)
+                .build(),
+        ),
+        CompletionApi::Chat => {
+            let mut builder =
+                AgentBuilder::new(completion_client.completion_model(model).completions_api())
+                    .preamble(LIBRARIAN_PREAMBLE)
+                    .temperature(params.temperature)
+                    .max_tokens(params.max_tokens);
+            if let Some(seed) = params.seed {
+                builder = builder.additional_params(serde_json::json!({ "seed": seed }));
+            }
+            LibrarianAgent::Chat(builder.build())
+        }
+    };
+    let agent = build_agent(completion_model);
+    // same preamble and sampling, only used when the primary answers 429
+    let fallback_model = env::var("LIBRARIAN_FALLBACK_MODEL")
+        .ok()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty() && m != completion_model);
+    let fallback_agent = fallback_model.as_deref().map(build_agent);
+
+    let catalog_hash = cache::catalog_hash(&catalog, &disabled);
+
+    Ok(Librarian {
+        agent,
+        index,
+        catalog,
+        disabled,
+        embeddings: Arc::new(embeddings),
+        embedding_model,
+        capability_index: Arc::new(capability_index),
+        policy: Arc::new(policy),
+        synonyms: Arc::new(synonyms),
+        pins: Arc::new(pins),
+        vocabulary: Arc::new(vocabulary),
+        protocol_versions: Arc::new(ProtocolVersions::from_env()),
+        verification: Arc::new(VerificationCache::from_env()),
+        prompt_template: Arc::new(PromptTemplate::from_env()?),
+        reranker: Reranker::from_env()?,
+        webhook: Webhook::from_env()?.map(Arc::new),
+        catalog_hash,
+        remote: None,
+        params,
+        retrieval_model: providers.embedding.model.clone(),
+        completion_model: completion_model.to_string(),
+        fallback_agent,
+        fallback_model,
+        loaded_at: chrono::Utc::now(),
+        source_check: Arc::default(),
+    })
+}
+
+pub const LIBRARIAN_PREAMBLE: &str = "
 You are the Librarian, an impartial and precise AI agent that assists other autonomous agents (A2A clients) by recommending the best Model Context Protocol (MCP) servers for their task.\n
 \n
 Hard rules:\n
@@ -132,13 +1031,67 @@ Behavioral constraints:\n
 Execution:\n
 - Read the user request, select up to three eligible MCP servers from your catalog that require no auth and match the task, fill the JSON, and return it exactly as specified.\n
 
-")
+";
 
-        .dynamic_context(3, index)
-        .build();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let test_prompt = "Test: Librarian ready for queries.";
-    agent.prompt(test_prompt).await?; // test call
+    fn provider(body: &str) -> EmbeddingError {
+        EmbeddingError::ProviderError(body.to_string())
+    }
+
+    #[test]
+    fn auth_errors_are_told_by_code_or_type() {
+        let revoked = r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key"}}"#;
+        assert!(is_auth_error(&provider(revoked)));
+        assert!(is_auth_error(&PromptError::CompletionError(
+            CompletionError::ProviderError(
+                r#"{"error": {"type": "authentication_error", "message": "bad key"}}"#.to_string()
+            )
+        )));
+        assert!(!is_rate_limit_error(&provider(revoked)));
+    }
+
+    #[test]
+    fn rate_limits_are_told_by_code_or_type() {
+        let limited = r#"{"error": {"message": "Rate limit reached for requests", "type": "requests", "code": "rate_limit_exceeded"}}"#;
+        assert!(is_rate_limit_error(&provider(limited)));
+        assert!(!is_auth_error(&provider(limited)));
+        let quota = r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}}"#;
+        assert!(!is_rate_limit_error(&provider(quota)));
+    }
 
-    Ok(agent)
+    #[test]
+    fn numbers_in_the_text_are_not_a_status() {
+        for body in [
+            r#"{"error": {"message": "Request req_401429 failed on port 4290", "type": "server_error", "code": null}}"#,
+            "upstream 429 at 10.0.0.1:401",
+            "",
+        ] {
+            assert!(!is_auth_error(&provider(body)), "{}", body);
+            assert!(!is_rate_limit_error(&provider(body)), "{}", body);
+        }
+        assert!(!is_rate_limit_error(&EmbeddingError::ResponseError(
+            "429 Too Many Requests".to_string()
+        )));
+    }
+    #[test]
+    fn index_size_is_estimated_before_embedding() {
+        // default profile: the name, each capability and the description
+        let entry: McpEntry = serde_json::from_value(serde_json::json!({
+            "name": "a/b",
+            "endpoint": "https://example.com/mcp",
+            "version": "1.0.0",
+            "capabilities": ["x1", "y2"],
+            "desc": "d",
+        }))
+        .unwrap();
+        let per_copy = 4 * (1536 * std::mem::size_of::<f64>()) + "a/bx1y2d".len();
+        assert_eq!(
+            estimate_index_bytes(&[&entry, &entry], 1536),
+            2 * 2 * per_copy
+        );
+        assert_eq!(estimate_index_bytes(&[], 1536), 0);
+    }
 }